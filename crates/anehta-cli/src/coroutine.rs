@@ -0,0 +1,90 @@
+//! Typed access to the coroutines of a module with generators, over its
+//! `__resume` / `__is_done` exports.
+
+use wasmtime::{AsContextMut, Instance, Result, TypedFunc};
+
+/// A coroutine returned by an exported generator, advanced by the host one
+/// step at a time (typically once per frame).
+pub struct Coroutine {
+    handle: i64,
+    resume: TypedFunc<i64, i64>,
+    is_done: TypedFunc<i64, i64>,
+}
+
+impl Coroutine {
+    /// Wrap the coroutine `handle` of `instance`, which must export the
+    /// coroutine helpers (any module with a generator does).
+    pub fn new(mut store: impl AsContextMut, instance: &Instance, handle: i64) -> Result<Self> {
+        let resume = instance.get_typed_func(&mut store, "__resume")?;
+        let is_done = instance.get_typed_func(&mut store, "__is_done")?;
+        Ok(Coroutine { handle, resume, is_done })
+    }
+
+    /// The packed handle, as passed to `resume` / `is_done` in scripts.
+    pub fn handle(&self) -> i64 {
+        self.handle
+    }
+
+    /// Whether the generator body has returned.
+    pub fn is_done(&self, mut store: impl AsContextMut) -> Result<bool> {
+        Ok(self.is_done.call(&mut store, self.handle)? != 0)
+    }
+
+    /// Run the generator to its next `yield` and return the yielded value; the
+    /// step that finishes it returns the generator's result. `None` once the
+    /// coroutine had already finished.
+    pub fn resume(&self, mut store: impl AsContextMut) -> Result<Option<i64>> {
+        if self.is_done(&mut store)? {
+            return Ok(None);
+        }
+        self.resume.call(&mut store, self.handle).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anehta_lexer::Lexer;
+    use anehta_parser::Parser;
+    use wasmtime::{Engine, Linker, Module, Store};
+
+    fn instantiate(src: &str) -> (Store<()>, Instance) {
+        let tokens = Lexer::new(src).tokenize().expect("lexer failed");
+        let program = Parser::new(tokens).parse().expect("parser failed");
+        let wasm = anehta_codegen_wasm::WasmCodegen::new().compile(&program).expect("codegen failed");
+        let engine = Engine::default();
+        let module = Module::new(&engine, wasm).expect("invalid module");
+        let mut linker = Linker::new(&engine);
+        linker.define_unknown_imports_as_traps(&module).unwrap();
+        let mut store = Store::new(&engine, ());
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        (store, instance)
+    }
+
+    #[test]
+    fn resume_steps_until_the_generator_returns() {
+        let src = "func count(n: int) -> int {
+for (var i = 0; i < n; i++) {
+    yield i * 10
+}
+return -1
+}";
+        let (mut store, instance) = instantiate(src);
+        let count = instance.get_typed_func::<i64, i64>(&mut store, "count").unwrap();
+        let handle = count.call(&mut store, 3).unwrap();
+        let co = Coroutine::new(&mut store, &instance, handle).unwrap();
+        let mut values = Vec::new();
+        while let Some(value) = co.resume(&mut store).unwrap() {
+            values.push(value);
+        }
+        assert_eq!(values, [0, 10, 20, -1]);
+        assert!(co.is_done(&mut store).unwrap());
+        assert_eq!(co.resume(&mut store).unwrap(), None);
+    }
+
+    #[test]
+    fn modules_without_generators_have_no_coroutines() {
+        let (mut store, instance) = instantiate("var x = 1");
+        assert!(Coroutine::new(&mut store, &instance, 0).is_err());
+    }
+}
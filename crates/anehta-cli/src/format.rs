@@ -0,0 +1,310 @@
+//! Value formatting shared by `print`, f-strings, `format` and vec/mat output.
+//!
+//! Format strings use `{}` / `{:spec}` placeholders, where spec is
+//! `[[fill]align][+][0][width][.precision][type]`:
+//! align is `<`, `>` or `^`; type is `x`, `X`, `b`, `o` (integers) or `e` (exponent).
+
+/// Type tags passed by generated code alongside each value; tag 0 is an int.
+pub const TAG_FLOAT: i64 = 1;
+pub const TAG_STR: i64 = 2;
+pub const TAG_VEC: i64 = 3;
+pub const TAG_MAT: i64 = 4;
+pub const TAG_LIST: i64 = 5;
+pub const TAG_TABLE: i64 = 6;
+pub const TAG_CLOSURE: i64 = 7;
+/// Struct and enum values are tagged `TAG_OBJECT + n`, where `__fmt_<n>` is the
+/// exported function that renders them.
+pub const TAG_OBJECT: i64 = 16;
+/// Set on the tag of an optional value; `NIL` is then shown as `nil`.
+pub const TAG_OPTIONAL: i64 = 1 << 33;
+
+/// Bit pattern of `nil`: a signalling NaN no float arithmetic produces. The
+/// compiler rejects the int literal with the same bits.
+pub const NIL: i64 = 0x7FF4_0000_0000_0000;
+
+/// A runtime value decoded out of linear memory.
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Vec(Vec<f64>),
+    Mat { cols: usize, data: Vec<f64> },
+    List(Vec<Value>),
+    /// Text rendered elsewhere: a struct, or a placeholder such as `<table>`
+    Rendered(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Default,
+    LowerHex,
+    UpperHex,
+    Binary,
+    Octal,
+    Exp,
+}
+
+/// A parsed placeholder spec.
+#[derive(Clone, Copy)]
+pub struct FormatSpec {
+    fill: char,
+    align: Option<Align>,
+    plus: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    kind: Kind,
+}
+
+impl Default for FormatSpec {
+    fn default() -> Self {
+        Self {
+            fill: ' ',
+            align: None,
+            plus: false,
+            zero: false,
+            width: 0,
+            precision: None,
+            kind: Kind::Default,
+        }
+    }
+}
+
+fn parse_align(ch: char) -> Option<Align> {
+    match ch {
+        '<' => Some(Align::Left),
+        '>' => Some(Align::Right),
+        '^' => Some(Align::Center),
+        _ => None,
+    }
+}
+
+impl FormatSpec {
+    /// Parse the text after the `:` of a placeholder.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let chars: Vec<char> = spec.chars().collect();
+        let mut out = FormatSpec::default();
+        let mut i = 0;
+
+        if let Some(align) = chars.get(1).copied().and_then(parse_align) {
+            out.fill = chars[0];
+            out.align = Some(align);
+            i = 2;
+        } else if let Some(align) = chars.first().copied().and_then(parse_align) {
+            out.align = Some(align);
+            i = 1;
+        }
+        if chars.get(i) == Some(&'+') {
+            out.plus = true;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'0') {
+            out.zero = true;
+            i += 1;
+        }
+        let (width, next) = read_digits(&chars, i);
+        out.width = width.unwrap_or(0);
+        i = next;
+        if chars.get(i) == Some(&'.') {
+            let (precision, next) = read_digits(&chars, i + 1);
+            if precision.is_none() {
+                return Err(format!("invalid format spec '{}': missing precision", spec));
+            }
+            out.precision = precision;
+            i = next;
+        }
+        out.kind = match chars.get(i) {
+            None => Kind::Default,
+            Some('x') => Kind::LowerHex,
+            Some('X') => Kind::UpperHex,
+            Some('b') => Kind::Binary,
+            Some('o') => Kind::Octal,
+            Some('e') => Kind::Exp,
+            Some(_) => return Err(format!("invalid format spec '{}'", spec)),
+        };
+        if out.kind != Kind::Default {
+            i += 1;
+        }
+        if i != chars.len() {
+            return Err(format!("invalid format spec '{}'", spec));
+        }
+        Ok(out)
+    }
+}
+
+fn read_digits(chars: &[char], start: usize) -> (Option<usize>, usize) {
+    let mut end = start;
+    while end < chars.len() && chars[end].is_ascii_digit() {
+        end += 1;
+    }
+    if end == start {
+        return (None, start);
+    }
+    let digits: String = chars[start..end].iter().collect();
+    (digits.parse().ok(), end)
+}
+
+/// Default float rendering: whole numbers keep one decimal place.
+fn float_default(f: f64) -> String {
+    if f.fract() == 0.0 && f.is_finite() {
+        format!("{:.1}", f)
+    } else {
+        format!("{}", f)
+    }
+}
+
+fn format_float(f: f64, spec: &FormatSpec) -> String {
+    match (spec.kind, spec.precision) {
+        (Kind::Exp, Some(p)) => format!("{:.*e}", p, f),
+        (Kind::Exp, None) => format!("{:e}", f),
+        (_, Some(p)) => format!("{:.*}", p, f),
+        (_, None) => float_default(f),
+    }
+}
+
+fn format_int(v: i64, spec: &FormatSpec) -> String {
+    match spec.kind {
+        Kind::LowerHex => format!("{:x}", v),
+        Kind::UpperHex => format!("{:X}", v),
+        Kind::Binary => format!("{:b}", v),
+        Kind::Octal => format!("{:o}", v),
+        // A precision or exponent on an int formats it as a float
+        Kind::Exp => format_float(v as f64, spec),
+        Kind::Default => match spec.precision {
+            Some(p) => format!("{:.*}", p, v as f64),
+            None => v.to_string(),
+        },
+    }
+}
+
+/// Apply sign, fill, alignment and width to an already-rendered scalar.
+fn pad(mut body: String, spec: &FormatSpec, numeric: bool) -> String {
+    if numeric && spec.plus && !body.starts_with('-') {
+        body.insert(0, '+');
+    }
+    let len = body.chars().count();
+    if len >= spec.width {
+        return body;
+    }
+    let missing = spec.width - len;
+
+    // `0` pads between the sign and the digits
+    if numeric && spec.zero && spec.align.is_none() {
+        let sign_len = usize::from(body.starts_with(['-', '+']));
+        let (sign, digits) = body.split_at(sign_len);
+        return format!("{}{}{}", sign, "0".repeat(missing), digits);
+    }
+
+    let align = spec.align.unwrap_or(if numeric { Align::Right } else { Align::Left });
+    let fill = |n: usize| spec.fill.to_string().repeat(n);
+    match align {
+        Align::Left => format!("{}{}", body, fill(missing)),
+        Align::Right => format!("{}{}", fill(missing), body),
+        Align::Center => format!("{}{}{}", fill(missing / 2), body, fill(missing - missing / 2)),
+    }
+}
+
+/// Format one value. Vec, mat and list apply the spec to every element;
+/// strings inside a list are quoted.
+pub fn format_value(value: &Value, spec: &FormatSpec) -> String {
+    let element = |f: &f64| pad(format_float(*f, spec), spec, true);
+    match value {
+        Value::Int(v) => pad(format_int(*v, spec), spec, true),
+        Value::Float(f) => pad(format_float(*f, spec), spec, true),
+        Value::Str(s) => {
+            let text = match spec.precision {
+                Some(p) => s.chars().take(p).collect(),
+                None => s.clone(),
+            };
+            pad(text, spec, false)
+        }
+        Value::Vec(data) => {
+            let items: Vec<String> = data.iter().map(element).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Mat { cols, data } => {
+            if *cols == 0 || data.is_empty() {
+                return "[[]]".to_string();
+            }
+            let rows: Vec<String> = data
+                .chunks(*cols)
+                .map(|row| row.iter().map(element).collect::<Vec<_>>().join(", "))
+                .collect();
+            format!("[{}]", rows.join("; "))
+        }
+        Value::List(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| match item {
+                    Value::Str(s) => format!("{:?}", s),
+                    _ => format_value(item, spec),
+                })
+                .collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Rendered(text) => pad(text.clone(), spec, false),
+    }
+}
+
+/// Expand a format string against its arguments.
+pub fn format_template(template: &str, args: &[Value]) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    let mut next_arg = 0;
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut inner = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    inner.push(c);
+                }
+                if !closed {
+                    return Err("format: unterminated '{'".to_string());
+                }
+                let spec = match inner.strip_prefix(':') {
+                    Some(spec) => FormatSpec::parse(spec)?,
+                    None if inner.is_empty() => FormatSpec::default(),
+                    None => return Err(format!("format: invalid placeholder '{{{}}}'", inner)),
+                };
+                let arg = args.get(next_arg).ok_or_else(|| {
+                    format!("format: not enough arguments for '{}'", template)
+                })?;
+                out.push_str(&format_value(arg, &spec));
+                next_arg += 1;
+            }
+            '}' => return Err("format: single '}'; use '}}' for a literal brace".to_string()),
+            _ => out.push(ch),
+        }
+    }
+
+    if next_arg != args.len() {
+        return Err(format!(
+            "format: {} argument(s) given, but '{}' uses {}",
+            args.len(),
+            template,
+            next_arg
+        ));
+    }
+    Ok(out)
+}
@@ -0,0 +1,3 @@
+//! Host-side helpers for embedding compiled Anehta modules in wasmtime.
+
+pub mod coroutine;
@@ -1,440 +1,649 @@
+// Matrix host functions index rows/columns directly; keep that style.
+#![allow(clippy::needless_range_loop)]
+
+mod format;
+mod modules;
+mod project;
+mod table;
+
 use std::env;
 use std::fs;
+use std::path::Path;
 
-// ============================================================================
-// SIMD Optimized Vector Operations (AVX2 on x86_64)
-// ============================================================================
-
-#[cfg(target_arch = "x86_64")]
-mod simd_ops {
-    use std::arch::x86_64::*;
-
-    /// SIMD-optimized vector addition: result[i] = a[i] + b[i]
-    /// Uses AVX2 to process 4 f64 values at once
-    #[target_feature(enable = "avx2")]
-    pub unsafe fn vec_add_simd(a: &[f64], b: &[f64], result: &mut [f64]) {
-        let len = a.len().min(b.len()).min(result.len());
-        let mut i = 0;
-
-        // Process 4 elements at a time using AVX2 (256-bit = 4 x f64)
-        while i + 4 <= len {
-            let va = _mm256_loadu_pd(a.as_ptr().add(i));
-            let vb = _mm256_loadu_pd(b.as_ptr().add(i));
-            let vr = _mm256_add_pd(va, vb);
-            _mm256_storeu_pd(result.as_mut_ptr().add(i), vr);
-            i += 4;
-        }
+use modules::ImportScope;
+use project::{LintLevel, Project};
+use table::{Table, TableKey};
+
+fn compile(source_path: &Path, project: Option<&Project>) -> Result<Vec<u8>, String> {
+    // Step 1: Lex and parse the file and the files it imports, into one program
+    let scope = project.map(ImportScope::for_project).unwrap_or_default();
+    let (program, files) = modules::load_program(source_path, scope)?;
 
-        // Handle remaining elements (scalar fallback)
-        for j in i..len {
-            result[j] = a[j] + b[j];
+    // Step 2: Codegen
+    let mut codegen = anehta_codegen_wasm::WasmCodegen::new();
+    let tail_calls = project.is_none_or(|p| p.manifest.features.tail_calls);
+    codegen.set_tail_calls(tail_calls && tail_calls_supported());
+    let wasm_bytes = codegen.compile(&program).map_err(|e| {
+        let anehta_codegen_wasm::CodegenError::Error { file, .. } = &e;
+        files.locate(*file, &e)
+    })?;
+
+    let lint = project.map_or(LintLevel::Warn, |p| p.manifest.lints.warnings);
+    if lint != LintLevel::Allow {
+        for warning in codegen.warnings() {
+            eprintln!("{}", files.locate(warning.file, warning));
         }
     }
+    if lint == LintLevel::Deny && !codegen.warnings().is_empty() {
+        return Err(format!(
+            "{}: {} warning(s) denied by warnings = \"deny\" in {}",
+            source_path.display(),
+            codegen.warnings().len(),
+            project::MANIFEST_NAME
+        ));
+    }
+    Ok(wasm_bytes)
+}
 
-    /// SIMD-optimized vector subtraction
-    #[target_feature(enable = "avx2")]
-    pub unsafe fn vec_sub_simd(a: &[f64], b: &[f64], result: &mut [f64]) {
-        let len = a.len().min(b.len()).min(result.len());
-        let mut i = 0;
-
-        while i + 4 <= len {
-            let va = _mm256_loadu_pd(a.as_ptr().add(i));
-            let vb = _mm256_loadu_pd(b.as_ptr().add(i));
-            let vr = _mm256_sub_pd(va, vb);
-            _mm256_storeu_pd(result.as_mut_ptr().add(i), vr);
-            i += 4;
-        }
+/// Print the error and exit.
+fn or_exit<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
 
-        for j in i..len {
-            result[j] = a[j] - b[j];
-        }
+/// The project holding `source`, if it is inside one.
+fn project_of(source: &Path) -> Option<Project> {
+    let dir = source.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    or_exit(Project::find(dir))
+}
+
+/// The project of the current directory; exits if there is none.
+fn current_project() -> Project {
+    or_exit(Project::find(Path::new("."))).unwrap_or_else(|| {
+        eprintln!("No {} found in this directory or its parents", project::MANIFEST_NAME);
+        std::process::exit(1);
+    })
+}
+
+/// Compile `source` and write the module to `output_path`.
+fn build_file(source: &Path, output_path: &Path, project: Option<&Project>) -> Result<(), String> {
+    let wasm_bytes = compile(source, project)?;
+    if let Some(dir) = output_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| format!("Error creating '{}': {}", dir.display(), e))?;
     }
+    fs::write(output_path, &wasm_bytes).map_err(|e| format!("Error writing '{}': {}", output_path.display(), e))?;
+    println!(
+        "Compiled {} -> {} ({} bytes)",
+        source.display(),
+        output_path.display(),
+        wasm_bytes.len()
+    );
+    Ok(())
+}
 
-    /// SIMD-optimized element-wise multiplication
-    #[target_feature(enable = "avx2")]
-    pub unsafe fn vec_mul_simd(a: &[f64], b: &[f64], result: &mut [f64]) {
-        let len = a.len().min(b.len()).min(result.len());
-        let mut i = 0;
-
-        while i + 4 <= len {
-            let va = _mm256_loadu_pd(a.as_ptr().add(i));
-            let vb = _mm256_loadu_pd(b.as_ptr().add(i));
-            let vr = _mm256_mul_pd(va, vb);
-            _mm256_storeu_pd(result.as_mut_ptr().add(i), vr);
-            i += 4;
-        }
+/// Build one file: into the project's output directory when it belongs to a
+/// project, otherwise next to it.
+fn cmd_build(source_path: &str) {
+    let source = Path::new(source_path);
+    let project = project_of(source);
+    let output_path = match &project {
+        Some(project) => project.output_path(source),
+        None => source.with_extension("wasm"),
+    };
+    or_exit(build_file(source, &output_path, project.as_ref()));
+}
 
-        for j in i..len {
-            result[j] = a[j] * b[j];
-        }
+/// Build every entry of the current project.
+fn cmd_build_project() {
+    let project = current_project();
+    let package = &project.manifest.package;
+    println!("Building {} v{}", package.name, package.version);
+    for entry in &project.manifest.build.entries {
+        or_exit(build_file(&project.path(entry), &project.output_path(entry), Some(&project)));
     }
+}
 
-    /// SIMD-optimized scalar addition: result[i] = a[i] + scalar
-    #[target_feature(enable = "avx2")]
-    pub unsafe fn vec_add_scalar_simd(a: &[f64], scalar: f64, result: &mut [f64]) {
-        let len = a.len().min(result.len());
-        let mut i = 0;
-        let vs = _mm256_set1_pd(scalar); // Broadcast scalar to all 4 lanes
-
-        while i + 4 <= len {
-            let va = _mm256_loadu_pd(a.as_ptr().add(i));
-            let vr = _mm256_add_pd(va, vs);
-            _mm256_storeu_pd(result.as_mut_ptr().add(i), vr);
-            i += 4;
-        }
+fn cmd_run(source_path: &str) {
+    let source = Path::new(source_path);
+    let project = project_of(source);
+    let wasm_bytes = or_exit(compile(source, project.as_ref()));
 
-        for j in i..len {
-            result[j] = a[j] + scalar;
-        }
+    if let Err(e) = execute_wasm(&wasm_bytes) {
+        eprintln!("Runtime error: {}", e);
+        std::process::exit(1);
     }
+}
 
-    /// SIMD-optimized scalar subtraction
-    #[target_feature(enable = "avx2")]
-    pub unsafe fn vec_sub_scalar_simd(a: &[f64], scalar: f64, result: &mut [f64]) {
-        let len = a.len().min(result.len());
-        let mut i = 0;
-        let vs = _mm256_set1_pd(scalar);
-
-        while i + 4 <= len {
-            let va = _mm256_loadu_pd(a.as_ptr().add(i));
-            let vr = _mm256_sub_pd(va, vs);
-            _mm256_storeu_pd(result.as_mut_ptr().add(i), vr);
-            i += 4;
-        }
+/// Run the entry of the current project.
+fn cmd_run_project() {
+    let project = current_project();
+    let entries = &project.manifest.build.entries;
+    if entries.len() != 1 {
+        eprintln!(
+            "The project has {} entries; name the one to run: anehta run <source.ah>",
+            entries.len()
+        );
+        std::process::exit(1);
+    }
+    cmd_run(&project.path(&entries[0]).to_string_lossy());
+}
+
+fn cmd_new(name: &str) {
+    let root = or_exit(project::create(name));
+    println!("Created project '{}'", root.display());
+}
+
+/// A host-side object that WASM refers to by its handle (slot index).
+enum HeapObject {
+    Table(Table),
+    /// Elements as (tag, value) pairs; the tag is one of `format::TAG_*`.
+    List(Vec<(i64, i64)>),
+}
+
+struct RuntimeState {
+    start_instant: std::time::Instant,
+    /// Host-side tables and lists: each slot is Some(object) or None (freed).
+    /// WASM references them by their index (handle) in this Vec.
+    objects: Vec<Option<HeapObject>>,
+    /// Indices of freed slots available for reuse.
+    free_slots: Vec<usize>,
+    /// Parent → children relationships for recursive freeing.
+    children: std::collections::HashMap<usize, Vec<usize>>,
+    /// Child → parents: an object nested in tables or lists is freed with the
+    /// last of them. A parent holding the child twice is listed twice.
+    parents: std::collections::HashMap<usize, Vec<usize>>,
+    /// Open interpolation / formatting buffers (a stack, so they can nest).
+    interp_frames: Vec<InterpFrame>,
+}
 
-        for j in i..len {
-            result[j] = a[j] - scalar;
+impl RuntimeState {
+    fn table(&self, id: i64) -> Option<&Table> {
+        match self.objects.get(usize::try_from(id).ok()?) {
+            Some(Some(HeapObject::Table(table))) => Some(table),
+            _ => None,
         }
     }
 
-    /// SIMD-optimized scalar multiplication (scaling)
-    #[target_feature(enable = "avx2")]
-    pub unsafe fn vec_scale_simd(a: &[f64], scalar: f64, result: &mut [f64]) {
-        let len = a.len().min(result.len());
-        let mut i = 0;
-        let vs = _mm256_set1_pd(scalar);
-
-        while i + 4 <= len {
-            let va = _mm256_loadu_pd(a.as_ptr().add(i));
-            let vr = _mm256_mul_pd(va, vs);
-            _mm256_storeu_pd(result.as_mut_ptr().add(i), vr);
-            i += 4;
+    fn table_mut(&mut self, id: i64) -> Option<&mut Table> {
+        match self.objects.get_mut(usize::try_from(id).ok()?) {
+            Some(Some(HeapObject::Table(table))) => Some(table),
+            _ => None,
         }
+    }
 
-        for j in i..len {
-            result[j] = a[j] * scalar;
+    fn list(&self, id: i64) -> Option<&Vec<(i64, i64)>> {
+        match self.objects.get(usize::try_from(id).ok()?) {
+            Some(Some(HeapObject::List(items))) => Some(items),
+            _ => None,
         }
     }
 
-    /// SIMD-optimized scalar division
-    #[target_feature(enable = "avx2")]
-    pub unsafe fn vec_div_scalar_simd(a: &[f64], scalar: f64, result: &mut [f64]) {
-        let len = a.len().min(result.len());
-        let mut i = 0;
-        let vs = _mm256_set1_pd(scalar);
-
-        while i + 4 <= len {
-            let va = _mm256_loadu_pd(a.as_ptr().add(i));
-            let vr = _mm256_div_pd(va, vs);
-            _mm256_storeu_pd(result.as_mut_ptr().add(i), vr);
-            i += 4;
+    fn list_mut(&mut self, id: i64) -> Option<&mut Vec<(i64, i64)>> {
+        match self.objects.get_mut(usize::try_from(id).ok()?) {
+            Some(Some(HeapObject::List(items))) => Some(items),
+            _ => None,
         }
+    }
 
-        for j in i..len {
-            result[j] = a[j] / scalar;
+    /// Store a new object, reusing a freed slot when available.
+    fn alloc(&mut self, object: HeapObject) -> i64 {
+        if let Some(slot) = self.free_slots.pop() {
+            self.objects[slot] = Some(object);
+            slot as i64
+        } else {
+            self.objects.push(Some(object));
+            (self.objects.len() - 1) as i64
         }
     }
 
-    /// SIMD-optimized dot product
-    #[target_feature(enable = "avx2")]
-    pub unsafe fn vec_dot_simd(a: &[f64], b: &[f64]) -> f64 {
-        let len = a.len().min(b.len());
-        let mut i = 0;
-        let mut sum_vec = _mm256_setzero_pd();
-
-        while i + 4 <= len {
-            let va = _mm256_loadu_pd(a.as_ptr().add(i));
-            let vb = _mm256_loadu_pd(b.as_ptr().add(i));
-            let vprod = _mm256_mul_pd(va, vb);
-            sum_vec = _mm256_add_pd(sum_vec, vprod);
-            i += 4;
+    /// Record that `child` is owned by `parent`, as well as by any earlier owners.
+    fn adopt(&mut self, parent: i64, child: i64) {
+        let (p, c) = (parent as usize, child as usize);
+        if parent < 0 || child < 0 || p == c {
+            return;
         }
+        self.parents.entry(c).or_default().push(p);
+        self.children.entry(p).or_default().push(c);
+    }
 
-        // Horizontal sum of the 4 lanes
-        let mut sum_array = [0.0; 4];
-        _mm256_storeu_pd(sum_array.as_mut_ptr(), sum_vec);
-        let mut sum = sum_array.iter().sum::<f64>();
-
-        // Handle remaining elements
-        for j in i..len {
-            sum += a[j] * b[j];
+    /// Drop one ownership of `child` by `parent`. Returns whether another parent
+    /// still owns the child.
+    fn disown(&mut self, parent: usize, child: usize) -> bool {
+        let Some(owners) = self.parents.get_mut(&child) else {
+            return false;
+        };
+        if let Some(pos) = owners.iter().position(|&o| o == parent) {
+            owners.swap_remove(pos);
         }
-
-        sum
+        if owners.is_empty() {
+            self.parents.remove(&child);
+            return false;
+        }
+        true
     }
-}
 
-// Runtime CPU feature detection wrapper
-#[cfg(target_arch = "x86_64")]
-fn vec_add_optimized(a: &[f64], b: &[f64], result: &mut [f64]) {
-    if is_x86_feature_detected!("avx2") {
-        unsafe { simd_ops::vec_add_simd(a, b, result) }
-    } else {
-        // Fallback to scalar
-        for i in 0..a.len().min(b.len()).min(result.len()) {
-            result[i] = a[i] + b[i];
+    /// Detach `child` from `parent`, e.g. when it is popped from a list.
+    fn release(&mut self, parent: i64, child: i64) {
+        let (p, c) = (parent as usize, child as usize);
+        if child < 0 || !self.parents.get(&c).is_some_and(|owners| owners.contains(&p)) {
+            return;
+        }
+        self.disown(p, c);
+        if let Some(siblings) = self.children.get_mut(&p)
+            && let Some(pos) = siblings.iter().position(|&s| s == c)
+        {
+            siblings.swap_remove(pos);
         }
     }
-}
 
-#[cfg(target_arch = "x86_64")]
-fn vec_sub_optimized(a: &[f64], b: &[f64], result: &mut [f64]) {
-    if is_x86_feature_detected!("avx2") {
-        unsafe { simd_ops::vec_sub_simd(a, b, result) }
-    } else {
-        for i in 0..a.len().min(b.len()).min(result.len()) {
-            result[i] = a[i] - b[i];
+    /// Free an object and everything only it owns. Objects owned by a live parent
+    /// are skipped: they are released together with the last such parent.
+    fn free(&mut self, id: i64) {
+        if id < 0 || self.parents.contains_key(&(id as usize)) {
+            return;
+        }
+        let mut stack = vec![id as usize];
+        while let Some(current) = stack.pop() {
+            if !matches!(self.objects.get(current), Some(Some(_))) {
+                continue;
+            }
+            if let Some(children) = self.children.remove(&current) {
+                for child in children {
+                    if !self.disown(current, child) {
+                        stack.push(child);
+                    }
+                }
+            }
+            self.objects[current] = None;
+            self.free_slots.push(current);
         }
     }
 }
 
-#[cfg(target_arch = "x86_64")]
-fn vec_mul_optimized(a: &[f64], b: &[f64], result: &mut [f64]) {
-    if is_x86_feature_detected!("avx2") {
-        unsafe { simd_ops::vec_mul_simd(a, b, result) }
-    } else {
-        for i in 0..a.len().min(b.len()).min(result.len()) {
-            result[i] = a[i] * b[i];
+// Helper macro to get and update heap_ptr from WASM global
+macro_rules! get_heap_ptr {
+    ($caller:expr) => {{
+        let global = ($caller)
+            .get_export("__heap_base")
+            .and_then(|e| e.into_global())
+            .expect("missing __heap_base global");
+        let val = global.get(&mut *$caller);
+        match val {
+            wasmtime::Val::I32(v) => v as u32,
+            _ => panic!("__heap_base is not i32"),
         }
-    }
+    }};
 }
 
-#[cfg(target_arch = "x86_64")]
-fn vec_add_scalar_optimized(a: &[f64], scalar: f64, result: &mut [f64]) {
-    if is_x86_feature_detected!("avx2") {
-        unsafe { simd_ops::vec_add_scalar_simd(a, scalar, result) }
-    } else {
-        for i in 0..a.len().min(result.len()) {
-            result[i] = a[i] + scalar;
-        }
-    }
+macro_rules! set_heap_ptr {
+    ($caller:expr, $new_ptr:expr) => {{
+        let global = ($caller)
+            .get_export("__heap_base")
+            .and_then(|e| e.into_global())
+            .expect("missing __heap_base global");
+        global
+            .set($caller, wasmtime::Val::I32($new_ptr as i32))
+            .expect("failed to set __heap_base");
+    }};
 }
 
-#[cfg(target_arch = "x86_64")]
-fn vec_sub_scalar_optimized(a: &[f64], scalar: f64, result: &mut [f64]) {
-    if is_x86_feature_detected!("avx2") {
-        unsafe { simd_ops::vec_sub_scalar_simd(a, scalar, result) }
-    } else {
-        for i in 0..a.len().min(result.len()) {
-            result[i] = a[i] - scalar;
-        }
+/// Read a packed string (ptr << 32 | len) out of linear memory.
+fn read_packed_str(caller: &mut wasmtime::Caller<'_, RuntimeState>, packed: i64) -> String {
+    let ptr = (packed >> 32) as u32 as usize;
+    let len = (packed & 0xFFFF_FFFF) as usize;
+    let memory = caller
+        .get_export("memory")
+        .and_then(|e| e.into_memory())
+        .expect("missing memory export");
+    let data = memory.data(&*caller);
+    match data.get(ptr..ptr + len) {
+        Some(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        None => String::new(),
     }
 }
 
-#[cfg(target_arch = "x86_64")]
-fn vec_scale_optimized(a: &[f64], scalar: f64, result: &mut [f64]) {
-    if is_x86_feature_detected!("avx2") {
-        unsafe { simd_ops::vec_scale_simd(a, scalar, result) }
-    } else {
-        for i in 0..a.len().min(result.len()) {
-            result[i] = a[i] * scalar;
-        }
+/// Return the heap pointer, first growing linear memory by whole pages
+/// if `size` more bytes would not fit.
+fn reserve_heap(caller: &mut wasmtime::Caller<'_, RuntimeState>, size: usize) -> u32 {
+    let memory = caller
+        .get_export("memory")
+        .and_then(|e| e.into_memory())
+        .expect("missing memory export");
+    let dest = get_heap_ptr!(caller);
+    let needed = dest as usize + size;
+    let available = memory.data_size(&*caller);
+    if needed > available {
+        let pages = (needed - available).div_ceil(65536) as u64;
+        memory
+            .grow(&mut *caller, pages)
+            .expect("out of memory: linear memory cannot grow further");
     }
+    dest
 }
 
-#[cfg(target_arch = "x86_64")]
-fn vec_div_scalar_optimized(a: &[f64], scalar: f64, result: &mut [f64]) {
-    if is_x86_feature_detected!("avx2") {
-        unsafe { simd_ops::vec_div_scalar_simd(a, scalar, result) }
-    } else {
-        for i in 0..a.len().min(result.len()) {
-            result[i] = a[i] / scalar;
-        }
+/// Copy a string onto the heap and return it packed as (ptr << 32 | len).
+fn alloc_packed_str(caller: &mut wasmtime::Caller<'_, RuntimeState>, s: &str) -> i64 {
+    let bytes = s.as_bytes();
+    let dest = reserve_heap(caller, bytes.len());
+    let memory = caller
+        .get_export("memory")
+        .and_then(|e| e.into_memory())
+        .expect("missing memory export");
+    memory.data_mut(&mut *caller)[dest as usize..dest as usize + bytes.len()]
+        .copy_from_slice(bytes);
+    set_heap_ptr!(caller, dest + bytes.len() as u32);
+    ((dest as i64) << 32) | (bytes.len() as i64)
+}
+
+/// Read the f64 elements at `ptr` out of linear memory.
+fn read_f64s(caller: &mut wasmtime::Caller<'_, RuntimeState>, ptr: u32, count: usize) -> Vec<f64> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(|e| e.into_memory())
+        .expect("missing memory export");
+    let data = memory.data(&*caller);
+    (0..count)
+        .map_while(|i| {
+            let offset = ptr as usize + i * 8;
+            data.get(offset..offset + 8)
+                .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap()))
+        })
+        .collect()
+}
+
+/// Copy f64 elements onto the heap and return their address.
+fn alloc_f64s(caller: &mut wasmtime::Caller<'_, RuntimeState>, values: &[f64]) -> u32 {
+    let dest = reserve_heap(caller, values.len() * 8);
+    let memory = caller
+        .get_export("memory")
+        .and_then(|e| e.into_memory())
+        .expect("missing memory export");
+    let data = memory.data_mut(&mut *caller);
+    for (i, v) in values.iter().enumerate() {
+        let offset = dest as usize + i * 8;
+        data[offset..offset + 8].copy_from_slice(&v.to_le_bytes());
     }
+    set_heap_ptr!(caller, dest + (values.len() * 8) as u32);
+    dest
 }
 
-#[cfg(target_arch = "x86_64")]
-fn vec_dot_optimized(a: &[f64], b: &[f64]) -> f64 {
-    if is_x86_feature_detected!("avx2") {
-        unsafe { simd_ops::vec_dot_simd(a, b) }
+/// Element count of a packed vec (`is_mat == false`) or mat.
+fn array_len(packed: i64, is_mat: bool) -> usize {
+    let meta = (packed & 0xFFFF_FFFF) as u32;
+    if is_mat {
+        ((meta >> 16) * (meta & 0xFFFF)) as usize
     } else {
-        let len = a.len().min(b.len());
-        (0..len).map(|i| a[i] * b[i]).sum()
+        meta as usize
     }
 }
 
-// Fallback for non-x86_64 platforms (use scalar operations)
-#[cfg(not(target_arch = "x86_64"))]
-fn vec_add_optimized(a: &[f64], b: &[f64], result: &mut [f64]) {
-    for i in 0..a.len().min(b.len()).min(result.len()) {
-        result[i] = a[i] + b[i];
+/// Unary math op codes shared with the code generator.
+fn math_unary(op: i64, x: f64) -> f64 {
+    match op {
+        0 => x.sqrt(),
+        1 => x.abs(),
+        2 => x.floor(),
+        3 => x.ceil(),
+        4 => x.round(),
+        5 => x.sin(),
+        6 => x.cos(),
+        7 => x.exp(),
+        _ => x.ln(),
     }
 }
 
-#[cfg(not(target_arch = "x86_64"))]
-fn vec_sub_optimized(a: &[f64], b: &[f64], result: &mut [f64]) {
-    for i in 0..a.len().min(b.len()).min(result.len()) {
-        result[i] = a[i] - b[i];
+/// Binary math op codes shared with the code generator.
+fn math_binary(op: i64, a: f64, b: f64) -> f64 {
+    match op {
+        0 => a.min(b),
+        1 => a.max(b),
+        _ => a.atan2(b),
     }
 }
 
-#[cfg(not(target_arch = "x86_64"))]
-fn vec_mul_optimized(a: &[f64], b: &[f64], result: &mut [f64]) {
-    for i in 0..a.len().min(b.len()).min(result.len()) {
-        result[i] = a[i] * b[i];
+/// Comparison op codes shared with the code generator (masks and `where`).
+fn compare(op: i64, a: f64, b: f64) -> bool {
+    match op {
+        0 => a > b,
+        1 => a < b,
+        2 => a >= b,
+        3 => a <= b,
+        4 => a == b,
+        5 => a != b,
+        _ => false,
     }
 }
 
-#[cfg(not(target_arch = "x86_64"))]
-fn vec_add_scalar_optimized(a: &[f64], scalar: f64, result: &mut [f64]) {
-    for i in 0..a.len().min(result.len()) {
-        result[i] = a[i] + scalar;
-    }
+/// Decode a tagged value (see `format::TAG_*`) for formatting.
+fn read_value(caller: &mut wasmtime::Caller<'_, RuntimeState>, value: i64, tag: i64) -> format::Value {
+    read_nested_value(caller, value, tag, 0)
 }
 
-#[cfg(not(target_arch = "x86_64"))]
-fn vec_sub_scalar_optimized(a: &[f64], scalar: f64, result: &mut [f64]) {
-    for i in 0..a.len().min(result.len()) {
-        result[i] = a[i] - scalar;
+/// Lists nested deeper than this (e.g. a list that contains itself) are elided.
+const MAX_FORMAT_DEPTH: usize = 16;
+
+fn read_nested_value(
+    caller: &mut wasmtime::Caller<'_, RuntimeState>,
+    value: i64,
+    tag: i64,
+    depth: usize,
+) -> format::Value {
+    match tag {
+        _ if tag & format::TAG_OPTIONAL != 0 => match value {
+            format::NIL => format::Value::Rendered("nil".to_string()),
+            _ => read_nested_value(caller, value, tag & !format::TAG_OPTIONAL, depth),
+        },
+        format::TAG_LIST if depth >= MAX_FORMAT_DEPTH => format::Value::Rendered("[...]".to_string()),
+        format::TAG_LIST => {
+            let items = caller.data().list(value).cloned().unwrap_or_default();
+            let items = items
+                .into_iter()
+                .map(|(tag, item)| read_nested_value(caller, item, tag, depth + 1))
+                .collect();
+            format::Value::List(items)
+        }
+        format::TAG_TABLE => format::Value::Rendered("<table>".to_string()),
+        format::TAG_CLOSURE => format::Value::Rendered("<closure>".to_string()),
+        _ if tag >= format::TAG_OBJECT => {
+            // Structs and enums are rendered by their generated formatter
+            let name = format!("__fmt_{}", tag - format::TAG_OBJECT);
+            let formatter = caller
+                .get_export(&name)
+                .and_then(|e| e.into_func())
+                .and_then(|f| f.typed::<i64, i64>(&*caller).ok());
+            match formatter.map(|f| f.call(&mut *caller, value)) {
+                Some(Ok(packed)) => format::Value::Rendered(read_packed_str(caller, packed)),
+                _ => format::Value::Rendered("<object>".to_string()),
+            }
+        }
+        format::TAG_FLOAT => format::Value::Float(f64::from_bits(value as u64)),
+        format::TAG_STR => format::Value::Str(read_packed_str(caller, value)),
+        format::TAG_VEC => {
+            let len = (value & 0xFFFF_FFFF) as usize;
+            format::Value::Vec(read_f64s(caller, (value >> 32) as u32, len))
+        }
+        format::TAG_MAT => {
+            let meta = (value & 0xFFFF_FFFF) as u32;
+            let (rows, cols) = ((meta >> 16) as usize, (meta & 0xFFFF) as usize);
+            let data = read_f64s(caller, (value >> 32) as u32, rows * cols);
+            format::Value::Mat { cols, data }
+        }
+        _ => format::Value::Int(value),
     }
 }
 
-#[cfg(not(target_arch = "x86_64"))]
-fn vec_scale_optimized(a: &[f64], scalar: f64, result: &mut [f64]) {
-    for i in 0..a.len().min(result.len()) {
-        result[i] = a[i] * scalar;
-    }
+/// Format a tagged value with the default spec, as `print` shows it.
+fn format_default(caller: &mut wasmtime::Caller<'_, RuntimeState>, value: i64, tag: i64) -> String {
+    let value = read_value(caller, value, tag);
+    format::format_value(&value, &format::FormatSpec::default())
 }
 
-#[cfg(not(target_arch = "x86_64"))]
-fn vec_div_scalar_optimized(a: &[f64], scalar: f64, result: &mut [f64]) {
-    for i in 0..a.len().min(result.len()) {
-        result[i] = a[i] / scalar;
-    }
+/// An open interpolation / formatting buffer.
+#[derive(Default)]
+struct InterpFrame {
+    /// Text rendered so far
+    text: String,
+    /// Raw arguments waiting for `interp_format` or `interp_print`
+    args: Vec<format::Value>,
 }
 
-#[cfg(not(target_arch = "x86_64"))]
-fn vec_dot_optimized(a: &[f64], b: &[f64]) -> f64 {
-    let len = a.len().min(b.len());
-    (0..len).map(|i| a[i] * b[i]).sum()
+/// Allocate an empty host table, reusing a freed slot when available.
+fn alloc_table(state: &mut RuntimeState) -> i64 {
+    state.alloc(HeapObject::Table(Table::default()))
 }
 
-// ============================================================================
-// End SIMD Module
-// ============================================================================
+/// Set by generated code on an element's tag when the list takes ownership of
+/// the table or list being stored (a fresh value, not a borrowed one).
+const TAG_OWNED: i64 = 1 << 32;
 
-fn compile(source_path: &str) -> Result<(String, Vec<u8>), String> {
-    let source = fs::read_to_string(source_path)
-        .map_err(|e| format!("Error reading file '{}': {}", source_path, e))?;
+/// Strip the ownership bit off an element tag, adopting the element if it is set.
+fn list_element(state: &mut RuntimeState, list: i64, value: i64, tag: i64) -> (i64, i64) {
+    if tag & TAG_OWNED != 0 {
+        state.adopt(list, value);
+    }
+    (tag & !TAG_OWNED, value)
+}
 
-    // Step 1: Lex
-    let mut lexer = anehta_lexer::Lexer::new(&source);
-    let tokens = lexer.tokenize().map_err(|e| format!("{}", e))?;
+/// A removed or overwritten element no longer belongs to the list.
+fn drop_element(state: &mut RuntimeState, list: i64, (tag, value): (i64, i64)) -> i64 {
+    if matches!(tag, format::TAG_LIST | format::TAG_TABLE) {
+        state.release(list, value);
+    }
+    value
+}
 
-    // Step 2: Parse
-    let mut parser = anehta_parser::Parser::new(tokens);
-    let program = parser.parse().map_err(|e| format!("{}", e))?;
+/// `Error.code` of a bad index, a missing key or an empty list
+const ERROR_INDEX: i64 = 1;
+/// `Error.code` of operands whose sizes do not match
+const ERROR_SIZE: i64 = 2;
+/// `Error.code` of a singular matrix
+const ERROR_SINGULAR: i64 = 3;
+/// `Error.code` of a format string that does not match its arguments
+const ERROR_FORMAT: i64 = 5;
+
+/// An error a host function raises in the script, where `try` can catch it.
+struct HostError {
+    code: i64,
+    message: String,
+}
 
-    // Step 3: Codegen
-    let mut codegen = anehta_codegen_wasm::WasmCodegen::new();
-    let wasm_bytes = codegen.compile(&program).map_err(|e| format!("{}", e))?;
+fn list_error(message: String) -> HostError {
+    HostError { code: ERROR_INDEX, message }
+}
 
-    let output_path = source_path.replace(".ah", ".wasm");
-    Ok((output_path, wasm_bytes))
+fn size_error(message: &str) -> HostError {
+    HostError { code: ERROR_SIZE, message: message.to_string() }
 }
 
-fn cmd_build(source_path: &str) {
-    let (output_path, wasm_bytes) = match compile(source_path) {
-        Ok(v) => v,
-        Err(e) => {
-            eprintln!("{}", e);
-            std::process::exit(1);
-        }
-    };
+fn singular_error() -> HostError {
+    HostError { code: ERROR_SINGULAR, message: "matrix is singular".to_string() }
+}
 
-    match fs::write(&output_path, &wasm_bytes) {
-        Ok(_) => println!(
-            "Compiled {} -> {} ({} bytes)",
-            source_path,
-            output_path,
-            wasm_bytes.len()
-        ),
-        Err(e) => {
-            eprintln!("Error writing '{}': {}", output_path, e);
-            std::process::exit(1);
-        }
-    }
+/// Raise an error in the script: build its `Error { message, code }` on the heap
+/// and store the address in `__error`, which the script checks after the call.
+/// The host function then returns a placeholder value.
+fn raise<T: Default>(caller: &mut wasmtime::Caller<'_, RuntimeState>, error: HostError) -> wasmtime::Result<T> {
+    let message = alloc_packed_str(caller, &error.message);
+    let dest = reserve_heap(caller, 16) as usize;
+    let memory = caller
+        .get_export("memory")
+        .and_then(|e| e.into_memory())
+        .expect("missing memory export");
+    let data = memory.data_mut(&mut *caller);
+    data[dest..dest + 8].copy_from_slice(&message.to_le_bytes());
+    data[dest + 8..dest + 16].copy_from_slice(&error.code.to_le_bytes());
+    set_heap_ptr!(&mut *caller, dest + 16);
+    caller
+        .get_export("__error")
+        .and_then(|e| e.into_global())
+        .expect("missing __error global")
+        .set(&mut *caller, wasmtime::Val::I32(dest as i32))?;
+    Ok(T::default())
 }
 
-fn cmd_run(source_path: &str) {
-    let (_output_path, wasm_bytes) = match compile(source_path) {
-        Ok(v) => v,
-        Err(e) => {
-            eprintln!("{}", e);
-            std::process::exit(1);
+/// Unwrap a host result, or raise its error and return from the host function.
+macro_rules! raise_on_err {
+    ($caller:expr, $result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(error) => return raise($caller, error),
         }
     };
+}
 
-    if let Err(e) = execute_wasm(&wasm_bytes) {
-        eprintln!("Runtime error: {}", e);
-        std::process::exit(1);
+/// Read a table entry; a missing key is an error rather than a silent 0.
+fn table_get(state: &RuntimeState, table_id: i64, key: TableKey) -> Result<i64, HostError> {
+    let Some(table) = state.table(table_id) else {
+        return Err(list_error("index: not a table".to_string()));
+    };
+    table
+        .get(&key)
+        .ok_or_else(|| list_error(format!("table has no key {}", key)))
+}
+
+/// Decode a key passed with its tag: an int, or a packed string.
+fn table_key(caller: &mut wasmtime::Caller<'_, RuntimeState>, key: i64, tag: i64) -> TableKey {
+    if tag == format::TAG_STR {
+        TableKey::from_name(read_packed_str(caller, key))
+    } else {
+        TableKey::Index(key)
     }
 }
 
-struct RuntimeState {
-    start_instant: std::time::Instant,
-    /// Host-side tables: each slot is Some(table) or None (freed).
-    /// WASM references tables by their index (table_id) in this Vec.
-    tables: Vec<Option<std::collections::HashMap<String, i64>>>,
-    /// Indices of freed table slots available for reuse.
-    free_slots: Vec<usize>,
-    /// Parent → children relationships for recursive table freeing.
-    table_children: std::collections::HashMap<usize, Vec<usize>>,
+/// Check `index` against a list of length `len` (`len` itself is allowed when inserting).
+fn list_index(index: i64, len: usize, inserting: bool) -> Result<usize, HostError> {
+    let limit = if inserting { len + 1 } else { len };
+    match usize::try_from(index) {
+        Ok(i) if i < limit => Ok(i),
+        _ => Err(list_error(format!("list index {} out of range for length {}", index, len))),
+    }
 }
 
-// Helper macro to get and update heap_ptr from WASM global
-macro_rules! get_heap_ptr {
-    ($caller:expr) => {{
-        let global = ($caller)
-            .get_export("__heap_base")
-            .and_then(|e| e.into_global())
-            .expect("missing __heap_base global");
-        let val = global.get(&mut *$caller);
-        match val {
-            wasmtime::Val::I32(v) => v as u32,
-            _ => panic!("__heap_base is not i32"),
-        }
-    }};
+/// Convert a char index range (-1 = open end) into a byte range of `s`.
+fn char_range_to_bytes(s: &str, start: i64, end: i64) -> std::ops::Range<usize> {
+    let count = s.chars().count() as i64;
+    let start = if start < 0 { 0 } else { start.min(count) };
+    let end = if end < 0 { count } else { end.min(count) }.max(start);
+    let byte_at = |ci: i64| {
+        s.char_indices()
+            .nth(ci as usize)
+            .map(|(b, _)| b)
+            .unwrap_or(s.len())
+    };
+    byte_at(start)..byte_at(end)
 }
 
-macro_rules! set_heap_ptr {
-    ($caller:expr, $new_ptr:expr) => {{
-        let global = ($caller)
-            .get_export("__heap_base")
-            .and_then(|e| e.into_global())
-            .expect("missing __heap_base global");
-        global
-            .set($caller, wasmtime::Val::I32($new_ptr as i32))
-            .expect("failed to set __heap_base");
-    }};
+/// 运行时配置：启用 SIMD，以及可选的尾调用（`return_call`）
+fn engine_config(tail_calls: bool) -> wasmtime::Config {
+    let mut config = wasmtime::Config::new();
+    config.wasm_simd(true); // 启用 WASM SIMD 指令
+    config.wasm_tail_call(tail_calls);
+    config
+}
+
+/// 当前平台的 wasmtime 是否支持尾调用；不支持时编译器把自递归尾调用改写为循环
+fn tail_calls_supported() -> bool {
+    wasmtime::Engine::new(&engine_config(true)).is_ok()
 }
 
 fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
     use rand::Rng;
     use wasmtime::*;
 
-    // 创建 Config 并启用 SIMD 支持
-    let mut config = Config::new();
-    config.wasm_simd(true); // 启用 WASM SIMD 指令
+    let config = engine_config(tail_calls_supported());
     let engine = Engine::new(&config).map_err(|e| format!("Failed to create engine: {}", e))?;
     let module = Module::new(&engine, wasm_bytes)
         .map_err(|e| format!("Failed to load WASM module: {:#?}", e))?;
 
     let state = RuntimeState {
         start_instant: std::time::Instant::now(),
-        tables: Vec::new(),
+        objects: Vec::new(),
         free_slots: Vec::new(),
-        table_children: std::collections::HashMap::new(),
+        children: std::collections::HashMap::new(),
+        parents: std::collections::HashMap::new(),
+        interp_frames: Vec::new(),
     };
     let mut store = Store::new(&engine, state);
     let mut linker = Linker::new(&engine);
@@ -548,7 +757,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 let b_bytes = data[b_ptr as usize..(b_ptr + b_len) as usize].to_vec();
 
                 let new_len = a_len + b_len;
-                let dest = get_heap_ptr!(&mut caller);
+                let dest = reserve_heap(&mut caller, new_len as usize);
 
                 // Write concatenated result into linear memory
                 let data_mut = memory.data_mut(&mut caller);
@@ -567,23 +776,13 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
         .map_err(|e| format!("Failed to register env.str_concat: {}", e))?;
 
     // Host function: env.table_new() -> i64
-    // Creates a new empty table and returns its handle (index into tables vec).
+    // Creates a new empty table and returns its handle (index into the objects vec).
     // Reuses freed slots when available.
     linker
         .func_wrap(
             "env",
             "table_new",
-            |mut caller: Caller<'_, RuntimeState>| -> i64 {
-                let state = caller.data_mut();
-                if let Some(slot) = state.free_slots.pop() {
-                    state.tables[slot] = Some(std::collections::HashMap::new());
-                    slot as i64
-                } else {
-                    let id = state.tables.len();
-                    state.tables.push(Some(std::collections::HashMap::new()));
-                    id as i64
-                }
-            },
+            |mut caller: Caller<'_, RuntimeState>| -> i64 { alloc_table(caller.data_mut()) },
         )
         .map_err(|e| format!("Failed to register env.table_new: {}", e))?;
 
@@ -612,23 +811,21 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                     String::new()
                 };
 
-                let id = table_id as usize;
-                if id < caller.data().tables.len() {
-                    if let Some(ref mut table) = caller.data_mut().tables[id] {
-                        table.insert(key_str, value);
-                    }
+                if let Some(table) = caller.data_mut().table_mut(table_id) {
+                    table.set(TableKey::from_name(key_str), value);
                 }
             },
         )
         .map_err(|e| format!("Failed to register env.table_set: {}", e))?;
 
     // Host function: env.table_get(table_id: i64, key: i64) -> i64
-    // key is a packed string. Reads the key from WASM memory, returns stored value or 0.
+    // key is a packed string. Reads the key from WASM memory and returns the stored
+    // value; reading a missing key is a runtime error.
     linker
         .func_wrap(
             "env",
             "table_get",
-            |mut caller: Caller<'_, RuntimeState>, table_id: i64, key: i64| -> i64 {
+            |mut caller: Caller<'_, RuntimeState>, table_id: i64, key: i64| -> wasmtime::Result<i64> {
                 let key_ptr = (key >> 32) as u32;
                 let key_len = (key & 0xFFFF_FFFF) as u32;
 
@@ -647,53 +844,22 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                     String::new()
                 };
 
-                let id = table_id as usize;
-                if id < caller.data().tables.len() {
-                    if let Some(ref table) = caller.data().tables[id] {
-                        table.get(&key_str).copied().unwrap_or(0)
-                    } else {
-                        0
-                    }
-                } else {
-                    0
-                }
+                let result = table_get(caller.data(), table_id, TableKey::from_name(key_str));
+                Ok(raise_on_err!(&mut caller, result))
             },
         )
         .map_err(|e| format!("Failed to register env.table_get: {}", e))?;
 
     // Host function: env.table_free(table_id: i64)
-    // Recursively frees a table and all its child tables.
-    // -1 sentinel is a no-op. Out-of-bounds or already-freed slots are ignored.
+    // Recursively frees a table or list and all the objects nested in it.
+    // -1 sentinel is a no-op. Out-of-bounds or already-freed slots are ignored,
+    // and so are objects nested in a live parent.
     linker
         .func_wrap(
             "env",
             "table_free",
             |mut caller: Caller<'_, RuntimeState>, table_id: i64| {
-                if table_id < 0 {
-                    return;
-                }
-                let id = table_id as usize;
-                let state = caller.data_mut();
-                if id >= state.tables.len() {
-                    return;
-                }
-                if state.tables[id].is_none() {
-                    return;
-                }
-                let mut stack = vec![id];
-                while let Some(current) = stack.pop() {
-                    if current >= state.tables.len() {
-                        continue;
-                    }
-                    if state.tables[current].is_none() {
-                        continue;
-                    }
-                    if let Some(children) = state.table_children.remove(&current) {
-                        stack.extend(children);
-                    }
-                    state.tables[current] = None;
-                    state.free_slots.push(current);
-                }
+                caller.data_mut().free(table_id);
             },
         )
         .map_err(|e| format!("Failed to register env.table_free: {}", e))?;
@@ -703,13 +869,8 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
         .func_wrap(
             "env",
             "print_float",
-            |_caller: Caller<'_, RuntimeState>, val: i64| {
-                let f = f64::from_bits(val as u64);
-                if f.fract() == 0.0 && f.is_finite() {
-                    println!("{:.1}", f);
-                } else {
-                    println!("{}", f);
-                }
+            |mut caller: Caller<'_, RuntimeState>, val: i64| {
+                println!("{}", format_default(&mut caller, val, format::TAG_FLOAT));
             },
         )
         .map_err(|e| format!("Failed to register env.print_float: {}", e))?;
@@ -740,17 +901,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 } else {
                     format!("{}", f)
                 };
-                let memory = caller
-                    .get_export("memory")
-                    .and_then(|e| e.into_memory())
-                    .expect("missing memory export");
-                let heap_ptr = get_heap_ptr!(&mut caller);
-                let bytes = s.as_bytes();
-                memory.data_mut(&mut caller)
-                    [heap_ptr as usize..heap_ptr as usize + bytes.len()]
-                    .copy_from_slice(bytes);
-                set_heap_ptr!(&mut caller, heap_ptr + bytes.len() as u32);
-                ((heap_ptr as i64) << 32) | (bytes.len() as i64)
+                alloc_packed_str(&mut caller, &s)
             },
         )
         .map_err(|e| format!("Failed to register env.float_to_str: {}", e))?;
@@ -776,17 +927,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
             "int_to_str",
             |mut caller: Caller<'_, RuntimeState>, val: i64| -> i64 {
                 let s = format!("{}", val);
-                let memory = caller
-                    .get_export("memory")
-                    .and_then(|e| e.into_memory())
-                    .expect("missing memory export");
-                let heap_ptr = get_heap_ptr!(&mut caller);
-                let bytes = s.as_bytes();
-                memory.data_mut(&mut caller)
-                    [heap_ptr as usize..heap_ptr as usize + bytes.len()]
-                    .copy_from_slice(bytes);
-                set_heap_ptr!(&mut caller, heap_ptr + bytes.len() as u32);
-                ((heap_ptr as i64) << 32) | (bytes.len() as i64)
+                alloc_packed_str(&mut caller, &s)
             },
         )
         .map_err(|e| format!("Failed to register env.int_to_str: {}", e))?;
@@ -816,14 +957,10 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                     String::new()
                 };
 
-                let pid = table_id as usize;
-                let cid = child_id as usize;
                 let state = caller.data_mut();
-                if pid < state.tables.len() {
-                    if let Some(ref mut table) = state.tables[pid] {
-                        table.insert(key_str, child_id);
-                    }
-                    state.table_children.entry(pid).or_default().push(cid);
+                if let Some(table) = state.table_mut(table_id) {
+                    table.set(TableKey::from_name(key_str), child_id);
+                    state.adopt(table_id, child_id);
                 }
             },
         )
@@ -835,38 +972,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
             "env",
             "print_vec",
             |mut caller: Caller<'_, RuntimeState>, val: i64| {
-                let ptr = (val >> 32) as u32;
-                let len = (val & 0xFFFF_FFFF) as u32;
-                if len == 0 {
-                    println!("[]");
-                    return;
-                }
-                let memory = caller
-                    .get_export("memory")
-                    .and_then(|e| e.into_memory())
-                    .expect("missing memory export");
-                let data = memory.data(&caller);
-                let mut elements = Vec::new();
-                for i in 0..len as usize {
-                    let offset = ptr as usize + i * 8;
-                    if offset + 8 <= data.len() {
-                        let bytes = &data[offset..offset + 8];
-                        let f = f64::from_le_bytes(bytes.try_into().unwrap());
-                        elements.push(f);
-                    }
-                }
-                print!("[");
-                for (i, e) in elements.iter().enumerate() {
-                    if i > 0 {
-                        print!(", ");
-                    }
-                    if e.fract() == 0.0 && e.is_finite() {
-                        print!("{:.1}", e);
-                    } else {
-                        print!("{}", e);
-                    }
-                }
-                println!("]");
+                println!("{}", format_default(&mut caller, val, format::TAG_VEC));
             },
         )
         .map_err(|e| format!("Failed to register env.print_vec: {}", e))?;
@@ -1198,38 +1304,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
             "env",
             "print_mat",
             |mut caller: Caller<'_, RuntimeState>, mat: i64| {
-                let ptr = (mat >> 32) as u32;
-                let meta = (mat & 0xFFFF_FFFF) as u32;
-                let rows = (meta >> 16) as usize;
-                let cols = (meta & 0xFFFF) as usize;
-                if rows == 0 || cols == 0 {
-                    println!("[[]]");
-                    return;
-                }
-                let memory = caller
-                    .get_export("memory")
-                    .and_then(|e| e.into_memory())
-                    .expect("missing memory export");
-                let data = memory.data(&caller);
-                print!("[");
-                for r in 0..rows {
-                    if r > 0 {
-                        print!("; ");
-                    }
-                    for c in 0..cols {
-                        if c > 0 {
-                            print!(", ");
-                        }
-                        let offset = ptr as usize + (r * cols + c) * 8;
-                        let val = f64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
-                        if val.fract() == 0.0 && val.is_finite() {
-                            print!("{:.1}", val);
-                        } else {
-                            print!("{}", val);
-                        }
-                    }
-                }
-                println!("]");
+                println!("{}", format_default(&mut caller, mat, format::TAG_MAT));
             },
         )
         .map_err(|e| format!("Failed to register env.print_mat: {}", e))?;
@@ -1239,7 +1314,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
         .func_wrap(
             "env",
             "mat_add",
-            |mut caller: Caller<'_, RuntimeState>, a: i64, b: i64| -> i64 {
+            |mut caller: Caller<'_, RuntimeState>, a: i64, b: i64| -> wasmtime::Result<i64> {
                 let ptr_a = (a >> 32) as u32;
                 let meta_a = (a & 0xFFFF_FFFF) as u32;
                 let rows_a = (meta_a >> 16) as usize;
@@ -1251,7 +1326,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 let cols_b = (meta_b & 0xFFFF) as usize;
 
                 if rows_a != rows_b || cols_a != cols_b {
-                    panic!("Matrix dimension mismatch");
+                    return raise(&mut caller, size_error("matrix dimension mismatch"));
                 }
 
                 let total = rows_a * cols_a;
@@ -1284,7 +1359,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 }
                 set_heap_ptr!(&mut caller, dest + (total * 8) as u32);
                 let result_meta = ((rows_a as i64) << 16) | (cols_a as i64);
-                ((dest as i64) << 32) | result_meta
+                Ok(((dest as i64) << 32) | result_meta)
             },
         )
         .map_err(|e| format!("Failed to register env.mat_add: {}", e))?;
@@ -1294,7 +1369,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
         .func_wrap(
             "env",
             "mat_sub",
-            |mut caller: Caller<'_, RuntimeState>, a: i64, b: i64| -> i64 {
+            |mut caller: Caller<'_, RuntimeState>, a: i64, b: i64| -> wasmtime::Result<i64> {
                 let ptr_a = (a >> 32) as u32;
                 let meta_a = (a & 0xFFFF_FFFF) as u32;
                 let rows_a = (meta_a >> 16) as usize;
@@ -1306,7 +1381,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 let cols_b = (meta_b & 0xFFFF) as usize;
 
                 if rows_a != rows_b || cols_a != cols_b {
-                    panic!("Matrix dimension mismatch");
+                    return raise(&mut caller, size_error("matrix dimension mismatch"));
                 }
 
                 let total = rows_a * cols_a;
@@ -1339,7 +1414,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 }
                 set_heap_ptr!(&mut caller, dest + (total * 8) as u32);
                 let result_meta = ((rows_a as i64) << 16) | (cols_a as i64);
-                ((dest as i64) << 32) | result_meta
+                Ok(((dest as i64) << 32) | result_meta)
             },
         )
         .map_err(|e| format!("Failed to register env.mat_sub: {}", e))?;
@@ -1349,7 +1424,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
         .func_wrap(
             "env",
             "mat_mul",
-            |mut caller: Caller<'_, RuntimeState>, a: i64, b: i64| -> i64 {
+            |mut caller: Caller<'_, RuntimeState>, a: i64, b: i64| -> wasmtime::Result<i64> {
                 let ptr_a = (a >> 32) as u32;
                 let meta_a = (a & 0xFFFF_FFFF) as u32;
                 let rows_a = (meta_a >> 16) as usize;
@@ -1361,7 +1436,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 let cols_b = (meta_b & 0xFFFF) as usize;
 
                 if cols_a != rows_b {
-                    panic!("Matrix dimension mismatch");
+                    return raise(&mut caller, size_error("matrix dimension mismatch"));
                 }
 
                 let memory = caller
@@ -1401,7 +1476,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 }
                 set_heap_ptr!(&mut caller, dest + (result.len() * 8) as u32);
                 let result_meta = ((rows_a as i64) << 16) | (cols_b as i64);
-                ((dest as i64) << 32) | result_meta
+                Ok(((dest as i64) << 32) | result_meta)
             },
         )
         .map_err(|e| format!("Failed to register env.mat_mul: {}", e))?;
@@ -1411,7 +1486,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
         .func_wrap(
             "env",
             "mat_vec_mul",
-            |mut caller: Caller<'_, RuntimeState>, mat: i64, vec: i64| -> i64 {
+            |mut caller: Caller<'_, RuntimeState>, mat: i64, vec: i64| -> wasmtime::Result<i64> {
                 let ptr_m = (mat >> 32) as u32;
                 let meta_m = (mat & 0xFFFF_FFFF) as u32;
                 let rows_m = (meta_m >> 16) as usize;
@@ -1421,7 +1496,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 let len_v = (vec & 0xFFFF_FFFF) as usize;
 
                 if cols_m != len_v {
-                    panic!("Dimension mismatch");
+                    return raise(&mut caller, size_error("dimension mismatch"));
                 }
 
                 let memory = caller
@@ -1457,7 +1532,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                         .copy_from_slice(&val.to_le_bytes());
                 }
                 set_heap_ptr!(&mut caller, dest + (result.len() * 8) as u32);
-                ((dest as i64) << 32) | (rows_m as i64)
+                Ok(((dest as i64) << 32) | (rows_m as i64))
             },
         )
         .map_err(|e| format!("Failed to register env.mat_vec_mul: {}", e))?;
@@ -1553,14 +1628,14 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
         .func_wrap(
             "env",
             "mat_det",
-            |mut caller: Caller<'_, RuntimeState>, mat: i64| -> i64 {
+            |mut caller: Caller<'_, RuntimeState>, mat: i64| -> wasmtime::Result<i64> {
                 let ptr_m = (mat >> 32) as u32;
                 let meta_m = (mat & 0xFFFF_FFFF) as u32;
                 let rows_m = (meta_m >> 16) as usize;
                 let cols_m = (meta_m & 0xFFFF) as usize;
 
                 if rows_m != cols_m {
-                    panic!("Determinant requires square matrix");
+                    return raise(&mut caller, size_error("determinant requires a square matrix"));
                 }
 
                 let n = rows_m;
@@ -1581,7 +1656,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                     }
                 }
 
-                calculate_determinant(&matrix).to_bits() as i64
+                Ok(calculate_determinant(&matrix).to_bits() as i64)
             },
         )
         .map_err(|e| format!("Failed to register env.mat_det: {}", e))?;
@@ -1591,14 +1666,14 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
         .func_wrap(
             "env",
             "mat_inv",
-            |mut caller: Caller<'_, RuntimeState>, mat: i64| -> i64 {
+            |mut caller: Caller<'_, RuntimeState>, mat: i64| -> wasmtime::Result<i64> {
                 let ptr_m = (mat >> 32) as u32;
                 let meta_m = (mat & 0xFFFF_FFFF) as u32;
                 let rows_m = (meta_m >> 16) as usize;
                 let cols_m = (meta_m & 0xFFFF) as usize;
 
                 if rows_m != cols_m {
-                    panic!("Inverse requires square matrix");
+                    return raise(&mut caller, size_error("inverse requires a square matrix"));
                 }
 
                 let n = rows_m;
@@ -1623,7 +1698,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 }
 
                 if !gauss_jordan_inversion(&mut matrix, n) {
-                    panic!("Matrix is singular");
+                    return raise(&mut caller, singular_error());
                 }
 
                 let mut result = vec![0.0; n * n];
@@ -1641,7 +1716,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 }
                 set_heap_ptr!(&mut caller, dest + (result.len() * 8) as u32);
                 let result_meta = ((rows_m as i64) << 16) | (cols_m as i64);
-                ((dest as i64) << 32) | result_meta
+                Ok(((dest as i64) << 32) | result_meta)
             },
         )
         .map_err(|e| format!("Failed to register env.mat_inv: {}", e))?;
@@ -1651,7 +1726,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
         .func_wrap(
             "env",
             "mat_get",
-            |mut caller: Caller<'_, RuntimeState>, mat: i64, linear_index: i64| -> i64 {
+            |mut caller: Caller<'_, RuntimeState>, mat: i64, linear_index: i64| -> wasmtime::Result<i64> {
                 let ptr = (mat >> 32) as u32;
                 let meta = (mat & 0xFFFF_FFFF) as u32;
                 let rows = (meta >> 16) as usize;
@@ -1659,7 +1734,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 let idx = linear_index as usize;
 
                 if idx >= rows * cols {
-                    panic!("Index out of bounds");
+                    return raise(&mut caller, list_error(format!("matrix index {} out of range for {} elements", linear_index, rows * cols)));
                 }
 
                 let memory = caller
@@ -1672,7 +1747,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                         .try_into()
                         .unwrap(),
                 );
-                val.to_bits() as i64
+                Ok(val.to_bits() as i64)
             },
         )
         .map_err(|e| format!("Failed to register env.mat_get: {}", e))?;
@@ -1682,7 +1757,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
         .func_wrap(
             "env",
             "mat_set",
-            |mut caller: Caller<'_, RuntimeState>, mat: i64, linear_index: i64, value: i64| {
+            |mut caller: Caller<'_, RuntimeState>, mat: i64, linear_index: i64, value: i64| -> wasmtime::Result<()> {
                 let ptr = (mat >> 32) as u32;
                 let meta = (mat & 0xFFFF_FFFF) as u32;
                 let rows = (meta >> 16) as usize;
@@ -1690,7 +1765,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 let idx = linear_index as usize;
 
                 if idx >= rows * cols {
-                    panic!("Index out of bounds");
+                    return raise(&mut caller, list_error(format!("matrix index {} out of range for {} elements", linear_index, rows * cols)));
                 }
 
                 let val = f64::from_bits(value as u64);
@@ -1701,6 +1776,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 let data_mut = memory.data_mut(&mut caller);
                 data_mut[ptr as usize + idx * 8..ptr as usize + (idx + 1) * 8]
                     .copy_from_slice(&val.to_le_bytes());
+                Ok(())
             },
         )
         .map_err(|e| format!("Failed to register env.mat_set: {}", e))?;
@@ -1712,14 +1788,14 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
         .func_wrap(
             "env",
             "mat_solve",
-            |mut caller: Caller<'_, RuntimeState>, a_val: i64, b_val: i64| -> i64 {
+            |mut caller: Caller<'_, RuntimeState>, a_val: i64, b_val: i64| -> wasmtime::Result<i64> {
                 // Extract matrix A
                 let a_ptr = (a_val >> 32) as u32;
                 let a_meta = (a_val & 0xFFFF_FFFF) as u32;
                 let a_rows = (a_meta >> 16) as usize;
                 let a_cols = (a_meta & 0xFFFF) as usize;
                 if a_rows == 0 || a_cols == 0 || a_rows != a_cols {
-                    return 0; // Must be square matrix
+                    return raise(&mut caller, size_error("solve requires a non-empty square matrix"));
                 }
 
                 let memory = caller
@@ -1749,7 +1825,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 };
 
                 if b_rows != a_rows {
-                    return 0; // Dimension mismatch
+                    return raise(&mut caller, size_error("dimension mismatch"));
                 }
 
                 // Read b
@@ -1782,7 +1858,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                         }
                     }
                     if lu[max_row][k].abs() < 1e-10 {
-                        return 0; // Singular matrix
+                        return raise(&mut caller, singular_error());
                     }
                     if max_row != k {
                         lu.swap(k, max_row);
@@ -1837,7 +1913,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                         data_mut[offset..offset + 8].copy_from_slice(&x[i][0].to_le_bytes());
                     }
                     set_heap_ptr!(&mut caller, dest + (n * 8) as u32);
-                    ((dest as i64) << 32) | (n as i64)
+                    Ok(((dest as i64) << 32) | (n as i64))
                 } else {
                     // Return as matrix
                     for i in 0..n {
@@ -1848,7 +1924,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                     }
                     set_heap_ptr!(&mut caller, dest + (n * b_cols * 8) as u32);
                     let meta = ((n as i64) << 16) | (b_cols as i64);
-                    ((dest as i64) << 32) | meta
+                    Ok(((dest as i64) << 32) | meta)
                 }
             },
         )
@@ -1889,7 +1965,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                     let offset = dest as usize + i * 8;
                     data_mut[offset..offset + 8].copy_from_slice(&val.to_le_bytes());
                 }
-                set_heap_ptr!(&mut caller, dest + (len * 8) as u32);
+                set_heap_ptr!(&mut caller, dest + len * 8);
                 ((dest as i64) << 32) | (len as i64)
             },
         )
@@ -2142,9 +2218,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                         }
                     } else {
                         // Out of bounds → fill with zeros
-                        for _ in 0..cols {
-                            result_data.push(0.0);
-                        }
+                        result_data.extend(std::iter::repeat_n(0.0, cols));
                     }
                 }
 
@@ -2533,16 +2607,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 for i in 0..len {
                     let offset = ptr as usize + i * 8;
                     let val = f64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
-                    let matches = match op {
-                        0 => val > threshold_f64,   // >
-                        1 => val < threshold_f64,   // <
-                        2 => val >= threshold_f64,  // >=
-                        3 => val <= threshold_f64,  // <=
-                        4 => val == threshold_f64,  // ==
-                        5 => val != threshold_f64,  // !=
-                        _ => false,
-                    };
-                    if matches {
+                    if compare(op, val, threshold_f64) {
                         result_data.push(val);
                     }
                 }
@@ -2585,16 +2650,7 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
                 for i in 0..total {
                     let offset = ptr as usize + i * 8;
                     let val = f64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
-                    let matches = match op {
-                        0 => val > threshold_f64,
-                        1 => val < threshold_f64,
-                        2 => val >= threshold_f64,
-                        3 => val <= threshold_f64,
-                        4 => val == threshold_f64,
-                        5 => val != threshold_f64,
-                        _ => false,
-                    };
-                    if matches {
+                    if compare(op, val, threshold_f64) {
                         result_data.push(val);
                     }
                 }
@@ -2612,19 +2668,840 @@ fn execute_wasm(wasm_bytes: &[u8]) -> Result<(), String> {
         )
         .map_err(|e| format!("Failed to register env.mat_mask: {}", e))?;
 
-    let instance = linker
-        .instantiate(&mut store, &module)
-        .map_err(|e| format!("Failed to instantiate module: {}", e))?;
-
-    // heap_ptr is now managed via __heap_base global in WASM, no initialization needed
+    // ── String library ──────────────────────────────────────────────
+    // All indices are character (not byte) offsets so that UTF-8 text
+    // slices on character boundaries.
 
-    let start = instance
+    // Host function: env.str_len(s: i64) -> i64
+    linker
+        .func_wrap(
+            "env",
+            "str_len",
+            |mut caller: Caller<'_, RuntimeState>, s: i64| -> i64 {
+                read_packed_str(&mut caller, s).chars().count() as i64
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_len: {}", e))?;
+
+    // Host function: env.str_char_at(s: i64, i: i64) -> i64
+    // Out-of-range indices yield the empty string.
+    linker
+        .func_wrap(
+            "env",
+            "str_char_at",
+            |mut caller: Caller<'_, RuntimeState>, s: i64, i: i64| -> i64 {
+                let text = read_packed_str(&mut caller, s);
+                let ch = if i >= 0 { text.chars().nth(i as usize) } else { None };
+                let mut buf = [0u8; 4];
+                let piece = ch.map(|c| &*c.encode_utf8(&mut buf)).unwrap_or("");
+                alloc_packed_str(&mut caller, piece)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_char_at: {}", e))?;
+
+    // Host function: env.str_slice(s: i64, start: i64, end: i64) -> i64
+    // -1 for start/end means "from beginning" / "to end", like vec_slice.
+    linker
+        .func_wrap(
+            "env",
+            "str_slice",
+            |mut caller: Caller<'_, RuntimeState>, s: i64, start: i64, end: i64| -> i64 {
+                let text = read_packed_str(&mut caller, s);
+                let range = char_range_to_bytes(&text, start, end);
+                alloc_packed_str(&mut caller, &text[range])
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_slice: {}", e))?;
+
+    // Host function: env.str_find(s: i64, sub: i64) -> i64 (char index, or -1)
+    linker
+        .func_wrap(
+            "env",
+            "str_find",
+            |mut caller: Caller<'_, RuntimeState>, s: i64, sub: i64| -> i64 {
+                let text = read_packed_str(&mut caller, s);
+                let needle = read_packed_str(&mut caller, sub);
+                match text.find(&needle) {
+                    Some(byte_idx) => text[..byte_idx].chars().count() as i64,
+                    None => -1,
+                }
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_find: {}", e))?;
+
+    // Host function: env.str_contains(s: i64, sub: i64) -> i64 (bool)
+    linker
+        .func_wrap(
+            "env",
+            "str_contains",
+            |mut caller: Caller<'_, RuntimeState>, s: i64, sub: i64| -> i64 {
+                let text = read_packed_str(&mut caller, s);
+                let needle = read_packed_str(&mut caller, sub);
+                text.contains(&needle) as i64
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_contains: {}", e))?;
+
+    // Host function: env.str_starts_with(s: i64, prefix: i64) -> i64 (bool)
+    linker
+        .func_wrap(
+            "env",
+            "str_starts_with",
+            |mut caller: Caller<'_, RuntimeState>, s: i64, prefix: i64| -> i64 {
+                let text = read_packed_str(&mut caller, s);
+                let prefix = read_packed_str(&mut caller, prefix);
+                text.starts_with(&prefix) as i64
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_starts_with: {}", e))?;
+
+    // Host function: env.str_ends_with(s: i64, suffix: i64) -> i64 (bool)
+    linker
+        .func_wrap(
+            "env",
+            "str_ends_with",
+            |mut caller: Caller<'_, RuntimeState>, s: i64, suffix: i64| -> i64 {
+                let text = read_packed_str(&mut caller, s);
+                let suffix = read_packed_str(&mut caller, suffix);
+                text.ends_with(&suffix) as i64
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_ends_with: {}", e))?;
+
+    // Host function: env.str_replace(s: i64, from: i64, to: i64) -> i64
+    linker
+        .func_wrap(
+            "env",
+            "str_replace",
+            |mut caller: Caller<'_, RuntimeState>, s: i64, from: i64, to: i64| -> i64 {
+                let text = read_packed_str(&mut caller, s);
+                let from = read_packed_str(&mut caller, from);
+                let to = read_packed_str(&mut caller, to);
+                let result = if from.is_empty() { text } else { text.replace(&from, &to) };
+                alloc_packed_str(&mut caller, &result)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_replace: {}", e))?;
+
+    // Host function: env.str_split(s: i64, sep: i64) -> i64
+    // Returns a new table with the parts in its array part.
+    // An empty separator splits into characters.
+    linker
+        .func_wrap(
+            "env",
+            "str_split",
+            |mut caller: Caller<'_, RuntimeState>, s: i64, sep: i64| -> i64 {
+                let text = read_packed_str(&mut caller, s);
+                let sep = read_packed_str(&mut caller, sep);
+                let parts: Vec<String> = if sep.is_empty() {
+                    text.chars().map(String::from).collect()
+                } else {
+                    text.split(sep.as_str()).map(String::from).collect()
+                };
+                let packed: Vec<i64> = parts
+                    .iter()
+                    .map(|p| alloc_packed_str(&mut caller, p))
+                    .collect();
+                let state = caller.data_mut();
+                let id = alloc_table(state);
+                if let Some(table) = state.table_mut(id) {
+                    for val in packed {
+                        table.insert(None, val);
+                    }
+                }
+                id
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_split: {}", e))?;
+
+    // Host function: env.str_join(table: i64, sep: i64) -> i64
+    // Joins the string entries of the table's array part.
+    linker
+        .func_wrap(
+            "env",
+            "str_join",
+            |mut caller: Caller<'_, RuntimeState>, table_id: i64, sep: i64| -> i64 {
+                let sep = read_packed_str(&mut caller, sep);
+                let mut packed_parts = Vec::new();
+                if let Some(table) = caller.data().table(table_id) {
+                    packed_parts.extend_from_slice(table.array());
+                }
+                let parts: Vec<String> = packed_parts
+                    .into_iter()
+                    .map(|p| read_packed_str(&mut caller, p))
+                    .collect();
+                alloc_packed_str(&mut caller, &parts.join(&sep))
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_join: {}", e))?;
+
+    // Host function: env.str_upper(s: i64) -> i64
+    linker
+        .func_wrap(
+            "env",
+            "str_upper",
+            |mut caller: Caller<'_, RuntimeState>, s: i64| -> i64 {
+                let text = read_packed_str(&mut caller, s).to_uppercase();
+                alloc_packed_str(&mut caller, &text)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_upper: {}", e))?;
+
+    // Host function: env.str_lower(s: i64) -> i64
+    linker
+        .func_wrap(
+            "env",
+            "str_lower",
+            |mut caller: Caller<'_, RuntimeState>, s: i64| -> i64 {
+                let text = read_packed_str(&mut caller, s).to_lowercase();
+                alloc_packed_str(&mut caller, &text)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_lower: {}", e))?;
+
+    // Host function: env.str_trim(s: i64) -> i64
+    // Trimming only narrows the range, so no allocation is needed.
+    linker
+        .func_wrap(
+            "env",
+            "str_trim",
+            |mut caller: Caller<'_, RuntimeState>, s: i64| -> i64 {
+                let text = read_packed_str(&mut caller, s);
+                let trimmed = text.trim_start();
+                let lead = (text.len() - trimmed.len()) as i64;
+                let len = trimmed.trim_end().len() as i64;
+                (((s >> 32) + lead) << 32) | len
+            },
+        )
+        .map_err(|e| format!("Failed to register env.str_trim: {}", e))?;
+
+    // Host function: env.parse_int(s: i64) -> i64 (0 if not a number)
+    linker
+        .func_wrap(
+            "env",
+            "parse_int",
+            |mut caller: Caller<'_, RuntimeState>, s: i64| -> i64 {
+                read_packed_str(&mut caller, s).trim().parse::<i64>().unwrap_or(0)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.parse_int: {}", e))?;
+
+    // Host function: env.parse_float(s: i64) -> i64 (f64 bits, 0.0 if not a number)
+    linker
+        .func_wrap(
+            "env",
+            "parse_float",
+            |mut caller: Caller<'_, RuntimeState>, s: i64| -> i64 {
+                let f = read_packed_str(&mut caller, s).trim().parse::<f64>().unwrap_or(0.0);
+                f.to_bits() as i64
+            },
+        )
+        .map_err(|e| format!("Failed to register env.parse_float: {}", e))?;
+
+    // Host function: env.table_len(table_id: i64) -> i64 (length of the array part)
+    linker
+        .func_wrap(
+            "env",
+            "table_len",
+            |caller: Caller<'_, RuntimeState>, table_id: i64| -> i64 {
+                caller.data().table(table_id).map_or(0, |table| table.len() as i64)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.table_len: {}", e))?;
+
+    // Host function: env.table_geti(table_id: i64, index: i64) -> i64
+    // Integer-keyed read that skips the string conversion; a missing key is an error.
+    linker
+        .func_wrap(
+            "env",
+            "table_geti",
+            |mut caller: Caller<'_, RuntimeState>, table_id: i64, index: i64| -> wasmtime::Result<i64> {
+                let result = table_get(caller.data(), table_id, TableKey::Index(index));
+                Ok(raise_on_err!(&mut caller, result))
+            },
+        )
+        .map_err(|e| format!("Failed to register env.table_geti: {}", e))?;
+
+    // Host function: env.table_get_opt(table_id: i64, key: i64) -> i64
+    // Read for `t?.field`: nil when the table is nil or lacks the key.
+    linker
+        .func_wrap(
+            "env",
+            "table_get_opt",
+            |mut caller: Caller<'_, RuntimeState>, table_id: i64, key: i64| -> i64 {
+                if table_id == format::NIL {
+                    return format::NIL;
+                }
+                let key = TableKey::from_name(read_packed_str(&mut caller, key));
+                table_get(caller.data(), table_id, key).unwrap_or(format::NIL)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.table_get_opt: {}", e))?;
+
+    // Host function: env.table_seti(table_id: i64, index: i64, value: i64)
+    linker
+        .func_wrap(
+            "env",
+            "table_seti",
+            |mut caller: Caller<'_, RuntimeState>, table_id: i64, index: i64, value: i64| {
+                if let Some(table) = caller.data_mut().table_mut(table_id) {
+                    table.set(TableKey::Index(index), value);
+                }
+            },
+        )
+        .map_err(|e| format!("Failed to register env.table_seti: {}", e))?;
+
+    // Host function: env.table_has(table_id: i64, key: i64, tag: i64) -> i64 (1 or 0)
+    // The key is an int, or a packed string when tag is TAG_STR.
+    linker
+        .func_wrap(
+            "env",
+            "table_has",
+            |mut caller: Caller<'_, RuntimeState>, table_id: i64, key: i64, tag: i64| -> i64 {
+                let key = table_key(&mut caller, key, tag);
+                caller.data().table(table_id).is_some_and(|table| table.has(&key)) as i64
+            },
+        )
+        .map_err(|e| format!("Failed to register env.table_has: {}", e))?;
+
+    // Host function: env.table_remove(table_id: i64, key: i64, tag: i64) -> i64
+    // Removes an entry and returns its value; later array elements shift down.
+    linker
+        .func_wrap(
+            "env",
+            "table_remove",
+            |mut caller: Caller<'_, RuntimeState>, table_id: i64, key: i64, tag: i64| -> wasmtime::Result<i64> {
+                let key = table_key(&mut caller, key, tag);
+                let state = caller.data_mut();
+                let Some(table) = state.table_mut(table_id) else {
+                    return raise(&mut caller, list_error("remove: not a table".to_string()));
+                };
+                let Some(value) = table.remove(&key) else {
+                    return raise(&mut caller, list_error(format!("table has no key {}", key)));
+                };
+                // A nested table no longer belongs to this one
+                state.release(table_id, value);
+                Ok(value)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.table_remove: {}", e))?;
+
+    // Host function: env.table_insert(table_id: i64, index: i64, value: i64) -> i64 (the table)
+    // Inserts into the array part before index, or appends when index is -1.
+    linker
+        .func_wrap(
+            "env",
+            "table_insert",
+            |mut caller: Caller<'_, RuntimeState>, table_id: i64, index: i64, value: i64| -> wasmtime::Result<i64> {
+                let Some(table) = caller.data_mut().table_mut(table_id) else {
+                    return raise(&mut caller, list_error("insert: not a table".to_string()));
+                };
+                let len = table.len();
+                if !table.insert((index != -1).then_some(index), value) {
+                    return raise(&mut caller, list_error(format!("table index {} out of range for length {}", index, len)));
+                }
+                Ok(table_id)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.table_insert: {}", e))?;
+
+    // Host function: env.table_keys(table_id: i64) -> i64 (a new list of str)
+    // Array indices come first, in order, then the other keys sorted by name.
+    linker
+        .func_wrap(
+            "env",
+            "table_keys",
+            |mut caller: Caller<'_, RuntimeState>, table_id: i64| -> i64 {
+                let keys = caller.data().table(table_id).map(table::Table::keys).unwrap_or_default();
+                let items = keys
+                    .into_iter()
+                    .map(|key| {
+                        let name = match key {
+                            TableKey::Index(i) => i.to_string(),
+                            TableKey::Name(name) => name,
+                        };
+                        (format::TAG_STR, alloc_packed_str(&mut caller, &name))
+                    })
+                    .collect();
+                caller.data_mut().alloc(HeapObject::List(items))
+            },
+        )
+        .map_err(|e| format!("Failed to register env.table_keys: {}", e))?;
+
+    // Host function: env.table_values(table_id: i64, tag: i64) -> i64 (a new list)
+    // Values in the order of table_keys; tables store no tags, so the caller passes one.
+    linker
+        .func_wrap(
+            "env",
+            "table_values",
+            |mut caller: Caller<'_, RuntimeState>, table_id: i64, tag: i64| -> i64 {
+                let state = caller.data_mut();
+                let values = state.table(table_id).map(table::Table::values).unwrap_or_default();
+                let items = values.into_iter().map(|value| (tag, value)).collect();
+                state.alloc(HeapObject::List(items))
+            },
+        )
+        .map_err(|e| format!("Failed to register env.table_values: {}", e))?;
+
+    // ── Lists ───────────────────────────────────────────────────────
+    // A list is a host object like a table; its elements are tagged values.
+
+    // Host function: env.list_new() -> i64 (handle of a new empty list)
+    linker
+        .func_wrap("env", "list_new", |mut caller: Caller<'_, RuntimeState>| -> i64 {
+            caller.data_mut().alloc(HeapObject::List(Vec::new()))
+        })
+        .map_err(|e| format!("Failed to register env.list_new: {}", e))?;
+
+    // Host function: env.list_push(list: i64, value: i64, tag: i64) -> i64 (the list)
+    linker
+        .func_wrap(
+            "env",
+            "list_push",
+            |mut caller: Caller<'_, RuntimeState>, list: i64, value: i64, tag: i64| -> wasmtime::Result<i64> {
+                let state = caller.data_mut();
+                if state.list(list).is_none() {
+                    return Err(wasmtime::Error::msg("push: not a list"));
+                }
+                let element = list_element(state, list, value, tag);
+                state.list_mut(list).unwrap().push(element);
+                Ok(list)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.list_push: {}", e))?;
+
+    // Host function: env.list_insert(list: i64, index: i64, value: i64, tag: i64) -> i64 (the list)
+    linker
+        .func_wrap(
+            "env",
+            "list_insert",
+            |mut caller: Caller<'_, RuntimeState>, list: i64, index: i64, value: i64, tag: i64| -> wasmtime::Result<i64> {
+                let state = caller.data_mut();
+                let Some(items) = state.list(list) else {
+                    return raise(&mut caller, list_error("insert: not a list".to_string()));
+                };
+                let i = raise_on_err!(&mut caller, list_index(index, items.len(), true));
+                let element = list_element(state, list, value, tag);
+                state.list_mut(list).unwrap().insert(i, element);
+                Ok(list)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.list_insert: {}", e))?;
+
+    // Host function: env.list_get(list: i64, index: i64) -> i64
+    linker
+        .func_wrap(
+            "env",
+            "list_get",
+            |mut caller: Caller<'_, RuntimeState>, list: i64, index: i64| -> wasmtime::Result<i64> {
+                let Some(items) = caller.data().list(list) else {
+                    return raise(&mut caller, list_error("index: not a list".to_string()));
+                };
+                let i = raise_on_err!(&mut caller, list_index(index, items.len(), false));
+                Ok(items[i].1)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.list_get: {}", e))?;
+
+    // Host function: env.list_set(list: i64, index: i64, value: i64, tag: i64)
+    linker
+        .func_wrap(
+            "env",
+            "list_set",
+            |mut caller: Caller<'_, RuntimeState>, list: i64, index: i64, value: i64, tag: i64| -> wasmtime::Result<()> {
+                let state = caller.data_mut();
+                let Some(items) = state.list(list) else {
+                    return raise(&mut caller, list_error("index: not a list".to_string()));
+                };
+                let i = raise_on_err!(&mut caller, list_index(index, items.len(), false));
+                let old = items[i];
+                drop_element(state, list, old);
+                let element = list_element(state, list, value, tag);
+                state.list_mut(list).unwrap()[i] = element;
+                Ok(())
+            },
+        )
+        .map_err(|e| format!("Failed to register env.list_set: {}", e))?;
+
+    // Host function: env.list_pop(list: i64) -> i64 (the removed last element)
+    linker
+        .func_wrap(
+            "env",
+            "list_pop",
+            |mut caller: Caller<'_, RuntimeState>, list: i64| -> wasmtime::Result<i64> {
+                let state = caller.data_mut();
+                match state.list_mut(list).map(|items| items.pop()) {
+                    Some(Some(element)) => Ok(drop_element(state, list, element)),
+                    Some(None) => raise(&mut caller, list_error("pop from an empty list".to_string())),
+                    None => raise(&mut caller, list_error("pop: not a list".to_string())),
+                }
+            },
+        )
+        .map_err(|e| format!("Failed to register env.list_pop: {}", e))?;
+
+    // Host function: env.list_remove(list: i64, index: i64) -> i64 (the removed element)
+    linker
+        .func_wrap(
+            "env",
+            "list_remove",
+            |mut caller: Caller<'_, RuntimeState>, list: i64, index: i64| -> wasmtime::Result<i64> {
+                let state = caller.data_mut();
+                let Some(items) = state.list_mut(list) else {
+                    return raise(&mut caller, list_error("remove: not a list".to_string()));
+                };
+                let i = raise_on_err!(&mut caller, list_index(index, items.len(), false));
+                let element = items.remove(i);
+                Ok(drop_element(state, list, element))
+            },
+        )
+        .map_err(|e| format!("Failed to register env.list_remove: {}", e))?;
+
+    // Host function: env.list_len(list: i64) -> i64
+    linker
+        .func_wrap(
+            "env",
+            "list_len",
+            |caller: Caller<'_, RuntimeState>, list: i64| -> i64 {
+                caller.data().list(list).map_or(0, |items| items.len() as i64)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.list_len: {}", e))?;
+
+    // Host function: env.list_slice(list: i64, start: i64, end: i64) -> i64
+    // A new list sharing the elements in [start, end); -1 means an open end.
+    // The slice does not own the tables and lists it shares.
+    linker
+        .func_wrap(
+            "env",
+            "list_slice",
+            |mut caller: Caller<'_, RuntimeState>, list: i64, start: i64, end: i64| -> wasmtime::Result<i64> {
+                let state = caller.data_mut();
+                let Some(items) = state.list(list) else {
+                    return Err(wasmtime::Error::msg("slice: not a list"));
+                };
+                let len = items.len() as i64;
+                let start = if start < 0 { 0 } else { start.min(len) } as usize;
+                let end = if end < 0 { len } else { end.min(len) } as usize;
+                let slice = items[start..end.max(start)].to_vec();
+                Ok(state.alloc(HeapObject::List(slice)))
+            },
+        )
+        .map_err(|e| format!("Failed to register env.list_slice: {}", e))?;
+
+    // Host function: env.list_pick(dst: i64, src: i64, index: i64) -> i64 (dst)
+    // Append src[index] to dst with its tag; like a slice, dst does not own it.
+    linker
+        .func_wrap(
+            "env",
+            "list_pick",
+            |mut caller: Caller<'_, RuntimeState>, dst: i64, src: i64, index: i64| -> wasmtime::Result<i64> {
+                let state = caller.data_mut();
+                let Some(items) = state.list(src) else {
+                    return raise(&mut caller, list_error("index: not a list".to_string()));
+                };
+                let i = raise_on_err!(&mut caller, list_index(index, items.len(), false));
+                let item = items[i];
+                let Some(out) = state.list_mut(dst) else {
+                    return raise(&mut caller, list_error("push: not a list".to_string()));
+                };
+                out.push(item);
+                Ok(dst)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.list_pick: {}", e))?;
+
+    // Host function: env.sort_order(keys: i64, n: i64, is_float: i64)
+    // Sort the n i64 (or f64 when is_float != 0) keys at address `keys` and
+    // overwrite them with the indices of the keys in ascending, stable order.
+    linker
+        .func_wrap(
+            "env",
+            "sort_order",
+            |mut caller: Caller<'_, RuntimeState>, keys: i64, n: i64, is_float: i64| {
+                let memory = caller
+                    .get_export("memory")
+                    .and_then(|e| e.into_memory())
+                    .expect("missing memory export");
+                let data = memory.data_mut(&mut caller);
+                let start = keys as usize;
+                let Some(bytes) = data.get_mut(start..start + n.max(0) as usize * 8) else {
+                    return;
+                };
+                let values: Vec<i64> = bytes
+                    .chunks_exact(8)
+                    .map(|chunk| i64::from_le_bytes(chunk.try_into().unwrap()))
+                    .collect();
+                let mut order: Vec<usize> = (0..values.len()).collect();
+                if is_float != 0 {
+                    order.sort_by(|&a, &b| {
+                        f64::from_bits(values[a] as u64).total_cmp(&f64::from_bits(values[b] as u64))
+                    });
+                } else {
+                    order.sort_by_key(|&i| values[i]);
+                }
+                for (chunk, index) in bytes.chunks_exact_mut(8).zip(order) {
+                    chunk.copy_from_slice(&(index as i64).to_le_bytes());
+                }
+            },
+        )
+        .map_err(|e| format!("Failed to register env.sort_order: {}", e))?;
+
+    // Host function: env.print_list(list: i64) — print as [1, "two", [3.0]]
+    linker
+        .func_wrap(
+            "env",
+            "print_list",
+            |mut caller: Caller<'_, RuntimeState>, list: i64| {
+                println!("{}", format_default(&mut caller, list, format::TAG_LIST));
+            },
+        )
+        .map_err(|e| format!("Failed to register env.print_list: {}", e))?;
+
+    // ── Math library ────────────────────────────────────────────────
+    // sqrt/abs/floor/ceil/min/max are native WASM instructions on scalars; the
+    // transcendental functions and all element-wise vec/mat forms live here.
+
+    for (name, op) in [("math_sin", 5), ("math_cos", 6), ("math_exp", 7), ("math_log", 8)] {
+        // Host function: env.math_<fn>(x: i64) -> i64 (f64 bits)
+        linker
+            .func_wrap("env", name, move |_caller: Caller<'_, RuntimeState>, x: i64| -> i64 {
+                math_unary(op, f64::from_bits(x as u64)).to_bits() as i64
+            })
+            .map_err(|e| format!("Failed to register env.{}: {}", name, e))?;
+    }
+
+    // Host function: env.math_atan2(y: i64, x: i64) -> i64 (f64 bits)
+    linker
+        .func_wrap(
+            "env",
+            "math_atan2",
+            |_caller: Caller<'_, RuntimeState>, y: i64, x: i64| -> i64 {
+                f64::from_bits(y as u64).atan2(f64::from_bits(x as u64)).to_bits() as i64
+            },
+        )
+        .map_err(|e| format!("Failed to register env.math_atan2: {}", e))?;
+
+    // Host function: env.math_map(op: i64, value: i64, shape: i64) -> i64
+    // Applies a unary op to every element; shape: 0 = vec, 1 = mat
+    linker
+        .func_wrap(
+            "env",
+            "math_map",
+            |mut caller: Caller<'_, RuntimeState>, op: i64, value: i64, shape: i64| -> i64 {
+                let len = array_len(value, shape == 1);
+                let result: Vec<f64> = read_f64s(&mut caller, (value >> 32) as u32, len)
+                    .into_iter()
+                    .map(|x| math_unary(op, x))
+                    .collect();
+                let dest = alloc_f64s(&mut caller, &result);
+                ((dest as i64) << 32) | (value & 0xFFFF_FFFF)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.math_map: {}", e))?;
+
+    // Host function: env.math_map2(op: i64, a: i64, b: i64, shape: i64) -> i64
+    // Applies a binary op element-wise; shape: 0 = vec/scalar, 1 = mat/scalar,
+    // 2 = vec/vec, 3 = mat/mat (both operands must have the same size)
+    linker
+        .func_wrap(
+            "env",
+            "math_map2",
+            |mut caller: Caller<'_, RuntimeState>, op: i64, a: i64, b: i64, shape: i64| -> wasmtime::Result<i64> {
+                let is_mat = shape & 1 == 1;
+                let len = array_len(a, is_mat);
+                let lhs = read_f64s(&mut caller, (a >> 32) as u32, len);
+                let rhs = if shape >= 2 {
+                    if (a & 0xFFFF_FFFF) != (b & 0xFFFF_FFFF) {
+                        return raise(&mut caller, size_error("math: vec/mat operands have different sizes"));
+                    }
+                    read_f64s(&mut caller, (b >> 32) as u32, len)
+                } else {
+                    vec![f64::from_bits(b as u64); len]
+                };
+                let result: Vec<f64> = lhs.iter().zip(&rhs).map(|(&x, &y)| math_binary(op, x, y)).collect();
+                let dest = alloc_f64s(&mut caller, &result);
+                Ok(((dest as i64) << 32) | (a & 0xFFFF_FFFF))
+            },
+        )
+        .map_err(|e| format!("Failed to register env.math_map2: {}", e))?;
+
+    // Host function: env.math_where(op: i64, l: i64, r: i64, a: i64, b: i64, shape: i64) -> i64
+    // Element-wise `l op r ? a : b`; shape bit 0 = mat, bits 1-4 mark which of
+    // l, r, a, b are vec/mat (the others are f64 scalars, broadcast to every element)
+    linker
+        .func_wrap(
+            "env",
+            "math_where",
+            |mut caller: Caller<'_, RuntimeState>, op: i64, l: i64, r: i64, a: i64, b: i64, shape: i64| -> wasmtime::Result<i64> {
+                let is_mat = shape & 1 == 1;
+                let operands = [l, r, a, b];
+                let is_array = |i: usize| (shape >> (i + 1)) & 1 == 1;
+                let Some(first) = (0..4).find(|&i| is_array(i)).map(|i| operands[i]) else {
+                    return Err(wasmtime::Error::msg("where: no vec/mat operand"));
+                };
+                if (0..4).any(|i| is_array(i) && (operands[i] & 0xFFFF_FFFF) != (first & 0xFFFF_FFFF)) {
+                    return raise(&mut caller, size_error("where: vec/mat operands have different sizes"));
+                }
+                let len = array_len(first, is_mat);
+                let values: Vec<Vec<f64>> = (0..4)
+                    .map(|i| {
+                        if is_array(i) {
+                            read_f64s(&mut caller, (operands[i] >> 32) as u32, len)
+                        } else {
+                            vec![f64::from_bits(operands[i] as u64); len]
+                        }
+                    })
+                    .collect();
+                let result: Vec<f64> = (0..len)
+                    .map(|i| if compare(op, values[0][i], values[1][i]) { values[2][i] } else { values[3][i] })
+                    .collect();
+                let dest = alloc_f64s(&mut caller, &result);
+                Ok(((dest as i64) << 32) | (first & 0xFFFF_FFFF))
+            },
+        )
+        .map_err(|e| format!("Failed to register env.math_where: {}", e))?;
+
+    // ── String interpolation and formatted output ───────────────────
+    // f"...", print(a, b, ...), write, printf and format open a host frame,
+    // push their parts with a type tag, then either print the result or copy
+    // it into linear memory in one allocation.
+
+    // Host function: env.interp_begin() — open a new frame
+    linker
+        .func_wrap("env", "interp_begin", |mut caller: Caller<'_, RuntimeState>| {
+            caller.data_mut().interp_frames.push(InterpFrame::default());
+        })
+        .map_err(|e| format!("Failed to register env.interp_begin: {}", e))?;
+
+    // Host function: env.interp_push(value: i64, tag: i64) — append a formatted part
+    // tag: 0 = int, 1 = float, 2 = str, 3 = vec, 4 = mat
+    linker
+        .func_wrap(
+            "env",
+            "interp_push",
+            |mut caller: Caller<'_, RuntimeState>, value: i64, tag: i64| {
+                let text = format_default(&mut caller, value, tag);
+                if let Some(frame) = caller.data_mut().interp_frames.last_mut() {
+                    frame.text.push_str(&text);
+                }
+            },
+        )
+        .map_err(|e| format!("Failed to register env.interp_push: {}", e))?;
+
+    // Host function: env.interp_arg(value: i64, tag: i64) — queue a raw argument
+    linker
+        .func_wrap(
+            "env",
+            "interp_arg",
+            |mut caller: Caller<'_, RuntimeState>, value: i64, tag: i64| {
+                let value = read_value(&mut caller, value, tag);
+                if let Some(frame) = caller.data_mut().interp_frames.last_mut() {
+                    frame.args.push(value);
+                }
+            },
+        )
+        .map_err(|e| format!("Failed to register env.interp_arg: {}", e))?;
+
+    // Host function: env.interp_format(fmt: i64) — expand fmt against the queued arguments;
+    // on a bad template the frame is closed, as the script jumps past its end
+    linker
+        .func_wrap(
+            "env",
+            "interp_format",
+            |mut caller: Caller<'_, RuntimeState>, fmt: i64| -> wasmtime::Result<()> {
+                let template = read_packed_str(&mut caller, fmt);
+                let frames = &mut caller.data_mut().interp_frames;
+                if let Some(frame) = frames.last_mut() {
+                    let args = std::mem::take(&mut frame.args);
+                    match format::format_template(&template, &args) {
+                        Ok(text) => frame.text.push_str(&text),
+                        Err(message) => {
+                            frames.pop();
+                            return raise(&mut caller, HostError { code: ERROR_FORMAT, message });
+                        }
+                    }
+                }
+                Ok(())
+            },
+        )
+        .map_err(|e| format!("Failed to register env.interp_format: {}", e))?;
+
+    // Host function: env.interp_print(newline: i64) — close the frame and print it;
+    // queued arguments are separated by spaces
+    linker
+        .func_wrap(
+            "env",
+            "interp_print",
+            |mut caller: Caller<'_, RuntimeState>, newline: i64| {
+                let frame = caller.data_mut().interp_frames.pop().unwrap_or_default();
+                let spec = format::FormatSpec::default();
+                let mut parts: Vec<String> = Vec::new();
+                if !frame.text.is_empty() {
+                    parts.push(frame.text);
+                }
+                parts.extend(frame.args.iter().map(|v| format::format_value(v, &spec)));
+                let line = parts.join(" ");
+                if newline != 0 {
+                    println!("{}", line);
+                } else {
+                    use std::io::Write;
+                    print!("{}", line);
+                    let _ = std::io::stdout().flush();
+                }
+            },
+        )
+        .map_err(|e| format!("Failed to register env.interp_print: {}", e))?;
+
+    // Host function: env.interp_end() -> i64 (packed str) — close the frame
+    linker
+        .func_wrap(
+            "env",
+            "interp_end",
+            |mut caller: Caller<'_, RuntimeState>| -> i64 {
+                let frame = caller.data_mut().interp_frames.pop().unwrap_or_default();
+                alloc_packed_str(&mut caller, &frame.text)
+            },
+        )
+        .map_err(|e| format!("Failed to register env.interp_end: {}", e))?;
+
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .map_err(|e| format!("Failed to instantiate module: {}", e))?;
+
+    // heap_ptr is now managed via __heap_base global in WASM, no initialization needed
+
+    let start = instance
         .get_typed_func::<(), ()>(&mut store, "_start")
         .map_err(|e| format!("No _start function found: {}", e))?;
 
     start
         .call(&mut store, ())
-        .map_err(|e| format!("Execution failed: {}", e))?;
+        .map_err(|e| format!("Execution failed: {}", e.root_cause()))?;
+
+    // An error no `try` caught leaves the script with its Error still pending
+    let error = instance
+        .get_global(&mut store, "__error")
+        .and_then(|global| global.get(&mut store).i32())
+        .unwrap_or(0);
+    if error != 0 {
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or("missing memory export")?;
+        let data = memory.data(&store);
+        let slot = |n: usize| {
+            let at = error as u32 as usize + n * 8;
+            i64::from_le_bytes(data[at..at + 8].try_into().unwrap())
+        };
+        let (message, code) = (slot(0), slot(1));
+        let ptr = (message >> 32) as u32 as usize;
+        let len = (message & 0xFFFF_FFFF) as usize;
+        let message = String::from_utf8_lossy(&data[ptr..ptr + len]);
+        return Err(if code == 0 {
+            format!("Uncaught error: {}", message)
+        } else {
+            format!("Uncaught error: {} (code {})", message, code)
+        });
+    }
 
     Ok(())
 }
@@ -2695,6 +3572,9 @@ fn print_usage() {
     eprintln!("AnehtaLanguage Compiler & Runtime");
     eprintln!();
     eprintln!("Usage:");
+    eprintln!("  anehta new <name>           Create a project in a new directory");
+    eprintln!("  anehta build                Build every entry of the current project");
+    eprintln!("  anehta run                  Build and execute the project's entry");
     eprintln!("  anehta build <source.ah>    Compile to .wasm");
     eprintln!("  anehta run <source.ah>      Compile and execute");
     eprintln!("  anehta <source.ah>          Compile to .wasm (shorthand)");
@@ -2708,11 +3588,14 @@ fn main() {
             print_usage();
             std::process::exit(1);
         }
-        2 => {
+        2 => match args[1].as_str() {
+            "build" => cmd_build_project(),
+            "run" => cmd_run_project(),
             // anehta <file.ah> — default to build
-            cmd_build(&args[1]);
-        }
+            _ => cmd_build(&args[1]),
+        },
         3 => match args[1].as_str() {
+            "new" => cmd_new(&args[2]),
             "build" => cmd_build(&args[2]),
             "run" => cmd_run(&args[2]),
             _ => {
@@ -2727,3 +3610,50 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> RuntimeState {
+        RuntimeState {
+            start_instant: std::time::Instant::now(),
+            objects: Vec::new(),
+            free_slots: Vec::new(),
+            children: std::collections::HashMap::new(),
+            parents: std::collections::HashMap::new(),
+            interp_frames: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn shared_child_lives_until_its_last_parent_is_freed() {
+        let mut s = state();
+        let inner = s.alloc(HeapObject::List(Vec::new()));
+        let a = s.alloc(HeapObject::List(vec![(format::TAG_LIST, inner)]));
+        let b = s.alloc(HeapObject::List(vec![(format::TAG_LIST, inner)]));
+        s.adopt(a, inner);
+        s.adopt(b, inner);
+        s.free(inner);
+        s.free(a);
+        assert!(s.list(inner).is_some());
+        s.free(b);
+        assert!(s.list(inner).is_none());
+    }
+
+    #[test]
+    fn child_held_twice_is_owned_until_both_are_released() {
+        let mut s = state();
+        let inner = s.alloc(HeapObject::List(Vec::new()));
+        let a = s.alloc(HeapObject::List(vec![(format::TAG_LIST, inner), (format::TAG_LIST, inner)]));
+        s.adopt(a, inner);
+        s.adopt(a, inner);
+        s.release(a, inner);
+        s.free(inner);
+        assert!(s.list(inner).is_some());
+        s.release(a, inner);
+        s.free(inner);
+        assert!(s.list(inner).is_none());
+        assert!(s.list(a).is_some());
+    }
+}
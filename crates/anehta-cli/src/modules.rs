@@ -0,0 +1,175 @@
+//! Loading a program made of several files: the entry file and every file it
+//! imports, directly or not, merged into one program.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anehta_parser::{Program, SourceModule, Statement};
+
+use crate::project::Project;
+
+/// A program's files, in the order they were loaded: `Span::file` indexes them.
+pub(crate) struct SourceFiles {
+    paths: Vec<String>,
+}
+
+impl SourceFiles {
+    /// A diagnostic prefixed with the file it comes from.
+    pub(crate) fn locate(&self, file: usize, message: impl Display) -> String {
+        format!("{}: {}", self.paths[file], message)
+    }
+}
+
+/// Where the imports of a project's files are looked up when the file is not
+/// next to the importing one.
+#[derive(Default)]
+pub(crate) struct ImportScope {
+    source_dirs: Vec<PathBuf>,
+    /// Project directory of each dependency, by the name it is imported as
+    dependencies: HashMap<String, PathBuf>,
+}
+
+impl ImportScope {
+    pub(crate) fn for_project(project: &Project) -> ImportScope {
+        let build = &project.manifest.build;
+        ImportScope {
+            source_dirs: build.source_dirs.iter().map(|dir| project.path(dir)).collect(),
+            dependencies: project
+                .manifest
+                .dependencies
+                .iter()
+                .map(|(name, dep)| (name.clone(), project.path(&dep.path)))
+                .collect(),
+        }
+    }
+}
+
+struct Loader {
+    files: Vec<String>,
+    /// Loaded files, those a file imports before it
+    modules: Vec<SourceModule>,
+    /// Index in `modules` of each loaded file, by canonical path
+    loaded: HashMap<PathBuf, usize>,
+    /// Files whose imports are being loaded, outermost first
+    loading: Vec<(PathBuf, String)>,
+    /// Prefixes given to the declarations of imported files
+    prefixes: HashSet<String>,
+    /// The entry's project, then the dependencies reached so far
+    scopes: Vec<ImportScope>,
+    /// Library file and index in `scopes` of each loaded dependency, by canonical directory
+    dependencies: HashMap<PathBuf, (PathBuf, usize)>,
+}
+
+/// Load `entry` and the files it imports, and link them into one program.
+pub(crate) fn load_program(entry: &Path, scope: ImportScope) -> Result<(Program, SourceFiles), String> {
+    let mut loader = Loader {
+        files: Vec::new(),
+        modules: Vec::new(),
+        loaded: HashMap::new(),
+        loading: Vec::new(),
+        prefixes: HashSet::new(),
+        scopes: vec![scope],
+        dependencies: HashMap::new(),
+    };
+    loader.load(entry, None, 0)?;
+    let files = SourceFiles { paths: loader.files };
+    let program = anehta_parser::link(loader.modules).map_err(|e| {
+        let anehta_parser::LinkError::Error { file, .. } = &e;
+        files.locate(*file, &e)
+    })?;
+    Ok((program, files))
+}
+
+impl Loader {
+    /// Load the file at `path` once, after the files it imports; `name` is the
+    /// import name of an imported file, and `scope` the index in `scopes` of its
+    /// project. Returns its index in `modules`.
+    fn load(&mut self, path: &Path, name: Option<&str>, scope: usize) -> Result<usize, String> {
+        let display = path.display().to_string();
+        let canonical = fs::canonicalize(path).map_err(|e| format!("Error reading file '{}': {}", display, e))?;
+        if let Some(&index) = self.loaded.get(&canonical) {
+            return Ok(index);
+        }
+        let source = fs::read_to_string(path).map_err(|e| format!("Error reading file '{}': {}", display, e))?;
+
+        let file = self.files.len();
+        self.files.push(display.clone());
+        let mut lexer = anehta_lexer::Lexer::for_file(&source, file);
+        let tokens = lexer.tokenize().map_err(|e| format!("{}: {}", display, e))?;
+        let mut parser = anehta_parser::Parser::new(tokens);
+        let program = parser.parse().map_err(|e| format!("{}: {}", display, e))?;
+
+        self.loading.push((canonical.clone(), display.clone()));
+        let mut imports = HashMap::new();
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for stmt in &program.statements {
+            let Statement::Import(imp) = stmt else {
+                continue;
+            };
+            let at = format!("{}: Import error at line {}, column {}", display, imp.span.line, imp.span.column);
+            if imports.contains_key(&imp.name) {
+                return Err(format!("{}: module '{}' is imported twice", at, imp.name));
+            }
+            let Some((target, target_scope)) = self.resolve(dir, &imp.path, scope).map_err(|e| format!("{}: {}", at, e))? else {
+                return Err(format!("{}: cannot find '{}'", at, dir.join(&imp.path).display()));
+            };
+            let Ok(canonical_target) = fs::canonicalize(&target) else {
+                return Err(format!("{}: cannot find '{}'", at, target.display()));
+            };
+            if let Some(start) = self.loading.iter().position(|(path, _)| *path == canonical_target) {
+                let mut cycle: Vec<&str> = self.loading[start..].iter().map(|(_, shown)| shown.as_str()).collect();
+                cycle.push(&self.loading[start].1);
+                return Err(format!("{}: import cycle: {}", at, cycle.join(" -> ")));
+            }
+            let index = self.load(&target, Some(&imp.name), target_scope)?;
+            imports.insert(imp.name.clone(), index);
+        }
+        self.loading.pop();
+
+        let prefix = name.map(|name| self.unique_prefix(name));
+        self.modules.push(SourceModule { prefix, file, program, imports });
+        self.loaded.insert(canonical, self.modules.len() - 1);
+        Ok(self.modules.len() - 1)
+    }
+
+    /// The file an import of `path` from a file in `dir` reaches, and its scope:
+    /// next to the importing file, in a source directory of its project, or the
+    /// library file of a dependency named like it.
+    fn resolve(&mut self, dir: &Path, path: &str, scope: usize) -> Result<Option<(PathBuf, usize)>, String> {
+        let beside = dir.join(path);
+        if beside.is_file() {
+            return Ok(Some((beside, scope)));
+        }
+        let scope_of = &self.scopes[scope];
+        if let Some(found) = scope_of.source_dirs.iter().map(|src| src.join(path)).find(|p| p.is_file()) {
+            return Ok(Some((found, scope)));
+        }
+        let Some(dep_dir) = path.strip_suffix(".ah").and_then(|name| scope_of.dependencies.get(name)) else {
+            return Ok(None);
+        };
+        let canonical = fs::canonicalize(dep_dir)
+            .map_err(|e| format!("cannot read dependency '{}': {}", dep_dir.display(), e))?;
+        if let Some((lib, dep_scope)) = self.dependencies.get(&canonical) {
+            return Ok(Some((lib.clone(), *dep_scope)));
+        }
+        let project = Project::load(dep_dir)?;
+        let lib = project.path(&project.manifest.package.lib);
+        self.scopes.push(ImportScope::for_project(&project));
+        let dep_scope = self.scopes.len() - 1;
+        self.dependencies.insert(canonical, (lib.clone(), dep_scope));
+        Ok(Some((lib, dep_scope)))
+    }
+
+    /// The import name, numbered when another file took it: `util`, `util2`, ...
+    fn unique_prefix(&mut self, name: &str) -> String {
+        let mut prefix = name.to_string();
+        let mut n = 2;
+        while !self.prefixes.insert(prefix.clone()) {
+            prefix = format!("{}{}", name, n);
+            n += 1;
+        }
+        prefix
+    }
+}
@@ -0,0 +1,234 @@
+//! Projects: a directory with an `anehta.toml` manifest that names the files to
+//! build, where imports are looked up, and how the compiler is configured.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+pub(crate) const MANIFEST_NAME: &str = "anehta.toml";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Manifest {
+    pub(crate) package: Package,
+    #[serde(default)]
+    pub(crate) build: Build,
+    #[serde(default)]
+    pub(crate) lints: Lints,
+    #[serde(default)]
+    pub(crate) features: Features,
+    /// Other projects on disk, by the name they are imported as
+    #[serde(default)]
+    pub(crate) dependencies: BTreeMap<String, Dependency>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Package {
+    pub(crate) name: String,
+    #[serde(default = "default_version")]
+    pub(crate) version: String,
+    /// The file `import <name>` reaches in projects that depend on this one
+    #[serde(default = "default_lib")]
+    pub(crate) lib: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct Build {
+    /// Files compiled to a module each, `<out-dir>/<file stem>.wasm`
+    pub(crate) entries: Vec<PathBuf>,
+    /// Directories searched for an import not found next to the importing file
+    pub(crate) source_dirs: Vec<PathBuf>,
+    pub(crate) out_dir: PathBuf,
+}
+
+impl Default for Build {
+    fn default() -> Self {
+        Build {
+            entries: vec![PathBuf::from("src/main.ah")],
+            source_dirs: vec![PathBuf::from("src")],
+            out_dir: PathBuf::from("build"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Lints {
+    /// What to do with compiler warnings
+    pub(crate) warnings: LintLevel,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LintLevel {
+    Allow,
+    #[default]
+    Warn,
+    /// Warnings fail the build
+    Deny,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct Features {
+    /// Compile `return f(...)` to `return_call` when the runtime supports it
+    pub(crate) tail_calls: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features { tail_calls: true }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Dependency {
+    /// Directory of the project, relative to this one
+    pub(crate) path: PathBuf,
+}
+
+fn default_version() -> String {
+    "0.1.0".to_string()
+}
+
+fn default_lib() -> PathBuf {
+    PathBuf::from("src/lib.ah")
+}
+
+/// A loaded project: its directory and manifest.
+pub(crate) struct Project {
+    pub(crate) root: PathBuf,
+    pub(crate) manifest: Manifest,
+}
+
+impl Manifest {
+    pub(crate) fn parse(text: &str) -> Result<Manifest, String> {
+        let manifest: Manifest = toml::from_str(text).map_err(|e| e.to_string())?;
+        for name in manifest.dependencies.keys() {
+            if !is_identifier(name) {
+                return Err(format!("dependency name '{}' is not a valid import name", name));
+            }
+        }
+        let mut outputs: HashMap<&std::ffi::OsStr, &Path> = HashMap::new();
+        for entry in &manifest.build.entries {
+            let Some(stem) = entry.file_stem() else {
+                return Err(format!("entry '{}' is not a file", entry.display()));
+            };
+            if let Some(other) = outputs.insert(stem, entry) {
+                return Err(format!(
+                    "entries '{}' and '{}' would both build to {}.wasm",
+                    other.display(),
+                    entry.display(),
+                    stem.to_string_lossy()
+                ));
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+impl Project {
+    /// Load the project whose manifest is in `root`.
+    pub(crate) fn load(root: &Path) -> Result<Project, String> {
+        let path = root.join(MANIFEST_NAME);
+        let text = fs::read_to_string(&path).map_err(|e| format!("Error reading '{}': {}", path.display(), e))?;
+        let manifest = Manifest::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Project { root: root.to_path_buf(), manifest })
+    }
+
+    /// The project `dir` or one of its parents holds, if any. Found in the
+    /// current directory, its root is the empty path so that its files are
+    /// shown relative to it.
+    pub(crate) fn find(dir: &Path) -> Result<Option<Project>, String> {
+        let dir = fs::canonicalize(dir).map_err(|e| format!("Error reading '{}': {}", dir.display(), e))?;
+        let cwd = std::env::current_dir().ok().and_then(|cwd| fs::canonicalize(cwd).ok());
+        for ancestor in dir.ancestors() {
+            if ancestor.join(MANIFEST_NAME).is_file() {
+                let root = if cwd.as_deref() == Some(ancestor) { PathBuf::new() } else { ancestor.to_path_buf() };
+                return Project::load(&root).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    pub(crate) fn path(&self, relative: &Path) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    /// Where a built entry goes: `<out-dir>/<file stem>.wasm`.
+    pub(crate) fn output_path(&self, entry: &Path) -> PathBuf {
+        let stem = entry.file_stem().unwrap_or_default();
+        self.path(&self.manifest.build.out_dir).join(stem).with_extension("wasm")
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Create the project `name` in a new directory of that name: a manifest with
+/// the default layout and a main file that prints a greeting.
+pub(crate) fn create(name: &str) -> Result<PathBuf, String> {
+    let root = PathBuf::from(name);
+    let Some(package) = root.file_name().and_then(|n| n.to_str()) else {
+        return Err(format!("'{}' is not a valid project name", name));
+    };
+    if root.exists() {
+        return Err(format!("'{}' already exists", root.display()));
+    }
+    let manifest = format!(
+        "[package]\nname = \"{package}\"\nversion = \"0.1.0\"\n\n[build]\nentries = [\"src/main.ah\"]\nsource-dirs = [\"src\"]\nout-dir = \"build\"\n\n[lints]\nwarnings = \"warn\"\n\n[features]\ntail-calls = true\n\n[dependencies]\n"
+    );
+    let main = format!("print(\"Hello from {package}!\")\n");
+    let write = |path: PathBuf, text: &str| fs::write(&path, text).map_err(|e| format!("Error writing '{}': {}", path.display(), e));
+    fs::create_dir_all(root.join("src")).map_err(|e| format!("Error creating '{}': {}", root.display(), e))?;
+    write(root.join(MANIFEST_NAME), &manifest)?;
+    write(root.join("src").join("main.ah"), &main)?;
+    write(root.join(".gitignore"), "/build\n")?;
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_fill_missing_sections() {
+        let manifest = Manifest::parse("[package]\nname = \"demo\"").unwrap();
+        assert_eq!(manifest.package.version, "0.1.0");
+        assert_eq!(manifest.package.lib, PathBuf::from("src/lib.ah"));
+        assert_eq!(manifest.build.entries, vec![PathBuf::from("src/main.ah")]);
+        assert_eq!(manifest.build.out_dir, PathBuf::from("build"));
+        assert_eq!(manifest.lints.warnings, LintLevel::Warn);
+        assert!(manifest.features.tail_calls);
+        assert!(manifest.dependencies.is_empty());
+    }
+
+    #[test]
+    fn full_manifest() {
+        let text = "[package]\nname = \"game\"\n\n[build]\nentries = [\"src/client.ah\", \"src/server.ah\"]\nsource-dirs = [\"src\", \"shared\"]\nout-dir = \"out\"\n\n[lints]\nwarnings = \"deny\"\n\n[features]\ntail-calls = false\n\n[dependencies]\nmathx = { path = \"../mathx\" }\n";
+        let manifest = Manifest::parse(text).unwrap();
+        assert_eq!(manifest.build.source_dirs.len(), 2);
+        assert_eq!(manifest.lints.warnings, LintLevel::Deny);
+        assert!(!manifest.features.tail_calls);
+        assert_eq!(manifest.dependencies["mathx"].path, PathBuf::from("../mathx"));
+        let project = Project { root: PathBuf::from("game"), manifest };
+        assert_eq!(project.output_path(Path::new("src/client.ah")), PathBuf::from("game/out/client.wasm"));
+    }
+
+    #[test]
+    fn invalid_manifests() {
+        assert!(Manifest::parse("[package]\nname = \"a\"\n[build]\nentry = \"main.ah\"").is_err());
+        assert!(Manifest::parse("[package]\nname = \"a\"\n[lints]\nwarnings = \"error\"").is_err());
+        let err = Manifest::parse("[package]\nname = \"a\"\n[dependencies]\nmy-lib = { path = \"../lib\" }").unwrap_err();
+        assert_eq!(err, "dependency name 'my-lib' is not a valid import name");
+        let err = Manifest::parse("[package]\nname = \"a\"\n[build]\nentries = [\"a/main.ah\", \"b/main.ah\"]").unwrap_err();
+        assert_eq!(err, "entries 'a/main.ah' and 'b/main.ah' would both build to main.wasm");
+    }
+}
@@ -0,0 +1,170 @@
+//! Host tables: a dense array part for the integer keys `0..n` and a hash part
+//! for every other key, as in Lua.
+
+use std::collections::HashMap;
+
+/// Key of a table entry. An integer key and its decimal string name the same
+/// entry, so `t[0]` and `t["0"]` agree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TableKey {
+    Index(i64),
+    Name(String),
+}
+
+impl TableKey {
+    /// Classify a string key: canonical non-negative integers become indices.
+    pub fn from_name(name: String) -> Self {
+        let canonical = !name.is_empty()
+            && name.bytes().all(|b| b.is_ascii_digit())
+            && (name == "0" || !name.starts_with('0'));
+        match name.parse() {
+            Ok(i) if canonical => TableKey::Index(i),
+            _ => TableKey::Name(name),
+        }
+    }
+
+    /// The key as it is stored in the hash part.
+    fn hash_key(&self) -> String {
+        match self {
+            TableKey::Index(i) => i.to_string(),
+            TableKey::Name(name) => name.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for TableKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableKey::Index(i) => write!(f, "{}", i),
+            TableKey::Name(name) => write!(f, "'{}'", name),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Table {
+    array: Vec<i64>,
+    hash: HashMap<String, i64>,
+}
+
+impl Table {
+    /// Position of `key` in the array part, if it lives there.
+    fn slot(&self, key: &TableKey) -> Option<usize> {
+        match key {
+            TableKey::Index(i) => usize::try_from(*i).ok().filter(|&i| i < self.array.len()),
+            TableKey::Name(_) => None,
+        }
+    }
+
+    pub fn get(&self, key: &TableKey) -> Option<i64> {
+        match self.slot(key) {
+            Some(i) => Some(self.array[i]),
+            None => self.hash.get(&key.hash_key()).copied(),
+        }
+    }
+
+    pub fn has(&self, key: &TableKey) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn set(&mut self, key: TableKey, value: i64) {
+        if let Some(i) = self.slot(&key) {
+            self.array[i] = value;
+        } else if key == TableKey::Index(self.array.len() as i64) {
+            self.array.push(value);
+            self.migrate();
+        } else {
+            self.hash.insert(key.hash_key(), value);
+        }
+    }
+
+    /// Move the keys that now continue the array part out of the hash part.
+    fn migrate(&mut self) {
+        while let Some(value) = self.hash.remove(&self.array.len().to_string()) {
+            self.array.push(value);
+        }
+    }
+
+    /// Remove an entry and return its value. Removing from the array part shifts
+    /// the later elements down by one.
+    pub fn remove(&mut self, key: &TableKey) -> Option<i64> {
+        match self.slot(key) {
+            Some(i) => Some(self.array.remove(i)),
+            None => self.hash.remove(&key.hash_key()),
+        }
+    }
+
+    /// Insert into the array part before `index`, shifting the later elements up;
+    /// `None` appends. Returns false when `index` is past the end of the array part.
+    pub fn insert(&mut self, index: Option<i64>, value: i64) -> bool {
+        let len = self.array.len();
+        match index.map(usize::try_from) {
+            None => self.array.push(value),
+            Some(Ok(i)) if i <= len => self.array.insert(i, value),
+            Some(_) => return false,
+        }
+        self.migrate();
+        true
+    }
+
+    /// Length of the array part.
+    pub fn len(&self) -> usize {
+        self.array.len()
+    }
+
+    /// The values of the array part in index order.
+    pub fn array(&self) -> &[i64] {
+        &self.array
+    }
+
+    /// The array indices in order, then the other keys sorted by name.
+    pub fn keys(&self) -> Vec<TableKey> {
+        let mut names: Vec<&String> = self.hash.keys().collect();
+        names.sort();
+        (0..self.array.len() as i64)
+            .map(TableKey::Index)
+            .chain(names.into_iter().map(|name| TableKey::Name(name.clone())))
+            .collect()
+    }
+
+    /// The values in the order of `keys`.
+    pub fn values(&self) -> Vec<i64> {
+        self.keys().iter().filter_map(|key| self.get(key)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_keys_fill_the_array_part() {
+        let mut t = Table::default();
+        t.set(TableKey::Index(1), 20);
+        t.set(TableKey::from_name("name".to_string()), 7);
+        assert_eq!(t.len(), 0);
+        t.set(TableKey::from_name("0".to_string()), 10);
+        assert_eq!(t.len(), 2);
+        assert_eq!(t.get(&TableKey::Index(1)), Some(20));
+        assert_eq!(
+            t.keys(),
+            vec![TableKey::Index(0), TableKey::Index(1), TableKey::Name("name".to_string())]
+        );
+        assert_eq!(t.values(), vec![10, 20, 7]);
+        assert_eq!(TableKey::from_name("01".to_string()), TableKey::Name("01".to_string()));
+    }
+
+    #[test]
+    fn insert_and_remove_shift_the_array_part() {
+        let mut t = Table::default();
+        t.insert(None, 1);
+        t.insert(None, 3);
+        assert!(t.insert(Some(1), 2));
+        assert!(!t.insert(Some(5), 9));
+        assert_eq!(t.values(), vec![1, 2, 3]);
+        assert_eq!(t.remove(&TableKey::Index(0)), Some(1));
+        assert_eq!(t.values(), vec![2, 3]);
+        assert_eq!(t.get(&TableKey::Index(2)), None);
+        assert!(!t.has(&TableKey::Name("x".to_string())));
+    }
+}
@@ -4,10 +4,10 @@ use std::collections::HashSet;
 
 use anehta_lexer::Span;
 use anehta_parser::{
-    Assignment, BinaryOp, Block, BooleanExpr, CallFunc, ClosureBody, ClosureExpr, ComparisonOp,
-    Expr, FieldAccess, FieldAssign, ForStmt, FuncDecl, IfStmt, IndexAccess, IndexAssign,
-    LogicalOp, MethodCall, Program, ReturnStmt, Statement, TableLiteral, TimerStmt, Transpose,
-    UnaryOp, VarDecl,
+    Assignment, BinaryOp, Block, BooleanExpr, CallFunc, Cast, ClosureBody, ClosureExpr, ComparisonOp,
+    DeferStmt, EnumDecl, Expr, FieldAccess, FieldAssign, ForInStmt, ForStmt, FuncDecl, IfExpr, IfStmt, IndexAccess,
+    IndexAssign, InterpPart, ListLiteral, LogicalOp, MatchBody, MatchExpr, MatchPattern, MethodCall, NewExpr, Program,
+    ReturnStmt, Statement, StructDecl, TableLiteral, ThrowStmt, TimerStmt, TryStmt, UnaryOp, VarDecl, YieldStmt,
 };
 
 use wasm_encoder::{
@@ -27,21 +27,51 @@ mod compile_stmt;
 mod compile_expr;
 mod compile_func;
 mod compile_bool;
+mod compile_str;
+mod compile_fmt;
+mod compile_math;
+mod compile_cast;
+mod compile_unary;
+mod compile_struct;
+mod compile_enum;
+mod compile_method;
+mod compile_list;
+mod compile_table;
+mod compile_optional;
+mod compile_func_value;
+mod compile_hof;
+mod compile_gen;
+mod compile_try;
+mod compile_defer;
+mod resolve_calls;
 
 #[cfg(test)]
 mod tests;
 
 use types::*;
-pub use types::CodegenError;
+use compile_fmt::FmtBuiltin;
+use compile_list::ListBuiltin;
+use compile_table::TableBuiltin;
+pub use types::{CodegenError, CodegenWarning};
 
 fn codegen_err(message: impl Into<String>, span: &Span) -> CodegenError {
     CodegenError::Error {
         message: message.into(),
         line: span.line,
         column: span.column,
+        file: span.file,
     }
 }
 
+/// Top-level functions and `impl` methods, in declaration order.
+fn user_funcs(program: &Program) -> impl Iterator<Item = &FuncDecl> {
+    program.statements.iter().flat_map(|stmt| match stmt {
+        Statement::FuncDecl(func) => std::slice::from_ref(func),
+        Statement::ImplDecl(imp) => imp.methods.as_slice(),
+        _ => &[],
+    })
+}
+
 /// WASM code generator: compiles AST into .wasm binary
 pub struct WasmCodegen {
     /// Type section entries: each is (params, results)
@@ -58,16 +88,55 @@ pub struct WasmCodegen {
     string_data: Vec<u8>,
     /// Inferred return types for user-defined functions (by name)
     func_return_types: HashMap<String, AhType>,
+    /// Declared parameter types of user-defined functions (by name)
+    func_param_types: HashMap<String, Vec<AhType>>,
+    /// Function types seen in annotations and function values (see `intern_func_type`)
+    func_types: std::cell::RefCell<Vec<FuncTypeInfo>>,
+    /// Named functions used as values, by name
+    func_refs: HashMap<String, FuncRef>,
+    /// Generator functions, by name
+    generators: HashMap<String, GenInfo>,
     /// Counter for generating unique closure names
     closure_counter: u32,
     /// Collected closure info (one per closure expression in the program)
     closures: Vec<ClosureInfo>,
-    /// Maps closure span (line, column) to closure ID for lookup during compilation
-    closure_span_map: HashMap<(usize, usize), u32>,
+    /// Maps closure span to closure ID for lookup during compilation
+    closure_span_map: HashMap<Span, u32>,
+    /// Captured variables that live in shared heap cells, by enclosing function
+    boxed_vars: HashMap<String, HashSet<String>>,
+    /// Types of each closure's captures, recorded where the closure is created
+    capture_types: std::cell::RefCell<HashMap<u32, Vec<AhType>>>,
+    /// Parameter types of closure literals passed to higher-order builtins such as
+    /// `map`, which take them from the elements rather than from annotations
+    hof_param_types: std::cell::RefCell<HashMap<u32, Vec<AhType>>>,
     /// Compile-time type info for table literals (one per table literal)
     table_types: Vec<TableTypeInfo>,
-    /// Maps table literal span (line, column) to table_type_id
-    table_type_span_map: HashMap<(usize, usize), u32>,
+    /// Maps table literal span to table_type_id
+    table_type_span_map: HashMap<Span, u32>,
+    /// Declared structs (indexed by struct ID)
+    structs: Vec<StructInfo>,
+    /// Maps struct name to struct ID
+    struct_map: HashMap<String, u32>,
+    /// Declared enums (indexed by enum ID)
+    enums: Vec<EnumInfo>,
+    /// Maps enum name to enum ID
+    enum_map: HashMap<String, u32>,
+    /// List types of typed variadic parameters, by element type name
+    variadic_list_types: HashMap<String, u32>,
+    /// Ids of the table types built from table literals
+    literal_tables: HashSet<u32>,
+    /// Table field reads and writes seen while compiling, for `field_warnings`
+    field_checks: std::cell::RefCell<FieldChecks>,
+    /// Warnings from the last `compile`
+    warnings: Vec<CodegenWarning>,
+    /// Whether calls in tail position use `return_call` (see `set_tail_calls`)
+    tail_calls: bool,
+}
+
+impl Default for WasmCodegen {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WasmCodegen {
@@ -80,14 +149,51 @@ impl WasmCodegen {
             string_pool: HashMap::new(),
             string_data: Vec::new(),
             func_return_types: HashMap::new(),
+            func_param_types: HashMap::new(),
+            func_types: std::cell::RefCell::new(Vec::new()),
+            func_refs: HashMap::new(),
+            generators: HashMap::new(),
             closure_counter: 0,
             closures: Vec::new(),
             closure_span_map: HashMap::new(),
-            table_types: Vec::new(),
+            boxed_vars: HashMap::new(),
+            capture_types: std::cell::RefCell::new(HashMap::new()),
+            hof_param_types: std::cell::RefCell::new(HashMap::new()),
+            table_types: vec![
+                TableTypeInfo {
+                    fields: HashMap::new(),
+                    elements: Some(AhType::Str),
+                },
+                TableTypeInfo {
+                    fields: HashMap::new(),
+                    elements: None,
+                },
+            ],
             table_type_span_map: HashMap::new(),
+            structs: Vec::new(),
+            struct_map: HashMap::new(),
+            enums: Vec::new(),
+            enum_map: HashMap::new(),
+            variadic_list_types: HashMap::new(),
+            literal_tables: HashSet::new(),
+            field_checks: std::cell::RefCell::new(FieldChecks::default()),
+            warnings: Vec::new(),
+            tail_calls: true,
         }
     }
 
+    /// Whether `return f(...)` compiles to `return_call`, so deep recursion in
+    /// tail position runs in constant stack (on by default). For runtimes without
+    /// the tail-call proposal; a function's tail calls to itself then become a loop.
+    pub fn set_tail_calls(&mut self, enabled: bool) {
+        self.tail_calls = enabled;
+    }
+
+    /// User functions and local variables shadow a builtin of the same name.
+    fn builtin_shadowed(&self, name: &str, ctx: &FuncCtx) -> bool {
+        self.func_map.contains_key(name) || ctx.get_local(name).is_some()
+    }
+
     /// Register a type and return its index
     fn add_type(&mut self, params: Vec<ValType>, results: Vec<ValType>) -> u32 {
         // Check if an identical type already exists
@@ -101,13 +207,27 @@ impl WasmCodegen {
         idx
     }
 
+    /// Warnings found by the last `compile`, in source order
+    pub fn warnings(&self) -> &[CodegenWarning] {
+        &self.warnings
+    }
+
     /// Compile a Program AST into WASM bytecode
     pub fn compile(&mut self, program: &Program) -> Result<Vec<u8>, CodegenError> {
-        // Phase 0: Collect all string literals into the string pool
+        // Phase 0: Fill in default, named and variadic arguments and lower nested
+        // functions to closures, then collect all string literals into the string pool
+        let resolved = self.resolve_calls(program)?;
+        let program = &resolved;
         self.collect_strings(program);
 
+        // Phase 0a: Register struct and enum declarations and their layouts
+        self.collect_type_decls(program)?;
+        self.check_impls(program)?;
+        self.collect_variadic_types(program);
+
         // Phase 0b: Collect table type info for compile-time field type inference
         self.collect_table_types(program);
+        self.check_param_defaults(program)?;
 
         // Phase 1: Collect all function declarations and build the function map.
         // Also determine imports needed.
@@ -556,23 +676,428 @@ impl WasmCodegen {
         self.next_func_idx += 1;
         self.num_imports = 55;
 
-        // Collect user-defined functions and their return types
-        for stmt in &program.statements {
-            if let Statement::FuncDecl(func) = stmt {
-                let params: Vec<ValType> = func.params.iter().map(|_| ValType::I64).collect();
-                let results: Vec<ValType> =
-                    func.return_types.iter().map(|_| ValType::I64).collect();
-                let type_idx = self.add_type(params, results);
-                let func_idx = self.next_func_idx;
-                self.func_map
-                    .insert(func.name.clone(), (func_idx, type_idx));
-                self.next_func_idx += 1;
-
-                // Infer return type from the first return type annotation
-                if let Some(first_ret) = func.return_types.first() {
-                    self.func_return_types
-                        .insert(func.name.clone(), type_name_to_ah(first_ret));
+        // Import: env.str_len(s: i64) -> i64 (length in characters)
+        let str_len_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let str_len_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_len".to_string(), (str_len_func_idx, str_len_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 56;
+
+        // Import: env.str_char_at(s: i64, i: i64) -> i64 (single-character string at char index)
+        let str_char_at_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let str_char_at_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_char_at".to_string(), (str_char_at_func_idx, str_char_at_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 57;
+
+        // Import: env.str_slice(s: i64, start: i64, end: i64) -> i64 (char-indexed substring)
+        let str_slice_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64], vec![ValType::I64]);
+        let str_slice_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_slice".to_string(), (str_slice_func_idx, str_slice_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 58;
+
+        // Import: env.str_find(s: i64, sub: i64) -> i64 (char index of first match, or -1)
+        let str_find_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let str_find_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_find".to_string(), (str_find_func_idx, str_find_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 59;
+
+        // Import: env.str_contains(s: i64, sub: i64) -> i64 (1 if sub occurs in s)
+        let str_contains_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let str_contains_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_contains".to_string(), (str_contains_func_idx, str_contains_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 60;
+
+        // Import: env.str_starts_with(s: i64, prefix: i64) -> i64
+        let str_starts_with_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let str_starts_with_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_starts_with".to_string(), (str_starts_with_func_idx, str_starts_with_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 61;
+
+        // Import: env.str_ends_with(s: i64, suffix: i64) -> i64
+        let str_ends_with_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let str_ends_with_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_ends_with".to_string(), (str_ends_with_func_idx, str_ends_with_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 62;
+
+        // Import: env.str_replace(s: i64, from: i64, to: i64) -> i64 (replace all occurrences)
+        let str_replace_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64], vec![ValType::I64]);
+        let str_replace_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_replace".to_string(), (str_replace_func_idx, str_replace_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 63;
+
+        // Import: env.str_split(s: i64, sep: i64) -> i64 (new table keyed "0".."n-1")
+        let str_split_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let str_split_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_split".to_string(), (str_split_func_idx, str_split_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 64;
+
+        // Import: env.str_join(table: i64, sep: i64) -> i64 (join entries "0".."n-1")
+        let str_join_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let str_join_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_join".to_string(), (str_join_func_idx, str_join_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 65;
+
+        // Import: env.str_upper(s: i64) -> i64
+        let str_upper_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let str_upper_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_upper".to_string(), (str_upper_func_idx, str_upper_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 66;
+
+        // Import: env.str_lower(s: i64) -> i64
+        let str_lower_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let str_lower_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_lower".to_string(), (str_lower_func_idx, str_lower_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 67;
+
+        // Import: env.str_trim(s: i64) -> i64 (strip leading/trailing whitespace)
+        let str_trim_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let str_trim_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_str_trim".to_string(), (str_trim_func_idx, str_trim_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 68;
+
+        // Import: env.parse_int(s: i64) -> i64 (0 if not a number)
+        let parse_int_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let parse_int_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_parse_int".to_string(), (parse_int_func_idx, parse_int_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 69;
+
+        // Import: env.parse_float(s: i64) -> i64 (f64 bits, 0.0 if not a number)
+        let parse_float_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let parse_float_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_parse_float".to_string(), (parse_float_func_idx, parse_float_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 70;
+
+        // Import: env.table_len(table: i64) -> i64 (length of the array part)
+        let table_len_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let table_len_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_table_len".to_string(), (table_len_func_idx, table_len_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 71;
+
+        // Import: env.interp_begin() (open an interpolated-string buffer)
+        let interp_begin_type_idx = self.add_type(vec![], vec![]);
+        let interp_begin_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_interp_begin".to_string(), (interp_begin_func_idx, interp_begin_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 72;
+
+        // Import: env.interp_push(value: i64, tag: i64) (append a formatted part)
+        let interp_push_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![]);
+        let interp_push_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_interp_push".to_string(), (interp_push_func_idx, interp_push_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 73;
+
+        // Import: env.interp_end() -> i64 (packed str built from the buffer)
+        let interp_end_type_idx = self.add_type(vec![], vec![ValType::I64]);
+        let interp_end_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_interp_end".to_string(), (interp_end_func_idx, interp_end_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 74;
+
+        // Import: env.interp_arg(value: i64, tag: i64) (queue a raw argument for formatting)
+        let interp_arg_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![]);
+        let interp_arg_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_interp_arg".to_string(), (interp_arg_func_idx, interp_arg_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 75;
+
+        // Import: env.interp_format(fmt: i64) (expand a format string against the queued arguments)
+        let interp_format_type_idx = self.add_type(vec![ValType::I64], vec![]);
+        let interp_format_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_interp_format".to_string(), (interp_format_func_idx, interp_format_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 76;
+
+        // Import: env.interp_print(newline: i64) (print the buffer, queued arguments separated by spaces)
+        let interp_print_type_idx = self.add_type(vec![ValType::I64], vec![]);
+        let interp_print_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_interp_print".to_string(), (interp_print_func_idx, interp_print_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 77;
+
+        // Import: env.math_sin(x: i64) -> i64 (f64 bits)
+        let math_sin_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let math_sin_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_math_sin".to_string(), (math_sin_func_idx, math_sin_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 78;
+
+        // Import: env.math_cos(x: i64) -> i64 (f64 bits)
+        let math_cos_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let math_cos_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_math_cos".to_string(), (math_cos_func_idx, math_cos_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 79;
+
+        // Import: env.math_exp(x: i64) -> i64 (f64 bits)
+        let math_exp_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let math_exp_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_math_exp".to_string(), (math_exp_func_idx, math_exp_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 80;
+
+        // Import: env.math_log(x: i64) -> i64 (natural log, f64 bits)
+        let math_log_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let math_log_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_math_log".to_string(), (math_log_func_idx, math_log_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 81;
+
+        // Import: env.math_atan2(y: i64, x: i64) -> i64 (f64 bits)
+        let math_atan2_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let math_atan2_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_math_atan2".to_string(), (math_atan2_func_idx, math_atan2_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 82;
+
+        // Import: env.math_map(op: i64, value: i64, shape: i64) -> i64 (element-wise unary op on vec/mat)
+        let math_map_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64], vec![ValType::I64]);
+        let math_map_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_math_map".to_string(), (math_map_func_idx, math_map_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 83;
+
+        // Import: env.math_map2(op: i64, a: i64, b: i64, shape: i64) -> i64 (element-wise binary op on vec/mat)
+        let math_map2_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64, ValType::I64], vec![ValType::I64]);
+        let math_map2_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_math_map2".to_string(), (math_map2_func_idx, math_map2_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 84;
+
+        // Import: env.math_where(op: i64, l: i64, r: i64, a: i64, b: i64, shape: i64) -> i64 (element-wise select on vec/mat)
+        let math_where_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64, ValType::I64, ValType::I64, ValType::I64], vec![ValType::I64]);
+        let math_where_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_math_where".to_string(), (math_where_func_idx, math_where_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 85;
+
+        // Import: env.list_new() -> i64 (create an empty list, return handle)
+        let list_new_type_idx = self.add_type(vec![], vec![ValType::I64]);
+        let list_new_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_list_new".to_string(), (list_new_func_idx, list_new_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 86;
+
+        // Import: env.list_push(list: i64, value: i64, tag: i64) -> i64 (append a tagged element, return the list)
+        let list_push_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64], vec![ValType::I64]);
+        let list_push_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_list_push".to_string(), (list_push_func_idx, list_push_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 87;
+
+        // Import: env.list_insert(list: i64, index: i64, value: i64, tag: i64) -> i64 (insert before index, return the list)
+        let list_insert_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64, ValType::I64], vec![ValType::I64]);
+        let list_insert_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_list_insert".to_string(), (list_insert_func_idx, list_insert_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 88;
+
+        // Import: env.list_get(list: i64, index: i64) -> i64 (element at index; traps when out of range)
+        let list_get_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let list_get_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_list_get".to_string(), (list_get_func_idx, list_get_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 89;
+
+        // Import: env.list_set(list: i64, index: i64, value: i64, tag: i64) (replace element at index)
+        let list_set_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64, ValType::I64], vec![]);
+        let list_set_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_list_set".to_string(), (list_set_func_idx, list_set_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 90;
+
+        // Import: env.list_pop(list: i64) -> i64 (remove and return the last element)
+        let list_pop_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let list_pop_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_list_pop".to_string(), (list_pop_func_idx, list_pop_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 91;
+
+        // Import: env.list_remove(list: i64, index: i64) -> i64 (remove and return the element at index)
+        let list_remove_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let list_remove_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_list_remove".to_string(), (list_remove_func_idx, list_remove_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 92;
+
+        // Import: env.list_len(list: i64) -> i64 (number of elements)
+        let list_len_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let list_len_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_list_len".to_string(), (list_len_func_idx, list_len_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 93;
+
+        // Import: env.list_slice(list: i64, start: i64, end: i64) -> i64 (new list of [start, end); -1 = open end)
+        let list_slice_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64], vec![ValType::I64]);
+        let list_slice_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_list_slice".to_string(), (list_slice_func_idx, list_slice_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 94;
+
+        // Import: env.print_list(list: i64) (print list with its elements rendered by tag)
+        let print_list_type_idx = self.add_type(vec![ValType::I64], vec![]);
+        let print_list_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_print_list".to_string(), (print_list_func_idx, print_list_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 95;
+
+        // Import: env.table_geti(table: i64, index: i64) -> i64 (integer-keyed read)
+        let table_geti_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let table_geti_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_table_geti".to_string(), (table_geti_func_idx, table_geti_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 96;
+
+        // Import: env.table_seti(table: i64, index: i64, value: i64) (integer-keyed write)
+        let table_seti_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64], vec![]);
+        let table_seti_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_table_seti".to_string(), (table_seti_func_idx, table_seti_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 97;
+
+        // Import: env.table_has(table: i64, key: i64, tag: i64) -> i64 (1 if the key is present)
+        let table_has_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64], vec![ValType::I64]);
+        let table_has_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_table_has".to_string(), (table_has_func_idx, table_has_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 98;
+
+        // Import: env.table_remove(table: i64, key: i64, tag: i64) -> i64 (the removed value)
+        let table_remove_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64], vec![ValType::I64]);
+        let table_remove_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_table_remove".to_string(), (table_remove_func_idx, table_remove_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 99;
+
+        // Import: env.table_insert(table: i64, index: i64, value: i64) -> i64 (the table; index -1 appends)
+        let table_insert_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64], vec![ValType::I64]);
+        let table_insert_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_table_insert".to_string(), (table_insert_func_idx, table_insert_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 100;
+
+        // Import: env.table_keys(table: i64) -> i64 (new list of the keys as str)
+        let table_keys_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let table_keys_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_table_keys".to_string(), (table_keys_func_idx, table_keys_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 101;
+
+        // Import: env.table_values(table: i64, tag: i64) -> i64 (new list of the values)
+        let table_values_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let table_values_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_table_values".to_string(), (table_values_func_idx, table_values_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 102;
+
+        // Import: env.table_get_opt(table: i64, key: i64) -> i64 (nil when the table is nil or lacks the key)
+        let table_get_opt_type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![ValType::I64]);
+        let table_get_opt_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_table_get_opt".to_string(), (table_get_opt_func_idx, table_get_opt_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 103;
+
+        // Import: env.list_pick(dst: i64, src: i64, index: i64) -> i64 (append src[index] to dst)
+        let list_pick_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64], vec![ValType::I64]);
+        let list_pick_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_list_pick".to_string(), (list_pick_func_idx, list_pick_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 104;
+
+        // Import: env.sort_order(keys: i64, n: i64, is_float: i64) (keys become the sorted order)
+        let sort_order_type_idx = self.add_type(vec![ValType::I64, ValType::I64, ValType::I64], vec![]);
+        let sort_order_func_idx = self.next_func_idx;
+        self.func_map
+            .insert("__env_sort_order".to_string(), (sort_order_func_idx, sort_order_type_idx));
+        self.next_func_idx += 1;
+        self.num_imports = 105;
+
+        // Collect user-defined functions (and methods) and their return types
+        for func in user_funcs(program) {
+            let params: Vec<ValType> = func.params.iter().map(|_| ValType::I64).collect();
+            let results: Vec<ValType> =
+                func.return_types.iter().map(|_| ValType::I64).collect();
+            let type_idx = self.add_type(params, results);
+            let func_idx = self.next_func_idx;
+            self.func_map
+                .insert(func.name.clone(), (func_idx, type_idx));
+            self.next_func_idx += 1;
+
+            let param_types = func.params.iter().map(|p| self.resolve_type(&p.type_name)).collect();
+            self.func_param_types.insert(func.name.clone(), param_types);
+
+            // Infer return type from the first return type annotation; a generator
+            // returns a coroutine of the annotated type
+            if let Some(first_ret) = func.return_types.first() {
+                let mut ret = self.resolve_type(first_ret);
+                if compile_gen::is_generator(func) {
+                    ret = self.coroutine_type(ret);
                 }
+                self.func_return_types.insert(func.name.clone(), ret);
             }
         }
 
@@ -580,7 +1105,12 @@ impl WasmCodegen {
         let has_top_level = program
             .statements
             .iter()
-            .any(|s| !matches!(s, Statement::FuncDecl(_)));
+            .any(|s| {
+                !matches!(
+                    s,
+                    Statement::FuncDecl(_) | Statement::ImplDecl(_) | Statement::StructDecl(_) | Statement::EnumDecl(_)
+                )
+            });
 
         if has_top_level {
             let start_type_idx = self.add_type(vec![], vec![]);
@@ -593,10 +1123,20 @@ impl WasmCodegen {
         // Collect closures (must happen after user functions and _start are registered)
         self.collect_closures(program);
 
+        // Trampolines for named functions used as values follow the closures
+        self.collect_func_refs(program);
+
+        // Then the step functions of generators
+        self.collect_generators(program)?;
+
         // Fixup table field types: now that closures are collected, re-infer fields
         // that were wrongly typed as Int during collect_table_types (Phase 0b).
         self.fixup_table_types(program);
 
+        // Struct and enum formatters come after the closures in the function index space
+        self.register_formatters();
+        self.register_throw();
+
         // Phase 2: Build sections
 
         // Type section
@@ -664,6 +1204,56 @@ impl WasmCodegen {
         import_section.import("env", "mat_sub_vec_broadcast", EntityType::Function(mat_sub_vec_broadcast_type_idx));
         import_section.import("env", "vec_mask", EntityType::Function(vec_mask_type_idx));
         import_section.import("env", "mat_mask", EntityType::Function(mat_mask_type_idx));
+        import_section.import("env", "str_len", EntityType::Function(str_len_type_idx));
+        import_section.import("env", "str_char_at", EntityType::Function(str_char_at_type_idx));
+        import_section.import("env", "str_slice", EntityType::Function(str_slice_type_idx));
+        import_section.import("env", "str_find", EntityType::Function(str_find_type_idx));
+        import_section.import("env", "str_contains", EntityType::Function(str_contains_type_idx));
+        import_section.import("env", "str_starts_with", EntityType::Function(str_starts_with_type_idx));
+        import_section.import("env", "str_ends_with", EntityType::Function(str_ends_with_type_idx));
+        import_section.import("env", "str_replace", EntityType::Function(str_replace_type_idx));
+        import_section.import("env", "str_split", EntityType::Function(str_split_type_idx));
+        import_section.import("env", "str_join", EntityType::Function(str_join_type_idx));
+        import_section.import("env", "str_upper", EntityType::Function(str_upper_type_idx));
+        import_section.import("env", "str_lower", EntityType::Function(str_lower_type_idx));
+        import_section.import("env", "str_trim", EntityType::Function(str_trim_type_idx));
+        import_section.import("env", "parse_int", EntityType::Function(parse_int_type_idx));
+        import_section.import("env", "parse_float", EntityType::Function(parse_float_type_idx));
+        import_section.import("env", "table_len", EntityType::Function(table_len_type_idx));
+        import_section.import("env", "interp_begin", EntityType::Function(interp_begin_type_idx));
+        import_section.import("env", "interp_push", EntityType::Function(interp_push_type_idx));
+        import_section.import("env", "interp_end", EntityType::Function(interp_end_type_idx));
+        import_section.import("env", "interp_arg", EntityType::Function(interp_arg_type_idx));
+        import_section.import("env", "interp_format", EntityType::Function(interp_format_type_idx));
+        import_section.import("env", "interp_print", EntityType::Function(interp_print_type_idx));
+        import_section.import("env", "math_sin", EntityType::Function(math_sin_type_idx));
+        import_section.import("env", "math_cos", EntityType::Function(math_cos_type_idx));
+        import_section.import("env", "math_exp", EntityType::Function(math_exp_type_idx));
+        import_section.import("env", "math_log", EntityType::Function(math_log_type_idx));
+        import_section.import("env", "math_atan2", EntityType::Function(math_atan2_type_idx));
+        import_section.import("env", "math_map", EntityType::Function(math_map_type_idx));
+        import_section.import("env", "math_map2", EntityType::Function(math_map2_type_idx));
+        import_section.import("env", "math_where", EntityType::Function(math_where_type_idx));
+        import_section.import("env", "list_new", EntityType::Function(list_new_type_idx));
+        import_section.import("env", "list_push", EntityType::Function(list_push_type_idx));
+        import_section.import("env", "list_insert", EntityType::Function(list_insert_type_idx));
+        import_section.import("env", "list_get", EntityType::Function(list_get_type_idx));
+        import_section.import("env", "list_set", EntityType::Function(list_set_type_idx));
+        import_section.import("env", "list_pop", EntityType::Function(list_pop_type_idx));
+        import_section.import("env", "list_remove", EntityType::Function(list_remove_type_idx));
+        import_section.import("env", "list_len", EntityType::Function(list_len_type_idx));
+        import_section.import("env", "list_slice", EntityType::Function(list_slice_type_idx));
+        import_section.import("env", "print_list", EntityType::Function(print_list_type_idx));
+        import_section.import("env", "table_geti", EntityType::Function(table_geti_type_idx));
+        import_section.import("env", "table_seti", EntityType::Function(table_seti_type_idx));
+        import_section.import("env", "table_has", EntityType::Function(table_has_type_idx));
+        import_section.import("env", "table_remove", EntityType::Function(table_remove_type_idx));
+        import_section.import("env", "table_insert", EntityType::Function(table_insert_type_idx));
+        import_section.import("env", "table_keys", EntityType::Function(table_keys_type_idx));
+        import_section.import("env", "table_values", EntityType::Function(table_values_type_idx));
+        import_section.import("env", "table_get_opt", EntityType::Function(table_get_opt_type_idx));
+        import_section.import("env", "list_pick", EntityType::Function(list_pick_type_idx));
+        import_section.import("env", "sort_order", EntityType::Function(sort_order_type_idx));
 
         // Function section (declares type index for each local function)
         let mut function_section = FunctionSection::new();
@@ -671,13 +1261,18 @@ impl WasmCodegen {
         let mut code_section = CodeSection::new();
 
         // Compile user-defined functions
-        for stmt in &program.statements {
-            if let Statement::FuncDecl(func) = stmt {
-                let (_, type_idx) = self.func_map[&func.name];
-                function_section.function(type_idx);
-                let wasm_func = self.compile_func_decl(func)?;
-                code_section.function(&wasm_func);
-            }
+        let mut generator_steps = Vec::new();
+        for func in user_funcs(program) {
+            let (_, type_idx) = self.func_map[&func.name];
+            function_section.function(type_idx);
+            let wasm_func = if self.generators.contains_key(&func.name) {
+                let (wasm_func, step) = self.compile_generator(func)?;
+                generator_steps.push(step);
+                wasm_func
+            } else {
+                self.compile_func_decl(func)?
+            };
+            code_section.function(&wasm_func);
         }
 
         // Compile _start function if there are top-level statements
@@ -686,10 +1281,12 @@ impl WasmCodegen {
             function_section.function(start_type_idx);
 
             let mut ctx = FuncCtx::new();
+            ctx.reserve_simd_helpers();
+            ctx.boxed = self.boxed_vars.get("_start").cloned().unwrap_or_default();
 
             // Pre-scan top-level statements to declare all variables
             for stmt in &program.statements {
-                if !matches!(stmt, Statement::FuncDecl(_)) {
+                if !matches!(stmt, Statement::FuncDecl(_) | Statement::ImplDecl(_)) {
                     self.prescan_stmt(stmt, &mut ctx);
                 }
             }
@@ -708,12 +1305,10 @@ impl WasmCodegen {
                 insn.i64_const(-1);
                 insn.local_set(var_idx);
             }
+            self.emit_upvalue_cells(&[], &mut insn, &ctx);
 
-            for stmt in &program.statements {
-                if !matches!(stmt, Statement::FuncDecl(_)) {
-                    self.compile_stmt(stmt, &mut insn, &mut ctx)?;
-                }
-            }
+            // Declarations compile to nothing here
+            self.compile_stmts(&program.statements, &mut insn, &mut ctx)?;
 
             // Free all owned tables before _start exits
             self.emit_table_cleanup(&mut insn, &ctx, None);
@@ -724,24 +1319,31 @@ impl WasmCodegen {
 
         // Compile closure functions
         self.compile_closure_functions(&mut function_section, &mut code_section, program)?;
+        self.compile_func_refs(&mut function_section, &mut code_section);
+        self.compile_generator_steps(generator_steps, &mut function_section, &mut code_section);
+        self.compile_struct_formatters(&mut function_section, &mut code_section);
+        self.compile_enum_formatters(&mut function_section, &mut code_section);
+        self.compile_throw_func(&mut function_section, &mut code_section);
 
-        // Table section: funcref table for closures
+        // Table section: funcref table for closures, function values and generator steps
         let mut table_section = TableSection::new();
-        let num_closures = self.closures.len() as u64;
-        if num_closures > 0 {
+        let num_entries = (self.closures.len() + self.func_refs.len() + self.generators.len()) as u64;
+        if num_entries > 0 {
             table_section.table(TableType {
                 element_type: RefType::FUNCREF,
-                minimum: num_closures,
-                maximum: Some(num_closures),
+                minimum: num_entries,
+                maximum: Some(num_entries),
                 table64: false,
                 shared: false,
             });
         }
 
-        // Element section: populate the table with closure function indices
+        // Element section: populate the table with closure and trampoline function indices
         let mut element_section = ElementSection::new();
-        if !self.closures.is_empty() {
-            let func_indices: Vec<u32> = self.closures.iter().map(|c| c.func_idx).collect();
+        if num_entries > 0 {
+            let mut func_indices: Vec<u32> = self.closures.iter().map(|c| c.func_idx).collect();
+            func_indices.extend(self.func_ref_order().iter().map(|r| r.func_idx));
+            func_indices.extend(self.generator_order().iter().map(|g| g.step_func_idx));
             let offset = ConstExpr::i32_const(0);
             element_section.active(
                 None, // table 0 (MVP encoding for funcref)
@@ -779,23 +1381,45 @@ impl WasmCodegen {
             },
             &ConstExpr::i32_const(heap_base_value),
         );
+        // __error (mutable i32): address of the pending Error, 0 when none
+        global_section.global(
+            GlobalType {
+                val_type: ValType::I32,
+                mutable: true,
+                shared: false,
+            },
+            &ConstExpr::i32_const(0),
+        );
 
         // Export section
         let mut export_section = ExportSection::new();
-        for stmt in &program.statements {
-            if let Statement::FuncDecl(func) = stmt {
-                let (func_idx, _) = self.func_map[&func.name];
-                export_section.export(&func.name, ExportKind::Func, func_idx);
-            }
+        for func in user_funcs(program) {
+            let (func_idx, _) = self.func_map[&func.name];
+            export_section.export(&func.name, ExportKind::Func, func_idx);
         }
         if has_top_level {
             let (start_idx, _) = self.func_map["_start"];
             export_section.export("_start", ExportKind::Func, start_idx);
         }
+        // Hosts drive coroutines returned by exported generators through these
+        if !self.generators.is_empty() {
+            for name in ["__resume", "__is_done"] {
+                export_section.export(name, ExportKind::Func, self.func_map[name].0);
+            }
+        }
+        // Struct and enum formatters, numbered in registration order, let the host
+        // render those values when they are stored in a list
+        let formatters = self.structs.iter().map(|info| &info.name).chain(self.enums.iter().map(|info| &info.name));
+        for (n, name) in formatters.enumerate() {
+            let (func_idx, _) = self.func_map[&format!("__fmt_{}", name)];
+            export_section.export(&format!("__fmt_{}", n), ExportKind::Func, func_idx);
+        }
         // Export memory so the host can read string data
         export_section.export("memory", ExportKind::Memory, 0);
         // Export __heap_base global (index 0)
         export_section.export("__heap_base", ExportKind::Global, 0);
+        // Export __error global (index 1) so the host can raise and report errors
+        export_section.export("__error", ExportKind::Global, compile_try::ERROR_GLOBAL);
 
         // Data section: string pool
         let mut data_section = DataSection::new();
@@ -810,13 +1434,13 @@ impl WasmCodegen {
         module.section(&type_section);
         module.section(&import_section);
         module.section(&function_section);
-        if num_closures > 0 {
+        if num_entries > 0 {
             module.section(&table_section);
         }
         module.section(&memory_section);
         module.section(&global_section);
         module.section(&export_section);
-        if !self.closures.is_empty() {
+        if num_entries > 0 {
             module.section(&element_section);
         }
         module.section(&code_section);
@@ -824,6 +1448,7 @@ impl WasmCodegen {
             module.section(&data_section);
         }
 
+        self.warnings = self.field_warnings();
         Ok(module.finish())
     }
 }
@@ -4,122 +4,229 @@ impl WasmCodegen {
     /// Walk the entire program AST and collect all closure expressions.
     /// For each closure, register a hidden WASM function and record its metadata.
     pub(super) fn collect_closures(&mut self, program: &Program) {
-        // Collect all closures from statements (depth-first order)
-        let stmts: Vec<&Statement> = program.statements.iter().collect();
+        // Collect all closures from statements (depth-first order); each is
+        // walked with the variables declared in its function in scope
+        let mut scope = HashSet::new();
+        for stmt in &program.statements {
+            if !matches!(stmt, Statement::FuncDecl(_) | Statement::ImplDecl(_)) {
+                Self::find_declared_vars_stmt(stmt, &mut scope);
+            }
+        }
+        for stmt in &program.statements {
+            self.collect_closures_stmt(stmt, &scope);
+        }
+
+        // Decide which captures live in shared cells, one function at a time
+        let top_level: Vec<&Statement> = program
+            .statements
+            .iter()
+            .filter(|stmt| !matches!(stmt, Statement::FuncDecl(_) | Statement::ImplDecl(_)))
+            .collect();
+        self.box_captured_vars("_start", &top_level);
+        for func in user_funcs(program) {
+            let body: Vec<&Statement> = func.body.statements.iter().collect();
+            self.box_captured_vars(&func.name, &body);
+        }
+    }
+
+    /// Captured variables that are reassigned anywhere in a function (its closures
+    /// included) are shared through heap cells, like Lua upvalues, so every closure
+    /// and the function itself see the same value. Others are copied at capture.
+    /// The decision is by name across the whole function, so every scope that sees
+    /// a boxed name agrees on its representation.
+    fn box_captured_vars(&mut self, func_name: &str, stmts: &[&Statement]) {
+        let mut closure_exprs = Vec::new();
+        let mut assigned = HashSet::new();
         for stmt in stmts {
-            self.collect_closures_stmt(stmt);
+            Self::collect_closure_expr_refs_stmt(stmt, &mut closure_exprs);
+            Self::find_assigned_stmt(stmt, &mut assigned);
         }
+        let ids: Vec<u32> = closure_exprs
+            .iter()
+            .map(|closure| self.closure_span_map[&closure.span])
+            .collect();
+        // A called capture may be a closure assigned after the one calling it,
+        // as in `var fib = |n| => ... fib(n - 1)`
+        let boxed: HashSet<String> = ids
+            .iter()
+            .flat_map(|&id| {
+                let info = &self.closures[id as usize];
+                info.captures.iter().filter(|name| assigned.contains(*name) || info.called.contains(*name))
+            })
+            .cloned()
+            .collect();
+        if boxed.is_empty() {
+            return;
+        }
+        for id in ids {
+            self.closures[id as usize].boxed = boxed.clone();
+        }
+        self.boxed_vars.insert(func_name.to_string(), boxed);
     }
 
-    fn collect_closures_stmt(&mut self, stmt: &Statement) {
+    fn collect_closures_stmt(&mut self, stmt: &Statement, scope: &HashSet<String>) {
         match stmt {
             Statement::VarDecl(VarDecl::Assignment(assign)) | Statement::Assignment(assign) => {
                 for val in &assign.values {
-                    self.collect_closures_expr(val);
+                    self.collect_closures_expr(val, scope);
                 }
             }
             Statement::VarDecl(VarDecl::TypeDecl { .. }) => {}
             Statement::IfStmt(if_stmt) => {
-                self.collect_closures_boolean_expr(&if_stmt.condition);
-                self.collect_closures_block(&if_stmt.body);
+                self.collect_closures_boolean_expr(&if_stmt.condition, scope);
+                self.collect_closures_block(&if_stmt.body, scope);
                 for branch in &if_stmt.else_if {
-                    self.collect_closures_boolean_expr(&branch.condition);
-                    self.collect_closures_block(&branch.body);
+                    self.collect_closures_boolean_expr(&branch.condition, scope);
+                    self.collect_closures_block(&branch.body, scope);
                 }
                 if let Some(else_body) = &if_stmt.else_body {
-                    self.collect_closures_block(else_body);
+                    self.collect_closures_block(else_body, scope);
                 }
             }
             Statement::ForStmt(for_stmt) => {
                 if let Some(init) = &for_stmt.init {
-                    self.collect_closures_stmt(init);
+                    self.collect_closures_stmt(init, scope);
                 }
                 if let Some(cond) = &for_stmt.condition {
-                    self.collect_closures_boolean_expr(cond);
+                    self.collect_closures_boolean_expr(cond, scope);
                 }
                 if let Some(step) = &for_stmt.step {
-                    self.collect_closures_stmt(step);
+                    self.collect_closures_stmt(step, scope);
                 }
-                self.collect_closures_block(&for_stmt.body);
+                self.collect_closures_block(&for_stmt.body, scope);
+            }
+            Statement::ForIn(for_in) => {
+                self.collect_closures_expr(&for_in.iterable, scope);
+                self.collect_closures_block(&for_in.body, scope);
             }
             Statement::Block(block) => {
-                self.collect_closures_block(block);
+                self.collect_closures_block(block, scope);
             }
             Statement::Return(ret) => {
                 for val in &ret.values {
-                    self.collect_closures_expr(val);
+                    self.collect_closures_expr(val, scope);
                 }
             }
+            Statement::Yield(y) => {
+                if let Some(val) = &y.value {
+                    self.collect_closures_expr(val, scope);
+                }
+            }
+            Statement::Try(t) => {
+                self.collect_closures_block(&t.body, scope);
+                self.collect_closures_block(&t.handler, scope);
+            }
+            Statement::Throw(throw) => {
+                self.collect_closures_expr(&throw.value, scope);
+            }
+            Statement::Defer(d) => {
+                self.collect_closures_block(&d.body, scope);
+            }
             Statement::CallFunc(call) => {
                 for arg in &call.args {
-                    self.collect_closures_expr(arg);
+                    self.collect_closures_expr(arg, scope);
                 }
             }
             Statement::FuncDecl(func) => {
-                self.collect_closures_block(&func.body);
+                self.collect_closures_block(&func.body, &Self::func_scope(func));
+            }
+            Statement::ImplDecl(imp) => {
+                for method in &imp.methods {
+                    self.collect_closures_block(&method.body, &Self::func_scope(method));
+                }
             }
             Statement::TimerStmt(timer) => {
-                self.collect_closures_block(&timer.body);
+                self.collect_closures_block(&timer.body, scope);
             }
             Statement::FieldAssign(fa) => {
-                self.collect_closures_expr(&fa.value);
+                self.collect_closures_expr(&fa.value, scope);
             }
             Statement::IndexAssign(ia) => {
-                self.collect_closures_expr(&ia.index);
-                self.collect_closures_expr(&ia.value);
+                self.collect_closures_expr(&ia.index, scope);
+                self.collect_closures_expr(&ia.value, scope);
             }
             Statement::MethodCall(mc) => {
-                self.collect_closures_expr(&mc.callee);
+                self.collect_closures_expr(&mc.callee, scope);
                 for arg in &mc.args {
-                    self.collect_closures_expr(arg);
+                    self.collect_closures_expr(arg, scope);
                 }
             }
+            Statement::Expr(expr) => {
+                self.collect_closures_expr(expr, scope);
+            }
             _ => {}
         }
     }
 
-    fn collect_closures_block(&mut self, block: &Block) {
+    fn collect_closures_block(&mut self, block: &Block, scope: &HashSet<String>) {
         for stmt in &block.statements {
-            self.collect_closures_stmt(stmt);
+            self.collect_closures_stmt(stmt, scope);
         }
     }
 
-    fn collect_closures_boolean_expr(&mut self, expr: &BooleanExpr) {
+    fn collect_closures_boolean_expr(&mut self, expr: &BooleanExpr, scope: &HashSet<String>) {
         match expr {
             BooleanExpr::Comparison { left, right, .. } => {
-                self.collect_closures_expr(left);
-                self.collect_closures_expr(right);
+                self.collect_closures_expr(left, scope);
+                self.collect_closures_expr(right, scope);
             }
             BooleanExpr::Logical { left, right, .. } => {
-                self.collect_closures_boolean_expr(left);
-                self.collect_closures_boolean_expr(right);
+                self.collect_closures_boolean_expr(left, scope);
+                self.collect_closures_boolean_expr(right, scope);
             }
             BooleanExpr::Grouped(inner) => {
-                self.collect_closures_boolean_expr(inner);
+                self.collect_closures_boolean_expr(inner, scope);
             }
         }
     }
 
-    fn collect_closures_expr(&mut self, expr: &Expr) {
+    fn collect_closures_expr(&mut self, expr: &Expr, scope: &HashSet<String>) {
         match expr {
             Expr::Closure(closure) => {
-                // First, recurse into the closure body to collect nested closures
-                match &closure.body {
-                    ClosureBody::Expr(e) => self.collect_closures_expr(e),
-                    ClosureBody::Block(b) => self.collect_closures_block(b),
-                }
-
                 // Extract param names
                 let param_names: HashSet<String> =
                     closure.params.iter().map(|p| p.name.clone()).collect();
                 let param_count = closure.params.len();
 
-                // Walk the closure body to find all variable references
+                // Variables declared inside the closure body are not captures
+                let mut body_locals = HashSet::new();
+                match &closure.body {
+                    ClosureBody::Expr(_) => {}
+                    ClosureBody::Block(b) => Self::find_declared_vars_block(b, &mut body_locals),
+                }
+
+                // First, recurse into the closure body to collect nested closures
+                let inner_scope: HashSet<String> =
+                    scope.iter().chain(&param_names).chain(&body_locals).cloned().collect();
+                match &closure.body {
+                    ClosureBody::Expr(e) => self.collect_closures_expr(e, &inner_scope),
+                    ClosureBody::Block(b) => self.collect_closures_block(b, &inner_scope),
+                }
+
+                // Walk the closure body to find all variable references, and the
+                // variables of the enclosing scope it calls (closures, itself included)
                 let mut referenced = HashSet::new();
+                let mut called = HashSet::new();
                 match &closure.body {
-                    ClosureBody::Expr(e) => Self::find_variables_expr(e, &mut referenced),
-                    ClosureBody::Block(b) => Self::find_variables_block(b, &mut referenced),
+                    ClosureBody::Expr(e) => {
+                        Self::find_variables_expr(e, &mut referenced);
+                        Self::find_refs_expr(e, &mut called, true);
+                    }
+                    ClosureBody::Block(b) => {
+                        Self::find_variables_block(b, &mut referenced);
+                        Self::find_refs_block(b, &mut called, true);
+                    }
+                }
+                called.retain(|name| scope.contains(name));
+                referenced.extend(called.iter().cloned());
+                // A variable of the enclosing scope the closure only assigns to is
+                // captured as well, or the store would go to a local of its own
+                let mut assigned = HashSet::new();
+                match &closure.body {
+                    ClosureBody::Expr(e) => Self::find_assigned_expr(e, &mut assigned),
+                    ClosureBody::Block(b) => Self::find_assigned_block(b, &mut assigned),
                 }
+                referenced.extend(assigned.into_iter().filter(|name| scope.contains(name)));
 
                 // Build the set of names that are NOT captures
                 let mut non_captures = HashSet::new();
@@ -132,14 +239,7 @@ impl WasmCodegen {
                 non_captures.insert("print".to_string());
                 non_captures.insert("input".to_string());
 
-                // Captures = referenced - non_captures
-                // Also exclude variables declared inside the closure body
-                let mut body_locals = HashSet::new();
-                match &closure.body {
-                    ClosureBody::Expr(_) => {}
-                    ClosureBody::Block(b) => Self::find_declared_vars_block(b, &mut body_locals),
-                }
-
+                // Captures = referenced - non_captures - body locals
                 let mut captures: Vec<String> = referenced
                     .into_iter()
                     .filter(|name| !non_captures.contains(name) && !body_locals.contains(name))
@@ -172,7 +272,7 @@ impl WasmCodegen {
                     for p in &closure.params {
                         tmp_ctx.add_param(&p.name);
                         if let Some(ref tn) = p.type_name {
-                            tmp_ctx.var_types.insert(p.name.clone(), type_name_to_ah(tn));
+                            tmp_ctx.var_types.insert(p.name.clone(), self.resolve_type(tn));
                         }
                     }
                     match &closure.body {
@@ -192,182 +292,520 @@ impl WasmCodegen {
                     param_count,
                     table_idx,
                     return_type,
+                    boxed: HashSet::new(),
+                    called: called.into_iter().filter(|name| !body_locals.contains(name)).collect(),
+                    param_types: closure
+                        .params
+                        .iter()
+                        .map(|p| p.type_name.as_deref().map_or(AhType::Int, |tn| self.resolve_type(tn)))
+                        .collect(),
                 };
                 self.closures.push(info);
 
                 // Map the span to the closure ID
                 self.closure_span_map
-                    .insert((closure.span.line, closure.span.column), closure_id);
+                    .insert(closure.span, closure_id);
             }
             Expr::BinaryOp { left, right, .. } => {
-                self.collect_closures_expr(left);
-                self.collect_closures_expr(right);
+                self.collect_closures_expr(left, scope);
+                self.collect_closures_expr(right, scope);
             }
             Expr::CallFunc(call) => {
                 for arg in &call.args {
-                    self.collect_closures_expr(arg);
+                    self.collect_closures_expr(arg, scope);
                 }
             }
             Expr::Grouped(inner) => {
-                self.collect_closures_expr(inner);
+                self.collect_closures_expr(inner, scope);
+            }
+            Expr::UnaryOp { operand, .. } => {
+                self.collect_closures_expr(operand, scope);
+            }
+            Expr::Cast(cast) => {
+                self.collect_closures_expr(&cast.expr, scope);
+            }
+            Expr::Interpolated { parts, .. } => {
+                for part in parts {
+                    if let InterpPart::Expr(e) = part {
+                        self.collect_closures_expr(e, scope);
+                    }
+                }
+            }
+            Expr::If(e) => {
+                self.collect_closures_boolean_expr(&e.condition, scope);
+                self.collect_closures_expr(&e.then_value, scope);
+                self.collect_closures_expr(&e.else_value, scope);
+            }
+            Expr::BooleanExpr(bool_expr) => {
+                self.collect_closures_boolean_expr(bool_expr, scope);
+            }
+            Expr::Match(m) => {
+                self.collect_closures_expr(&m.subject, scope);
+                for arm in &m.arms {
+                    match &arm.body {
+                        MatchBody::Expr(body) => self.collect_closures_expr(body, scope),
+                        MatchBody::Block(block) => self.collect_closures_block(block, scope),
+                    }
+                }
+            }
+            Expr::New(new) => {
+                for entry in &new.fields {
+                    self.collect_closures_expr(&entry.value, scope);
+                }
             }
             Expr::TableLiteral(table) => {
                 for entry in &table.entries {
-                    self.collect_closures_expr(&entry.value);
+                    self.collect_closures_expr(&entry.value, scope);
+                }
+            }
+            Expr::ListLiteral(list) => {
+                for element in &list.elements {
+                    self.collect_closures_expr(element, scope);
                 }
             }
             Expr::FieldAccess(fa) => {
-                self.collect_closures_expr(&fa.object);
+                self.collect_closures_expr(&fa.object, scope);
             }
             Expr::IndexAccess(ia) => {
-                self.collect_closures_expr(&ia.object);
-                self.collect_closures_expr(&ia.index);
+                self.collect_closures_expr(&ia.object, scope);
+                self.collect_closures_expr(&ia.index, scope);
             }
             Expr::MethodCall(mc) => {
-                self.collect_closures_expr(&mc.callee);
+                self.collect_closures_expr(&mc.callee, scope);
                 for arg in &mc.args {
-                    self.collect_closures_expr(arg);
+                    self.collect_closures_expr(arg, scope);
                 }
             }
             _ => {}
         }
     }
 
-    /// Find all variable references in an expression (recursively).
+    /// Find all variable references in an expression (recursively). Call
+    /// targets are not variable references.
     pub(super) fn find_variables_expr(expr: &Expr, vars: &mut HashSet<String>) {
+        Self::find_refs_expr(expr, vars, false)
+    }
+
+    pub(super) fn find_variables_block(block: &Block, vars: &mut HashSet<String>) {
+        Self::find_refs_block(block, vars, false)
+    }
+
+    pub(super) fn find_variables_stmt(stmt: &Statement, vars: &mut HashSet<String>) {
+        Self::find_refs_stmt(stmt, vars, false)
+    }
+
+    /// Find variable references like `find_variables_expr`, or with `calls`
+    /// the names called as functions instead (`f(x)` may call a closure variable).
+    fn find_refs_expr(expr: &Expr, vars: &mut HashSet<String>, calls: bool) {
         match expr {
-            Expr::Variable(name, _) => {
+            Expr::Variable(name, _) if !calls => {
                 vars.insert(name.clone());
             }
             Expr::BinaryOp { left, right, .. } => {
-                Self::find_variables_expr(left, vars);
-                Self::find_variables_expr(right, vars);
+                Self::find_refs_expr(left, vars, calls);
+                Self::find_refs_expr(right, vars, calls);
             }
             Expr::CallFunc(call) => {
-                // The call target name is not a variable reference for capture purposes
+                if calls {
+                    vars.insert(call.name.clone());
+                }
                 for arg in &call.args {
-                    Self::find_variables_expr(arg, vars);
+                    Self::find_refs_expr(arg, vars, calls);
                 }
             }
             Expr::Grouped(inner) => {
-                Self::find_variables_expr(inner, vars);
+                Self::find_refs_expr(inner, vars, calls);
             }
             Expr::UnaryOp { operand, .. } => {
-                vars.insert(operand.clone());
+                Self::find_refs_expr(operand, vars, calls);
+            }
+            Expr::Cast(cast) => {
+                Self::find_refs_expr(&cast.expr, vars, calls);
+            }
+            Expr::Interpolated { parts, .. } => {
+                for part in parts {
+                    if let InterpPart::Expr(e) = part {
+                        Self::find_refs_expr(e, vars, calls);
+                    }
+                }
             }
             Expr::Closure(closure) => {
                 // Variables inside a nested closure may also reference outer scope
                 match &closure.body {
-                    ClosureBody::Expr(e) => Self::find_variables_expr(e, vars),
-                    ClosureBody::Block(b) => Self::find_variables_block(b, vars),
+                    ClosureBody::Expr(e) => Self::find_refs_expr(e, vars, calls),
+                    ClosureBody::Block(b) => Self::find_refs_block(b, vars, calls),
+                }
+            }
+            Expr::If(e) => {
+                Self::find_refs_boolean_expr(&e.condition, vars, calls);
+                Self::find_refs_expr(&e.then_value, vars, calls);
+                Self::find_refs_expr(&e.else_value, vars, calls);
+            }
+            Expr::BooleanExpr(bool_expr) => {
+                Self::find_refs_boolean_expr(bool_expr, vars, calls);
+            }
+            Expr::Match(m) => {
+                Self::find_refs_expr(&m.subject, vars, calls);
+                for arm in &m.arms {
+                    match &arm.body {
+                        MatchBody::Expr(body) => Self::find_refs_expr(body, vars, calls),
+                        MatchBody::Block(block) => Self::find_refs_block(block, vars, calls),
+                    }
+                }
+            }
+            Expr::New(new) => {
+                for entry in &new.fields {
+                    Self::find_refs_expr(&entry.value, vars, calls);
                 }
             }
             Expr::TableLiteral(table) => {
                 for entry in &table.entries {
-                    Self::find_variables_expr(&entry.value, vars);
+                    Self::find_refs_expr(&entry.value, vars, calls);
+                }
+            }
+            Expr::ListLiteral(list) => {
+                for element in &list.elements {
+                    Self::find_refs_expr(element, vars, calls);
                 }
             }
             Expr::FieldAccess(fa) => {
-                Self::find_variables_expr(&fa.object, vars);
+                Self::find_refs_expr(&fa.object, vars, calls);
             }
             Expr::IndexAccess(ia) => {
-                Self::find_variables_expr(&ia.object, vars);
-                Self::find_variables_expr(&ia.index, vars);
+                Self::find_refs_expr(&ia.object, vars, calls);
+                Self::find_refs_expr(&ia.index, vars, calls);
             }
             Expr::MethodCall(mc) => {
-                Self::find_variables_expr(&mc.callee, vars);
+                Self::find_refs_expr(&mc.callee, vars, calls);
                 for arg in &mc.args {
-                    Self::find_variables_expr(arg, vars);
+                    Self::find_refs_expr(arg, vars, calls);
                 }
             }
             _ => {}
         }
     }
 
-    pub(super) fn find_variables_block(block: &Block, vars: &mut HashSet<String>) {
+    fn find_refs_block(block: &Block, vars: &mut HashSet<String>, calls: bool) {
         for stmt in &block.statements {
-            Self::find_variables_stmt(stmt, vars);
+            Self::find_refs_stmt(stmt, vars, calls);
         }
     }
 
-    fn find_variables_stmt(stmt: &Statement, vars: &mut HashSet<String>) {
+    fn find_refs_stmt(stmt: &Statement, vars: &mut HashSet<String>, calls: bool) {
         match stmt {
             Statement::VarDecl(VarDecl::Assignment(assign)) | Statement::Assignment(assign) => {
                 for val in &assign.values {
-                    Self::find_variables_expr(val, vars);
+                    Self::find_refs_expr(val, vars, calls);
                 }
             }
             Statement::IfStmt(if_stmt) => {
-                Self::find_variables_boolean_expr(&if_stmt.condition, vars);
-                Self::find_variables_block(&if_stmt.body, vars);
+                Self::find_refs_boolean_expr(&if_stmt.condition, vars, calls);
+                Self::find_refs_block(&if_stmt.body, vars, calls);
                 for branch in &if_stmt.else_if {
-                    Self::find_variables_boolean_expr(&branch.condition, vars);
-                    Self::find_variables_block(&branch.body, vars);
+                    Self::find_refs_boolean_expr(&branch.condition, vars, calls);
+                    Self::find_refs_block(&branch.body, vars, calls);
                 }
                 if let Some(else_body) = &if_stmt.else_body {
-                    Self::find_variables_block(else_body, vars);
+                    Self::find_refs_block(else_body, vars, calls);
                 }
             }
             Statement::ForStmt(for_stmt) => {
                 if let Some(init) = &for_stmt.init {
-                    Self::find_variables_stmt(init, vars);
+                    Self::find_refs_stmt(init, vars, calls);
                 }
                 if let Some(cond) = &for_stmt.condition {
-                    Self::find_variables_boolean_expr(cond, vars);
+                    Self::find_refs_boolean_expr(cond, vars, calls);
                 }
                 if let Some(step) = &for_stmt.step {
-                    Self::find_variables_stmt(step, vars);
+                    Self::find_refs_stmt(step, vars, calls);
                 }
-                Self::find_variables_block(&for_stmt.body, vars);
+                Self::find_refs_block(&for_stmt.body, vars, calls);
+            }
+            Statement::ForIn(for_in) => {
+                Self::find_refs_expr(&for_in.iterable, vars, calls);
+                Self::find_refs_block(&for_in.body, vars, calls);
             }
             Statement::Block(block) => {
-                Self::find_variables_block(block, vars);
+                Self::find_refs_block(block, vars, calls);
             }
             Statement::Return(ret) => {
                 for val in &ret.values {
-                    Self::find_variables_expr(val, vars);
+                    Self::find_refs_expr(val, vars, calls);
                 }
             }
+            Statement::Yield(y) => {
+                if let Some(val) = &y.value {
+                    Self::find_refs_expr(val, vars, calls);
+                }
+            }
+            Statement::Try(t) => {
+                Self::find_refs_block(&t.body, vars, calls);
+                Self::find_refs_block(&t.handler, vars, calls);
+            }
+            Statement::Throw(throw) => {
+                Self::find_refs_expr(&throw.value, vars, calls);
+            }
+            Statement::Defer(d) => {
+                Self::find_refs_block(&d.body, vars, calls);
+            }
             Statement::CallFunc(call) => {
+                if calls {
+                    vars.insert(call.name.clone());
+                }
                 for arg in &call.args {
-                    Self::find_variables_expr(arg, vars);
+                    Self::find_refs_expr(arg, vars, calls);
                 }
             }
             Statement::FieldAssign(fa) => {
-                vars.insert(fa.object.clone());
-                Self::find_variables_expr(&fa.value, vars);
+                if !calls {
+                    vars.insert(fa.object.clone());
+                }
+                Self::find_refs_expr(&fa.value, vars, calls);
             }
             Statement::IndexAssign(ia) => {
-                vars.insert(ia.object.clone());
-                Self::find_variables_expr(&ia.index, vars);
-                Self::find_variables_expr(&ia.value, vars);
+                if !calls {
+                    vars.insert(ia.object.clone());
+                }
+                Self::find_refs_expr(&ia.index, vars, calls);
+                Self::find_refs_expr(&ia.value, vars, calls);
             }
             Statement::MethodCall(mc) => {
-                Self::find_variables_expr(&mc.callee, vars);
+                Self::find_refs_expr(&mc.callee, vars, calls);
                 for arg in &mc.args {
-                    Self::find_variables_expr(arg, vars);
+                    Self::find_refs_expr(arg, vars, calls);
                 }
             }
+            Statement::Expr(expr) => {
+                Self::find_refs_expr(expr, vars, calls);
+            }
             _ => {}
         }
     }
 
-    fn find_variables_boolean_expr(expr: &BooleanExpr, vars: &mut HashSet<String>) {
+    fn find_refs_boolean_expr(expr: &BooleanExpr, vars: &mut HashSet<String>, calls: bool) {
         match expr {
             BooleanExpr::Comparison { left, right, .. } => {
-                Self::find_variables_expr(left, vars);
-                Self::find_variables_expr(right, vars);
+                Self::find_refs_expr(left, vars, calls);
+                Self::find_refs_expr(right, vars, calls);
             }
             BooleanExpr::Logical { left, right, .. } => {
-                Self::find_variables_boolean_expr(left, vars);
-                Self::find_variables_boolean_expr(right, vars);
+                Self::find_refs_boolean_expr(left, vars, calls);
+                Self::find_refs_boolean_expr(right, vars, calls);
             }
             BooleanExpr::Grouped(inner) => {
-                Self::find_variables_boolean_expr(inner, vars);
+                Self::find_refs_boolean_expr(inner, vars, calls);
             }
         }
     }
 
+    /// Find all variables that are reassigned (`x = ...`, `x++`, `x--`), including
+    /// inside nested closures. Declarations do not count.
+    fn find_assigned_stmt(stmt: &Statement, assigned: &mut HashSet<String>) {
+        match stmt {
+            Statement::Assignment(assign) => {
+                assigned.extend(assign.targets.iter().cloned());
+                for val in &assign.values {
+                    Self::find_assigned_expr(val, assigned);
+                }
+            }
+            Statement::VarDecl(VarDecl::Assignment(assign)) => {
+                for val in &assign.values {
+                    Self::find_assigned_expr(val, assigned);
+                }
+            }
+            Statement::IfStmt(if_stmt) => {
+                Self::find_assigned_boolean_expr(&if_stmt.condition, assigned);
+                Self::find_assigned_block(&if_stmt.body, assigned);
+                for branch in &if_stmt.else_if {
+                    Self::find_assigned_boolean_expr(&branch.condition, assigned);
+                    Self::find_assigned_block(&branch.body, assigned);
+                }
+                if let Some(else_body) = &if_stmt.else_body {
+                    Self::find_assigned_block(else_body, assigned);
+                }
+            }
+            Statement::ForStmt(for_stmt) => {
+                if let Some(init) = &for_stmt.init {
+                    Self::find_assigned_stmt(init, assigned);
+                }
+                if let Some(cond) = &for_stmt.condition {
+                    Self::find_assigned_boolean_expr(cond, assigned);
+                }
+                if let Some(step) = &for_stmt.step {
+                    Self::find_assigned_stmt(step, assigned);
+                }
+                Self::find_assigned_block(&for_stmt.body, assigned);
+            }
+            Statement::ForIn(for_in) => {
+                Self::find_assigned_expr(&for_in.iterable, assigned);
+                Self::find_assigned_block(&for_in.body, assigned);
+            }
+            Statement::Block(block) => {
+                Self::find_assigned_block(block, assigned);
+            }
+            Statement::TimerStmt(timer) => {
+                Self::find_assigned_block(&timer.body, assigned);
+            }
+            Statement::Return(ret) => {
+                for val in &ret.values {
+                    Self::find_assigned_expr(val, assigned);
+                }
+            }
+            Statement::Yield(y) => {
+                if let Some(val) = &y.value {
+                    Self::find_assigned_expr(val, assigned);
+                }
+            }
+            Statement::Try(t) => {
+                Self::find_assigned_block(&t.body, assigned);
+                Self::find_assigned_block(&t.handler, assigned);
+            }
+            Statement::Throw(throw) => {
+                Self::find_assigned_expr(&throw.value, assigned);
+            }
+            Statement::Defer(d) => {
+                Self::find_assigned_block(&d.body, assigned);
+            }
+            Statement::CallFunc(call) => {
+                for arg in &call.args {
+                    Self::find_assigned_expr(arg, assigned);
+                }
+            }
+            Statement::FieldAssign(fa) => {
+                Self::find_assigned_expr(&fa.value, assigned);
+            }
+            Statement::IndexAssign(ia) => {
+                Self::find_assigned_expr(&ia.index, assigned);
+                Self::find_assigned_expr(&ia.value, assigned);
+            }
+            Statement::MethodCall(mc) => {
+                Self::find_assigned_expr(&mc.callee, assigned);
+                for arg in &mc.args {
+                    Self::find_assigned_expr(arg, assigned);
+                }
+            }
+            Statement::Expr(expr) => {
+                Self::find_assigned_expr(expr, assigned);
+            }
+            _ => {}
+        }
+    }
+
+    fn find_assigned_block(block: &Block, assigned: &mut HashSet<String>) {
+        for stmt in &block.statements {
+            Self::find_assigned_stmt(stmt, assigned);
+        }
+    }
+
+    fn find_assigned_boolean_expr(expr: &BooleanExpr, assigned: &mut HashSet<String>) {
+        match expr {
+            BooleanExpr::Comparison { left, right, .. } => {
+                Self::find_assigned_expr(left, assigned);
+                Self::find_assigned_expr(right, assigned);
+            }
+            BooleanExpr::Logical { left, right, .. } => {
+                Self::find_assigned_boolean_expr(left, assigned);
+                Self::find_assigned_boolean_expr(right, assigned);
+            }
+            BooleanExpr::Grouped(inner) => {
+                Self::find_assigned_boolean_expr(inner, assigned);
+            }
+        }
+    }
+
+    fn find_assigned_expr(expr: &Expr, assigned: &mut HashSet<String>) {
+        match expr {
+            Expr::UnaryOp { op: UnaryOp::Increment | UnaryOp::Decrement, operand, .. } => {
+                if let Expr::Variable(name, _) = operand.as_ref() {
+                    assigned.insert(name.clone());
+                }
+                Self::find_assigned_expr(operand, assigned);
+            }
+            Expr::UnaryOp { operand, .. } => {
+                Self::find_assigned_expr(operand, assigned);
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                Self::find_assigned_expr(left, assigned);
+                Self::find_assigned_expr(right, assigned);
+            }
+            Expr::CallFunc(call) => {
+                for arg in &call.args {
+                    Self::find_assigned_expr(arg, assigned);
+                }
+            }
+            Expr::Grouped(inner) => {
+                Self::find_assigned_expr(inner, assigned);
+            }
+            Expr::Cast(cast) => {
+                Self::find_assigned_expr(&cast.expr, assigned);
+            }
+            Expr::Interpolated { parts, .. } => {
+                for part in parts {
+                    if let InterpPart::Expr(e) = part {
+                        Self::find_assigned_expr(e, assigned);
+                    }
+                }
+            }
+            Expr::Closure(closure) => match &closure.body {
+                ClosureBody::Expr(e) => Self::find_assigned_expr(e, assigned),
+                ClosureBody::Block(b) => Self::find_assigned_block(b, assigned),
+            },
+            Expr::If(e) => {
+                Self::find_assigned_boolean_expr(&e.condition, assigned);
+                Self::find_assigned_expr(&e.then_value, assigned);
+                Self::find_assigned_expr(&e.else_value, assigned);
+            }
+            Expr::BooleanExpr(bool_expr) => {
+                Self::find_assigned_boolean_expr(bool_expr, assigned);
+            }
+            Expr::Match(m) => {
+                Self::find_assigned_expr(&m.subject, assigned);
+                for arm in &m.arms {
+                    match &arm.body {
+                        MatchBody::Expr(body) => Self::find_assigned_expr(body, assigned),
+                        MatchBody::Block(block) => Self::find_assigned_block(block, assigned),
+                    }
+                }
+            }
+            Expr::New(new) => {
+                for entry in &new.fields {
+                    Self::find_assigned_expr(&entry.value, assigned);
+                }
+            }
+            Expr::TableLiteral(table) => {
+                for entry in &table.entries {
+                    Self::find_assigned_expr(&entry.value, assigned);
+                }
+            }
+            Expr::ListLiteral(list) => {
+                for element in &list.elements {
+                    Self::find_assigned_expr(element, assigned);
+                }
+            }
+            Expr::FieldAccess(fa) => {
+                Self::find_assigned_expr(&fa.object, assigned);
+            }
+            Expr::IndexAccess(ia) => {
+                Self::find_assigned_expr(&ia.object, assigned);
+                Self::find_assigned_expr(&ia.index, assigned);
+            }
+            Expr::MethodCall(mc) => {
+                Self::find_assigned_expr(&mc.callee, assigned);
+                for arg in &mc.args {
+                    Self::find_assigned_expr(arg, assigned);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Parameters and variables of a function: what closures in its body may capture.
+    fn func_scope(func: &FuncDecl) -> HashSet<String> {
+        let mut scope: HashSet<String> = func.params.iter().map(|p| p.name.clone()).collect();
+        Self::find_declared_vars_block(&func.body, &mut scope);
+        scope
+    }
+
     /// Find all variables declared inside a block (for excluding from captures).
     pub(super) fn find_declared_vars_block(block: &Block, declared: &mut HashSet<String>) {
         for stmt in &block.statements {
@@ -400,6 +838,18 @@ impl WasmCodegen {
                 }
                 Self::find_declared_vars_block(&for_stmt.body, declared);
             }
+            Statement::ForIn(for_in) => {
+                declared.insert(for_in.var.clone());
+                Self::find_declared_vars_block(&for_in.body, declared);
+            }
+            Statement::Try(t) => {
+                Self::find_declared_vars_block(&t.body, declared);
+                declared.extend(t.error_name.iter().cloned());
+                Self::find_declared_vars_block(&t.handler, declared);
+            }
+            Statement::Defer(d) => {
+                Self::find_declared_vars_block(&d.body, declared);
+            }
             Statement::Block(block) => {
                 Self::find_declared_vars_block(block, declared);
             }
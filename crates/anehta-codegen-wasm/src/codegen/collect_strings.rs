@@ -52,6 +52,10 @@ impl WasmCodegen {
                 }
                 self.collect_strings_block(&for_stmt.body);
             }
+            Statement::ForIn(for_in) => {
+                self.collect_strings_expr(&for_in.iterable);
+                self.collect_strings_block(&for_in.body);
+            }
             Statement::Block(block) => {
                 self.collect_strings_block(block);
             }
@@ -60,14 +64,37 @@ impl WasmCodegen {
                     self.collect_strings_expr(val);
                 }
             }
+            Statement::Yield(y) => {
+                if let Some(val) = &y.value {
+                    self.collect_strings_expr(val);
+                }
+            }
+            Statement::Try(t) => {
+                self.collect_strings_block(&t.body);
+                self.collect_strings_block(&t.handler);
+            }
+            Statement::Throw(throw) => {
+                self.collect_strings_expr(&throw.value);
+            }
+            Statement::Defer(d) => {
+                self.collect_strings_block(&d.body);
+            }
             Statement::CallFunc(call) => {
                 for arg in &call.args {
                     self.collect_strings_expr(arg);
                 }
+                for key in compile_hof::pair_keys(&call.name) {
+                    self.intern_string(key);
+                }
             }
             Statement::FuncDecl(func) => {
                 self.collect_strings_block(&func.body);
             }
+            Statement::ImplDecl(imp) => {
+                for method in &imp.methods {
+                    self.collect_strings_block(&method.body);
+                }
+            }
             Statement::TimerStmt(timer) => {
                 self.collect_strings_block(&timer.body);
             }
@@ -85,6 +112,9 @@ impl WasmCodegen {
                     self.collect_strings_expr(arg);
                 }
             }
+            Statement::Expr(expr) => {
+                self.collect_strings_expr(expr);
+            }
             _ => {}
         }
     }
@@ -116,22 +146,60 @@ impl WasmCodegen {
                 for arg in &call.args {
                     self.collect_strings_expr(arg);
                 }
+                for key in compile_hof::pair_keys(&call.name) {
+                    self.intern_string(key);
+                }
             }
             Expr::Grouped(inner) => {
                 self.collect_strings_expr(inner);
             }
+            Expr::UnaryOp { operand, .. } => {
+                self.collect_strings_expr(operand);
+            }
+            Expr::Cast(cast) => {
+                self.collect_strings_expr(&cast.expr);
+            }
+            Expr::Interpolated { parts, .. } => {
+                for part in parts {
+                    match part {
+                        InterpPart::Text(text) => {
+                            self.intern_string(text);
+                        }
+                        InterpPart::Expr(e) => self.collect_strings_expr(e),
+                    }
+                }
+            }
             Expr::Closure(closure) => {
                 match &closure.body {
                     ClosureBody::Expr(e) => self.collect_strings_expr(e),
                     ClosureBody::Block(b) => self.collect_strings_block(b),
                 }
             }
+            Expr::Match(m) => {
+                self.collect_strings_expr(&m.subject);
+                for arm in &m.arms {
+                    match &arm.body {
+                        MatchBody::Expr(body) => self.collect_strings_expr(body),
+                        MatchBody::Block(block) => self.collect_strings_block(block),
+                    }
+                }
+            }
+            Expr::New(new) => {
+                for entry in &new.fields {
+                    self.collect_strings_expr(&entry.value);
+                }
+            }
             Expr::TableLiteral(table) => {
                 for entry in &table.entries {
                     self.intern_string(&entry.key);
                     self.collect_strings_expr(&entry.value);
                 }
             }
+            Expr::ListLiteral(list) => {
+                for element in &list.elements {
+                    self.collect_strings_expr(element);
+                }
+            }
             Expr::FieldAccess(fa) => {
                 self.intern_string(&fa.field);
                 self.collect_strings_expr(&fa.object);
@@ -166,6 +234,11 @@ impl WasmCodegen {
                     self.collect_strings_expr(e);
                 }
             }
+            Expr::If(e) => {
+                self.collect_strings_boolean_expr(&e.condition);
+                self.collect_strings_expr(&e.then_value);
+                self.collect_strings_expr(&e.else_value);
+            }
             Expr::BooleanExpr(bool_expr) => {
                 self.collect_strings_boolean_expr(bool_expr);
             }
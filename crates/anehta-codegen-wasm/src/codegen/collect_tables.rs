@@ -34,6 +34,10 @@ impl WasmCodegen {
                 }
                 self.collect_table_types_block(&for_stmt.body);
             }
+            Statement::ForIn(for_in) => {
+                self.collect_table_types_expr(&for_in.iterable);
+                self.collect_table_types_block(&for_in.body);
+            }
             Statement::Block(block) => {
                 self.collect_table_types_block(block);
             }
@@ -42,6 +46,21 @@ impl WasmCodegen {
                     self.collect_table_types_expr(val);
                 }
             }
+            Statement::Yield(y) => {
+                if let Some(val) = &y.value {
+                    self.collect_table_types_expr(val);
+                }
+            }
+            Statement::Try(t) => {
+                self.collect_table_types_block(&t.body);
+                self.collect_table_types_block(&t.handler);
+            }
+            Statement::Throw(throw) => {
+                self.collect_table_types_expr(&throw.value);
+            }
+            Statement::Defer(d) => {
+                self.collect_table_types_block(&d.body);
+            }
             Statement::CallFunc(call) => {
                 for arg in &call.args {
                     self.collect_table_types_expr(arg);
@@ -50,6 +69,11 @@ impl WasmCodegen {
             Statement::FuncDecl(func) => {
                 self.collect_table_types_block(&func.body);
             }
+            Statement::ImplDecl(imp) => {
+                for method in &imp.methods {
+                    self.collect_table_types_block(&method.body);
+                }
+            }
             Statement::TimerStmt(timer) => {
                 self.collect_table_types_block(&timer.body);
             }
@@ -66,6 +90,9 @@ impl WasmCodegen {
                     self.collect_table_types_expr(arg);
                 }
             }
+            Statement::Expr(expr) => {
+                self.collect_table_types_expr(expr);
+            }
             _ => {}
         }
     }
@@ -78,6 +105,20 @@ impl WasmCodegen {
 
     fn collect_table_types_expr(&mut self, expr: &Expr) {
         match expr {
+            Expr::Match(m) => {
+                self.collect_table_types_expr(&m.subject);
+                for arm in &m.arms {
+                    match &arm.body {
+                        MatchBody::Expr(body) => self.collect_table_types_expr(body),
+                        MatchBody::Block(block) => self.collect_table_types_block(block),
+                    }
+                }
+            }
+            Expr::New(new) => {
+                for entry in &new.fields {
+                    self.collect_table_types_expr(&entry.value);
+                }
+            }
             Expr::TableLiteral(table) => {
                 // Recurse into values first (nested tables)
                 for entry in &table.entries {
@@ -91,9 +132,23 @@ impl WasmCodegen {
                     fields.insert(entry.key.clone(), ty);
                 }
                 let id = self.table_types.len() as u32;
-                self.table_types.push(TableTypeInfo { fields });
+                self.table_types.push(TableTypeInfo { fields, elements: None });
+                self.literal_tables.insert(id);
                 self.table_type_span_map
-                    .insert((table.span.line, table.span.column), id);
+                    .insert(table.span, id);
+            }
+            Expr::ListLiteral(list) => {
+                for element in &list.elements {
+                    self.collect_table_types_expr(element);
+                }
+                let tmp_ctx = FuncCtx::new();
+                let types: Vec<AhType> =
+                    list.elements.iter().map(|e| self.infer_expr_type(e, &tmp_ctx)).collect();
+                let elements = self.unify_element_types(&types);
+                let id = self.table_types.len() as u32;
+                self.table_types.push(TableTypeInfo { fields: HashMap::new(), elements });
+                self.table_type_span_map
+                    .insert(list.span, id);
             }
             Expr::BinaryOp { left, right, .. } => {
                 self.collect_table_types_expr(left);
@@ -103,10 +158,42 @@ impl WasmCodegen {
                 for arg in &call.args {
                     self.collect_table_types_expr(arg);
                 }
+                // values(t) gets its element type once the table's fields are known
+                if call.name == "values" {
+                    let id = self.table_types.len() as u32;
+                    self.table_types.push(TableTypeInfo { fields: HashMap::new(), elements: None });
+                    self.table_type_span_map
+                        .insert(call.span, id);
+                }
+                // So do the results of map, zip and enumerate; the pairs made by zip
+                // and enumerate get the next type
+                if matches!(call.name.as_str(), "map" | "zip" | "enumerate") {
+                    let id = self.table_types.len() as u32;
+                    self.table_types.push(TableTypeInfo { fields: HashMap::new(), elements: None });
+                    self.table_type_span_map
+                        .insert(call.span, id);
+                    if call.name != "map" {
+                        self.table_types.push(TableTypeInfo { fields: HashMap::new(), elements: None });
+                        self.literal_tables.insert(id + 1);
+                    }
+                }
             }
             Expr::Grouped(inner) => {
                 self.collect_table_types_expr(inner);
             }
+            Expr::UnaryOp { operand, .. } => {
+                self.collect_table_types_expr(operand);
+            }
+            Expr::Cast(cast) => {
+                self.collect_table_types_expr(&cast.expr);
+            }
+            Expr::Interpolated { parts, .. } => {
+                for part in parts {
+                    if let InterpPart::Expr(e) = part {
+                        self.collect_table_types_expr(e);
+                    }
+                }
+            }
             Expr::Closure(closure) => {
                 match &closure.body {
                     ClosureBody::Expr(e) => self.collect_table_types_expr(e),
@@ -146,6 +233,11 @@ impl WasmCodegen {
                     self.collect_table_types_expr(e);
                 }
             }
+            Expr::If(e) => {
+                self.collect_table_types_boolean_expr(&e.condition);
+                self.collect_table_types_expr(&e.then_value);
+                self.collect_table_types_expr(&e.else_value);
+            }
             Expr::BooleanExpr(bool_expr) => {
                 self.collect_table_types_boolean_expr(bool_expr);
             }
@@ -169,17 +261,69 @@ impl WasmCodegen {
         }
     }
 
+    /// Common element type of a list literal: the shared type, float for an int/float
+    /// mix, or the first table type with the other tables' fields merged into it.
+    /// `None` when the elements are mixed, which compile_list_literal rejects.
+    fn unify_element_types(&mut self, types: &[AhType]) -> Option<AhType> {
+        let (&first, rest) = types.split_first()?;
+        let mut common = first;
+        for &ty in rest {
+            common = self.unify_element_type(common, ty)?;
+        }
+        Some(common)
+    }
+
+    fn unify_element_type(&mut self, a: AhType, b: AhType) -> Option<AhType> {
+        match (a, b) {
+            (AhType::Table(x), AhType::Table(y)) => {
+                if x != y && x != STR_LIST_TABLE_TYPE {
+                    let extra: Vec<(String, AhType)> = self.table_types[y as usize]
+                        .fields
+                        .iter()
+                        .map(|(k, &v)| (k.clone(), v))
+                        .collect();
+                    let info = &mut self.table_types[x as usize];
+                    for (key, ty) in extra {
+                        info.fields.entry(key).or_insert(ty);
+                    }
+                }
+                Some(a)
+            }
+            (AhType::List(x), AhType::List(y))
+                if self.table_types[x as usize].elements == self.table_types[y as usize].elements =>
+            {
+                Some(a)
+            }
+            // Callables with the same signature are called the same way
+            (AhType::Closure(_) | AhType::Func(_), AhType::Closure(_) | AhType::Func(_))
+                if self.callable_signature(a).is_some()
+                    && self.callable_signature(a) == self.callable_signature(b) =>
+            {
+                Some(a)
+            }
+            _ => unify_branch_types(a, b),
+        }
+    }
+
     /// Re-infer table field types after closures are collected.
     /// During collect_table_types (Phase 0b), closure variables weren't known yet,
     /// so fields like `{asd: readB}` where readB is a closure were typed as Int.
     /// Now that closures are collected, we can walk VarDecl assignments to fix this.
+    /// Lists created by an empty `list[]` take their element type from later
+    /// `push`/`insert` calls on the same variable.
     pub(super) fn fixup_table_types(&mut self, program: &Program) {
         // Build a simple var_types map from top-level assignments
         let mut var_types = HashMap::new();
-        self.fixup_table_types_stmts(&program.statements, &mut var_types);
+        let mut refinable = HashSet::new();
+        self.fixup_table_types_stmts(&program.statements, &mut var_types, &mut refinable);
     }
 
-    fn fixup_table_types_stmts(&mut self, stmts: &[Statement], var_types: &mut HashMap<String, AhType>) {
+    fn fixup_table_types_stmts(
+        &mut self,
+        stmts: &[Statement],
+        var_types: &mut HashMap<String, AhType>,
+        refinable: &mut HashSet<u32>,
+    ) {
         for stmt in stmts {
             match stmt {
                 Statement::VarDecl(VarDecl::Assignment(assign)) | Statement::Assignment(assign) => {
@@ -187,57 +331,145 @@ impl WasmCodegen {
                     for (i, target) in assign.targets.iter().enumerate() {
                         if let Some(val) = assign.values.get(i) {
                             let ctx = FuncCtx::new_with_var_types(var_types.clone());
+                            self.refine_list_calls(val, &ctx, refinable);
                             let ty = self.infer_expr_type(val, &ctx);
                             var_types.insert(target.clone(), ty);
 
                             // If value is a table literal, re-infer its field types
-                            if let AhType::Table(id) = ty {
-                                if let Expr::TableLiteral(table) = val {
-                                    // Collect updates first to avoid borrow conflict
-                                    let updates: Vec<(String, AhType)> = table.entries.iter().map(|entry| {
-                                        let field_ty = self.infer_expr_type(&entry.value, &ctx);
-                                        (entry.key.clone(), field_ty)
-                                    }).collect();
-                                    if let Some(info) = self.table_types.get_mut(id as usize) {
-                                        for (key, field_ty) in updates {
-                                            info.fields.insert(key, field_ty);
-                                        }
+                            if let AhType::Table(id) = ty
+                                && let Expr::TableLiteral(table) = val {
+                                // Collect updates first to avoid borrow conflict
+                                let updates: Vec<(String, AhType)> = table.entries.iter().map(|entry| {
+                                    let field_ty = self.infer_expr_type(&entry.value, &ctx);
+                                    (entry.key.clone(), field_ty)
+                                }).collect();
+                                if let Some(info) = self.table_types.get_mut(id as usize) {
+                                    for (key, field_ty) in updates {
+                                        info.fields.insert(key, field_ty);
                                     }
                                 }
                             }
+                            // Likewise for list literals, whose elements may be variables
+                            if let AhType::List(id) = ty
+                                && let Expr::ListLiteral(list) = val {
+                                if list.elements.is_empty() {
+                                    refinable.insert(id);
+                                } else {
+                                    let types: Vec<AhType> = list.elements.iter()
+                                        .map(|e| self.infer_expr_type(e, &ctx))
+                                        .collect();
+                                    self.table_types[id as usize].elements = self.unify_element_types(&types);
+                                }
+                            }
                         }
                     }
                 }
+                Statement::CallFunc(call) => {
+                    let ctx = FuncCtx::new_with_var_types(var_types.clone());
+                    for arg in &call.args {
+                        self.refine_list_calls(arg, &ctx, refinable);
+                    }
+                    self.refine_list_call(call, &ctx, refinable);
+                }
                 Statement::FuncDecl(func) => {
-                    let mut inner_types = var_types.clone();
-                    for param in &func.params {
-                        inner_types.insert(param.name.clone(), type_name_to_ah(&param.type_name));
+                    self.fixup_table_types_func(func, var_types, refinable);
+                }
+                Statement::ImplDecl(imp) => {
+                    for method in &imp.methods {
+                        self.fixup_table_types_func(method, var_types, refinable);
                     }
-                    self.fixup_table_types_stmts(
-                        &func.body.statements,
-                        &mut inner_types,
-                    );
                 }
                 Statement::IfStmt(if_stmt) => {
-                    self.fixup_table_types_stmts(&if_stmt.body.statements, var_types);
+                    self.fixup_table_types_stmts(&if_stmt.body.statements, var_types, refinable);
                     for branch in &if_stmt.else_if {
-                        self.fixup_table_types_stmts(&branch.body.statements, var_types);
+                        self.fixup_table_types_stmts(&branch.body.statements, var_types, refinable);
                     }
                     if let Some(else_body) = &if_stmt.else_body {
-                        self.fixup_table_types_stmts(&else_body.statements, var_types);
+                        self.fixup_table_types_stmts(&else_body.statements, var_types, refinable);
                     }
                 }
                 Statement::ForStmt(for_stmt) => {
                     if let Some(init) = &for_stmt.init {
-                        self.fixup_table_types_stmts(std::slice::from_ref(init.as_ref()), var_types);
+                        self.fixup_table_types_stmts(std::slice::from_ref(init.as_ref()), var_types, refinable);
                     }
-                    self.fixup_table_types_stmts(&for_stmt.body.statements, var_types);
+                    self.fixup_table_types_stmts(&for_stmt.body.statements, var_types, refinable);
+                }
+                Statement::ForIn(for_in) => {
+                    let ctx = FuncCtx::new_with_var_types(var_types.clone());
+                    self.refine_list_calls(&for_in.iterable, &ctx, refinable);
+                    let elem_ty = self.iteration_type(&for_in.iterable, &ctx);
+                    var_types.insert(for_in.var.clone(), elem_ty);
+                    self.fixup_table_types_stmts(&for_in.body.statements, var_types, refinable);
                 }
                 Statement::Block(block) => {
-                    self.fixup_table_types_stmts(&block.statements, var_types);
+                    self.fixup_table_types_stmts(&block.statements, var_types, refinable);
                 }
                 _ => {}
             }
         }
     }
+
+    /// Refine the element type of empty-literal lists from `push`/`insert` calls
+    /// nested anywhere in a call chain such as `push(push(xs, 1), 2)`, of
+    /// `values(t)` lists from the fields of `t`, and of the collections built by
+    /// `map`, `zip` and `enumerate`.
+    fn refine_list_calls(&mut self, expr: &Expr, ctx: &FuncCtx, refinable: &mut HashSet<u32>) {
+        if let Expr::CallFunc(call) = expr {
+            for arg in &call.args {
+                self.refine_list_calls(arg, ctx, refinable);
+            }
+            self.refine_list_call(call, ctx, refinable);
+        }
+    }
+
+    fn refine_list_call(&mut self, call: &CallFunc, ctx: &FuncCtx, refinable: &mut HashSet<u32>) {
+        if let Some(builtin) = self.hof_builtin(&call.name, ctx) {
+            self.refine_hof_call(builtin, call, ctx);
+            return;
+        }
+        if self.table_builtin(call, ctx) == Some(TableBuiltin::Values)
+            && let Some(&id) = self.table_type_span_map.get(&call.span)
+        {
+            let table_ty = call.args.first().map_or(AhType::Int, |arg| self.infer_expr_type(arg, ctx));
+            self.table_types[id as usize].elements = self.table_value_type(table_ty);
+            return;
+        }
+        let value = match self.list_builtin(&call.name, ctx) {
+            Some(ListBuiltin::Push) if call.args.len() == 2 => &call.args[1],
+            Some(ListBuiltin::Insert) if call.args.len() == 3 => &call.args[2],
+            _ => return,
+        };
+        let AhType::List(id) = self.infer_expr_type(&call.args[0], ctx) else {
+            return;
+        };
+        if !refinable.contains(&id) {
+            return;
+        }
+        let ty = self.infer_expr_type(value, ctx);
+        let refined = match self.table_types[id as usize].elements {
+            None => Some(ty),
+            Some(current) => self.unify_element_type(current, ty),
+        };
+        match refined {
+            Some(_) => self.table_types[id as usize].elements = refined,
+            // A push of another type: keep the established element type, so that
+            // compile_element reports the mismatch
+            None => {
+                refinable.remove(&id);
+            }
+        }
+    }
+
+    fn fixup_table_types_func(
+        &mut self,
+        func: &FuncDecl,
+        var_types: &HashMap<String, AhType>,
+        refinable: &mut HashSet<u32>,
+    ) {
+        let mut inner_types = var_types.clone();
+        for param in &func.params {
+            inner_types.insert(param.name.clone(), self.resolve_type(&param.type_name));
+        }
+        self.fixup_table_types_stmts(&func.body.statements, &mut inner_types, refinable);
+    }
 }
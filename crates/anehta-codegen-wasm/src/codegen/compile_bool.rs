@@ -9,11 +9,25 @@ impl WasmCodegen {
     ) -> Result<(), CodegenError> {
         match expr {
             BooleanExpr::Comparison {
-                left, op, right, ..
+                left, op, right, span,
             } => {
                 let lt = self.infer_expr_type(left, ctx);
                 let rt = self.infer_expr_type(right, ctx);
-                if lt == AhType::Float || rt == AhType::Float {
+                let optional = matches!(lt, AhType::Optional(_)) || matches!(rt, AhType::Optional(_));
+                if optional && !matches!(op, ComparisonOp::Eq | ComparisonOp::NotEq) {
+                    self.check_not_optional(left, span, ctx)?;
+                    self.check_not_optional(right, span, ctx)?;
+                }
+                if optional {
+                    // Optionals compare by bit pattern, so `x == nil` tests for nil
+                    self.compile_expr(left, insn, ctx)?;
+                    self.compile_expr(right, insn, ctx)?;
+                    if matches!(op, ComparisonOp::Eq) {
+                        insn.i64_eq();
+                    } else {
+                        insn.i64_ne();
+                    }
+                } else if lt == AhType::Float || rt == AhType::Float {
                     self.emit_float_operand(left, insn, ctx)?;
                     self.emit_float_operand(right, insn, ctx)?;
                     match op {
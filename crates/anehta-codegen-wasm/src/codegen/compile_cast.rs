@@ -0,0 +1,151 @@
+use super::*;
+
+/// High 32 bits of a packed vec/mat: the data pointer.
+const PTR_MASK: i64 = 0xFFFF_FFFF_0000_0000_u64 as i64;
+
+impl WasmCodegen {
+    /// Compile `expr -> type`: numeric conversions, str formatting and parsing,
+    /// and vec/mat reshapes. Conversions that cannot succeed are compile errors.
+    pub(super) fn compile_cast(
+        &self,
+        cast: &Cast,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let source = self.infer_expr_type(&cast.expr, ctx);
+        let target = match cast.target.as_str() {
+            "int" | "float" | "f64" | "str" | "string" | "vec" | "mat" | "matrix" => {
+                type_name_to_ah(&cast.target)
+            }
+            other => {
+                return Err(codegen_err(
+                    format!("unknown conversion target '{}'", other),
+                    &cast.span,
+                ));
+            }
+        };
+        let (t, _) = ctx.claim_math_temps();
+        let mode = cast.mode.as_deref();
+
+        // Modes: int.trunc/round/floor/ceil for float -> int, mat.col/row for vec -> mat
+        let mode_ok = matches!(
+            (mode, source, target),
+            (None, _, _)
+                | (Some("trunc" | "round" | "floor" | "ceil"), AhType::Int | AhType::Float, AhType::Int)
+                | (Some("col" | "row"), AhType::Vec, AhType::Mat)
+        );
+        if !mode_ok {
+            return Err(codegen_err(
+                format!(
+                    "conversion mode '.{}' does not apply to {} -> {}",
+                    mode.unwrap_or_default(),
+                    source.name(),
+                    target.name()
+                ),
+                &cast.span,
+            ));
+        }
+
+        match (source, target) {
+            (AhType::Table(_) | AhType::Closure(_) | AhType::Func(_) | AhType::Coroutine(_), _)
+            | (_, AhType::Table(_) | AhType::Closure(_) | AhType::Func(_) | AhType::Coroutine(_))
+            | (AhType::List(_), AhType::Int | AhType::Float | AhType::Vec | AhType::Mat) => {
+                return Err(codegen_err(
+                    format!("cannot convert {} to {}", source.name(), target.name()),
+                    &cast.span,
+                ));
+            }
+            (_, AhType::Str) => {
+                if source == AhType::Str {
+                    self.compile_expr(&cast.expr, insn, ctx)?;
+                } else {
+                    self.compile_to_str(&cast.expr, &cast.span, insn, ctx)?;
+                }
+            }
+            (AhType::Str, AhType::Int | AhType::Float) => {
+                self.compile_expr(&cast.expr, insn, ctx)?;
+                let import = if target == AhType::Int { "__env_parse_int" } else { "__env_parse_float" };
+                insn.call(self.func_map[import].0);
+            }
+            (AhType::Int, AhType::Int) | (AhType::Float, AhType::Float)
+            | (AhType::Vec, AhType::Vec) | (AhType::Mat, AhType::Mat) => {
+                self.compile_expr(&cast.expr, insn, ctx)?;
+            }
+            (AhType::Int, AhType::Float) => {
+                self.compile_expr(&cast.expr, insn, ctx)?;
+                insn.f64_convert_i64_s();
+                insn.i64_reinterpret_f64();
+            }
+            (AhType::Float, AhType::Int) => {
+                self.compile_expr(&cast.expr, insn, ctx)?;
+                insn.f64_reinterpret_i64();
+                match mode {
+                    Some("floor") => {
+                        insn.f64_floor();
+                    }
+                    Some("ceil") => {
+                        insn.f64_ceil();
+                    }
+                    Some("round") => {
+                        // Half away from zero: trunc(x + copysign(0.5, x))
+                        insn.i64_reinterpret_f64();
+                        insn.local_tee(t);
+                        insn.f64_reinterpret_i64();
+                        insn.f64_const(0.5);
+                        insn.local_get(t);
+                        insn.f64_reinterpret_i64();
+                        insn.f64_copysign();
+                        insn.f64_add();
+                    }
+                    _ => {}
+                }
+                // Saturating: NaN becomes 0 and out-of-range values clamp
+                insn.i64_trunc_sat_f64_s();
+            }
+            (AhType::Vec, AhType::Mat) => {
+                // Same buffer, reinterpreted as n×1 (col, default) or 1×n (row)
+                self.compile_expr(&cast.expr, insn, ctx)?;
+                insn.local_tee(t);
+                insn.i64_const(PTR_MASK);
+                insn.i64_and();
+                insn.local_get(t);
+                insn.i64_const(0xFFFF);
+                insn.i64_and();
+                if mode == Some("row") {
+                    insn.i64_or();
+                    insn.i64_const(1 << 16);
+                } else {
+                    insn.i64_const(16);
+                    insn.i64_shl();
+                    insn.i64_or();
+                    insn.i64_const(1);
+                }
+                insn.i64_or();
+            }
+            (AhType::Mat, AhType::Vec) => {
+                // Flatten row-major: len = rows * cols over the same buffer
+                self.compile_expr(&cast.expr, insn, ctx)?;
+                insn.local_tee(t);
+                insn.i64_const(PTR_MASK);
+                insn.i64_and();
+                insn.local_get(t);
+                insn.i64_const(16);
+                insn.i64_shr_u();
+                insn.i64_const(0xFFFF);
+                insn.i64_and();
+                insn.local_get(t);
+                insn.i64_const(0xFFFF);
+                insn.i64_and();
+                insn.i64_mul();
+                insn.i64_or();
+            }
+            _ => {
+                return Err(codegen_err(
+                    format!("cannot convert {} to {}", source.name(), target.name()),
+                    &cast.span,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
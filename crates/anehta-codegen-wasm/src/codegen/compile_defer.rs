@@ -0,0 +1,92 @@
+use super::*;
+
+use super::compile_try::ERROR_GLOBAL;
+
+impl WasmCodegen {
+    /// Compile `defer { body }` together with `rest`, the statements after it in
+    /// its block. The rest runs inside a block that an error raised in it also
+    /// leaves; the body runs after that block and the error, if any, goes on.
+    /// `return`, `break` and `continue` run the body on their way out.
+    pub(super) fn compile_defer(
+        &self,
+        d: &DeferStmt,
+        rest: &[Statement],
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        if ctx.generator.is_some() {
+            return Err(codegen_err("defer cannot be used in a generator", &d.span));
+        }
+        let temps = ctx.defer_temps[ctx.defer_temps_cursor];
+        ctx.defer_temps_cursor += 1;
+        let deferred = Deferred {
+            body: d.body.clone(),
+            catch_len: ctx.catch_depths.len(),
+            cursors: ctx.temp_cursors(),
+        };
+        // The body's temps are claimed each time it is compiled
+        ctx.set_temp_cursors(temps.end);
+
+        insn.block(BlockType::Empty);
+        ctx.block_depth += 1;
+        ctx.catch_depths.push(ctx.block_depth);
+        ctx.defers.push(deferred);
+        let result = self.compile_stmts(rest, insn, ctx);
+        let deferred = ctx.defers.pop().expect("pushed above");
+        ctx.catch_depths.pop();
+        result?;
+        insn.end();
+        ctx.block_depth -= 1;
+
+        // The body runs with no error pending, so its own calls are checked as usual
+        insn.global_get(ERROR_GLOBAL);
+        insn.i64_extend_i32_u();
+        insn.local_set(temps.error);
+        insn.i32_const(0);
+        insn.global_set(ERROR_GLOBAL);
+        self.compile_deferred(&deferred, insn, ctx)?;
+        insn.local_get(temps.error);
+        insn.i32_wrap_i64();
+        insn.global_set(ERROR_GLOBAL);
+        self.emit_error_check(insn, ctx);
+        Ok(())
+    }
+
+    /// Compile a deferred body where it runs. Its errors go to the handlers
+    /// around the `defer` statement, and its loops are its own.
+    fn compile_deferred(
+        &self,
+        deferred: &Deferred,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let cursors = ctx.temp_cursors();
+        ctx.set_temp_cursors(deferred.cursors);
+        let catch_depths = ctx.catch_depths.split_off(deferred.catch_len);
+        let loops = std::mem::take(&mut ctx.loop_depth_stack);
+        let in_defer = std::mem::replace(&mut ctx.in_defer, true);
+
+        let result = self.compile_block(&deferred.body, insn, ctx);
+
+        ctx.in_defer = in_defer;
+        ctx.loop_depth_stack = loops;
+        ctx.catch_depths.extend(catch_depths);
+        ctx.set_temp_cursors(cursors);
+        result
+    }
+
+    /// Run the bodies deferred since `ctx.defers` had `depth` entries, innermost first,
+    /// before a jump out of their blocks.
+    pub(super) fn emit_pending_defers(
+        &self,
+        depth: usize,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let pending = ctx.defers[depth..].to_vec();
+        for deferred in pending.iter().rev() {
+            self.compile_deferred(deferred, insn, ctx)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+use super::*;
+
+impl WasmCodegen {
+    /// Resolve enum variant payload types (names are registered by `collect_type_decls`).
+    pub(super) fn collect_enum_variants(&mut self, program: &Program) -> Result<(), CodegenError> {
+        let decls: Vec<&EnumDecl> = program
+            .statements
+            .iter()
+            .filter_map(|s| match s {
+                Statement::EnumDecl(decl) => Some(decl),
+                _ => None,
+            })
+            .collect();
+
+        for (id, decl) in decls.iter().enumerate() {
+            if decl.variants.is_empty() {
+                return Err(codegen_err(
+                    format!("enum '{}' has no variants", decl.name),
+                    &decl.span,
+                ));
+            }
+            let mut variants: Vec<(String, Vec<AhType>)> = Vec::new();
+            for variant in &decl.variants {
+                if variants.iter().any(|(name, _)| name == &variant.name) {
+                    return Err(codegen_err(
+                        format!("duplicate variant '{}' in enum '{}'", variant.name, decl.name),
+                        &variant.span,
+                    ));
+                }
+                let mut payload = Vec::new();
+                for type_name in &variant.payload {
+                    let Some(ty) = self.decl_type(type_name) else {
+                        return Err(codegen_err(
+                            format!("unknown type '{}' in variant '{}'", type_name, variant.name),
+                            &variant.span,
+                        ));
+                    };
+                    payload.push(ty);
+                }
+                variants.push((variant.name.clone(), payload));
+            }
+            self.enums[id].variants = variants;
+        }
+        Ok(())
+    }
+
+    /// The enum named by `expr` when it is a bare enum name, as in `State.Idle`.
+    pub(super) fn enum_ref(&self, expr: &Expr) -> Option<u32> {
+        match expr {
+            Expr::Variable(name, _) => self.enum_map.get(name).copied(),
+            _ => None,
+        }
+    }
+
+    /// `Enum.Variant(values...)` parses as a method call; returns the enum and the callee.
+    pub(super) fn variant_call<'a>(&self, mc: &'a MethodCall) -> Option<(u32, &'a FieldAccess)> {
+        match mc.callee.as_ref() {
+            Expr::FieldAccess(fa) => self.enum_ref(&fa.object).map(|id| (id, fa)),
+            _ => None,
+        }
+    }
+
+    /// Compile `Enum.Variant` or `Enum.Variant(values...)`: the tag and payload are
+    /// stored in consecutive 8-byte slots on the heap.
+    pub(super) fn compile_variant(
+        &self,
+        id: u32,
+        variant: &str,
+        args: &[Expr],
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let info = &self.enums[id as usize];
+        let Some((tag, payload)) = info.variant(variant) else {
+            return Err(codegen_err(
+                format!("enum '{}' has no variant '{}'", info.name, variant),
+                span,
+            ));
+        };
+        if args.len() != payload.len() {
+            return Err(codegen_err(
+                format!(
+                    "variant '{}.{}' expects {} value(s), found {}",
+                    info.name,
+                    variant,
+                    payload.len(),
+                    args.len()
+                ),
+                span,
+            ));
+        }
+
+        // Reserve the slots before evaluating the payload, which may allocate too
+        let (ptr, _) = ctx.claim_math_temps();
+        insn.global_get(0);
+        insn.i64_extend_i32_u();
+        insn.local_set(ptr);
+        insn.global_get(0);
+        insn.i32_const(((payload.len() + 1) * 8) as i32);
+        insn.i32_add();
+        insn.global_set(0);
+
+        insn.local_get(ptr);
+        insn.i32_wrap_i64();
+        insn.i64_const(tag as i64);
+        insn.i64_store(Self::slot_arg(0));
+        for (i, (arg, ty)) in args.iter().zip(payload).enumerate() {
+            insn.local_get(ptr);
+            insn.i32_wrap_i64();
+            let what = format!("value {} of '{}.{}'", i + 1, info.name, variant);
+            self.compile_slot_value(*ty, arg, &what, span, insn, ctx)?;
+            insn.i64_store(Self::slot_arg(i + 1));
+        }
+        insn.local_get(ptr);
+        Ok(())
+    }
+
+    /// For each variant tag of enum `id`, the index of the `match` arm that handles it.
+    /// Rejects unknown variants, wrong binding counts, unreachable arms and missing variants.
+    pub(super) fn match_targets(&self, id: u32, m: &MatchExpr) -> Result<Vec<u32>, CodegenError> {
+        let info = &self.enums[id as usize];
+        let mut targets: Vec<Option<u32>> = vec![None; info.variants.len()];
+        let mut wildcard = None;
+        for (i, arm) in m.arms.iter().enumerate() {
+            if wildcard.is_some() {
+                return Err(codegen_err(
+                    "unreachable match arm: '_' above already matches everything",
+                    &arm.span,
+                ));
+            }
+            match &arm.pattern {
+                MatchPattern::Wildcard => {
+                    if targets.iter().all(Option::is_some) {
+                        return Err(codegen_err(
+                            "unreachable match arm: every variant is already matched",
+                            &arm.span,
+                        ));
+                    }
+                    wildcard = Some(i as u32);
+                }
+                MatchPattern::Variant { enum_name, name, bindings } => {
+                    if let Some(enum_name) = enum_name
+                        && enum_name != &info.name
+                    {
+                        return Err(codegen_err(
+                            format!("pattern '{}.{}' cannot match a '{}' value", enum_name, name, info.name),
+                            &arm.span,
+                        ));
+                    }
+                    let Some((tag, payload)) = info.variant(name) else {
+                        return Err(codegen_err(
+                            format!("enum '{}' has no variant '{}'", info.name, name),
+                            &arm.span,
+                        ));
+                    };
+                    // A bare variant name ignores the payload
+                    if !bindings.is_empty() && bindings.len() != payload.len() {
+                        return Err(codegen_err(
+                            format!(
+                                "variant '{}.{}' has {} value(s), but the pattern binds {}",
+                                info.name,
+                                name,
+                                payload.len(),
+                                bindings.len()
+                            ),
+                            &arm.span,
+                        ));
+                    }
+                    if targets[tag].is_some() {
+                        return Err(codegen_err(
+                            format!("unreachable match arm: '{}' is already matched", name),
+                            &arm.span,
+                        ));
+                    }
+                    targets[tag] = Some(i as u32);
+                }
+            }
+        }
+
+        let missing: Vec<&str> = info
+            .variants
+            .iter()
+            .zip(&targets)
+            .filter(|(_, target)| target.is_none())
+            .map(|((name, _), _)| name.as_str())
+            .collect();
+        if wildcard.is_none() && !missing.is_empty() {
+            return Err(codegen_err(
+                format!("match on '{}' is not exhaustive: missing {}", info.name, missing.join(", ")),
+                &m.span,
+            ));
+        }
+        Ok(targets.into_iter().map(|t| t.or(wildcard).unwrap_or_default()).collect())
+    }
+
+    /// Variables bound by an arm's pattern: (name, payload slot, type). `_` binds nothing.
+    pub(super) fn arm_bindings(&self, id: u32, pattern: &MatchPattern) -> Vec<(String, usize, AhType)> {
+        let MatchPattern::Variant { name, bindings, .. } = pattern else {
+            return Vec::new();
+        };
+        let Some((_, payload)) = self.enums[id as usize].variant(name) else {
+            return Vec::new();
+        };
+        bindings
+            .iter()
+            .zip(payload)
+            .enumerate()
+            .filter(|(_, (binding, _))| binding.as_str() != "_")
+            .map(|(i, (binding, ty))| (binding.clone(), i + 1, *ty))
+            .collect()
+    }
+
+    /// Type of a `match` used as a value: the type of its first arm, with the
+    /// arm's bindings in scope.
+    pub(super) fn infer_match_type(&self, m: &MatchExpr, ctx: &FuncCtx) -> AhType {
+        let AhType::Enum(id) = self.infer_expr_type(&m.subject, ctx) else {
+            return AhType::Int;
+        };
+        let arm = &m.arms[0];
+        let MatchBody::Expr(body) = &arm.body else {
+            return AhType::Int;
+        };
+        let mut arm_ctx = FuncCtx::new_with_var_types(ctx.var_types.clone());
+        arm_ctx.locals = ctx.locals.clone();
+        for (name, _, ty) in self.arm_bindings(id, &arm.pattern) {
+            arm_ctx.var_types.insert(name, ty);
+        }
+        self.infer_expr_type(body, &arm_ctx)
+    }
+
+    /// Text pieces of one variant's formatted form: `Name`, or `Name(`, `, `, ..., `)`.
+    fn variant_fmt_pieces(&self, id: u32, tag: usize) -> Vec<String> {
+        let (name, payload) = &self.enums[id as usize].variants[tag];
+        if payload.is_empty() {
+            return vec![name.clone()];
+        }
+        let mut pieces = vec![format!("{}(", name)];
+        pieces.extend(std::iter::repeat_n(", ".to_string(), payload.len() - 1));
+        pieces.push(")".to_string());
+        pieces
+    }
+
+    /// Every text piece used by an enum's formatter.
+    pub(super) fn enum_fmt_pieces(&self, id: u32) -> Vec<String> {
+        (0..self.enums[id as usize].variants.len())
+            .flat_map(|tag| self.variant_fmt_pieces(id, tag))
+            .collect()
+    }
+
+    /// Compile the enum formatters, after the struct formatters. Each one dispatches
+    /// on the tag with a `br_table` and renders that variant's payload.
+    pub(super) fn compile_enum_formatters(
+        &self,
+        function_section: &mut FunctionSection,
+        code_section: &mut CodeSection,
+    ) {
+        for (id, info) in self.enums.iter().enumerate() {
+            let (_, type_idx) = self.func_map[&format!("__fmt_{}", info.name)];
+            function_section.function(type_idx);
+
+            let mut func = Function::new(vec![]);
+            let mut insn = func.instructions();
+            insn.call(self.func_map["__env_interp_begin"].0);
+
+            let count = info.variants.len() as u32;
+            insn.block(BlockType::Empty); // done
+            for _ in 0..count {
+                insn.block(BlockType::Empty);
+            }
+            insn.local_get(0);
+            insn.i32_wrap_i64();
+            insn.i64_load(Self::slot_arg(0));
+            insn.i32_wrap_i64();
+            insn.br_table(0..count, count - 1);
+            for (tag, (_, payload)) in info.variants.iter().enumerate() {
+                insn.end();
+                for (i, piece) in self.variant_fmt_pieces(id as u32, tag).iter().enumerate() {
+                    self.emit_fmt_text(piece, &mut insn);
+                    if let Some(ty) = payload.get(i) {
+                        self.emit_fmt_slot(i + 1, *ty, &mut insn);
+                    }
+                }
+                insn.br(count - 1 - tag as u32);
+            }
+            insn.end();
+
+            insn.call(self.func_map["__env_interp_end"].0);
+            insn.end();
+            code_section.function(&func);
+        }
+    }
+}
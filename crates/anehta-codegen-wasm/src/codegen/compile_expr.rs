@@ -18,6 +18,24 @@ impl WasmCodegen {
         Ok(())
     }
 
+    /// Bitwise operators only apply to ints; anything else is a compile error.
+    pub(super) fn check_int_operand(
+        &self,
+        expr: &Expr,
+        symbol: &str,
+        span: &Span,
+        ctx: &FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let ty = self.infer_expr_type(expr, ctx);
+        if ty != AhType::Int {
+            return Err(codegen_err(
+                format!("operator '{}' requires int operands, found {}", symbol, ty.name()),
+                span,
+            ));
+        }
+        Ok(())
+    }
+
     pub(super) fn compile_expr(
         &self,
         expr: &Expr,
@@ -35,12 +53,21 @@ impl WasmCodegen {
                     let val = s.parse::<i64>().map_err(|_| {
                         codegen_err(format!("invalid number literal: {}", s), span)
                     })?;
+                    if val == NIL {
+                        return Err(codegen_err(
+                            format!("{} is reserved for nil and cannot be used as an int", s),
+                            span,
+                        ));
+                    }
                     insn.i64_const(val);
                 }
             }
             Expr::Bool(b, _) => {
                 insn.i64_const(if *b { 1 } else { 0 });
             }
+            Expr::Nil(_) => {
+                insn.i64_const(NIL);
+            }
             Expr::StringLit(s, _) => {
                 let (offset, len) = self
                     .string_pool
@@ -50,10 +77,13 @@ impl WasmCodegen {
                 let packed: i64 = ((offset as i64) << 32) | (len as i64);
                 insn.i64_const(packed);
             }
+            Expr::Interpolated { parts, span } => {
+                self.compile_interpolated(parts, span, insn, ctx)?;
+            }
             Expr::Variable(name, span) => {
-                if let Some(idx) = ctx.get_local(name) {
-                    insn.local_get(idx);
-                } else {
+                if ctx.get_local(name).is_none() && self.func_map.contains_key(name) {
+                    self.compile_func_ref(name, span, insn)?;
+                } else if !ctx.emit_get(name, insn) {
                     return Err(codegen_err(
                         format!("undefined variable: {}", name),
                         span,
@@ -61,9 +91,40 @@ impl WasmCodegen {
                 }
             }
             Expr::BinaryOp {
-                left, op, right, ..
+                left, op, right, span,
             } => {
+                if !matches!(op, BinaryOp::Coalesce) {
+                    self.check_not_optional(left, span, ctx)?;
+                    self.check_not_optional(right, span, ctx)?;
+                }
                 match op {
+                    BinaryOp::Coalesce => {
+                        self.compile_coalesce(left, right, span, insn, ctx)?;
+                    }
+                    BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor
+                    | BinaryOp::Shl | BinaryOp::Shr | BinaryOp::UShr => {
+                        let symbol = match op {
+                            BinaryOp::BitAnd => "&",
+                            BinaryOp::BitOr => "|",
+                            BinaryOp::BitXor => "xor",
+                            BinaryOp::Shl => "<<",
+                            BinaryOp::Shr => ">>",
+                            _ => ">>>",
+                        };
+                        self.check_int_operand(left, symbol, span, ctx)?;
+                        self.check_int_operand(right, symbol, span, ctx)?;
+                        self.compile_expr(left, insn, ctx)?;
+                        self.compile_expr(right, insn, ctx)?;
+                        // Shift counts are taken modulo 64, as in WASM
+                        match op {
+                            BinaryOp::BitAnd => insn.i64_and(),
+                            BinaryOp::BitOr => insn.i64_or(),
+                            BinaryOp::BitXor => insn.i64_xor(),
+                            BinaryOp::Shl => insn.i64_shl(),
+                            BinaryOp::Shr => insn.i64_shr_s(),
+                            _ => insn.i64_shr_u(),
+                        };
+                    }
                     BinaryOp::Rand => {
                         self.compile_expr(left, insn, ctx)?;
                         self.compile_expr(right, insn, ctx)?;
@@ -326,6 +387,7 @@ impl WasmCodegen {
                         } else {
                             self.compile_expr(left, insn, ctx)?;
                             self.compile_expr(right, insn, ctx)?;
+                            self.emit_divisor_check(insn, ctx);
                             insn.i64_div_s();
                         }
                     }
@@ -342,6 +404,7 @@ impl WasmCodegen {
                         } else {
                             self.compile_expr(left, insn, ctx)?;
                             self.compile_expr(right, insn, ctx)?;
+                            self.emit_divisor_check(insn, ctx);
                             insn.i64_rem_s();
                         }
                     }
@@ -362,8 +425,7 @@ impl WasmCodegen {
                         // Supports: mat \ vec → vec, mat \ mat → mat
                         self.compile_expr(left, insn, ctx)?;
                         self.compile_expr(right, insn, ctx)?;
-                        let (func_idx, _) = self.func_map["__env_mat_solve"];
-                        insn.call(func_idx);
+                        self.emit_raising_call("__env_mat_solve", insn, ctx);
                     }
                     BinaryOp::DotPow => {
                         // Element-wise power: v .^ exponent
@@ -419,40 +481,10 @@ impl WasmCodegen {
                 }
             }
             Expr::UnaryOp { op, operand, span } => {
-                if let Some(idx) = ctx.get_local(operand) {
-                    let var_ty = ctx.var_types.get(operand).copied().unwrap_or(AhType::Int);
-                    insn.local_get(idx);
-                    insn.local_get(idx);
-                    if var_ty == AhType::Float {
-                        insn.f64_reinterpret_i64();
-                        insn.f64_const(1.0);
-                        match op {
-                            UnaryOp::Increment => { insn.f64_add(); }
-                            UnaryOp::Decrement => { insn.f64_sub(); }
-                        }
-                        insn.i64_reinterpret_f64();
-                    } else {
-                        match op {
-                            UnaryOp::Increment => {
-                                insn.i64_const(1);
-                                insn.i64_add();
-                            }
-                            UnaryOp::Decrement => {
-                                insn.i64_const(1);
-                                insn.i64_sub();
-                            }
-                        }
-                    }
-                    insn.local_set(idx);
-                } else {
-                    return Err(codegen_err(
-                        format!("undefined variable: {}", operand),
-                        span,
-                    ));
-                }
+                self.compile_unary(*op, operand, span, insn, ctx)?;
             }
             Expr::CallFunc(call) => {
-                self.compile_call_func_expr(call, insn, ctx)?;
+                self.compile_call_func_expr(call, false, insn, ctx)?;
             }
             Expr::Grouped(inner) => {
                 self.compile_expr(inner, insn, ctx)?;
@@ -463,6 +495,9 @@ impl WasmCodegen {
             Expr::TableLiteral(table) => {
                 self.compile_table_literal(table, insn, ctx)?;
             }
+            Expr::ListLiteral(list) => {
+                self.compile_list_literal(list, insn, ctx)?;
+            }
             Expr::FieldAccess(fa) => {
                 self.compile_field_access(fa, insn, ctx)?;
             }
@@ -478,6 +513,18 @@ impl WasmCodegen {
             Expr::MatLiteral(mat_lit) => {
                 self.compile_mat_literal(mat_lit, insn, ctx)?;
             }
+            Expr::New(new) => {
+                self.compile_new(new, insn, ctx)?;
+            }
+            Expr::Match(m) => {
+                self.compile_match(m, true, insn, ctx)?;
+            }
+            Expr::If(e) => {
+                self.compile_if_expr(e, false, insn, ctx)?;
+            }
+            Expr::Cast(cast) => {
+                self.compile_cast(cast, insn, ctx)?;
+            }
             Expr::Transpose(t) => {
                 self.compile_expr(&t.operand, insn, ctx)?;
                 self.emit_transpose_simd(insn, ctx)?;
@@ -491,11 +538,11 @@ impl WasmCodegen {
                         match &**inner {
                             BooleanExpr::Comparison { span, .. } => span,
                             BooleanExpr::Logical { span, .. } => span,
-                            _ => &Span { line: 0, column: 0 },
+                            _ => &Span { line: 0, column: 0, file: 0 },
                         }
                     }
                 };
-                return Err(codegen_err("Boolean expression not allowed in this context (use in v[v > 0] for masking)", span));
+                return Err(codegen_err("Boolean expression not allowed in this context (use in v[v > 0] for masking or where(v > 0, a, b))", span));
             }
             Expr::Range { span, .. } => {
                 // Range should only appear in IndexAccess context
@@ -505,15 +552,27 @@ impl WasmCodegen {
         Ok(())
     }
 
+    /// Remember the types of a closure's captures where it is created; the closure
+    /// function is compiled with them.
+    pub(super) fn record_capture_types(&self, closure_id: u32, ctx: &FuncCtx) {
+        let capture_types = self.closures[closure_id as usize]
+            .captures
+            .iter()
+            .map(|capture| ctx.var_types.get(capture).copied().unwrap_or(AhType::Int))
+            .collect();
+        self.capture_types.borrow_mut().insert(closure_id, capture_types);
+    }
+
     fn compile_closure_expr(
         &self,
         closure: &ClosureExpr,
         insn: &mut wasm_encoder::InstructionSink<'_>,
         ctx: &mut FuncCtx,
     ) -> Result<(), CodegenError> {
-        let key = (closure.span.line, closure.span.column);
+        let key = closure.span;
         let closure_id = self.closure_span_map[&key];
         let info = &self.closures[closure_id as usize];
+        self.record_capture_types(closure_id, ctx);
 
         let num_captures = info.captures.len();
 
@@ -582,7 +641,7 @@ impl WasmCodegen {
             insn.i64_const(packed_key);
             self.compile_expr(&entry.value, insn, ctx)?;
             let val_ty = self.infer_expr_type(&entry.value, ctx);
-            if matches!(val_ty, AhType::Table(_)) {
+            if matches!(val_ty, AhType::Table(_) | AhType::List(_)) {
                 insn.call(table_set_table_idx);
             } else {
                 insn.call(table_set_idx);
@@ -599,24 +658,47 @@ impl WasmCodegen {
         insn: &mut wasm_encoder::InstructionSink<'_>,
         ctx: &mut FuncCtx,
     ) -> Result<(), CodegenError> {
+        if fa.safe {
+            return self.compile_safe_field(fa, insn, ctx);
+        }
+        if let Some(id) = self.enum_ref(&fa.object) {
+            return self.compile_variant(id, &fa.field, &[], &fa.span, insn, ctx);
+        }
         let obj_ty = self.infer_expr_type(&fa.object, ctx);
+        if let AhType::Optional(_) = obj_ty {
+            return Err(codegen_err(
+                format!(
+                    "value of type {} may be nil; read its fields with '?.'",
+                    self.type_display(obj_ty)
+                ),
+                &fa.span,
+            ));
+        }
         if obj_ty == AhType::Vec {
             return self.compile_vec_field_access(fa, insn, ctx);
         }
         if obj_ty == AhType::Mat {
             return self.compile_mat_field_access(fa, insn, ctx);
         }
-        let (table_get_idx, _) = self.func_map["__env_table_get"];
+        if let AhType::Struct(id) = obj_ty {
+            return self.compile_struct_field_access(id, fa, insn, ctx);
+        }
+        self.check_table_field(fa, ctx);
         self.compile_expr(&fa.object, insn, ctx)?;
+        self.emit_table_get(&fa.field, insn, ctx);
+        Ok(())
+    }
+
+    /// Look up `field` in the table handle on top of the stack.
+    fn emit_table_get(&self, field: &str, insn: &mut wasm_encoder::InstructionSink<'_>, ctx: &FuncCtx) {
         let (offset, len) = self
             .string_pool
-            .get(fa.field.as_str())
+            .get(field)
             .copied()
             .unwrap_or((0, 0));
         let packed_key: i64 = ((offset as i64) << 32) | (len as i64);
         insn.i64_const(packed_key);
-        insn.call(table_get_idx);
-        Ok(())
+        self.emit_raising_call("__env_table_get", insn, ctx);
     }
 
     fn compile_vec_field_access(
@@ -691,14 +773,12 @@ impl WasmCodegen {
             "det" => {
                 // Determinant → call mat_det host function (returns f64 bits)
                 self.compile_expr(&fa.object, insn, ctx)?;
-                let (func_idx, _) = self.func_map["__env_mat_det"];
-                insn.call(func_idx);
+                self.emit_raising_call("__env_mat_det", insn, ctx);
             }
             "inv" => {
                 // Inverse → call mat_inv host function
                 self.compile_expr(&fa.object, insn, ctx)?;
-                let (func_idx, _) = self.func_map["__env_mat_inv"];
-                insn.call(func_idx);
+                self.emit_raising_call("__env_mat_inv", insn, ctx);
             }
             "rows" => {
                 // Extract rows from packed i64: (val >> 16) & 0xFFFF
@@ -731,9 +811,29 @@ impl WasmCodegen {
         ctx: &mut FuncCtx,
     ) -> Result<(), CodegenError> {
         let obj_ty = self.infer_expr_type(&ia.object, ctx);
+        if let AhType::List(_) = obj_ty {
+            return self.compile_list_index(ia, insn, ctx);
+        }
 
         // Check if index is a Range expression (for slicing)
         if let Expr::Range { start, end, .. } = &*ia.index {
+            if obj_ty == AhType::Str {
+                // String slicing by character: s[start..end]
+                self.compile_expr(&ia.object, insn, ctx)?;
+                if let Some(s) = start {
+                    self.compile_expr(s, insn, ctx)?;
+                } else {
+                    insn.i64_const(-1);
+                }
+                if let Some(e) = end {
+                    self.compile_expr(e, insn, ctx)?;
+                } else {
+                    insn.i64_const(-1);
+                }
+                let (func_idx, _) = self.func_map["__env_str_slice"];
+                insn.call(func_idx);
+                return Ok(());
+            }
             if obj_ty == AhType::Vec {
                 // Vec slicing: v[start..end]
                 self.compile_expr(&ia.object, insn, ctx)?;
@@ -770,12 +870,12 @@ impl WasmCodegen {
                 insn.call(func_idx);
                 return Ok(());
             }
-            return Err(codegen_err("Slicing only supported for vec and mat", &ia.span));
+            return Err(codegen_err("Slicing only supported for str, vec, mat and list", &ia.span));
         }
 
         // Check if index is a BooleanExpr (masking: v[v > 0])
-        if let Expr::BooleanExpr(boxed_bool) = &*ia.index {
-            if let BooleanExpr::Comparison { left, op, right, .. } = &**boxed_bool {
+        if let Expr::BooleanExpr(boxed_bool) = &*ia.index
+            && let BooleanExpr::Comparison { left: _, op, right, .. } = &**boxed_bool {
             // For now, support simple cases: v[v > threshold]
             // Compile object (vec or mat)
             self.compile_expr(&ia.object, insn, ctx)?;
@@ -784,15 +884,7 @@ impl WasmCodegen {
             self.compile_expr(right, insn, ctx)?;
 
             // Encode comparison operator as i64
-            let op_code = match op {
-                ComparisonOp::Gt => 0,
-                ComparisonOp::Lt => 1,
-                ComparisonOp::GtEq => 2,
-                ComparisonOp::LtEq => 3,
-                ComparisonOp::Eq => 4,
-                ComparisonOp::NotEq => 5,
-            };
-            insn.i64_const(op_code);
+            insn.i64_const(compile_math::comparison_code(op));
 
             // Call masking host function
             if obj_ty == AhType::Vec {
@@ -806,7 +898,6 @@ impl WasmCodegen {
                 return Ok(());
             }
             return Err(codegen_err("Boolean masking only supported for vec and mat", &ia.span));
-            }
         }
 
         // Check if index is a Vec type (fancy indexing)
@@ -832,6 +923,14 @@ impl WasmCodegen {
         }
 
         // Regular indexing
+        if obj_ty == AhType::Str {
+            // s[i] → single-character string
+            self.compile_expr(&ia.object, insn, ctx)?;
+            self.compile_expr(&ia.index, insn, ctx)?;
+            let (func_idx, _) = self.func_map["__env_str_char_at"];
+            insn.call(func_idx);
+            return Ok(());
+        }
         if obj_ty == AhType::Vec {
             self.compile_expr(&ia.object, insn, ctx)?;
             self.compile_expr(&ia.index, insn, ctx)?;
@@ -844,14 +943,12 @@ impl WasmCodegen {
             // Returns f64 bits as i64
             self.compile_expr(&ia.object, insn, ctx)?;
             self.compile_expr(&ia.index, insn, ctx)?;
-            let (func_idx, _) = self.func_map["__env_mat_get"];
-            insn.call(func_idx);
+            self.emit_raising_call("__env_mat_get", insn, ctx);
             return Ok(());
         }
-        let (table_get_idx, _) = self.func_map["__env_table_get"];
         self.compile_expr(&ia.object, insn, ctx)?;
-        self.compile_expr(&ia.index, insn, ctx)?;
-        insn.call(table_get_idx);
+        let (get, _) = self.compile_table_key(&ia.index, insn, ctx)?;
+        self.emit_raising_call(get, insn, ctx);
         Ok(())
     }
 
@@ -861,10 +958,40 @@ impl WasmCodegen {
         insn: &mut wasm_encoder::InstructionSink<'_>,
         ctx: &mut FuncCtx,
     ) -> Result<(), CodegenError> {
-        let num_args = mc.args.len();
+        if let Expr::FieldAccess(fa) = mc.callee.as_ref()
+            && fa.safe
+        {
+            return Err(codegen_err(
+                "cannot call a method through '?.'; compare the value with nil first",
+                &mc.span,
+            ));
+        }
+        if let Some((id, fa)) = self.variant_call(mc) {
+            return self.compile_variant(id, &fa.field, &mc.args, &mc.span, insn, ctx);
+        }
+        if let Some((name, fa)) = self.method_target(mc, ctx) {
+            return self.compile_method_call(&name, fa, mc, insn, ctx);
+        }
+        // obj:method(args) passes obj as an extra first argument
+        let first_arg = usize::from(mc.pass_self);
+        let num_args = mc.args.len() + first_arg;
         let temps = ctx.claim_closure_call_temps();
 
-        self.compile_expr(&mc.callee, insn, ctx)?;
+        match mc.callee.as_ref() {
+            Expr::FieldAccess(fa) if mc.pass_self => {
+                let obj_ty = self.infer_expr_type(&fa.object, ctx);
+                if !matches!(obj_ty, AhType::Table(_) | AhType::Int) {
+                    return Err(codegen_err(
+                        format!("'{}' values have no methods", self.type_display(obj_ty)),
+                        &mc.span,
+                    ));
+                }
+                self.compile_expr(&fa.object, insn, ctx)?;
+                insn.local_tee(temps[0]);
+                self.emit_table_get(&fa.field, insn, ctx);
+            }
+            callee => self.compile_expr(callee, insn, ctx)?,
+        }
         let closure_temp = temps[num_args];
         insn.local_set(closure_temp);
 
@@ -872,15 +999,15 @@ impl WasmCodegen {
             self.compile_expr(arg, insn, ctx)?;
         }
 
-        for i in (0..num_args).rev() {
+        for i in (first_arg..num_args).rev() {
             insn.local_set(temps[i]);
         }
 
         insn.local_get(closure_temp);
         insn.i32_wrap_i64();
 
-        for i in 0..num_args {
-            insn.local_get(temps[i]);
+        for &temp in &temps[..num_args] {
+            insn.local_get(temp);
         }
 
         insn.local_get(closure_temp);
@@ -892,30 +1019,22 @@ impl WasmCodegen {
         let call_type_idx = if let AhType::Closure(id) = callee_ty {
             self.closures[id as usize].type_idx
         } else {
-            let mut params = vec![ValType::I32];
-            for _ in 0..num_args {
-                params.push(ValType::I64);
-            }
-            let results = vec![ValType::I64];
-            let mut found = None;
-            for (i, (p, r)) in self.types.iter().enumerate() {
-                if p == &params && r == &results {
-                    found = Some(i as u32);
-                    break;
-                }
-            }
-            found.ok_or_else(|| {
+            self.callable_type_idx(num_args).ok_or_else(|| {
                 codegen_err("cannot call a non-closure expression", &mc.span)
             })?
         };
 
         insn.call_indirect(0, call_type_idx);
+        self.emit_error_check(insn, ctx);
         Ok(())
     }
 
-    fn compile_call_func_expr(
+    /// Compile a call. With `tail`, a call to a user function or a function value
+    /// returning what the current function returns leaves it with `return_call`.
+    pub(super) fn compile_call_func_expr(
         &self,
         call: &CallFunc,
+        tail: bool,
         insn: &mut wasm_encoder::InstructionSink<'_>,
         ctx: &mut FuncCtx,
     ) -> Result<(), CodegenError> {
@@ -949,6 +1068,20 @@ impl WasmCodegen {
                 insn.i64_const(0);
                 return Ok(());
             }
+            if let AhType::List(_) = arg_type {
+                self.compile_expr(&call.args[0], insn, ctx)?;
+                let (func_idx, _) = self.func_map["__env_print_list"];
+                insn.call(func_idx);
+                insn.i64_const(0);
+                return Ok(());
+            }
+            if let Some(fmt) = self.formatter(arg_type) {
+                self.compile_expr(&call.args[0], insn, ctx)?;
+                insn.call(fmt);
+                insn.call(self.func_map["__env_print_str"].0);
+                insn.i64_const(0);
+                return Ok(());
+            }
         }
 
         if call.name == "len" && call.args.len() == 1 {
@@ -959,6 +1092,34 @@ impl WasmCodegen {
                 insn.i64_and();
                 return Ok(());
             }
+            if arg_ty == AhType::Str {
+                // Character count, not byte length
+                self.compile_expr(&call.args[0], insn, ctx)?;
+                let (func_idx, _) = self.func_map["__env_str_len"];
+                insn.call(func_idx);
+                return Ok(());
+            }
+            if matches!(arg_ty, AhType::Table(_)) {
+                self.compile_expr(&call.args[0], insn, ctx)?;
+                let (func_idx, _) = self.func_map["__env_table_len"];
+                insn.call(func_idx);
+                return Ok(());
+            }
+            if let AhType::List(_) = arg_ty {
+                self.compile_expr(&call.args[0], insn, ctx)?;
+                let (func_idx, _) = self.func_map["__env_list_len"];
+                insn.call(func_idx);
+                return Ok(());
+            }
+            if !self.builtin_shadowed(&call.name, ctx) && !self.is_unknown(&call.args[0], arg_ty, ctx) {
+                return Err(codegen_err(
+                    format!(
+                        "argument 1 of 'len' must be a str, vec, list or table, found {}",
+                        self.type_display(arg_ty)
+                    ),
+                    &call.span,
+                ));
+            }
         }
 
         if call.name == "int" && call.args.len() == 1 {
@@ -981,6 +1142,33 @@ impl WasmCodegen {
             return Ok(());
         }
 
+        if let Some(builtin) = self.str_builtin(&call.name, ctx) {
+            return self.compile_str_builtin(builtin, call, insn, ctx);
+        }
+
+        if let Some(builtin) = self.table_builtin(call, ctx) {
+            return self.compile_table_builtin(builtin, call, insn, ctx);
+        }
+        if let Some(builtin) = self.list_builtin(&call.name, ctx) {
+            return self.compile_list_builtin(builtin, call, insn, ctx);
+        }
+        if let Some(builtin) = self.hof_builtin(&call.name, ctx) {
+            return self.compile_hof_builtin(builtin, call, insn, ctx);
+        }
+
+        if let Some(builtin) = self.fmt_builtin(call, ctx) {
+            return self.compile_fmt_builtin(builtin, call, insn, ctx);
+        }
+
+        if let Some(builtin) = self.math_builtin(&call.name, ctx) {
+            return self.compile_math_builtin(builtin, call, insn, ctx);
+        }
+
+        if let Some(builtin) = self.coroutine_builtin(call, ctx) {
+            return self.compile_coroutine_builtin(builtin, call, insn, ctx);
+        }
+
+        self.check_call_args(call, ctx)?;
         for arg in &call.args {
             self.compile_expr(arg, insn, ctx)?;
         }
@@ -995,10 +1183,18 @@ impl WasmCodegen {
                     &call.span,
                 ));
             }
-            insn.call(func_idx);
-        } else if let Some(&local_idx) = ctx.locals.get(&call.name) {
-            if let Some(AhType::Closure(closure_id)) = ctx.var_types.get(&call.name) {
-                self.emit_closure_call_indirect(*closure_id, local_idx, call, insn, ctx)?;
+            let (_, results) = &self.types[type_idx as usize];
+            if tail && results.len() == ctx.result_count {
+                insn.return_call(func_idx);
+            } else {
+                insn.call(func_idx);
+                self.emit_error_check(insn, ctx);
+            }
+        } else if ctx.locals.contains_key(&call.name) {
+            if let Some(&ty) = ctx.var_types.get(&call.name)
+                && self.callable_signature(ty).is_some()
+            {
+                self.emit_closure_call_indirect(ty, call, tail, insn, ctx)?;
             } else {
                 return Err(codegen_err(
                     format!("undefined function: {}", call.name),
@@ -1037,6 +1233,7 @@ impl WasmCodegen {
 
         // 3. Write each element
         for (i, elem) in vec_lit.elements.iter().enumerate() {
+            self.check_not_optional(elem, &vec_lit.span, ctx)?;
             insn.local_get(temp);
             insn.i32_wrap_i64();
             self.emit_float_operand(elem, insn, ctx)?;
@@ -1132,6 +1329,13 @@ impl WasmCodegen {
         insn.local_set(vec_b); // 保存右操作数
         insn.local_set(vec_a); // 保存左操作数
 
+        // 长度（或行列）不同则抛出错误
+        insn.local_get(vec_a);
+        insn.i32_wrap_i64();
+        insn.local_get(vec_b);
+        insn.i32_wrap_i64();
+        self.emit_size_check(insn, ctx);
+
         // 2. 解包 vec_a：提取 ptr 和 len
         insn.local_get(vec_a);
         insn.i64_const(32);
@@ -1323,6 +1527,13 @@ impl WasmCodegen {
         insn.local_set(vec_b);
         insn.local_set(vec_a);
 
+        // 长度（或行列）不同则抛出错误
+        insn.local_get(vec_a);
+        insn.i32_wrap_i64();
+        insn.local_get(vec_b);
+        insn.i32_wrap_i64();
+        self.emit_size_check(insn, ctx);
+
         // 解包 vec_a
         insn.local_get(vec_a);
         insn.i64_const(32);
@@ -1510,6 +1721,13 @@ impl WasmCodegen {
         insn.local_set(vec_b);
         insn.local_set(vec_a);
 
+        // 长度（或行列）不同则抛出错误
+        insn.local_get(vec_a);
+        insn.i32_wrap_i64();
+        insn.local_get(vec_b);
+        insn.i32_wrap_i64();
+        self.emit_size_check(insn, ctx);
+
         insn.local_get(vec_a);
         insn.i64_const(32);
         insn.i64_shr_u();
@@ -1664,7 +1882,7 @@ impl WasmCodegen {
     }
 
     /// 生成内联 WASM SIMD 代码来执行 vec * scalar（标量乘法）
-    fn emit_vec_scale_simd(
+    pub(super) fn emit_vec_scale_simd(
         &self,
         insn: &mut wasm_encoder::InstructionSink<'_>,
         ctx: &FuncCtx,
@@ -1846,6 +2064,13 @@ impl WasmCodegen {
         insn.local_set(vec_b);
         insn.local_set(vec_a);
 
+        // 长度（或行列）不同则抛出错误
+        insn.local_get(vec_a);
+        insn.i32_wrap_i64();
+        insn.local_get(vec_b);
+        insn.i32_wrap_i64();
+        self.emit_size_check(insn, ctx);
+
         // 解包 vec_a
         insn.local_get(vec_a);
         insn.i64_const(32);
@@ -2705,6 +2930,13 @@ impl WasmCodegen {
         insn.local_set(mat_b);
         insn.local_set(mat_a);
 
+        // 长度（或行列）不同则抛出错误
+        insn.local_get(mat_a);
+        insn.i32_wrap_i64();
+        insn.local_get(mat_b);
+        insn.i32_wrap_i64();
+        self.emit_size_check(insn, ctx);
+
         // 解包 mat_a
         insn.local_get(mat_a);
         insn.i64_const(32);
@@ -2903,6 +3135,13 @@ impl WasmCodegen {
         insn.local_set(mat_b);
         insn.local_set(mat_a);
 
+        // 长度（或行列）不同则抛出错误
+        insn.local_get(mat_a);
+        insn.i32_wrap_i64();
+        insn.local_get(mat_b);
+        insn.i32_wrap_i64();
+        self.emit_size_check(insn, ctx);
+
         insn.local_get(mat_a);
         insn.i64_const(32);
         insn.i64_shr_u();
@@ -3065,7 +3304,7 @@ impl WasmCodegen {
     }
 
     /// Mat * scalar（标量乘法）
-    fn emit_mat_scale_simd(
+    pub(super) fn emit_mat_scale_simd(
         &self,
         insn: &mut wasm_encoder::InstructionSink<'_>,
         ctx: &FuncCtx,
@@ -3260,6 +3499,17 @@ impl WasmCodegen {
         insn.i64_and();
         insn.local_set(k); // cols of A = rows of B
 
+        // A 的列数必须等于 B 的行数
+        insn.local_get(k);
+        insn.i32_wrap_i64();
+        insn.local_get(mat_b);
+        insn.i64_const(16);
+        insn.i64_shr_u();
+        insn.i32_wrap_i64();
+        insn.i32_const(0xFFFF);
+        insn.i32_and();
+        self.emit_size_check(insn, ctx);
+
         // 解包 mat_b: (ptr_b << 32) | (k2 << 16) | n
         insn.local_get(mat_b);
         insn.i64_const(32);
@@ -3482,6 +3732,13 @@ impl WasmCodegen {
         insn.i64_and();
         insn.local_set(n);
 
+        // 矩阵列数必须等于向量长度
+        insn.local_get(n);
+        insn.i32_wrap_i64();
+        insn.local_get(vec);
+        insn.i32_wrap_i64();
+        self.emit_size_check(insn, ctx);
+
         // 解包 vec
         insn.local_get(vec);
         insn.i64_const(32);
@@ -0,0 +1,223 @@
+use super::*;
+
+/// Formatted output builtins backed by the host's interpolation frames.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum FmtBuiltin {
+    /// `print(a, b, ...)` — space separated, with newline (single-arg print is typed dispatch)
+    Print,
+    /// `write(a, b, ...)` — like print, without the newline
+    Write,
+    /// `printf(fmt, args...)` — formatted, without the newline
+    Printf,
+    /// `format(fmt, args...)` — formatted, returns a str
+    Format,
+}
+
+// Tags understood by env.interp_push / env.interp_arg
+const TAG_INT: i64 = 0;
+const TAG_FLOAT: i64 = 1;
+pub(super) const TAG_STR: i64 = 2;
+const TAG_VEC: i64 = 3;
+const TAG_MAT: i64 = 4;
+const TAG_LIST: i64 = 5;
+/// Set on the tag of an optional value, which the host shows as `nil` when it is `NIL`
+pub(super) const TAG_OPTIONAL: i64 = 1 << 33;
+
+/// Tag passed to the host so it can decode and format a value of this type.
+/// Structs and enums reach the host already rendered by their formatter, as a str.
+pub(super) fn type_tag(ty: AhType) -> Option<i64> {
+    match ty {
+        AhType::Int => Some(TAG_INT),
+        AhType::Float => Some(TAG_FLOAT),
+        AhType::Str | AhType::Struct(_) | AhType::Enum(_) => Some(TAG_STR),
+        AhType::Vec => Some(TAG_VEC),
+        AhType::Mat => Some(TAG_MAT),
+        AhType::List(_) => Some(TAG_LIST),
+        AhType::Table(_) | AhType::Closure(_) | AhType::Func(_) | AhType::Coroutine(_) | AhType::Optional(_) => None,
+    }
+}
+
+/// Count the `{}` / `{:spec}` placeholders of a format string.
+fn count_placeholders(template: &str) -> Result<usize, String> {
+    let mut chars = template.chars().peekable();
+    let mut count = 0;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' | '}' if chars.peek() == Some(&ch) => {
+                chars.next();
+            }
+            '{' => {
+                let inner: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                if !inner.is_empty() && !inner.starts_with(':') {
+                    return Err(format!("invalid placeholder '{{{}}}' in format string", inner));
+                }
+                count += 1;
+            }
+            '}' => return Err("single '}' in format string; use '}}' for a literal brace".to_string()),
+            _ => {}
+        }
+    }
+    Ok(count)
+}
+
+impl WasmCodegen {
+    /// Look up a formatted output builtin.
+    /// User functions and local variables shadow `write`, `printf` and `format`.
+    pub(super) fn fmt_builtin(&self, call: &CallFunc, ctx: &FuncCtx) -> Option<FmtBuiltin> {
+        if call.name == "print" {
+            // A single optional has no typed print, so it goes through the frame
+            let single_optional = call.args.len() == 1
+                && matches!(self.infer_expr_type(&call.args[0], ctx), AhType::Optional(_));
+            return (call.args.len() != 1 || single_optional).then_some(FmtBuiltin::Print);
+        }
+        if self.builtin_shadowed(&call.name, ctx) {
+            return None;
+        }
+        match call.name.as_str() {
+            "write" => Some(FmtBuiltin::Write),
+            "printf" => Some(FmtBuiltin::Printf),
+            "format" => Some(FmtBuiltin::Format),
+            _ => None,
+        }
+    }
+
+    /// Compile a value for the host formatter and return its tag. Structs and
+    /// enums are rendered to a str by their generated formatter first.
+    fn compile_tagged(
+        &self,
+        expr: &Expr,
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<i64, CodegenError> {
+        let ty = self.infer_expr_type(expr, ctx);
+        if let AhType::Optional(_) = ty {
+            // The host calls the formatter itself, and only for a present value
+            self.compile_expr(expr, insn, ctx)?;
+            return Ok(self.element_tag(ty));
+        }
+        let Some(tag) = type_tag(ty) else {
+            return Err(codegen_err(
+                "cannot format a table or closure value".to_string(),
+                span,
+            ));
+        };
+        self.compile_expr(expr, insn, ctx)?;
+        if let Some(fmt) = self.formatter(ty) {
+            insn.call(fmt);
+        }
+        Ok(tag)
+    }
+
+    /// Compile an interpolated string `f"..."`: each part is appended to a host
+    /// frame tagged with its type, and `interp_end` copies the finished text into
+    /// linear memory in a single allocation.
+    pub(super) fn compile_interpolated(
+        &self,
+        parts: &[InterpPart],
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let (push_idx, _) = self.func_map["__env_interp_push"];
+
+        insn.call(self.func_map["__env_interp_begin"].0);
+        for part in parts {
+            match part {
+                InterpPart::Text(text) => {
+                    let (offset, len) = self.string_pool.get(text.as_str()).copied().unwrap_or((0, 0));
+                    insn.i64_const(((offset as i64) << 32) | (len as i64));
+                    insn.i64_const(TAG_STR);
+                }
+                InterpPart::Expr(e) => {
+                    let tag = self.compile_tagged(e, span, insn, ctx)?;
+                    insn.i64_const(tag);
+                }
+            }
+            insn.call(push_idx);
+        }
+        insn.call(self.func_map["__env_interp_end"].0);
+        Ok(())
+    }
+
+    /// Compile `expr` rendered as a str, formatted the way `print` shows it.
+    pub(super) fn compile_to_str(
+        &self,
+        expr: &Expr,
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        insn.call(self.func_map["__env_interp_begin"].0);
+        let tag = self.compile_tagged(expr, span, insn, ctx)?;
+        insn.i64_const(tag);
+        insn.call(self.func_map["__env_interp_push"].0);
+        insn.call(self.func_map["__env_interp_end"].0);
+        Ok(())
+    }
+
+    /// Compile a formatted output builtin. Leaves the formatted str on the stack
+    /// for `format`, and 0 for the printing builtins.
+    pub(super) fn compile_fmt_builtin(
+        &self,
+        builtin: FmtBuiltin,
+        call: &CallFunc,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let formatted = matches!(builtin, FmtBuiltin::Printf | FmtBuiltin::Format);
+        let args = if formatted {
+            let Some(template) = call.args.first() else {
+                return Err(codegen_err(
+                    format!("function '{}' expects a format string", call.name),
+                    &call.span,
+                ));
+            };
+            match template {
+                Expr::StringLit(s, _) => {
+                    let expected = count_placeholders(s).map_err(|msg| codegen_err(msg, &call.span))?;
+                    if expected != call.args.len() - 1 {
+                        return Err(codegen_err(
+                            format!(
+                                "format string has {} placeholder(s), but {} argument(s) were given",
+                                expected,
+                                call.args.len() - 1
+                            ),
+                            &call.span,
+                        ));
+                    }
+                }
+                _ if self.infer_expr_type(template, ctx) != AhType::Str => {
+                    return Err(codegen_err(
+                        format!("argument 1 of '{}' must be a string", call.name),
+                        &call.span,
+                    ));
+                }
+                _ => {}
+            }
+            &call.args[1..]
+        } else {
+            &call.args[..]
+        };
+
+        insn.call(self.func_map["__env_interp_begin"].0);
+        for arg in args {
+            let tag = self.compile_tagged(arg, &call.span, insn, ctx)?;
+            insn.i64_const(tag);
+            insn.call(self.func_map["__env_interp_arg"].0);
+        }
+        if formatted {
+            self.compile_expr(&call.args[0], insn, ctx)?;
+            self.emit_raising_call("__env_interp_format", insn, ctx);
+        }
+
+        if builtin == FmtBuiltin::Format {
+            insn.call(self.func_map["__env_interp_end"].0);
+        } else {
+            insn.i64_const(i64::from(builtin == FmtBuiltin::Print));
+            insn.call(self.func_map["__env_interp_print"].0);
+            insn.i64_const(0);
+        }
+        Ok(())
+    }
+}
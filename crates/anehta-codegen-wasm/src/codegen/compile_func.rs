@@ -7,10 +7,16 @@ impl WasmCodegen {
 
         for param in &func.params {
             ctx.add_param(&param.name);
-            ctx.var_types
-                .insert(param.name.clone(), type_name_to_ah(&param.type_name));
+            let ty = self.resolve_type(&param.type_name);
+            if ty == AhType::Int {
+                ctx.int_vars.insert(param.name.clone());
+            }
+            ctx.var_types.insert(param.name.clone(), ty);
             ctx.param_names.insert(param.name.clone());
         }
+        ctx.reserve_simd_helpers();
+        ctx.boxed = self.boxed_vars.get(&func.name).cloned().unwrap_or_default();
+        ctx.result_count = func.return_types.len();
 
         self.prescan_block(&func.body, &mut ctx);
 
@@ -22,13 +28,26 @@ impl WasmCodegen {
         );
         let mut insn = wasm_func.instructions();
 
+        // Without tail calls, `return` of a call to this function sets the
+        // parameters and starts over from here
+        if !self.tail_calls {
+            insn.loop_(BlockType::Empty);
+            ctx.block_depth += 1;
+            ctx.self_loop = Some((func.name.clone(), ctx.block_depth));
+        }
+
         for owned in &ctx.owned_tables {
             let var_idx = ctx.locals[owned];
             insn.i64_const(-1);
             insn.local_set(var_idx);
         }
+        self.emit_upvalue_cells(&[], &mut insn, &ctx);
 
         self.compile_block(&func.body, &mut insn, &mut ctx)?;
+        if ctx.self_loop.take().is_some() {
+            insn.end();
+            ctx.block_depth -= 1;
+        }
         self.emit_table_cleanup(&mut insn, &ctx, None);
 
         for _ in &func.return_types {
@@ -51,18 +70,87 @@ impl WasmCodegen {
             Self::collect_closure_expr_refs_stmt(stmt, &mut closure_exprs);
         }
 
-        for (i, info) in self.closures.iter().enumerate() {
+        // Inner closures come first, so compile in reverse: each closure is then
+        // created (recording its capture types) before its own body is compiled
+        let mut wasm_funcs = Vec::with_capacity(self.closures.len());
+        for (i, info) in self.closures.iter().enumerate().rev() {
+            wasm_funcs.push(self.compile_single_closure(i as u32, info, closure_exprs[i])?);
+        }
+        for (info, wasm_func) in self.closures.iter().zip(wasm_funcs.iter().rev()) {
             function_section.function(info.type_idx);
-            let closure_expr = closure_exprs[i];
-            let wasm_func = self.compile_single_closure(info, closure_expr)?;
-            code_section.function(&wasm_func);
+            code_section.function(wasm_func);
         }
 
         Ok(())
     }
 
+    /// Move the boxed variables of a function into fresh heap cells, each starting
+    /// with the variable's current value (a parameter's argument, otherwise its
+    /// initial local value). Captures already hold the cell address of the creator.
+    pub(super) fn emit_upvalue_cells(
+        &self,
+        captures: &[String],
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &FuncCtx,
+    ) {
+        let mut names: Vec<&String> = ctx.boxed.iter().filter(|name| !captures.contains(name)).collect();
+        names.sort(); // deterministic output
+        for name in names {
+            let Some(idx) = ctx.get_local(name) else {
+                continue;
+            };
+            insn.global_get(0);
+            insn.local_get(idx);
+            insn.i64_store(CELL_ARG);
+            insn.global_get(0);
+            insn.i64_extend_i32_u();
+            insn.local_set(idx);
+            insn.global_get(0);
+            insn.i32_const(8);
+            insn.i32_add();
+            insn.global_set(0);
+        }
+    }
+
+    /// Boxed variables declared in a loop body (`var` is the for-in variable), which
+    /// need new cells on every iteration.
+    pub(super) fn iteration_cells(body: &Block, var: Option<&String>, ctx: &FuncCtx) -> Vec<String> {
+        let mut declared = HashSet::new();
+        Self::find_declared_vars_block(body, &mut declared);
+        declared.extend(var.cloned());
+        let mut names: Vec<String> = declared.into_iter().filter(|name| ctx.boxed.contains(name)).collect();
+        names.sort(); // deterministic output
+        names
+    }
+
+    /// Move boxed variables into new cells holding their current values: the
+    /// closures created from here on share the new cells, and earlier ones keep
+    /// theirs, so each loop iteration has its own variables, as in Lua.
+    pub(super) fn emit_fresh_cells(
+        &self,
+        names: &[String],
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &FuncCtx,
+    ) {
+        for name in names {
+            let Some(idx) = ctx.get_local(name) else {
+                continue;
+            };
+            insn.global_get(0);
+            ctx.emit_get(name, insn);
+            insn.i64_store(CELL_ARG);
+            insn.global_get(0);
+            insn.i64_extend_i32_u();
+            insn.local_set(idx);
+            insn.global_get(0);
+            insn.i32_const(8);
+            insn.i32_add();
+            insn.global_set(0);
+        }
+    }
+
     /// Recursively collect references to ClosureExpr nodes in depth-first order.
-    fn collect_closure_expr_refs_stmt<'a>(
+    pub(super) fn collect_closure_expr_refs_stmt<'a>(
         stmt: &'a Statement,
         out: &mut Vec<&'a ClosureExpr>,
     ) {
@@ -96,6 +184,10 @@ impl WasmCodegen {
                 }
                 Self::collect_closure_expr_refs_block(&for_stmt.body, out);
             }
+            Statement::ForIn(for_in) => {
+                Self::collect_closure_expr_refs_expr(&for_in.iterable, out);
+                Self::collect_closure_expr_refs_block(&for_in.body, out);
+            }
             Statement::Block(block) => {
                 Self::collect_closure_expr_refs_block(block, out);
             }
@@ -104,6 +196,21 @@ impl WasmCodegen {
                     Self::collect_closure_expr_refs_expr(val, out);
                 }
             }
+            Statement::Yield(y) => {
+                if let Some(val) = &y.value {
+                    Self::collect_closure_expr_refs_expr(val, out);
+                }
+            }
+            Statement::Try(t) => {
+                Self::collect_closure_expr_refs_block(&t.body, out);
+                Self::collect_closure_expr_refs_block(&t.handler, out);
+            }
+            Statement::Throw(throw) => {
+                Self::collect_closure_expr_refs_expr(&throw.value, out);
+            }
+            Statement::Defer(d) => {
+                Self::collect_closure_expr_refs_block(&d.body, out);
+            }
             Statement::CallFunc(call) => {
                 for arg in &call.args {
                     Self::collect_closure_expr_refs_expr(arg, out);
@@ -112,6 +219,11 @@ impl WasmCodegen {
             Statement::FuncDecl(func) => {
                 Self::collect_closure_expr_refs_block(&func.body, out);
             }
+            Statement::ImplDecl(imp) => {
+                for method in &imp.methods {
+                    Self::collect_closure_expr_refs_block(&method.body, out);
+                }
+            }
             Statement::TimerStmt(timer) => {
                 Self::collect_closure_expr_refs_block(&timer.body, out);
             }
@@ -121,6 +233,9 @@ impl WasmCodegen {
                     Self::collect_closure_expr_refs_expr(arg, out);
                 }
             }
+            Statement::Expr(expr) => {
+                Self::collect_closure_expr_refs_expr(expr, out);
+            }
             Statement::FieldAssign(fa) => {
                 Self::collect_closure_expr_refs_expr(&fa.value, out);
             }
@@ -184,17 +299,57 @@ impl WasmCodegen {
             Expr::Grouped(inner) => {
                 Self::collect_closure_expr_refs_expr(inner, out);
             }
+            Expr::UnaryOp { operand, .. } => {
+                Self::collect_closure_expr_refs_expr(operand, out);
+            }
+            Expr::Cast(cast) => {
+                Self::collect_closure_expr_refs_expr(&cast.expr, out);
+            }
+            Expr::Interpolated { parts, .. } => {
+                for part in parts {
+                    if let InterpPart::Expr(e) = part {
+                        Self::collect_closure_expr_refs_expr(e, out);
+                    }
+                }
+            }
             Expr::MethodCall(mc) => {
                 Self::collect_closure_expr_refs_expr(&mc.callee, out);
                 for arg in &mc.args {
                     Self::collect_closure_expr_refs_expr(arg, out);
                 }
             }
+            Expr::If(e) => {
+                Self::collect_closure_expr_refs_boolean(&e.condition, out);
+                Self::collect_closure_expr_refs_expr(&e.then_value, out);
+                Self::collect_closure_expr_refs_expr(&e.else_value, out);
+            }
+            Expr::BooleanExpr(bool_expr) => {
+                Self::collect_closure_expr_refs_boolean(bool_expr, out);
+            }
+            Expr::Match(m) => {
+                Self::collect_closure_expr_refs_expr(&m.subject, out);
+                for arm in &m.arms {
+                    match &arm.body {
+                        MatchBody::Expr(body) => Self::collect_closure_expr_refs_expr(body, out),
+                        MatchBody::Block(block) => Self::collect_closure_expr_refs_block(block, out),
+                    }
+                }
+            }
+            Expr::New(new) => {
+                for entry in &new.fields {
+                    Self::collect_closure_expr_refs_expr(&entry.value, out);
+                }
+            }
             Expr::TableLiteral(table) => {
                 for entry in &table.entries {
                     Self::collect_closure_expr_refs_expr(&entry.value, out);
                 }
             }
+            Expr::ListLiteral(list) => {
+                for element in &list.elements {
+                    Self::collect_closure_expr_refs_expr(element, out);
+                }
+            }
             Expr::FieldAccess(fa) => {
                 Self::collect_closure_expr_refs_expr(&fa.object, out);
             }
@@ -209,6 +364,7 @@ impl WasmCodegen {
     /// Compile a single closure function body.
     fn compile_single_closure(
         &self,
+        closure_id: u32,
         info: &ClosureInfo,
         closure_expr: &ClosureExpr,
     ) -> Result<Function, CodegenError> {
@@ -216,17 +372,28 @@ impl WasmCodegen {
 
         let env_ptr_idx = ctx.add_param_with_type("__env_ptr", ValType::I32);
 
-        for param in &closure_expr.params {
+        let hof_param_types = self.hof_param_types.borrow().get(&closure_id).cloned().unwrap_or_default();
+        for (i, param) in closure_expr.params.iter().enumerate() {
             ctx.add_param(&param.name);
-            ctx.var_types.insert(param.name.clone(), AhType::Int);
+            let ty = hof_param_types.get(i).copied().unwrap_or(info.param_types[i]);
+            if param.type_name.is_some() && ty == AhType::Int {
+                ctx.int_vars.insert(param.name.clone());
+            }
+            ctx.var_types.insert(param.name.clone(), ty);
             ctx.param_names.insert(param.name.clone());
         }
+        ctx.reserve_simd_helpers();
 
-        for capture in &info.captures {
+        // Captures take the types they had where the closure was created
+        let capture_types = self.capture_types.borrow().get(&closure_id).cloned().unwrap_or_default();
+        for (i, capture) in info.captures.iter().enumerate() {
             ctx.declare_local(capture);
-            ctx.var_types.insert(capture.clone(), AhType::Int);
+            let ty = capture_types.get(i).copied().unwrap_or(AhType::Int);
+            ctx.var_types.insert(capture.clone(), ty);
             ctx.param_names.insert(capture.clone());
         }
+        ctx.boxed = info.boxed.clone();
+        ctx.result_count = 1;
 
         match &closure_expr.body {
             ClosureBody::Expr(e) => self.prescan_expr(e, &mut ctx),
@@ -257,10 +424,11 @@ impl WasmCodegen {
             insn.i64_const(-1);
             insn.local_set(var_idx);
         }
+        self.emit_upvalue_cells(&info.captures, &mut insn, &ctx);
 
         match &closure_expr.body {
             ClosureBody::Expr(e) => {
-                self.compile_expr(e, &mut insn, &mut ctx)?;
+                self.compile_tail_value(e, &mut insn, &mut ctx)?;
                 insn.return_();
             }
             ClosureBody::Block(b) => {
@@ -275,16 +443,60 @@ impl WasmCodegen {
         Ok(wasm_func)
     }
 
+    /// Compile the expression body of a closure; a comparison such as `x > 0`
+    /// yields 1 or 0.
+    pub(super) fn compile_closure_body_expr(
+        &self,
+        body: &Expr,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        if let Expr::BooleanExpr(cond) = body {
+            self.compile_boolean_expr(cond, insn, ctx)?;
+            insn.i64_extend_i32_u();
+            return Ok(());
+        }
+        self.compile_expr(body, insn, ctx)
+    }
+
+    /// Compile the value a function returns, with a call to leave it by as a tail
+    /// call: the value itself, or a branch of an if-expression.
+    pub(super) fn compile_tail_value(
+        &self,
+        value: &Expr,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        match value {
+            Expr::CallFunc(call) => self.compile_call_func_expr(call, self.tail_calls, insn, ctx),
+            Expr::Grouped(inner) => self.compile_tail_value(inner, insn, ctx),
+            Expr::If(e) => self.compile_if_expr(e, self.tail_calls, insn, ctx),
+            _ => self.compile_closure_body_expr(value, insn, ctx),
+        }
+    }
+
+    /// Call the closure or function value in variable `call.name`, whose type is
+    /// `callee_ty`; with `tail`, as the last thing a function returning one value does.
     pub(super) fn emit_closure_call_indirect(
         &self,
-        closure_id: u32,
-        closure_local_idx: u32,
+        callee_ty: AhType,
         call: &CallFunc,
+        tail: bool,
         insn: &mut wasm_encoder::InstructionSink<'_>,
         ctx: &mut FuncCtx,
     ) -> Result<(), CodegenError> {
-        let info = &self.closures[closure_id as usize];
         let num_args = call.args.len();
+        if let Some((arity, _)) = self.callable_signature(callee_ty)
+            && arity != num_args
+        {
+            return Err(codegen_err(
+                format!(
+                    "function '{}' expects {} argument(s), but {} were given",
+                    call.name, arity, num_args
+                ),
+                &call.span,
+            ));
+        }
 
         let arg_temps = ctx.claim_closure_call_temps();
 
@@ -292,20 +504,27 @@ impl WasmCodegen {
             insn.local_set(arg_temps[i]);
         }
 
-        insn.local_get(closure_local_idx);
+        ctx.emit_get(&call.name, insn);
         insn.i32_wrap_i64();
 
-        for i in 0..num_args {
-            insn.local_get(arg_temps[i]);
+        for &temp in &arg_temps[..num_args] {
+            insn.local_get(temp);
         }
 
-        insn.local_get(closure_local_idx);
+        ctx.emit_get(&call.name, insn);
         insn.i64_const(32);
         insn.i64_shr_u();
         insn.i32_wrap_i64();
 
-        let call_type_idx = info.type_idx;
-        insn.call_indirect(0, call_type_idx);
+        let call_type_idx = self
+            .callable_type_idx(num_args)
+            .expect("callable values register their call type");
+        if tail && ctx.result_count == 1 {
+            insn.return_call_indirect(0, call_type_idx);
+        } else {
+            insn.call_indirect(0, call_type_idx);
+            self.emit_error_check(insn, ctx);
+        }
 
         Ok(())
     }
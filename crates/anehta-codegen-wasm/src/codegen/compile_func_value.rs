@@ -0,0 +1,205 @@
+use super::*;
+
+impl WasmCodegen {
+    /// Resolve a function type in its canonical spelling `fn(int,int)->int`.
+    pub(super) fn resolve_func_type(&self, name: &str) -> AhType {
+        let Some((params, ret)) = split_func_type(name) else {
+            return AhType::Int;
+        };
+        self.intern_func_type(FuncTypeInfo {
+            params: params.iter().map(|p| self.resolve_type(p)).collect(),
+            ret: self.resolve_type(ret),
+        })
+    }
+
+    /// The `AhType::Func` of a signature; equal signatures get the same id, so
+    /// function types compare with `==`.
+    pub(super) fn intern_func_type(&self, info: FuncTypeInfo) -> AhType {
+        let mut types = self.func_types.borrow_mut();
+        let id = match types.iter().position(|t| *t == info) {
+            Some(id) => id,
+            None => {
+                types.push(info);
+                types.len() - 1
+            }
+        };
+        AhType::Func(id as u32)
+    }
+
+    /// Source spelling of a function type, for diagnostics.
+    pub(super) fn func_type_display(&self, id: u32) -> String {
+        let types = self.func_types.borrow();
+        let info = &types[id as usize];
+        let params: Vec<_> = info.params.iter().map(|&p| self.type_display(p)).collect();
+        format!("fn({}) -> {}", params.join(", "), self.type_display(info.ret))
+    }
+
+    /// Parameter count and result type of a callable value: a closure or a value
+    /// of a function type.
+    pub(super) fn callable_signature(&self, ty: AhType) -> Option<(usize, AhType)> {
+        match ty {
+            AhType::Closure(id) => self
+                .closures
+                .get(id as usize)
+                .map(|info| (info.param_count, info.return_type)),
+            AhType::Func(id) => {
+                let types = self.func_types.borrow();
+                let info = &types[id as usize];
+                Some((info.params.len(), info.ret))
+            }
+            _ => None,
+        }
+    }
+
+    /// Type index used by `call_indirect` for a callable taking `arity` arguments:
+    /// (i32 env_ptr, i64 * arity) -> i64.
+    pub(super) fn callable_type_idx(&self, arity: usize) -> Option<u32> {
+        let mut params = vec![ValType::I32];
+        params.extend(std::iter::repeat_n(ValType::I64, arity));
+        self.types
+            .iter()
+            .position(|(p, r)| *p == params && *r == [ValType::I64])
+            .map(|i| i as u32)
+    }
+
+    /// Give every function used as a value (a name that is not called but read,
+    /// like `apply(add, 1)`) a trampoline and a slot in the function table.
+    /// Functions returning several values cannot be used as values.
+    pub(super) fn collect_func_refs(&mut self, program: &Program) {
+        let mut referenced = HashSet::new();
+        for stmt in &program.statements {
+            if !matches!(stmt, Statement::FuncDecl(_) | Statement::ImplDecl(_)) {
+                Self::find_variables_stmt(stmt, &mut referenced);
+            }
+        }
+        for func in user_funcs(program) {
+            Self::find_variables_block(&func.body, &mut referenced);
+        }
+
+        let mut names: Vec<&FuncDecl> = user_funcs(program)
+            .filter(|func| referenced.contains(&func.name) && func.return_types.len() == 1)
+            .collect();
+        names.sort_by(|a, b| a.name.cmp(&b.name)); // deterministic table layout
+        for func in names {
+            let ty = self.intern_func_type(FuncTypeInfo {
+                params: self.func_param_types[&func.name].clone(),
+                ret: self.func_return_types[&func.name],
+            });
+            let mut params = vec![ValType::I32];
+            params.extend(func.params.iter().map(|_| ValType::I64));
+            let type_idx = self.add_type(params, vec![ValType::I64]);
+            let func_idx = self.next_func_idx;
+            self.next_func_idx += 1;
+            let table_idx = (self.closures.len() + self.func_refs.len()) as u32;
+            self.func_refs.insert(
+                func.name.clone(),
+                FuncRef {
+                    func_idx,
+                    type_idx,
+                    table_idx,
+                    ty,
+                },
+            );
+        }
+
+        // Every function type needs its call_indirect type, even when no value of
+        // that arity is ever created
+        let arities: Vec<usize> = self.func_types.borrow().iter().map(|t| t.params.len()).collect();
+        for arity in arities {
+            let mut params = vec![ValType::I32];
+            params.extend(std::iter::repeat_n(ValType::I64, arity));
+            self.add_type(params, vec![ValType::I64]);
+        }
+    }
+
+    /// Function values in table order.
+    pub(super) fn func_ref_order(&self) -> Vec<&FuncRef> {
+        let mut refs: Vec<&FuncRef> = self.func_refs.values().collect();
+        refs.sort_by_key(|r| r.table_idx);
+        refs
+    }
+
+    /// Compile the trampolines of function values: forward the arguments,
+    /// skipping the env pointer, to the function itself, as a tail call when
+    /// enabled so calls through a function value add no frame.
+    pub(super) fn compile_func_refs(&self, function_section: &mut FunctionSection, code_section: &mut CodeSection) {
+        let mut targets: Vec<(&String, &FuncRef)> = self.func_refs.iter().collect();
+        targets.sort_by_key(|(_, r)| r.table_idx);
+        for (name, func_ref) in targets {
+            let (target_idx, target_type) = self.func_map[name];
+            let arity = self.types[target_type as usize].0.len() as u32;
+            let mut wasm_func = Function::new(Vec::<(u32, ValType)>::new());
+            let mut insn = wasm_func.instructions();
+            for i in 1..=arity {
+                insn.local_get(i);
+            }
+            if self.tail_calls {
+                insn.return_call(target_idx);
+            } else {
+                insn.call(target_idx);
+            }
+            insn.end();
+            function_section.function(func_ref.type_idx);
+            code_section.function(&wasm_func);
+        }
+    }
+
+    /// Compile a named function used as a value: its table slot, with no env.
+    pub(super) fn compile_func_ref(&self, name: &str, span: &Span, insn: &mut wasm_encoder::InstructionSink<'_>) -> Result<(), CodegenError> {
+        let Some(func_ref) = self.func_refs.get(name) else {
+            return Err(codegen_err(
+                format!("function '{}' returns several values and cannot be used as a value", name),
+                span,
+            ));
+        };
+        insn.i64_const(i64::from(func_ref.table_idx) << 32);
+        Ok(())
+    }
+
+    /// Check the arguments of a user function call that go to parameters of a
+    /// function type.
+    pub(super) fn check_call_args(&self, call: &CallFunc, ctx: &FuncCtx) -> Result<(), CodegenError> {
+        let Some(param_types) = self.func_param_types.get(&call.name) else {
+            return Ok(());
+        };
+        for (i, (arg, &expected)) in call.args.iter().zip(param_types).enumerate() {
+            self.check_callable_arg(expected, arg, i, &call.name, &call.span, ctx)?;
+        }
+        Ok(())
+    }
+
+    /// Check an argument passed for a parameter of a function type: a function
+    /// value must have the same signature, a closure the same number of parameters.
+    fn check_callable_arg(
+        &self,
+        expected: AhType,
+        arg: &Expr,
+        position: usize,
+        func_name: &str,
+        span: &Span,
+        ctx: &FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let AhType::Func(id) = expected else {
+            return Ok(());
+        };
+        let found = self.infer_expr_type(arg, ctx);
+        let fits = match found {
+            AhType::Func(_) => found == expected,
+            AhType::Closure(_) => self.callable_signature(found).map(|(n, _)| n) == self.callable_signature(expected).map(|(n, _)| n),
+            _ => self.is_unknown(arg, found, ctx),
+        };
+        if fits {
+            return Ok(());
+        }
+        Err(codegen_err(
+            format!(
+                "argument {} of '{}' must be {}, found {}",
+                position + 1,
+                func_name,
+                self.func_type_display(id),
+                self.type_display(found)
+            ),
+            span,
+        ))
+    }
+}
@@ -0,0 +1,685 @@
+use super::*;
+
+/// Resume state of a finished generator.
+const FINISHED: i64 = -1;
+
+/// Frame slots of a generator: the resume state, the last value, then the locals.
+const STATE_SLOT: usize = 0;
+const VALUE_SLOT: usize = 1;
+const FIRST_LOCAL_SLOT: usize = 2;
+
+/// Script builtins working on coroutines.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum CoroutineBuiltin {
+    /// `resume(co)`: run to the next `yield` and return its value
+    Resume,
+    /// `is_done(co)`: 1 once the generator has returned
+    IsDone,
+}
+
+/// Number of `yield` statements in a statement; closures are not entered.
+pub(super) fn count_yields(stmt: &Statement) -> i64 {
+    match stmt {
+        Statement::Yield(_) => 1,
+        Statement::IfStmt(if_stmt) => {
+            block_yields(&if_stmt.body)
+                + if_stmt.else_if.iter().map(|branch| block_yields(&branch.body)).sum::<i64>()
+                + if_stmt.else_body.as_ref().map_or(0, block_yields)
+        }
+        Statement::ForStmt(for_stmt) => block_yields(&for_stmt.body),
+        Statement::ForIn(for_in) => block_yields(&for_in.body),
+        Statement::Block(block) => block_yields(block),
+        Statement::TimerStmt(timer) => block_yields(&timer.body),
+        Statement::Try(t) => block_yields(&t.body) + block_yields(&t.handler),
+        Statement::Defer(d) => block_yields(&d.body),
+        Statement::Expr(Expr::Match(m)) => m
+            .arms
+            .iter()
+            .map(|arm| match &arm.body {
+                MatchBody::Block(block) => block_yields(block),
+                MatchBody::Expr(_) => 0,
+            })
+            .sum(),
+        _ => 0,
+    }
+}
+
+pub(super) fn block_yields(block: &Block) -> i64 {
+    block.statements.iter().map(count_yields).sum()
+}
+
+/// A function whose body yields is a generator: calling it returns a coroutine.
+pub(super) fn is_generator(func: &FuncDecl) -> bool {
+    block_yields(&func.body) > 0
+}
+
+impl WasmCodegen {
+    /// Type of the coroutines of a generator yielding values of `value_ty`.
+    pub(super) fn coroutine_type(&self, value_ty: AhType) -> AhType {
+        let AhType::Func(id) = self.intern_func_type(FuncTypeInfo {
+            params: Vec::new(),
+            ret: value_ty,
+        }) else {
+            unreachable!("intern_func_type returns a function type");
+        };
+        AhType::Coroutine(id)
+    }
+
+    /// Type of the values a coroutine yields.
+    fn coroutine_value_type(&self, ty: AhType) -> AhType {
+        match ty {
+            AhType::Coroutine(id) => self.func_types.borrow()[id as usize].ret,
+            _ => AhType::Int,
+        }
+    }
+
+    /// Give every generator a step function and a slot in the function table,
+    /// after the function values, and register `__resume` / `__is_done`.
+    pub(super) fn collect_generators(&mut self, program: &Program) -> Result<(), CodegenError> {
+        for stmt in &program.statements {
+            if let Statement::ImplDecl(imp) = stmt
+                && let Some(method) = imp.methods.iter().find(|m| is_generator(m))
+            {
+                return Err(codegen_err(
+                    format!("method '{}' cannot yield; generators must be top-level functions", method.name),
+                    &method.span,
+                ));
+            }
+        }
+
+        let generators: Vec<&FuncDecl> = program
+            .statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::FuncDecl(func) if is_generator(func) => Some(func),
+                _ => None,
+            })
+            .collect();
+        if generators.is_empty() {
+            return Ok(());
+        }
+
+        let step_type_idx = self.add_type(vec![ValType::I32], vec![ValType::I64]);
+        for func in generators {
+            if func.return_types.len() != 1 {
+                return Err(codegen_err(
+                    format!("generator '{}' needs one return type, the type of the values it yields", func.name),
+                    &func.span,
+                ));
+            }
+            let table_idx = (self.closures.len() + self.func_refs.len() + self.generators.len()) as u32;
+            self.generators.insert(
+                func.name.clone(),
+                GenInfo {
+                    step_func_idx: self.next_func_idx,
+                    table_idx,
+                },
+            );
+            self.next_func_idx += 1;
+        }
+
+        let helper_type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        for name in ["__resume", "__is_done"] {
+            self.func_map.insert(name.to_string(), (self.next_func_idx, helper_type_idx));
+            self.next_func_idx += 1;
+        }
+        debug_assert_eq!(self.callable_type_idx(0), Some(step_type_idx));
+        Ok(())
+    }
+
+    /// Step functions in table order.
+    pub(super) fn generator_order(&self) -> Vec<&GenInfo> {
+        let mut generators: Vec<&GenInfo> = self.generators.values().collect();
+        generators.sort_by_key(|info| info.table_idx);
+        generators
+    }
+
+    /// Compile a generator: the function called by scripts, which sets up a frame
+    /// holding the arguments and returns the coroutine, and the step function.
+    pub(super) fn compile_generator(&self, func: &FuncDecl) -> Result<(Function, Function), CodegenError> {
+        let (step, frame_size) = self.compile_generator_step(func)?;
+        let info = &self.generators[&func.name];
+
+        let mut wasm_func = Function::new(Vec::<(u32, ValType)>::new());
+        let mut insn = wasm_func.instructions();
+        // The frame starts zeroed: state 0, locals 0
+        insn.global_get(0);
+        insn.i32_const(0);
+        insn.i32_const(frame_size as i32);
+        insn.memory_fill(0);
+        for i in 0..func.params.len() {
+            insn.global_get(0);
+            insn.local_get(i as u32);
+            insn.i64_store(Self::slot_arg(FIRST_LOCAL_SLOT + i));
+        }
+        insn.global_get(0);
+        insn.i64_extend_i32_u();
+        insn.i64_const(i64::from(info.table_idx) << 32);
+        insn.i64_or();
+        insn.global_get(0);
+        insn.i32_const(frame_size as i32);
+        insn.i32_add();
+        insn.global_set(0);
+        insn.end();
+        Ok((wasm_func, step))
+    }
+
+    /// Compile the step function of a generator, `(i32 frame) -> i64`, and return
+    /// it with the frame size. Each call reloads the locals from the frame, jumps
+    /// back to the `yield` it stopped at, and runs to the next `yield` or the end.
+    fn compile_generator_step(&self, func: &FuncDecl) -> Result<(Function, usize), CodegenError> {
+        let mut ctx = FuncCtx::new();
+        let frame = ctx.add_param_with_type("__frame", ValType::I32);
+        for param in &func.params {
+            ctx.declare_local(&param.name);
+            let ty = self.resolve_type(&param.type_name);
+            if ty == AhType::Int {
+                ctx.int_vars.insert(param.name.clone());
+            }
+            ctx.var_types.insert(param.name.clone(), ty);
+            ctx.param_names.insert(param.name.clone());
+        }
+        ctx.reserve_simd_helpers();
+        let resuming = ctx.alloc_anonymous_local();
+        ctx.boxed = self.boxed_vars.get(&func.name).cloned().unwrap_or_default();
+        ctx.result_count = 1;
+        ctx.generator = Some(GenFrame {
+            frame,
+            resuming,
+            value_type: self.resolve_type(&func.return_types[0]),
+            next_yield: 1,
+            guarded: false,
+        });
+
+        self.prescan_block(&func.body, &mut ctx);
+        let local_count = ctx.next_local;
+
+        let mut wasm_func = Function::new(
+            ctx.extra_locals
+                .iter()
+                .map(|ty| (1u32, *ty))
+                .collect::<Vec<_>>(),
+        );
+        let mut insn = wasm_func.instructions();
+
+        for local in 1..local_count {
+            insn.local_get(frame);
+            insn.i64_load(Self::slot_arg(FIRST_LOCAL_SLOT + local as usize - 1));
+            insn.local_set(local);
+        }
+        insn.local_get(frame);
+        insn.i64_load(Self::slot_arg(STATE_SLOT));
+        insn.i64_const(0);
+        insn.i64_ne();
+        insn.i64_extend_i32_u();
+        insn.local_set(resuming);
+
+        // First run only
+        insn.local_get(resuming);
+        insn.i64_eqz();
+        insn.if_(BlockType::Empty);
+        for owned in &ctx.owned_tables {
+            let var_idx = ctx.locals[owned];
+            insn.i64_const(-1);
+            insn.local_set(var_idx);
+        }
+        self.emit_upvalue_cells(&[], &mut insn, &ctx);
+        insn.end();
+
+        self.compile_block(&func.body, &mut insn, &mut ctx)?;
+        self.emit_table_cleanup(&mut insn, &ctx, None);
+
+        // Falling off the end finishes with 0
+        insn.local_get(frame);
+        insn.i64_const(FINISHED);
+        insn.i64_store(Self::slot_arg(STATE_SLOT));
+        insn.local_get(frame);
+        insn.i64_const(0);
+        insn.i64_store(Self::slot_arg(VALUE_SLOT));
+        insn.i64_const(0);
+        insn.end();
+
+        let frame_size = (FIRST_LOCAL_SLOT + local_count as usize - 1) * 8;
+        Ok((wasm_func, frame_size))
+    }
+
+    /// Compile the step functions, then `__resume` and `__is_done`, which scripts
+    /// reach through `resume`/`is_done` and hosts through the exports.
+    pub(super) fn compile_generator_steps(
+        &self,
+        steps: Vec<Function>,
+        function_section: &mut FunctionSection,
+        code_section: &mut CodeSection,
+    ) {
+        if steps.is_empty() {
+            return;
+        }
+        let step_type_idx = self.callable_type_idx(0).expect("collect_generators registers the step type");
+        for step in &steps {
+            function_section.function(step_type_idx);
+            code_section.function(step);
+        }
+
+        // __resume(co): the value of a finished coroutine, otherwise its next step
+        let (_, helper_type_idx) = self.func_map["__resume"];
+        let mut resume = Function::new(Vec::<(u32, ValType)>::new());
+        let mut insn = resume.instructions();
+        Self::emit_is_finished(&mut insn);
+        insn.if_(BlockType::Result(ValType::I64));
+        insn.local_get(0);
+        insn.i32_wrap_i64();
+        insn.i64_load(Self::slot_arg(VALUE_SLOT));
+        insn.else_();
+        insn.local_get(0);
+        insn.i32_wrap_i64();
+        insn.local_get(0);
+        insn.i64_const(32);
+        insn.i64_shr_u();
+        insn.i32_wrap_i64();
+        insn.call_indirect(0, step_type_idx);
+        insn.end();
+        insn.end();
+        function_section.function(helper_type_idx);
+        code_section.function(&resume);
+
+        let mut is_done = Function::new(Vec::<(u32, ValType)>::new());
+        let mut insn = is_done.instructions();
+        Self::emit_is_finished(&mut insn);
+        insn.i64_extend_i32_u();
+        insn.end();
+        function_section.function(helper_type_idx);
+        code_section.function(&is_done);
+    }
+
+    /// Push whether the coroutine in local 0 has finished (i32).
+    fn emit_is_finished(insn: &mut wasm_encoder::InstructionSink<'_>) {
+        insn.local_get(0);
+        insn.i32_wrap_i64();
+        insn.i64_load(Self::slot_arg(STATE_SLOT));
+        insn.i64_const(FINISHED);
+        insn.i64_eq();
+    }
+
+    /// Compile a statement of a generator body. While resuming, statements are
+    /// skipped until the `yield` the generator stopped at; a statement holding
+    /// yields is entered only when that `yield` is inside it.
+    pub(super) fn compile_gen_stmt(
+        &self,
+        stmt: &Statement,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let generator = ctx.generator.expect("compile_gen_stmt runs in a generator");
+        let yields = count_yields(stmt);
+        if yields == 0 {
+            insn.local_get(generator.resuming);
+            insn.i64_eqz();
+            insn.if_(BlockType::Empty);
+            ctx.block_depth += 1;
+            Self::set_guarded(ctx, true);
+            let result = self.compile_stmt(stmt, insn, ctx);
+            Self::set_guarded(ctx, false);
+            result?;
+            insn.end();
+            ctx.block_depth -= 1;
+            return Ok(());
+        }
+
+        let (first, end) = (generator.next_yield, generator.next_yield + yields);
+        match stmt {
+            Statement::Yield(y) => self.compile_yield(y, insn, ctx),
+            Statement::Block(block) => self.compile_block(block, insn, ctx),
+            Statement::IfStmt(if_stmt) => self.compile_gen_if(if_stmt, insn, ctx),
+            Statement::ForStmt(_) | Statement::ForIn(_) => {
+                self.emit_resume_choice(first, end, insn, ctx, |_, insn, _| {
+                    insn.i32_const(1);
+                    Ok(())
+                })?;
+                insn.if_(BlockType::Empty);
+                ctx.block_depth += 1;
+                match stmt {
+                    Statement::ForStmt(for_stmt) => self.compile_gen_for(for_stmt, insn, ctx)?,
+                    Statement::ForIn(for_in) => self.compile_gen_for_in(for_in, insn, ctx)?,
+                    _ => unreachable!(),
+                }
+                insn.end();
+                ctx.block_depth -= 1;
+                Ok(())
+            }
+            Statement::TimerStmt(timer) => Err(codegen_err("yield cannot be used inside a timer block", &timer.span)),
+            Statement::Try(t) => Err(codegen_err("yield cannot be used inside a try block", &t.span)),
+            Statement::Defer(d) => Err(codegen_err("yield cannot be used inside a defer block", &d.span)),
+            Statement::Expr(Expr::Match(m)) => Err(codegen_err("yield cannot be used inside a match arm", &m.span)),
+            _ => unreachable!("count_yields only finds yields in the statements above"),
+        }
+    }
+
+    fn set_guarded(ctx: &mut FuncCtx, guarded: bool) {
+        if let Some(generator) = &mut ctx.generator {
+            generator.guarded = guarded;
+        }
+    }
+
+    /// Push an i32: while resuming, whether the generator stopped at a yield
+    /// numbered `first..end`; otherwise the value pushed by `otherwise`.
+    fn emit_resume_choice(
+        &self,
+        first: i64,
+        end: i64,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+        otherwise: impl FnOnce(&Self, &mut wasm_encoder::InstructionSink<'_>, &mut FuncCtx) -> Result<(), CodegenError>,
+    ) -> Result<(), CodegenError> {
+        let generator = ctx.generator.expect("resume choices are made in a generator");
+        insn.local_get(generator.resuming);
+        insn.i32_wrap_i64();
+        insn.if_(BlockType::Result(ValType::I32));
+        ctx.block_depth += 1;
+        insn.local_get(generator.frame);
+        insn.i64_load(Self::slot_arg(STATE_SLOT));
+        insn.i64_const(first);
+        insn.i64_ge_s();
+        insn.local_get(generator.frame);
+        insn.i64_load(Self::slot_arg(STATE_SLOT));
+        insn.i64_const(end);
+        insn.i64_lt_s();
+        insn.i32_and();
+        insn.else_();
+        otherwise(self, insn, ctx)?;
+        insn.end();
+        ctx.block_depth -= 1;
+        Ok(())
+    }
+
+    /// `yield value`: save the value, the state and every local in the frame and
+    /// return the value. When resuming, reaching its own `yield` ends the resume.
+    fn compile_yield(
+        &self,
+        y: &YieldStmt,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let generator = ctx.generator.expect("yields compile in a generator");
+        let number = generator.next_yield;
+        if let Some(generator) = &mut ctx.generator {
+            generator.next_yield += 1;
+        }
+
+        insn.local_get(generator.resuming);
+        insn.i64_eqz();
+        insn.if_(BlockType::Empty);
+        ctx.block_depth += 1;
+        insn.local_get(generator.frame);
+        self.compile_gen_value(generator.value_type, y.value.as_ref(), insn, ctx)?;
+        insn.i64_store(Self::slot_arg(VALUE_SLOT));
+        insn.local_get(generator.frame);
+        insn.i64_const(number);
+        insn.i64_store(Self::slot_arg(STATE_SLOT));
+        for local in 1..ctx.next_local {
+            insn.local_get(generator.frame);
+            insn.local_get(local);
+            insn.i64_store(Self::slot_arg(FIRST_LOCAL_SLOT + local as usize - 1));
+        }
+        insn.local_get(generator.frame);
+        insn.i64_load(Self::slot_arg(VALUE_SLOT));
+        insn.return_();
+        insn.else_();
+        insn.local_get(generator.frame);
+        insn.i64_load(Self::slot_arg(STATE_SLOT));
+        insn.i64_const(number);
+        insn.i64_eq();
+        insn.if_(BlockType::Empty);
+        insn.i64_const(0);
+        insn.local_set(generator.resuming);
+        insn.end();
+        insn.end();
+        ctx.block_depth -= 1;
+        Ok(())
+    }
+
+    /// A yielded or returned value, 0 when there is none; ints widen to float.
+    fn compile_gen_value(
+        &self,
+        value_type: AhType,
+        value: Option<&Expr>,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        match value {
+            None => {
+                insn.i64_const(0);
+            }
+            Some(value) if value_type == AhType::Float && self.infer_expr_type(value, ctx) == AhType::Int => {
+                self.emit_float_operand(value, insn, ctx)?;
+                insn.i64_reinterpret_f64();
+            }
+            Some(value) => self.compile_expr(value, insn, ctx)?,
+        }
+        Ok(())
+    }
+
+    /// Mark a generator finished, as when an error leaves its step function.
+    pub(super) fn emit_gen_finish(generator: &GenFrame, insn: &mut wasm_encoder::InstructionSink<'_>) {
+        insn.local_get(generator.frame);
+        insn.i64_const(FINISHED);
+        insn.i64_store(Self::slot_arg(STATE_SLOT));
+    }
+
+    /// `return` in a generator: finish, keeping the value for `resume`.
+    pub(super) fn compile_gen_return(
+        &self,
+        ret: &ReturnStmt,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let generator = ctx.generator.expect("generator returns compile in a generator");
+        if ret.values.len() > 1 {
+            return Err(codegen_err("a generator cannot return several values", &ret.span));
+        }
+        insn.local_get(generator.frame);
+        self.compile_gen_value(generator.value_type, ret.values.first(), insn, ctx)?;
+        insn.i64_store(Self::slot_arg(VALUE_SLOT));
+        insn.local_get(generator.frame);
+        insn.i64_const(FINISHED);
+        insn.i64_store(Self::slot_arg(STATE_SLOT));
+
+        let returned = match ret.values.first() {
+            Some(Expr::Variable(name, _)) if ctx.owned_tables.contains(name) => Some(name.as_str()),
+            _ => None,
+        };
+        self.emit_table_cleanup(insn, ctx, returned);
+        insn.local_get(generator.frame);
+        insn.i64_load(Self::slot_arg(VALUE_SLOT));
+        insn.return_();
+        Ok(())
+    }
+
+    /// `if` holding yields: while resuming, the branch holding the `yield` the
+    /// generator stopped at is taken without evaluating conditions.
+    fn compile_gen_if(
+        &self,
+        if_stmt: &IfStmt,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let mut first = ctx.generator.expect("generator if").next_yield;
+        let mut branches = vec![(&if_stmt.condition, &if_stmt.body)];
+        branches.extend(if_stmt.else_if.iter().map(|branch| (&branch.condition, &branch.body)));
+
+        for (i, (condition, body)) in branches.iter().enumerate() {
+            let end = first + block_yields(body);
+            self.emit_resume_choice(first, end, insn, ctx, |codegen, insn, ctx| {
+                codegen.compile_boolean_expr(condition, insn, ctx)
+            })?;
+            first = end;
+            insn.if_(BlockType::Empty);
+            ctx.block_depth += 1;
+            self.compile_block(body, insn, ctx)?;
+            if i + 1 < branches.len() || if_stmt.else_body.is_some() {
+                insn.else_();
+            }
+        }
+        if let Some(else_body) = &if_stmt.else_body {
+            self.compile_block(else_body, insn, ctx)?;
+        }
+        for _ in &branches {
+            insn.end();
+            ctx.block_depth -= 1;
+        }
+        Ok(())
+    }
+
+    /// C-style `for` holding yields: the init and the first condition check are
+    /// skipped when resuming into the body.
+    fn compile_gen_for(
+        &self,
+        for_stmt: &ForStmt,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let generator = ctx.generator.expect("generator for");
+        if let Some(init) = &for_stmt.init {
+            self.compile_stmt(init, insn, ctx)?;
+        }
+
+        insn.block(BlockType::Empty);
+        ctx.block_depth += 1;
+        let break_depth = ctx.block_depth;
+        insn.loop_(BlockType::Empty);
+        ctx.block_depth += 1;
+        let continue_depth = ctx.block_depth;
+        ctx.loop_depth_stack.push(LoopInfo {
+            break_depth,
+            continue_depth,
+            defer_depth: ctx.defers.len(),
+        });
+
+        if let Some(cond) = &for_stmt.condition {
+            insn.local_get(generator.resuming);
+            insn.i64_eqz();
+            insn.if_(BlockType::Empty);
+            ctx.block_depth += 1;
+            self.compile_boolean_expr(cond, insn, ctx)?;
+            insn.i32_eqz();
+            insn.br_if(ctx.block_depth - break_depth);
+            insn.end();
+            ctx.block_depth -= 1;
+        }
+
+        // Resuming into the body keeps the cells of the suspended iteration
+        let cells = Self::iteration_cells(&for_stmt.body, None, ctx);
+        if !cells.is_empty() {
+            insn.local_get(generator.resuming);
+            insn.i64_eqz();
+            insn.if_(BlockType::Empty);
+            self.emit_fresh_cells(&cells, insn, ctx);
+            insn.end();
+        }
+        self.compile_block(&for_stmt.body, insn, ctx)?;
+        if let Some(step) = &for_stmt.step {
+            self.compile_stmt(step, insn, ctx)?;
+        }
+        insn.br(ctx.block_depth - continue_depth);
+
+        insn.end();
+        ctx.block_depth -= 1;
+        insn.end();
+        ctx.block_depth -= 1;
+        ctx.loop_depth_stack.pop();
+        Ok(())
+    }
+
+    /// `for (x in iterable)` holding yields: the iterable and its index live in
+    /// the frame like any local, and are not advanced when resuming into the body.
+    fn compile_gen_for_in(
+        &self,
+        for_in: &ForInStmt,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let generator = ctx.generator.expect("generator for-in");
+        insn.local_get(generator.resuming);
+        insn.i64_eqz();
+        insn.if_(BlockType::Empty);
+        ctx.block_depth += 1;
+        let head = self.compile_for_in_head(for_in, insn, ctx)?;
+        insn.end();
+        ctx.block_depth -= 1;
+
+        insn.block(BlockType::Empty);
+        ctx.block_depth += 1;
+        let break_depth = ctx.block_depth;
+        insn.loop_(BlockType::Empty);
+        ctx.block_depth += 1;
+        let continue_depth = ctx.block_depth;
+        ctx.loop_depth_stack.push(LoopInfo {
+            break_depth,
+            continue_depth,
+            defer_depth: ctx.defers.len(),
+        });
+
+        insn.local_get(generator.resuming);
+        insn.i64_eqz();
+        insn.if_(BlockType::Empty);
+        ctx.block_depth += 1;
+        self.compile_for_in_next(for_in, head, break_depth, insn, ctx);
+        let cells = Self::iteration_cells(&for_in.body, Some(&for_in.var), ctx);
+        self.emit_fresh_cells(&cells, insn, ctx);
+        insn.end();
+        ctx.block_depth -= 1;
+
+        self.compile_block(&for_in.body, insn, ctx)?;
+        insn.br(ctx.block_depth - continue_depth);
+
+        insn.end();
+        ctx.block_depth -= 1;
+        insn.end();
+        ctx.block_depth -= 1;
+        ctx.loop_depth_stack.pop();
+        Ok(())
+    }
+
+    /// `resume(co)` or `is_done(co)`, unless a function or variable takes the name.
+    pub(super) fn coroutine_builtin(&self, call: &CallFunc, ctx: &FuncCtx) -> Option<CoroutineBuiltin> {
+        if call.args.len() != 1 || self.builtin_shadowed(&call.name, ctx) {
+            return None;
+        }
+        match call.name.as_str() {
+            "resume" => Some(CoroutineBuiltin::Resume),
+            "is_done" => Some(CoroutineBuiltin::IsDone),
+            _ => None,
+        }
+    }
+
+    /// Result type of a coroutine builtin: the yielded type for `resume`.
+    pub(super) fn coroutine_result_type(&self, builtin: CoroutineBuiltin, call: &CallFunc, ctx: &FuncCtx) -> AhType {
+        match builtin {
+            CoroutineBuiltin::Resume => self.coroutine_value_type(self.infer_expr_type(&call.args[0], ctx)),
+            CoroutineBuiltin::IsDone => AhType::Int,
+        }
+    }
+
+    pub(super) fn compile_coroutine_builtin(
+        &self,
+        builtin: CoroutineBuiltin,
+        call: &CallFunc,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let arg_ty = self.infer_expr_type(&call.args[0], ctx);
+        let helper = match builtin {
+            CoroutineBuiltin::Resume => "__resume",
+            CoroutineBuiltin::IsDone => "__is_done",
+        };
+        let Some(&(func_idx, _)) = self.func_map.get(helper).filter(|_| matches!(arg_ty, AhType::Coroutine(_)) || self.is_unknown(&call.args[0], arg_ty, ctx)) else {
+            return Err(codegen_err(
+                format!("'{}' needs a coroutine, found {}", call.name, self.type_display(arg_ty)),
+                &call.span,
+            ));
+        };
+        self.compile_expr(&call.args[0], insn, ctx)?;
+        insn.call(func_idx);
+        self.emit_error_check(insn, ctx);
+        Ok(())
+    }
+}
@@ -0,0 +1,913 @@
+use super::*;
+
+/// Higher-order collection builtins; each takes the collection as its first argument.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum HofBuiltin {
+    /// `map(xs, f)` — a new collection of the same kind holding `f(x)` for each element
+    Map,
+    /// `filter(xs, f)` — a new collection of the elements for which `f(x)` holds
+    Filter,
+    /// `reduce(xs, init, f)` — fold the elements with `acc = f(acc, x)`
+    Reduce,
+    /// `any(xs, f)` — 1 if `f(x)` holds for some element, stopping at the first
+    Any,
+    /// `all(xs, f)` — 1 if `f(x)` holds for every element, stopping at the first miss
+    All,
+    /// `sort_by(xs, f)` — the elements ordered by the int or float key `f(x)`; stable
+    SortBy,
+    /// `zip(xs, ys)` — list of `{first, second}` tables, as long as the shorter input
+    Zip,
+    /// `enumerate(xs)` — list of `{index, value}` tables
+    Enumerate,
+}
+
+impl HofBuiltin {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "map" => Some(HofBuiltin::Map),
+            "filter" => Some(HofBuiltin::Filter),
+            "reduce" => Some(HofBuiltin::Reduce),
+            "any" => Some(HofBuiltin::Any),
+            "all" => Some(HofBuiltin::All),
+            "sort_by" => Some(HofBuiltin::SortBy),
+            "zip" => Some(HofBuiltin::Zip),
+            "enumerate" => Some(HofBuiltin::Enumerate),
+            _ => None,
+        }
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            HofBuiltin::Enumerate => 1,
+            HofBuiltin::Reduce => 3,
+            _ => 2,
+        }
+    }
+}
+
+/// Builtins that need a group of temp locals (pre-allocated by prescan).
+pub(super) fn needs_hof_temps(name: &str) -> bool {
+    HofBuiltin::from_name(name).is_some()
+}
+
+/// Keys of the tables built by `zip` and `enumerate`, interned by `collect_strings`.
+pub(super) fn pair_keys(name: &str) -> &'static [&'static str] {
+    match name {
+        "zip" => &["first", "second"],
+        "enumerate" => &["index", "value"],
+        _ => &[],
+    }
+}
+
+/// Kind of collection walked by a higher-order builtin.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Vec,
+    List,
+    Table,
+}
+
+/// How the function argument of a higher-order builtin is called.
+enum Callback<'a> {
+    /// A closure literal with an expression body, compiled in place with its
+    /// parameters bound to the temps holding the arguments
+    Inline(&'a ClosureExpr, &'a Expr),
+    /// Any other function value, held in the callee temp and called through the
+    /// function table; the usize is its parameter count
+    Value(usize),
+}
+
+const ELEMENT_ARG: MemArg = MemArg {
+    offset: 0,
+    align: 3,
+    memory_index: 0,
+};
+
+impl WasmCodegen {
+    /// Look up a higher-order builtin by name.
+    pub(super) fn hof_builtin(&self, name: &str, ctx: &FuncCtx) -> Option<HofBuiltin> {
+        if self.builtin_shadowed(name, ctx) {
+            return None;
+        }
+        HofBuiltin::from_name(name)
+    }
+
+    /// Result type of a higher-order builtin.
+    pub(super) fn hof_result_type(&self, builtin: HofBuiltin, call: &CallFunc, ctx: &FuncCtx) -> AhType {
+        let source_ty = call.args.first().map_or(AhType::Int, |arg| self.infer_expr_type(arg, ctx));
+        let built = self.table_type_span_map.get(&call.span).copied();
+        match builtin {
+            HofBuiltin::Map => match source_ty {
+                AhType::Vec => AhType::Vec,
+                AhType::Table(id) => AhType::Table(built.unwrap_or(id)),
+                _ => AhType::List(built.unwrap_or(ANY_LIST_TYPE)),
+            },
+            HofBuiltin::Filter | HofBuiltin::SortBy => source_ty,
+            HofBuiltin::Reduce => call.args.get(1).map_or(AhType::Int, |init| {
+                reduce_acc_type(self.infer_expr_type(init, ctx), self.hof_element_type(source_ty))
+            }),
+            HofBuiltin::Any | HofBuiltin::All => AhType::Int,
+            HofBuiltin::Zip | HofBuiltin::Enumerate => AhType::List(built.unwrap_or(ANY_LIST_TYPE)),
+        }
+    }
+
+    /// Fill in the types of the collections built by `map`, `zip` and `enumerate`,
+    /// registered per call by `collect_table_types`.
+    pub(super) fn refine_hof_call(&mut self, builtin: HofBuiltin, call: &CallFunc, ctx: &FuncCtx) {
+        let Some(&id) = self.table_type_span_map.get(&call.span) else {
+            return;
+        };
+        let Some(source) = call.args.first() else {
+            return;
+        };
+        let source_ty = self.infer_expr_type(source, ctx);
+        let element = self.hof_element_type(source_ty);
+        let (pair, fields) = match builtin {
+            HofBuiltin::Map => {
+                let Ok((_, ret)) = self.hof_callback(call, 1, &[element], ctx) else {
+                    return;
+                };
+                let info = match source_ty {
+                    AhType::Table(source_id) => {
+                        let source_info = &self.table_types[source_id as usize];
+                        TableTypeInfo {
+                            fields: source_info.fields.keys().map(|key| (key.clone(), ret)).collect(),
+                            elements: source_info.elements.map(|_| ret),
+                        }
+                    }
+                    _ => TableTypeInfo {
+                        fields: HashMap::new(),
+                        elements: Some(ret),
+                    },
+                };
+                self.table_types[id as usize] = info;
+                return;
+            }
+            HofBuiltin::Zip => {
+                let second = call
+                    .args
+                    .get(1)
+                    .map_or(AhType::Int, |arg| self.hof_element_type(self.infer_expr_type(arg, ctx)));
+                (id + 1, [("first", element), ("second", second)])
+            }
+            HofBuiltin::Enumerate => (id + 1, [("index", AhType::Int), ("value", element)]),
+            _ => return,
+        };
+        self.table_types[pair as usize].fields = fields.iter().map(|&(key, ty)| (key.to_string(), ty)).collect();
+        self.table_types[id as usize].elements = Some(AhType::Table(pair));
+    }
+
+    /// Type of the elements a higher-order builtin hands to its function: the
+    /// values of a table, the elements of a list or vec. Unknown types fall back to int.
+    fn hof_element_type(&self, ty: AhType) -> AhType {
+        match ty {
+            AhType::Vec => AhType::Float,
+            AhType::Table(_) => self.table_value_type(ty).unwrap_or(AhType::Int),
+            _ => self.element_type(ty),
+        }
+    }
+
+    /// Kind and element type of collection argument `position`.
+    fn hof_source(&self, call: &CallFunc, position: usize, ctx: &FuncCtx) -> Result<(Source, AhType), CodegenError> {
+        let ty = self.infer_expr_type(&call.args[position], ctx);
+        let tables = matches!(
+            HofBuiltin::from_name(&call.name),
+            Some(HofBuiltin::Map | HofBuiltin::Filter | HofBuiltin::Reduce | HofBuiltin::Any | HofBuiltin::All)
+        );
+        let source = match ty {
+            AhType::Vec => Source::Vec,
+            AhType::List(_) => Source::List,
+            _ if self.is_unknown(&call.args[position], ty, ctx) => Source::List,
+            AhType::Table(_) if tables => Source::Table,
+            _ => {
+                let expected = if tables { "a vec, list or table" } else { "a vec or list" };
+                return Err(codegen_err(
+                    format!(
+                        "argument {} of '{}' must be {}, found {}",
+                        position + 1,
+                        call.name,
+                        expected,
+                        self.type_display(ty)
+                    ),
+                    &call.span,
+                ));
+            }
+        };
+        Ok((source, self.hof_element_type(ty)))
+    }
+
+    /// Check the function argument `position` of a call against the types it is
+    /// given, and return how to call it along with its result type.
+    fn hof_callback<'a>(
+        &self,
+        call: &'a CallFunc,
+        position: usize,
+        given: &[AhType],
+        ctx: &FuncCtx,
+    ) -> Result<(Callback<'a>, AhType), CodegenError> {
+        let arg = &call.args[position];
+        let arity_err = |arity: usize| {
+            codegen_err(
+                format!(
+                    "the function passed to '{}' must take {} parameter(s), but takes {}",
+                    call.name,
+                    given.len(),
+                    arity
+                ),
+                &call.span,
+            )
+        };
+
+        if let Expr::Closure(closure) = arg {
+            if closure.params.len() != given.len() {
+                return Err(arity_err(closure.params.len()));
+            }
+            let bound = self.closure_param_types(call, closure, given)?;
+            let mut bound_ctx = FuncCtx::new_with_var_types(ctx.var_types.clone());
+            bound_ctx.locals = ctx.locals.clone();
+            for (param, &ty) in closure.params.iter().zip(&bound) {
+                bound_ctx.declare_local(&param.name);
+                bound_ctx.var_types.insert(param.name.clone(), ty);
+            }
+            return Ok(match &closure.body {
+                ClosureBody::Expr(body) => (Callback::Inline(closure, body), self.infer_expr_type(body, &bound_ctx)),
+                ClosureBody::Block(block) => (
+                    Callback::Value(given.len()),
+                    Self::infer_block_return_type(block, self, &bound_ctx),
+                ),
+            });
+        }
+
+        let ty = self.infer_expr_type(arg, ctx);
+        let Some((arity, ret)) = self.callable_signature(ty) else {
+            return Err(codegen_err(
+                format!(
+                    "argument {} of '{}' must be a function, found {}",
+                    position + 1,
+                    call.name,
+                    self.type_display(ty)
+                ),
+                &call.span,
+            ));
+        };
+        if arity != given.len() {
+            return Err(arity_err(arity));
+        }
+        let params = match ty {
+            AhType::Closure(id) => self.closures[id as usize].param_types.clone(),
+            AhType::Func(id) => self.func_types.borrow()[id as usize].params.clone(),
+            _ => unreachable!("callable types are closures and function types"),
+        };
+        for (i, (&expected, &found)) in params.iter().zip(given).enumerate() {
+            self.check_hof_param(call, i, expected, found, matches!(ty, AhType::Closure(_)))?;
+        }
+        Ok((Callback::Value(arity), ret))
+    }
+
+    /// Parameter types of a closure literal passed to a higher-order builtin:
+    /// the annotated types, or else the types of the arguments it is given.
+    fn closure_param_types(&self, call: &CallFunc, closure: &ClosureExpr, given: &[AhType]) -> Result<Vec<AhType>, CodegenError> {
+        let mut types = Vec::with_capacity(given.len());
+        for (i, (param, &found)) in closure.params.iter().zip(given).enumerate() {
+            match &param.type_name {
+                Some(type_name) => {
+                    let expected = self.resolve_type(type_name);
+                    self.check_hof_param(call, i, expected, found, true)?;
+                    types.push(expected);
+                }
+                None => types.push(found),
+            }
+        }
+        Ok(types)
+    }
+
+    fn check_hof_param(
+        &self,
+        call: &CallFunc,
+        position: usize,
+        expected: AhType,
+        found: AhType,
+        closure: bool,
+    ) -> Result<(), CodegenError> {
+        // An int element may be of unknown type, but never passes for a float
+        // parameter: the bits would be misread
+        if expected == found || (found == AhType::Int && expected != AhType::Float) {
+            return Ok(());
+        }
+        let hint = if closure {
+            format!(" (annotate it, e.g. |x: {}|)", self.type_display(found))
+        } else {
+            String::new()
+        };
+        Err(codegen_err(
+            format!(
+                "the function passed to '{}' takes {} as parameter {}, but is given {}{}",
+                call.name,
+                self.type_display(expected),
+                position + 1,
+                self.type_display(found),
+                hint
+            ),
+            &call.span,
+        ))
+    }
+
+    /// Compile a call to a higher-order builtin, leaving its result on the stack.
+    pub(super) fn compile_hof_builtin(
+        &self,
+        builtin: HofBuiltin,
+        call: &CallFunc,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        if call.args.len() != builtin.arity() {
+            return Err(codegen_err(
+                format!(
+                    "function '{}' expects {} argument(s), but {} were given",
+                    call.name,
+                    builtin.arity(),
+                    call.args.len()
+                ),
+                &call.span,
+            ));
+        }
+        if matches!(builtin, HofBuiltin::Zip | HofBuiltin::Enumerate) {
+            return self.compile_pairs(builtin, call, insn, ctx);
+        }
+
+        let (source, element) = self.hof_source(call, 0, ctx)?;
+        let acc_ty = match builtin {
+            HofBuiltin::Reduce => reduce_acc_type(self.infer_expr_type(&call.args[1], ctx), element),
+            _ => AhType::Int,
+        };
+        let given: &[AhType] = match builtin {
+            HofBuiltin::Reduce => &[acc_ty, element],
+            _ => &[element],
+        };
+        let fn_position = call.args.len() - 1;
+        let (callback, ret) = self.hof_callback(call, fn_position, given, ctx)?;
+        self.check_hof_result(builtin, call, source, ret, acc_ty)?;
+
+        let t = ctx.claim_hof_temps();
+        self.compile_expr(&call.args[0], insn, ctx)?;
+        insn.local_set(t.source);
+        self.prepare_callback(&callback, &call.args[fn_position], given, t, insn, ctx)?;
+        if builtin == HofBuiltin::Reduce {
+            if acc_ty == AhType::Float {
+                self.emit_float_operand(&call.args[1], insn, ctx)?;
+                insn.i64_reinterpret_f64();
+            } else {
+                self.compile_expr(&call.args[1], insn, ctx)?;
+            }
+            insn.local_set(t.acc);
+        }
+
+        // Length, and the key list of a table
+        match source {
+            Source::Vec => {
+                insn.local_get(t.source);
+                insn.i64_const(0xFFFF_FFFF);
+                insn.i64_and();
+            }
+            Source::List => {
+                insn.local_get(t.source);
+                insn.call(self.func_map["__env_list_len"].0);
+            }
+            Source::Table => {
+                insn.local_get(t.source);
+                insn.call(self.func_map["__env_table_keys"].0);
+                insn.local_tee(t.other);
+                insn.call(self.func_map["__env_list_len"].0);
+            }
+        }
+        insn.local_set(t.len);
+
+        // The result, before any element is seen
+        match (builtin, source) {
+            (HofBuiltin::Map | HofBuiltin::Filter, Source::Vec) => {
+                emit_reserve(t.result, t.len, insn);
+                insn.i64_const(0);
+                insn.local_set(t.acc);
+            }
+            (HofBuiltin::Map | HofBuiltin::Filter, Source::List) => {
+                insn.call(self.func_map["__env_list_new"].0);
+                insn.local_set(t.result);
+            }
+            (HofBuiltin::Map | HofBuiltin::Filter, Source::Table) => {
+                insn.call(self.func_map["__env_table_new"].0);
+                insn.local_set(t.result);
+            }
+            (HofBuiltin::Any, _) => {
+                insn.i64_const(0);
+                insn.local_set(t.result);
+            }
+            (HofBuiltin::All, _) => {
+                insn.i64_const(1);
+                insn.local_set(t.result);
+            }
+            (HofBuiltin::SortBy, _) => emit_reserve(t.acc, t.len, insn),
+            _ => {}
+        }
+
+        self.begin_hof_loop(t, insn, ctx);
+        self.emit_hof_element(source, t.source, t, insn);
+        insn.local_set(t.item);
+        let args: &[(u32, AhType)] = match builtin {
+            HofBuiltin::Reduce => &[(t.acc, acc_ty), (t.item, element)],
+            _ => &[(t.item, element)],
+        };
+        match builtin {
+            HofBuiltin::Map => self.emit_map_step(&callback, args, source, ret, t, insn, ctx)?,
+            HofBuiltin::Filter => {
+                self.emit_callback(&callback, args, t, insn, ctx)?;
+                insn.i64_const(0);
+                insn.i64_ne();
+                insn.if_(BlockType::Empty);
+                ctx.block_depth += 1;
+                self.emit_keep_element(source, t, insn);
+                insn.end();
+                ctx.block_depth -= 1;
+            }
+            HofBuiltin::Reduce => {
+                self.emit_callback(&callback, args, t, insn, ctx)?;
+                if acc_ty == AhType::Float && ret == AhType::Int {
+                    insn.f64_convert_i64_s();
+                    insn.i64_reinterpret_f64();
+                }
+                insn.local_set(t.acc);
+            }
+            HofBuiltin::Any | HofBuiltin::All => {
+                // Keep the answer so far and leave once it is settled
+                self.emit_callback(&callback, args, t, insn, ctx)?;
+                insn.i64_const(0);
+                insn.i64_ne();
+                insn.i64_extend_i32_u();
+                insn.local_tee(t.result);
+                if builtin == HofBuiltin::All {
+                    insn.i64_eqz();
+                } else {
+                    insn.i32_wrap_i64();
+                }
+                insn.br_if(1);
+            }
+            HofBuiltin::SortBy => {
+                emit_slot_address(t.acc, t.index, insn);
+                self.emit_callback(&callback, args, t, insn, ctx)?;
+                insn.i64_store(ELEMENT_ARG);
+            }
+            HofBuiltin::Zip | HofBuiltin::Enumerate => unreachable!("built by compile_pairs"),
+        }
+        self.end_hof_loop(t, insn, ctx);
+
+        match builtin {
+            HofBuiltin::Map | HofBuiltin::Filter => {
+                if source == Source::Table {
+                    insn.local_get(t.other);
+                    insn.call(self.func_map["__env_table_free"].0);
+                }
+                insn.local_get(t.result);
+                if source == Source::Vec {
+                    // Packed as (ptr << 32) | len, with the kept count for filter
+                    insn.i64_const(32);
+                    insn.i64_shl();
+                    insn.local_get(if builtin == HofBuiltin::Map { t.len } else { t.acc });
+                    insn.i64_or();
+                }
+            }
+            HofBuiltin::Reduce | HofBuiltin::Any | HofBuiltin::All => {
+                if source == Source::Table {
+                    insn.local_get(t.other);
+                    insn.call(self.func_map["__env_table_free"].0);
+                }
+                insn.local_get(if builtin == HofBuiltin::Reduce { t.acc } else { t.result });
+            }
+            HofBuiltin::SortBy => self.emit_sorted(source, ret, t, insn, ctx),
+            HofBuiltin::Zip | HofBuiltin::Enumerate => unreachable!("built by compile_pairs"),
+        }
+        Ok(())
+    }
+
+    /// Check what the function of a higher-order builtin returns.
+    fn check_hof_result(
+        &self,
+        builtin: HofBuiltin,
+        call: &CallFunc,
+        source: Source,
+        ret: AhType,
+        acc_ty: AhType,
+    ) -> Result<(), CodegenError> {
+        let (fits, expected) = match builtin {
+            HofBuiltin::Map if source == Source::Vec => (matches!(ret, AhType::Int | AhType::Float), "int or float".into()),
+            HofBuiltin::Filter | HofBuiltin::Any | HofBuiltin::All => (ret == AhType::Int, "a bool".into()),
+            HofBuiltin::SortBy => (matches!(ret, AhType::Int | AhType::Float), "an int or float key".into()),
+            HofBuiltin::Reduce => (
+                ret == acc_ty || (acc_ty == AhType::Float && ret == AhType::Int),
+                self.type_display(acc_ty),
+            ),
+            _ => (true, Cow::Borrowed("")),
+        };
+        if fits {
+            return Ok(());
+        }
+        Err(codegen_err(
+            format!(
+                "the function passed to '{}' must return {}, found {}",
+                call.name,
+                expected,
+                self.type_display(ret)
+            ),
+            &call.span,
+        ))
+    }
+
+    /// Set up the function argument: record the types of a closure literal, and
+    /// evaluate a function value into the callee temp.
+    fn prepare_callback(
+        &self,
+        callback: &Callback<'_>,
+        arg: &Expr,
+        given: &[AhType],
+        t: HofTemps,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        if let Expr::Closure(closure) = arg {
+            let closure_id = self.closure_span_map[&closure.span];
+            let types = closure
+                .params
+                .iter()
+                .zip(given)
+                .map(|(param, &found)| param.type_name.as_deref().map_or(found, |tn| self.resolve_type(tn)))
+                .collect();
+            self.hof_param_types.borrow_mut().insert(closure_id, types);
+            if matches!(callback, Callback::Inline(..)) {
+                // The closure function itself is never called, but is still compiled
+                self.record_capture_types(closure_id, ctx);
+            }
+        }
+        if let Callback::Value(_) = callback {
+            self.compile_expr(arg, insn, ctx)?;
+            insn.local_set(t.callee);
+        }
+        Ok(())
+    }
+
+    /// Call the function argument with `args` (temps and their types), leaving
+    /// its result on the stack.
+    fn emit_callback(
+        &self,
+        callback: &Callback<'_>,
+        args: &[(u32, AhType)],
+        t: HofTemps,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        match *callback {
+            Callback::Inline(closure, body) => {
+                // Bind the parameters to the argument temps for the body, then put
+                // back whatever the names meant outside it
+                let saved: Vec<_> = closure
+                    .params
+                    .iter()
+                    .map(|param| {
+                        (
+                            ctx.locals.get(&param.name).copied(),
+                            ctx.var_types.get(&param.name).copied(),
+                            ctx.boxed.remove(&param.name),
+                        )
+                    })
+                    .collect();
+                for (param, &(local, found)) in closure.params.iter().zip(args) {
+                    let ty = param.type_name.as_deref().map_or(found, |tn| self.resolve_type(tn));
+                    ctx.locals.insert(param.name.clone(), local);
+                    ctx.var_types.insert(param.name.clone(), ty);
+                }
+                let result = self.compile_closure_body_expr(body, insn, ctx);
+                for (param, (local, ty, boxed)) in closure.params.iter().zip(saved) {
+                    match local {
+                        Some(local) => ctx.locals.insert(param.name.clone(), local),
+                        None => ctx.locals.remove(&param.name),
+                    };
+                    match ty {
+                        Some(ty) => ctx.var_types.insert(param.name.clone(), ty),
+                        None => ctx.var_types.remove(&param.name),
+                    };
+                    if boxed {
+                        ctx.boxed.insert(param.name.clone());
+                    }
+                }
+                result
+            }
+            Callback::Value(arity) => {
+                insn.local_get(t.callee);
+                insn.i32_wrap_i64();
+                for &(local, _) in args {
+                    insn.local_get(local);
+                }
+                insn.local_get(t.callee);
+                insn.i64_const(32);
+                insn.i64_shr_u();
+                insn.i32_wrap_i64();
+                let type_idx = self
+                    .callable_type_idx(arity)
+                    .expect("callable values register their call type");
+                insn.call_indirect(0, type_idx);
+                self.emit_error_check(insn, ctx);
+                Ok(())
+            }
+        }
+    }
+
+    /// Store `f(item)` as element `index` of the result of `map`.
+    #[allow(clippy::too_many_arguments)]
+    fn emit_map_step(
+        &self,
+        callback: &Callback<'_>,
+        args: &[(u32, AhType)],
+        source: Source,
+        ret: AhType,
+        t: HofTemps,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        // A table or list made by the function belongs to the result
+        let owned = matches!(ret, AhType::Table(_) | AhType::List(_))
+            && match callback {
+                Callback::Inline(_, body) => self.owns_value(body, ctx),
+                Callback::Value(_) => true,
+            };
+        match source {
+            Source::Vec => {
+                emit_slot_address(t.result, t.index, insn);
+                self.emit_callback(callback, args, t, insn, ctx)?;
+                if ret == AhType::Int {
+                    insn.f64_convert_i64_s();
+                    insn.i64_reinterpret_f64();
+                }
+                insn.i64_store(ELEMENT_ARG);
+            }
+            Source::List => {
+                insn.local_get(t.result);
+                self.emit_callback(callback, args, t, insn, ctx)?;
+                let mut tag = self.element_tag(ret);
+                if owned {
+                    tag |= compile_list::TAG_OWNED;
+                }
+                insn.i64_const(tag);
+                insn.call(self.func_map["__env_list_push"].0);
+                insn.drop();
+            }
+            Source::Table => {
+                insn.local_get(t.result);
+                self.emit_table_key(t, insn);
+                self.emit_callback(callback, args, t, insn, ctx)?;
+                let set = if owned { "__env_table_set_table" } else { "__env_table_set" };
+                insn.call(self.func_map[set].0);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the current element to the result of `filter`. Tables and lists
+    /// stored in the source are shared, not moved.
+    fn emit_keep_element(&self, source: Source, t: HofTemps, insn: &mut wasm_encoder::InstructionSink<'_>) {
+        match source {
+            Source::Vec => {
+                emit_slot_address(t.result, t.acc, insn);
+                insn.local_get(t.item);
+                insn.i64_store(ELEMENT_ARG);
+                insn.local_get(t.acc);
+                insn.i64_const(1);
+                insn.i64_add();
+                insn.local_set(t.acc);
+            }
+            Source::List => {
+                insn.local_get(t.result);
+                insn.local_get(t.source);
+                insn.local_get(t.index);
+                insn.call(self.func_map["__env_list_pick"].0);
+                insn.drop();
+            }
+            Source::Table => {
+                insn.local_get(t.result);
+                self.emit_table_key(t, insn);
+                insn.local_get(t.item);
+                insn.call(self.func_map["__env_table_set"].0);
+            }
+        }
+    }
+
+    /// Build the result of `sort_by` once the keys are stored: the host turns
+    /// them into the sorted order of the indices, which is then gathered.
+    fn emit_sorted(
+        &self,
+        source: Source,
+        key_ty: AhType,
+        t: HofTemps,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) {
+        insn.local_get(t.acc);
+        insn.local_get(t.len);
+        insn.i64_const(i64::from(key_ty == AhType::Float));
+        insn.call(self.func_map["__env_sort_order"].0);
+
+        if source == Source::Vec {
+            emit_reserve(t.result, t.len, insn);
+        } else {
+            insn.call(self.func_map["__env_list_new"].0);
+            insn.local_set(t.result);
+        }
+        self.begin_hof_loop(t, insn, ctx);
+        // item = order[index]
+        emit_slot_address(t.acc, t.index, insn);
+        insn.i64_load(ELEMENT_ARG);
+        insn.local_set(t.item);
+        if source == Source::Vec {
+            emit_slot_address(t.result, t.index, insn);
+            self.emit_hof_element(Source::Vec, t.source, HofTemps { index: t.item, ..t }, insn);
+            insn.i64_store(ELEMENT_ARG);
+        } else {
+            insn.local_get(t.result);
+            insn.local_get(t.source);
+            insn.local_get(t.item);
+            insn.call(self.func_map["__env_list_pick"].0);
+            insn.drop();
+        }
+        self.end_hof_loop(t, insn, ctx);
+
+        insn.local_get(t.result);
+        if source == Source::Vec {
+            insn.i64_const(32);
+            insn.i64_shl();
+            insn.local_get(t.len);
+            insn.i64_or();
+        }
+    }
+
+    /// Compile `zip(xs, ys)` or `enumerate(xs)`: a list of two-field tables.
+    fn compile_pairs(
+        &self,
+        builtin: HofBuiltin,
+        call: &CallFunc,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let (first, _) = self.hof_source(call, 0, ctx)?;
+        let second = match builtin {
+            HofBuiltin::Zip => Some(self.hof_source(call, 1, ctx)?.0),
+            _ => None,
+        };
+
+        let t = ctx.claim_hof_temps();
+        let (list_len, _) = self.func_map["__env_list_len"];
+        for (i, source) in [Some(first), second].into_iter().enumerate() {
+            let Some(source) = source else {
+                continue;
+            };
+            let items = if i == 0 { t.source } else { t.other };
+            let len = if i == 0 { t.len } else { t.item };
+            self.compile_expr(&call.args[i], insn, ctx)?;
+            insn.local_tee(items);
+            if source == Source::Vec {
+                insn.i64_const(0xFFFF_FFFF);
+                insn.i64_and();
+            } else {
+                insn.call(list_len);
+            }
+            insn.local_set(len);
+        }
+        if second.is_some() {
+            // As long as the shorter input
+            insn.local_get(t.len);
+            insn.local_get(t.item);
+            insn.local_get(t.len);
+            insn.local_get(t.item);
+            insn.i64_lt_s();
+            insn.select();
+            insn.local_set(t.len);
+        }
+        insn.call(self.func_map["__env_list_new"].0);
+        insn.local_set(t.result);
+
+        let keys = pair_keys(&call.name);
+        let (table_set, _) = self.func_map["__env_table_set"];
+        self.begin_hof_loop(t, insn, ctx);
+        insn.call(self.func_map["__env_table_new"].0);
+        insn.local_set(t.acc);
+        for (i, key) in keys.iter().enumerate() {
+            let (offset, len) = self.string_pool.get(*key).copied().unwrap_or((0, 0));
+            insn.local_get(t.acc);
+            insn.i64_const((i64::from(offset) << 32) | i64::from(len));
+            match (builtin, i) {
+                (HofBuiltin::Enumerate, 0) => {
+                    insn.local_get(t.index);
+                }
+                (HofBuiltin::Enumerate, _) | (_, 0) => self.emit_hof_element(first, t.source, t, insn),
+                _ => self.emit_hof_element(second.unwrap_or(first), t.other, t, insn),
+            }
+            insn.call(table_set);
+        }
+        insn.local_get(t.result);
+        insn.local_get(t.acc);
+        insn.i64_const(compile_list::TAG_TABLE | compile_list::TAG_OWNED);
+        insn.call(self.func_map["__env_list_push"].0);
+        insn.drop();
+        self.end_hof_loop(t, insn, ctx);
+
+        insn.local_get(t.result);
+        Ok(())
+    }
+
+    /// Push element `t.index` of the vec or list in `items`, or the value of
+    /// the table in `t.source` under the key `t.index` of `t.other`.
+    fn emit_hof_element(&self, source: Source, items: u32, t: HofTemps, insn: &mut wasm_encoder::InstructionSink<'_>) {
+        match source {
+            Source::Vec => {
+                insn.local_get(items);
+                insn.i64_const(32);
+                insn.i64_shr_u();
+                insn.local_get(t.index);
+                insn.i64_const(3);
+                insn.i64_shl();
+                insn.i64_add();
+                insn.i32_wrap_i64();
+                insn.i64_load(ELEMENT_ARG);
+            }
+            Source::List => {
+                insn.local_get(items);
+                insn.local_get(t.index);
+                insn.call(self.func_map["__env_list_get"].0);
+            }
+            Source::Table => {
+                insn.local_get(t.source);
+                self.emit_table_key(t, insn);
+                insn.call(self.func_map["__env_table_get"].0);
+            }
+        }
+    }
+
+    /// Push the key of the current entry of a table walk.
+    fn emit_table_key(&self, t: HofTemps, insn: &mut wasm_encoder::InstructionSink<'_>) {
+        insn.local_get(t.other);
+        insn.local_get(t.index);
+        insn.call(self.func_map["__env_list_get"].0);
+    }
+
+    /// Open `for (index = 0; index < len; index++)`; `br 1` in the body leaves it.
+    fn begin_hof_loop(&self, t: HofTemps, insn: &mut wasm_encoder::InstructionSink<'_>, ctx: &mut FuncCtx) {
+        insn.i64_const(0);
+        insn.local_set(t.index);
+        insn.block(BlockType::Empty);
+        insn.loop_(BlockType::Empty);
+        ctx.block_depth += 2;
+        insn.local_get(t.index);
+        insn.local_get(t.len);
+        insn.i64_ge_s();
+        insn.br_if(1);
+    }
+
+    fn end_hof_loop(&self, t: HofTemps, insn: &mut wasm_encoder::InstructionSink<'_>, ctx: &mut FuncCtx) {
+        insn.local_get(t.index);
+        insn.i64_const(1);
+        insn.i64_add();
+        insn.local_set(t.index);
+        insn.br(0);
+        insn.end();
+        insn.end();
+        ctx.block_depth -= 2;
+    }
+}
+
+/// Type of the accumulator of `reduce`: the initial value's, with an int
+/// widened to float when the elements are floats.
+fn reduce_acc_type(init: AhType, element: AhType) -> AhType {
+    if init == AhType::Int && element == AhType::Float {
+        AhType::Float
+    } else {
+        init
+    }
+}
+
+/// Reserve `len` 8-byte slots on the heap; `base` receives their address.
+fn emit_reserve(base: u32, len: u32, insn: &mut wasm_encoder::InstructionSink<'_>) {
+    insn.global_get(0);
+    insn.i64_extend_i32_u();
+    insn.local_tee(base);
+    insn.local_get(len);
+    insn.i64_const(3);
+    insn.i64_shl();
+    insn.i64_add();
+    insn.i32_wrap_i64();
+    insn.global_set(0);
+}
+
+/// Push the i32 address of slot `index` of the array at `base`.
+fn emit_slot_address(base: u32, index: u32, insn: &mut wasm_encoder::InstructionSink<'_>) {
+    insn.local_get(base);
+    insn.local_get(index);
+    insn.i64_const(3);
+    insn.i64_shl();
+    insn.i64_add();
+    insn.i32_wrap_i64();
+}
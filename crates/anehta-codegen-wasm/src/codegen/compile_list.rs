@@ -0,0 +1,435 @@
+use super::*;
+
+/// List library builtins; each takes the list as its first argument.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum ListBuiltin {
+    /// `push(xs, x)` — append, returns the list
+    Push,
+    /// `pop(xs)` — remove and return the last element
+    Pop,
+    /// `insert(xs, i, x)` — insert before index i, returns the list
+    Insert,
+    /// `remove(xs, i)` — remove and return the element at index i
+    Remove,
+}
+
+// Element tags understood by env.list_* beyond the formatting tags of compile_fmt
+pub(super) const TAG_TABLE: i64 = 6;
+const TAG_CLOSURE: i64 = 7;
+/// Structs and enums: `TAG_OBJECT + n` is rendered by the formatter exported as `__fmt_<n>`
+const TAG_OBJECT: i64 = 16;
+/// Set on an element's tag when the list takes ownership of the table or list stored
+pub(super) const TAG_OWNED: i64 = 1 << 32;
+
+impl WasmCodegen {
+    /// Look up a list library builtin by name.
+    pub(super) fn list_builtin(&self, name: &str, ctx: &FuncCtx) -> Option<ListBuiltin> {
+        if self.builtin_shadowed(name, ctx) {
+            return None;
+        }
+        match name {
+            "push" => Some(ListBuiltin::Push),
+            "pop" => Some(ListBuiltin::Pop),
+            "insert" => Some(ListBuiltin::Insert),
+            "remove" => Some(ListBuiltin::Remove),
+            _ => None,
+        }
+    }
+
+    /// Result type of a list builtin: the list itself, or one of its elements.
+    pub(super) fn list_builtin_result(&self, builtin: ListBuiltin, call: &CallFunc, ctx: &FuncCtx) -> AhType {
+        let list_ty = call
+            .args
+            .first()
+            .map_or(AhType::Int, |arg| self.infer_expr_type(arg, ctx));
+        match builtin {
+            ListBuiltin::Push | ListBuiltin::Insert => list_ty,
+            ListBuiltin::Pop | ListBuiltin::Remove => self.element_type(list_ty),
+        }
+    }
+
+    /// Static element type of a list, when all its elements share one.
+    pub(super) fn list_elements(&self, list_ty: AhType) -> Option<AhType> {
+        match list_ty {
+            AhType::List(id) => self.table_types.get(id as usize).and_then(|info| info.elements),
+            _ => None,
+        }
+    }
+
+    /// Type of a value read out of a list; unknown element types fall back to int.
+    pub(super) fn element_type(&self, list_ty: AhType) -> AhType {
+        self.list_elements(list_ty).unwrap_or(AhType::Int)
+    }
+
+    /// Type of the loop variable of `for (x in iterable)`.
+    pub(super) fn iteration_type(&self, iterable: &Expr, ctx: &FuncCtx) -> AhType {
+        match self.infer_expr_type(iterable, ctx) {
+            AhType::Vec => AhType::Float,
+            AhType::Str => AhType::Str,
+            list_ty => self.element_type(list_ty),
+        }
+    }
+
+    /// Runtime tag stored next to a list element, so the host can format it.
+    pub(super) fn element_tag(&self, ty: AhType) -> i64 {
+        match ty {
+            AhType::Table(_) => TAG_TABLE,
+            AhType::Closure(_) | AhType::Func(_) | AhType::Coroutine(_) => TAG_CLOSURE,
+            // Formatters are numbered in registration order: structs, then enums
+            AhType::Struct(id) => TAG_OBJECT + i64::from(id),
+            AhType::Enum(id) => TAG_OBJECT + (self.structs.len() + id as usize) as i64,
+            AhType::Optional(_) => self.element_tag(ty.unwrap_optional()) | compile_fmt::TAG_OPTIONAL,
+            _ => compile_fmt::type_tag(ty).expect("value types have a format tag"),
+        }
+    }
+
+    /// Whether a stored table or list is handed over to the list: a literal, a call
+    /// result, a slice or a variable this function owns. Anything else is borrowed.
+    pub(super) fn owns_value(&self, value: &Expr, ctx: &FuncCtx) -> bool {
+        match value {
+            Expr::TableLiteral(_) | Expr::ListLiteral(_) | Expr::MethodCall(_) => true,
+            Expr::CallFunc(call) => match self.list_builtin(&call.name, ctx) {
+                // push and insert return their first argument
+                Some(ListBuiltin::Push | ListBuiltin::Insert) => {
+                    call.args.first().is_some_and(|arg| self.owns_value(arg, ctx))
+                }
+                _ => true,
+            },
+            Expr::IndexAccess(ia) => matches!(*ia.index, Expr::Range { .. }),
+            Expr::Variable(name, _) => {
+                ctx.owned_tables.contains(name) && !ctx.captured_tables.contains(name)
+            }
+            Expr::Grouped(inner) => self.owns_value(inner, ctx),
+            _ => false,
+        }
+    }
+
+    /// Whether a value of type `ty` may be stored in a list of `elements`. Reads
+    /// are typed by the element type, so anything else would be misread.
+    fn element_fits(&self, elements: AhType, ty: AhType) -> bool {
+        match (elements, ty) {
+            (AhType::Table(_), AhType::Table(_)) => true,
+            // A list of unknown elements fits any list of lists
+            (AhType::List(x), AhType::List(y)) => {
+                let inner = self.table_types[y as usize].elements;
+                inner.is_none() || self.table_types[x as usize].elements == inner
+            }
+            (AhType::Closure(_) | AhType::Func(_), AhType::Closure(_) | AhType::Func(_)) => {
+                self.callable_signature(elements) == self.callable_signature(ty)
+            }
+            _ => unify_branch_types(elements, ty) == Some(elements),
+        }
+    }
+
+    /// Compile a value stored in a list, followed by its tag. Ints stored in a
+    /// list of floats are widened so that reads of the list stay uniform.
+    fn compile_element(
+        &self,
+        elements: Option<AhType>,
+        value: &Expr,
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let mut ty = self.infer_expr_type(value, ctx);
+        if let Some(expected) = elements
+            && !self.element_fits(expected, ty)
+        {
+            return Err(codegen_err(
+                format!(
+                    "cannot store {} in a list of {}",
+                    self.type_display(ty),
+                    self.type_display(expected)
+                ),
+                span,
+            ));
+        }
+        if elements == Some(AhType::Float) && ty == AhType::Int {
+            self.emit_float_operand(value, insn, ctx)?;
+            insn.i64_reinterpret_f64();
+            ty = AhType::Float;
+        } else {
+            self.compile_expr(value, insn, ctx)?;
+        }
+        let mut tag = self.element_tag(ty);
+        if matches!(ty, AhType::Table(_) | AhType::List(_)) && self.owns_value(value, ctx) {
+            tag |= TAG_OWNED;
+        }
+        insn.i64_const(tag);
+        Ok(())
+    }
+
+    fn check_list_index(&self, index: &Expr, span: &Span, ctx: &FuncCtx) -> Result<(), CodegenError> {
+        let ty = self.infer_expr_type(index, ctx);
+        if ty != AhType::Int || matches!(index, Expr::BooleanExpr(_)) {
+            return Err(codegen_err(
+                format!("list index must be an int, found {}", self.type_display(ty)),
+                span,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Compile `list[a, b, ...]`: a new host list with each element pushed in order.
+    pub(super) fn compile_list_literal(
+        &self,
+        list: &ListLiteral,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let elements = self
+            .table_type_span_map
+            .get(&list.span)
+            .and_then(|&id| self.table_types[id as usize].elements);
+        if elements.is_none()
+            && let Some((first, rest)) = list.elements.split_first()
+        {
+            let first_ty = self.infer_expr_type(first, ctx);
+            for element in rest {
+                let ty = self.infer_expr_type(element, ctx);
+                if !self.element_fits(first_ty, ty) && !self.element_fits(ty, first_ty) {
+                    return Err(codegen_err(
+                        format!(
+                            "list elements must share one type, found {} and {}",
+                            self.type_display(first_ty),
+                            self.type_display(ty)
+                        ),
+                        &list.span,
+                    ));
+                }
+            }
+        }
+        let (push_idx, _) = self.func_map["__env_list_push"];
+
+        insn.call(self.func_map["__env_list_new"].0);
+        for element in &list.elements {
+            self.compile_element(elements, element, &list.span, insn, ctx)?;
+            insn.call(push_idx);
+        }
+        Ok(())
+    }
+
+    /// Compile a call to a list library builtin, leaving its result on the stack.
+    pub(super) fn compile_list_builtin(
+        &self,
+        builtin: ListBuiltin,
+        call: &CallFunc,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let arity = match builtin {
+            ListBuiltin::Pop => 1,
+            ListBuiltin::Push | ListBuiltin::Remove => 2,
+            ListBuiltin::Insert => 3,
+        };
+        if call.args.len() != arity {
+            return Err(codegen_err(
+                format!(
+                    "function '{}' expects {} argument(s), but {} were given",
+                    call.name, arity, call.args.len()
+                ),
+                &call.span,
+            ));
+        }
+        let list_ty = self.infer_expr_type(&call.args[0], ctx);
+        if !matches!(list_ty, AhType::List(_)) && !self.is_unknown(&call.args[0], list_ty, ctx) {
+            return Err(codegen_err(
+                format!(
+                    "argument 1 of '{}' must be a list, found {}",
+                    call.name,
+                    self.type_display(list_ty)
+                ),
+                &call.span,
+            ));
+        }
+        let elements = self.list_elements(list_ty);
+
+        self.compile_expr(&call.args[0], insn, ctx)?;
+        let import = match builtin {
+            ListBuiltin::Push => {
+                self.compile_element(elements, &call.args[1], &call.span, insn, ctx)?;
+                "__env_list_push"
+            }
+            ListBuiltin::Pop => "__env_list_pop",
+            ListBuiltin::Insert => {
+                self.check_list_index(&call.args[1], &call.span, ctx)?;
+                self.compile_expr(&call.args[1], insn, ctx)?;
+                self.compile_element(elements, &call.args[2], &call.span, insn, ctx)?;
+                "__env_list_insert"
+            }
+            ListBuiltin::Remove => {
+                self.check_list_index(&call.args[1], &call.span, ctx)?;
+                self.compile_expr(&call.args[1], insn, ctx)?;
+                "__env_list_remove"
+            }
+        };
+        self.emit_raising_call(import, insn, ctx);
+        Ok(())
+    }
+
+    /// Compile `xs[i]` or the slice `xs[a..b]` (a new list sharing the elements).
+    pub(super) fn compile_list_index(
+        &self,
+        ia: &IndexAccess,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        self.compile_expr(&ia.object, insn, ctx)?;
+        if let Expr::Range { start, end, .. } = &*ia.index {
+            for bound in [start, end] {
+                match bound {
+                    Some(e) => self.compile_expr(e, insn, ctx)?,
+                    None => {
+                        insn.i64_const(-1);
+                    }
+                }
+            }
+            insn.call(self.func_map["__env_list_slice"].0);
+            return Ok(());
+        }
+        self.check_list_index(&ia.index, &ia.span, ctx)?;
+        self.compile_expr(&ia.index, insn, ctx)?;
+        self.emit_raising_call("__env_list_get", insn, ctx);
+        Ok(())
+    }
+
+    /// Compile `xs[i] = value`; the index must already exist.
+    pub(super) fn compile_list_index_assign(
+        &self,
+        ia: &IndexAssign,
+        list_ty: AhType,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        self.check_list_index(&ia.index, &ia.span, ctx)?;
+        if !ctx.emit_get(&ia.object, insn) {
+            return Err(codegen_err(format!("undefined variable: {}", ia.object), &ia.span));
+        }
+        self.compile_expr(&ia.index, insn, ctx)?;
+        self.compile_element(self.list_elements(list_ty), &ia.value, &ia.span, insn, ctx)?;
+        self.emit_raising_call("__env_list_set", insn, ctx);
+        Ok(())
+    }
+
+    /// Compile `for (x in iterable) { ... }` over a list, a vec or the chars of a str.
+    /// The index is advanced at the top of the loop so that `continue` needs no step.
+    pub(super) fn compile_for_in(
+        &self,
+        for_in: &ForInStmt,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let head = self.compile_for_in_head(for_in, insn, ctx)?;
+
+        // block (for break)
+        insn.block(BlockType::Empty);
+        ctx.block_depth += 1;
+        let break_depth = ctx.block_depth;
+
+        // loop (for continue)
+        insn.loop_(BlockType::Empty);
+        ctx.block_depth += 1;
+        let continue_depth = ctx.block_depth;
+
+        ctx.loop_depth_stack.push(LoopInfo {
+            break_depth,
+            continue_depth,
+            defer_depth: ctx.defers.len(),
+        });
+
+        self.compile_for_in_next(for_in, head, break_depth, insn, ctx);
+        let cells = Self::iteration_cells(&for_in.body, Some(&for_in.var), ctx);
+        self.emit_fresh_cells(&cells, insn, ctx);
+        self.compile_block(&for_in.body, insn, ctx)?;
+
+        // Jump back to loop start
+        insn.br(ctx.block_depth - continue_depth);
+
+        insn.end();
+        ctx.block_depth -= 1;
+        insn.end();
+        ctx.block_depth -= 1;
+
+        ctx.loop_depth_stack.pop();
+        Ok(())
+    }
+
+    /// Evaluate the iterable of a for-in loop into its temps, with the index
+    /// before the first element. Returns the temps and the element getter.
+    pub(super) fn compile_for_in_head(
+        &self,
+        for_in: &ForInStmt,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<ForInHead, CodegenError> {
+        let iter_ty = self.infer_expr_type(&for_in.iterable, ctx);
+        let get_import = match iter_ty {
+            AhType::List(_) | AhType::Int => "__env_list_get",
+            AhType::Vec => "__env_vec_get",
+            AhType::Str => "__env_str_char_at",
+            _ => {
+                return Err(codegen_err(
+                    format!("cannot iterate over a {} value", self.type_display(iter_ty)),
+                    &for_in.span,
+                ));
+            }
+        };
+
+        self.compile_expr(&for_in.iterable, insn, ctx)?;
+        let (items, index, len) = ctx.claim_for_in_temps();
+        insn.local_tee(items);
+        match iter_ty {
+            AhType::Vec => {
+                insn.i64_const(0xFFFF_FFFF);
+                insn.i64_and();
+            }
+            AhType::Str => {
+                insn.call(self.func_map["__env_str_len"].0);
+            }
+            _ => {
+                insn.call(self.func_map["__env_list_len"].0);
+            }
+        }
+        insn.local_set(len);
+        insn.i64_const(-1);
+        insn.local_set(index);
+
+        ctx.declare_local(&for_in.var);
+        ctx.var_types.insert(for_in.var.clone(), self.iteration_type(&for_in.iterable, ctx));
+        Ok(ForInHead { items, index, len, get_import })
+    }
+
+    /// Advance a for-in loop: leave it once the index reaches the length,
+    /// otherwise load the next element into the loop variable.
+    pub(super) fn compile_for_in_next(
+        &self,
+        for_in: &ForInStmt,
+        head: ForInHead,
+        break_depth: u32,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) {
+        insn.local_get(head.index);
+        insn.i64_const(1);
+        insn.i64_add();
+        insn.local_tee(head.index);
+        insn.local_get(head.len);
+        insn.i64_ge_s();
+        insn.br_if(ctx.block_depth - break_depth);
+
+        ctx.begin_set(&for_in.var, insn);
+        insn.local_get(head.items);
+        insn.local_get(head.index);
+        insn.call(self.func_map[head.get_import].0);
+        ctx.end_set(&for_in.var, insn);
+    }
+}
+
+/// Temps of a for-in loop and the host import reading one element.
+#[derive(Clone, Copy)]
+pub(super) struct ForInHead {
+    items: u32,
+    index: u32,
+    len: u32,
+    get_import: &'static str,
+}
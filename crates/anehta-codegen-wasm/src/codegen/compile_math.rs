@@ -0,0 +1,443 @@
+use super::*;
+
+/// Operation performed by a math builtin.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum MathOp {
+    Sqrt,
+    Abs,
+    Floor,
+    Ceil,
+    Round,
+    Sin,
+    Cos,
+    Exp,
+    Log,
+    Min,
+    Max,
+    Clamp,
+    Atan2,
+    Pi,
+    E,
+    Where,
+}
+
+impl MathOp {
+    /// Op code understood by env.math_map (unary) and env.math_map2 (binary).
+    fn host_code(self) -> i64 {
+        match self {
+            MathOp::Sqrt => 0,
+            MathOp::Abs => 1,
+            MathOp::Floor => 2,
+            MathOp::Ceil => 3,
+            MathOp::Round => 4,
+            MathOp::Sin => 5,
+            MathOp::Cos => 6,
+            MathOp::Exp => 7,
+            MathOp::Log => 8,
+            MathOp::Min => 0,
+            MathOp::Max => 1,
+            MathOp::Atan2 => 2,
+            MathOp::Clamp | MathOp::Pi | MathOp::E | MathOp::Where => unreachable!("no host op code"),
+        }
+    }
+
+    /// Ops that return an int when every argument is an int.
+    fn preserves_int(self) -> bool {
+        matches!(
+            self,
+            MathOp::Abs | MathOp::Floor | MathOp::Ceil | MathOp::Round | MathOp::Min | MathOp::Max | MathOp::Clamp
+        )
+    }
+}
+
+/// A math library builtin: source name, operation and arity.
+pub(super) struct MathBuiltin {
+    pub(super) name: &'static str,
+    pub(super) op: MathOp,
+    pub(super) arity: usize,
+}
+
+const MATH_BUILTINS: &[MathBuiltin] = &[
+    MathBuiltin { name: "sqrt", op: MathOp::Sqrt, arity: 1 },
+    MathBuiltin { name: "abs", op: MathOp::Abs, arity: 1 },
+    MathBuiltin { name: "floor", op: MathOp::Floor, arity: 1 },
+    MathBuiltin { name: "ceil", op: MathOp::Ceil, arity: 1 },
+    MathBuiltin { name: "round", op: MathOp::Round, arity: 1 },
+    MathBuiltin { name: "sin", op: MathOp::Sin, arity: 1 },
+    MathBuiltin { name: "cos", op: MathOp::Cos, arity: 1 },
+    MathBuiltin { name: "exp", op: MathOp::Exp, arity: 1 },
+    MathBuiltin { name: "log", op: MathOp::Log, arity: 1 },
+    MathBuiltin { name: "min", op: MathOp::Min, arity: 2 },
+    MathBuiltin { name: "max", op: MathOp::Max, arity: 2 },
+    MathBuiltin { name: "clamp", op: MathOp::Clamp, arity: 3 },
+    MathBuiltin { name: "atan2", op: MathOp::Atan2, arity: 2 },
+    MathBuiltin { name: "pi", op: MathOp::Pi, arity: 0 },
+    MathBuiltin { name: "e", op: MathOp::E, arity: 0 },
+    MathBuiltin { name: "where", op: MathOp::Where, arity: 3 },
+];
+
+/// Builtins that may need a pair of temp locals (pre-allocated by prescan).
+pub(super) fn needs_math_temps(name: &str) -> bool {
+    matches!(name, "abs" | "round" | "min" | "max" | "clamp")
+}
+
+fn is_array(ty: AhType) -> bool {
+    matches!(ty, AhType::Vec | AhType::Mat)
+}
+
+/// Op code of a comparison, shared by the mask and `where` host functions.
+pub(super) fn comparison_code(op: &ComparisonOp) -> i64 {
+    match op {
+        ComparisonOp::Gt => 0,
+        ComparisonOp::Lt => 1,
+        ComparisonOp::GtEq => 2,
+        ComparisonOp::LtEq => 3,
+        ComparisonOp::Eq => 4,
+        ComparisonOp::NotEq => 5,
+    }
+}
+
+/// The operands of `where(l op r, a, b)`: the comparison and `[l, r, a, b]`.
+fn where_operands(args: &[Expr]) -> Option<(&ComparisonOp, [&Expr; 4])> {
+    let [Expr::BooleanExpr(cond), a, b] = args else {
+        return None;
+    };
+    let BooleanExpr::Comparison { left, op, right, .. } = cond.as_ref() else {
+        return None;
+    };
+    Some((op, [left, right, a, b]))
+}
+
+impl WasmCodegen {
+    /// Look up a math library builtin by name.
+    pub(super) fn math_builtin(&self, name: &str, ctx: &FuncCtx) -> Option<&'static MathBuiltin> {
+        if self.builtin_shadowed(name, ctx) {
+            return None;
+        }
+        MATH_BUILTINS.iter().find(|b| b.name == name)
+    }
+
+    /// Result type of a math builtin call: the vec/mat type for element-wise
+    /// calls, int when an int-preserving op gets only ints, float otherwise.
+    pub(super) fn math_result_type(&self, builtin: &MathBuiltin, args: &[Expr], ctx: &FuncCtx) -> AhType {
+        let types: Vec<AhType> = match where_operands(args) {
+            Some((_, operands)) if builtin.op == MathOp::Where => {
+                operands.iter().map(|a| self.infer_expr_type(a, ctx)).collect()
+            }
+            _ => args.iter().map(|a| self.infer_expr_type(a, ctx)).collect(),
+        };
+        if let Some(&array) = types.iter().find(|&&t| is_array(t)) {
+            return array;
+        }
+        if builtin.op.preserves_int() && types.iter().all(|&t| t == AhType::Int) {
+            AhType::Int
+        } else {
+            AhType::Float
+        }
+    }
+
+    /// Compile a call to a math library builtin, leaving its result on the stack.
+    pub(super) fn compile_math_builtin(
+        &self,
+        builtin: &MathBuiltin,
+        call: &CallFunc,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        if call.args.len() != builtin.arity {
+            return Err(codegen_err(
+                format!(
+                    "function '{}' expects {} argument(s), but {} were given",
+                    builtin.name, builtin.arity, call.args.len()
+                ),
+                &call.span,
+            ));
+        }
+
+        if builtin.op == MathOp::Where {
+            return self.compile_math_where(call, insn, ctx);
+        }
+
+        let types: Vec<AhType> = call.args.iter().map(|a| self.infer_expr_type(a, ctx)).collect();
+        for (i, ty) in types.iter().enumerate() {
+            if !matches!(ty, AhType::Int | AhType::Float | AhType::Vec | AhType::Mat) {
+                return Err(codegen_err(
+                    format!("argument {} of '{}' must be a number, vec or mat", i + 1, builtin.name),
+                    &call.span,
+                ));
+            }
+        }
+
+        match self.math_result_type(builtin, &call.args, ctx) {
+            array @ (AhType::Vec | AhType::Mat) => {
+                self.compile_math_elementwise(builtin, call, &types, array, insn, ctx)
+            }
+            AhType::Int => self.compile_math_int(builtin.op, call, insn, ctx),
+            _ => self.compile_math_float(builtin.op, call, &types, insn, ctx),
+        }
+    }
+
+    /// Scalar math on ints: abs/min/max/clamp via `select`, floor/ceil/round are identity.
+    fn compile_math_int(
+        &self,
+        op: MathOp,
+        call: &CallFunc,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        match op {
+            MathOp::Floor | MathOp::Ceil | MathOp::Round => {
+                self.compile_expr(&call.args[0], insn, ctx)?;
+            }
+            MathOp::Abs => {
+                let (t, _) = ctx.claim_math_temps();
+                self.compile_expr(&call.args[0], insn, ctx)?;
+                insn.local_set(t);
+                insn.i64_const(0);
+                insn.local_get(t);
+                insn.i64_sub();
+                insn.local_get(t);
+                insn.local_get(t);
+                insn.i64_const(0);
+                insn.i64_lt_s();
+                insn.select();
+            }
+            MathOp::Min | MathOp::Max => {
+                let (a, b) = ctx.claim_math_temps();
+                self.compile_expr(&call.args[0], insn, ctx)?;
+                insn.local_set(a);
+                self.compile_expr(&call.args[1], insn, ctx)?;
+                insn.local_set(b);
+                Self::emit_int_select(op, a, b, insn);
+            }
+            MathOp::Clamp => {
+                // clamp(x, lo, hi) = min(max(x, lo), hi)
+                let (a, b) = ctx.claim_math_temps();
+                self.compile_expr(&call.args[0], insn, ctx)?;
+                insn.local_set(a);
+                self.compile_expr(&call.args[1], insn, ctx)?;
+                insn.local_set(b);
+                Self::emit_int_select(MathOp::Max, a, b, insn);
+                insn.local_set(a);
+                self.compile_expr(&call.args[2], insn, ctx)?;
+                insn.local_set(b);
+                Self::emit_int_select(MathOp::Min, a, b, insn);
+            }
+            _ => unreachable!("only int-preserving ops reach compile_math_int"),
+        }
+        Ok(())
+    }
+
+    /// Push min(a, b) or max(a, b) of two int locals.
+    fn emit_int_select(op: MathOp, a: u32, b: u32, insn: &mut wasm_encoder::InstructionSink<'_>) {
+        insn.local_get(a);
+        insn.local_get(b);
+        insn.local_get(a);
+        insn.local_get(b);
+        if op == MathOp::Min {
+            insn.i64_lt_s();
+        } else {
+            insn.i64_gt_s();
+        }
+        insn.select();
+    }
+
+    /// Compile an argument as an f64 on the stack, converting ints.
+    fn compile_as_f64(
+        &self,
+        arg: &Expr,
+        ty: AhType,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        self.compile_expr(arg, insn, ctx)?;
+        if ty == AhType::Int {
+            insn.f64_convert_i64_s();
+        } else {
+            insn.f64_reinterpret_i64();
+        }
+        Ok(())
+    }
+
+    /// Scalar math on floats: native f64 instructions where WASM has them,
+    /// host imports for the transcendental functions.
+    fn compile_math_float(
+        &self,
+        op: MathOp,
+        call: &CallFunc,
+        types: &[AhType],
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let args = &call.args;
+        match op {
+            MathOp::Pi => insn.f64_const(std::f64::consts::PI),
+            MathOp::E => insn.f64_const(std::f64::consts::E),
+            MathOp::Sqrt | MathOp::Abs | MathOp::Floor | MathOp::Ceil => {
+                self.compile_as_f64(&args[0], types[0], insn, ctx)?;
+                match op {
+                    MathOp::Sqrt => insn.f64_sqrt(),
+                    MathOp::Abs => insn.f64_abs(),
+                    MathOp::Floor => insn.f64_floor(),
+                    _ => insn.f64_ceil(),
+                }
+            }
+            MathOp::Round => {
+                // Round half away from zero: trunc(x + copysign(0.5, x))
+                // (f64.nearest would round ties to even)
+                let (t, _) = ctx.claim_math_temps();
+                self.compile_as_f64(&args[0], types[0], insn, ctx)?;
+                insn.i64_reinterpret_f64();
+                insn.local_tee(t);
+                insn.f64_reinterpret_i64();
+                insn.f64_const(0.5);
+                insn.local_get(t);
+                insn.f64_reinterpret_i64();
+                insn.f64_copysign();
+                insn.f64_add();
+                insn.f64_trunc()
+            }
+            MathOp::Min | MathOp::Max => {
+                self.compile_as_f64(&args[0], types[0], insn, ctx)?;
+                self.compile_as_f64(&args[1], types[1], insn, ctx)?;
+                if op == MathOp::Min { insn.f64_min() } else { insn.f64_max() }
+            }
+            MathOp::Clamp => {
+                self.compile_as_f64(&args[0], types[0], insn, ctx)?;
+                self.compile_as_f64(&args[1], types[1], insn, ctx)?;
+                insn.f64_max();
+                self.compile_as_f64(&args[2], types[2], insn, ctx)?;
+                insn.f64_min()
+            }
+            MathOp::Sin | MathOp::Cos | MathOp::Exp | MathOp::Log | MathOp::Atan2 => {
+                for (arg, &ty) in args.iter().zip(types) {
+                    self.compile_as_f64(arg, ty, insn, ctx)?;
+                    insn.i64_reinterpret_f64();
+                }
+                let import = match op {
+                    MathOp::Sin => "__env_math_sin",
+                    MathOp::Cos => "__env_math_cos",
+                    MathOp::Exp => "__env_math_exp",
+                    MathOp::Log => "__env_math_log",
+                    _ => "__env_math_atan2",
+                };
+                insn.call(self.func_map[import].0);
+                return Ok(());
+            }
+            MathOp::Where => unreachable!("where is compiled by compile_math_where"),
+        };
+        insn.i64_reinterpret_f64();
+        Ok(())
+    }
+
+    /// Element-wise math on a vec or mat through env.math_map / env.math_map2.
+    /// The other operands of binary ops are broadcast scalars or arrays of the same type.
+    fn compile_math_elementwise(
+        &self,
+        builtin: &MathBuiltin,
+        call: &CallFunc,
+        types: &[AhType],
+        array: AhType,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let shape: i64 = if array == AhType::Mat { 1 } else { 0 };
+        let args = &call.args;
+
+        if let Some(i) = types.iter().position(|&t| is_array(t) && t != array) {
+            return Err(codegen_err(
+                format!("argument {} of '{}' cannot mix vec and mat", i + 1, builtin.name),
+                &call.span,
+            ));
+        }
+
+        match builtin.op {
+            MathOp::Min | MathOp::Max | MathOp::Atan2 | MathOp::Clamp => {
+                // The array operand comes first; min/max are symmetric so it may be either one
+                let (first, rest) = if is_array(types[0]) {
+                    (0, (1..args.len()).collect::<Vec<_>>())
+                } else if builtin.op != MathOp::Atan2 && builtin.op != MathOp::Clamp {
+                    (1, vec![0])
+                } else {
+                    return Err(codegen_err(
+                        format!("the vec/mat argument of '{}' must come first", builtin.name),
+                        &call.span,
+                    ));
+                };
+                let ops: Vec<i64> = if builtin.op == MathOp::Clamp {
+                    vec![MathOp::Min.host_code(), MathOp::Max.host_code()]
+                } else {
+                    vec![builtin.op.host_code()]
+                };
+                for code in ops {
+                    insn.i64_const(code);
+                }
+                self.compile_expr(&args[first], insn, ctx)?;
+                // Clamp applies max with lo first, then min with hi
+                for &i in &rest {
+                    if is_array(types[i]) {
+                        self.compile_expr(&args[i], insn, ctx)?;
+                        insn.i64_const(shape + 2);
+                    } else {
+                        self.compile_as_f64(&args[i], types[i], insn, ctx)?;
+                        insn.i64_reinterpret_f64();
+                        insn.i64_const(shape);
+                    }
+                    self.emit_raising_call("__env_math_map2", insn, ctx);
+                }
+            }
+            op => {
+                insn.i64_const(op.host_code());
+                self.compile_expr(&args[0], insn, ctx)?;
+                insn.i64_const(shape);
+                insn.call(self.func_map["__env_math_map"].0);
+            }
+        }
+        Ok(())
+    }
+
+    /// `where(l op r, a, b)`: element-wise `a` where the comparison holds, `b` elsewhere.
+    /// Any operand may be a scalar; the vec/mat operands must share one shape.
+    fn compile_math_where(
+        &self,
+        call: &CallFunc,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let Some((op, operands)) = where_operands(&call.args) else {
+            return Err(codegen_err(
+                "'where' expects a comparison and two values, as in where(v > 0, v, 0)",
+                &call.span,
+            ));
+        };
+        let types: Vec<AhType> = operands.iter().map(|a| self.infer_expr_type(a, ctx)).collect();
+        if types.iter().any(|t| !matches!(t, AhType::Int | AhType::Float | AhType::Vec | AhType::Mat)) {
+            return Err(codegen_err("the operands of 'where' must be numbers, vecs or mats", &call.span));
+        }
+        let Some(&array) = types.iter().find(|&&t| is_array(t)) else {
+            return Err(codegen_err(
+                "'where' needs a vec or mat operand; use an if-expression for scalars",
+                &call.span,
+            ));
+        };
+        if types.iter().any(|&t| is_array(t) && t != array) {
+            return Err(codegen_err("'where' cannot mix vec and mat", &call.span));
+        }
+
+        // shape: bit 0 = mat, bits 1-4 mark which of l, r, a, b are arrays
+        let mut shape: i64 = if array == AhType::Mat { 1 } else { 0 };
+        insn.i64_const(comparison_code(op));
+        for (i, (operand, &ty)) in operands.iter().zip(&types).enumerate() {
+            if is_array(ty) {
+                self.compile_expr(operand, insn, ctx)?;
+                shape |= 1 << (i + 1);
+            } else {
+                self.compile_as_f64(operand, ty, insn, ctx)?;
+                insn.i64_reinterpret_f64();
+            }
+        }
+        insn.i64_const(shape);
+        self.emit_raising_call("__env_math_where", insn, ctx);
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use super::*;
+
+impl WasmCodegen {
+    /// Validate `impl` blocks: the target must be a declared struct or enum, and each
+    /// method name must be unique and must not shadow a struct field.
+    pub(super) fn check_impls(&self, program: &Program) -> Result<(), CodegenError> {
+        let mut seen: HashSet<&str> = HashSet::new();
+        for stmt in &program.statements {
+            let Statement::ImplDecl(imp) = stmt else {
+                continue;
+            };
+            let Some(ty) = self.decl_type(&imp.type_name) else {
+                return Err(codegen_err(
+                    format!("cannot impl unknown type '{}'", imp.type_name),
+                    &imp.span,
+                ));
+            };
+            if !matches!(ty, AhType::Struct(_) | AhType::Enum(_)) {
+                return Err(codegen_err(
+                    format!("methods can only be added to structs and enums, not '{}'", imp.type_name),
+                    &imp.span,
+                ));
+            }
+            for method in &imp.methods {
+                let name = &method.name[imp.type_name.len() + 1..];
+                if !seen.insert(&method.name) {
+                    return Err(codegen_err(
+                        format!("method '{}' is already defined for '{}'", name, imp.type_name),
+                        &method.span,
+                    ));
+                }
+                if let AhType::Struct(id) = ty
+                    && self.structs[id as usize].field(name).is_some()
+                {
+                    return Err(codegen_err(
+                        format!("'{}' is both a field and a method of '{}'", name, imp.type_name),
+                        &method.span,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `obj.method(args)` or `obj:method(args)` on a struct or enum value resolves to the
+    /// function `Type.method`; returns that name and the callee.
+    pub(super) fn method_target<'a>(&self, mc: &'a MethodCall, ctx: &FuncCtx) -> Option<(String, &'a FieldAccess)> {
+        let Expr::FieldAccess(fa) = mc.callee.as_ref() else {
+            return None;
+        };
+        match self.infer_expr_type(&fa.object, ctx) {
+            // A struct field of a function type is called like a closure
+            AhType::Struct(id)
+                if matches!(self.structs[id as usize].field(&fa.field), Some((_, AhType::Func(_)))) =>
+            {
+                None
+            }
+            ty @ (AhType::Struct(_) | AhType::Enum(_)) => {
+                Some((format!("{}.{}", self.type_display(ty), fa.field), fa))
+            }
+            _ => None,
+        }
+    }
+
+    /// Direct call of a method: the receiver is passed first, then the arguments.
+    /// Only the first result is kept when the method returns several.
+    pub(super) fn compile_method_call(
+        &self,
+        name: &str,
+        fa: &FieldAccess,
+        mc: &MethodCall,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let Some(&(func_idx, type_idx)) = self.func_map.get(name) else {
+            let ty = self.infer_expr_type(&fa.object, ctx);
+            return Err(codegen_err(
+                format!("'{}' has no method '{}'", self.type_display(ty), fa.field),
+                &mc.span,
+            ));
+        };
+        let (params, results) = &self.types[type_idx as usize];
+        if mc.args.len() + 1 != params.len() {
+            return Err(codegen_err(
+                format!(
+                    "method '{}' expects {} argument(s), but {} were given",
+                    name,
+                    params.len() - 1,
+                    mc.args.len()
+                ),
+                &mc.span,
+            ));
+        }
+        self.compile_expr(&fa.object, insn, ctx)?;
+        for arg in &mc.args {
+            self.compile_expr(arg, insn, ctx)?;
+        }
+        insn.call(func_idx);
+        self.emit_error_check(insn, ctx);
+        for _ in 1..results.len() {
+            insn.drop();
+        }
+        Ok(())
+    }
+}
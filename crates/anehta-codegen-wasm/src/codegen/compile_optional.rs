@@ -0,0 +1,156 @@
+use super::*;
+
+impl WasmCodegen {
+    /// Result type of `a ?? b`: the type inside `a`, unified with `b`.
+    pub(super) fn coalesce_type(&self, left: AhType, right: AhType) -> Option<AhType> {
+        if left == AhType::NIL {
+            return Some(right);
+        }
+        unify_branch_types(left.unwrap_optional(), right)
+    }
+
+    /// Type of `t?.field`: the field's type made optional. A field the table type
+    /// does not know is typed like `nil`, so `??` takes the default's type.
+    pub(super) fn safe_field_type(&self, fa: &FieldAccess, ctx: &FuncCtx) -> AhType {
+        match self.infer_expr_type(&fa.object, ctx).unwrap_optional() {
+            AhType::Table(id) => self.table_types[id as usize]
+                .fields
+                .get(&fa.field)
+                .map_or(AhType::NIL, |ty| ty.optional()),
+            _ => AhType::NIL,
+        }
+    }
+
+    /// Reject an optional operand where a plain value is needed.
+    pub(super) fn check_not_optional(&self, operand: &Expr, span: &Span, ctx: &FuncCtx) -> Result<(), CodegenError> {
+        let ty = self.infer_expr_type(operand, ctx);
+        if let AhType::Optional(_) = ty {
+            return Err(codegen_err(
+                format!("value of type {} may be nil; give it a default with '??'", self.type_display(ty)),
+                span,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Compile `a ?? b`: `a` unless it is nil, otherwise `b`, which is only
+    /// evaluated in that case.
+    pub(super) fn compile_coalesce(
+        &self,
+        left: &Expr,
+        right: &Expr,
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let left_ty = self.infer_expr_type(left, ctx);
+        let right_ty = self.infer_expr_type(right, ctx);
+        let Some(result_ty) = self.coalesce_type(left_ty, right_ty) else {
+            return Err(codegen_err(
+                format!(
+                    "'??' operands have different types: {} and {}",
+                    self.type_display(left_ty),
+                    self.type_display(right_ty)
+                ),
+                span,
+            ));
+        };
+
+        let temp = ctx.claim_coalesce_temp();
+        self.compile_expr(left, insn, ctx)?;
+        insn.local_tee(temp);
+        insn.i64_const(NIL);
+        insn.i64_ne();
+        insn.if_(BlockType::Result(ValType::I64));
+        ctx.block_depth += 1;
+        insn.local_get(temp);
+        if result_ty.unwrap_optional() == AhType::Float && left_ty.unwrap_optional() == AhType::Int {
+            insn.f64_convert_i64_s();
+            insn.i64_reinterpret_f64();
+        }
+        insn.else_();
+        self.compile_slot_value(result_ty, right, "'??' default", span, insn, ctx)?;
+        insn.end();
+        ctx.block_depth -= 1;
+        Ok(())
+    }
+
+    /// Compile `t?.field`: nil when `t` is nil or has no such field.
+    pub(super) fn compile_safe_field(
+        &self,
+        fa: &FieldAccess,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let obj_ty = self.infer_expr_type(&fa.object, ctx);
+        let inner = obj_ty.unwrap_optional();
+        if !matches!(inner, AhType::Table(_)) && !self.is_unknown(&fa.object, inner, ctx) {
+            return Err(codegen_err(
+                format!("'?.' needs a table, found {}", self.type_display(obj_ty)),
+                &fa.span,
+            ));
+        }
+        self.compile_expr(&fa.object, insn, ctx)?;
+        let (offset, len) = self.string_pool.get(fa.field.as_str()).copied().unwrap_or((0, 0));
+        insn.i64_const(((offset as i64) << 32) | (len as i64));
+        insn.call(self.func_map["__env_table_get_opt"].0);
+        Ok(())
+    }
+
+    /// Note a read of `t.field` where `t` comes from a table literal without that
+    /// field. It becomes a warning unless the field is assigned somewhere (see
+    /// `field_warnings`).
+    pub(super) fn check_table_field(&self, fa: &FieldAccess, ctx: &FuncCtx) {
+        let AhType::Table(id) = self.infer_expr_type(&fa.object, ctx) else {
+            return;
+        };
+        if self.literal_tables.contains(&id) && !self.table_types[id as usize].fields.contains_key(&fa.field) {
+            self.field_checks.borrow_mut().reads.push((id, fa.field.clone(), fa.span));
+        }
+    }
+
+    /// Note a write to a table field or key, which may add a field the literal lacks.
+    pub(super) fn note_table_write(&self, table_ty: AhType, key: &Expr, ctx: &FuncCtx) {
+        let mut checks = self.field_checks.borrow_mut();
+        match key {
+            Expr::StringLit(name, _) => {
+                checks.assigned.insert(name.clone());
+            }
+            // Keys known only at runtime may add any field
+            _ if self.infer_expr_type(key, ctx) == AhType::Int => {}
+            _ => match table_ty {
+                AhType::Table(id) => {
+                    checks.open.insert(id);
+                }
+                _ => checks.all_open = true,
+            },
+        }
+    }
+
+    /// Warnings for the field reads that no write can satisfy.
+    pub(super) fn field_warnings(&self) -> Vec<CodegenWarning> {
+        let checks = self.field_checks.borrow();
+        if checks.all_open {
+            return Vec::new();
+        }
+        let mut warnings: Vec<CodegenWarning> = Vec::new();
+        for (id, field, span) in &checks.reads {
+            if checks.open.contains(id)
+                || checks.assigned.contains(field)
+                || warnings.iter().any(|w| (w.line, w.column, w.file) == (span.line, span.column, span.file))
+            {
+                continue;
+            }
+            warnings.push(CodegenWarning {
+                message: format!(
+                    "table has no field '{}'; reading it fails at runtime (use '?.' if it may be missing)",
+                    field
+                ),
+                line: span.line,
+                column: span.column,
+                file: span.file,
+            });
+        }
+        warnings
+    }
+}
@@ -7,7 +7,21 @@ impl WasmCodegen {
         insn: &mut wasm_encoder::InstructionSink<'_>,
         ctx: &mut FuncCtx,
     ) -> Result<(), CodegenError> {
-        for stmt in &block.statements {
+        self.compile_stmts(&block.statements, insn, ctx)
+    }
+
+    /// Compile statements in order; a `defer` takes the ones after it, which
+    /// run before its body.
+    pub(super) fn compile_stmts(
+        &self,
+        stmts: &[Statement],
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        for (i, stmt) in stmts.iter().enumerate() {
+            if let Statement::Defer(d) = stmt {
+                return self.compile_defer(d, &stmts[i + 1..], insn, ctx);
+            }
             self.compile_stmt(stmt, insn, ctx)?;
         }
         Ok(())
@@ -19,12 +33,22 @@ impl WasmCodegen {
         insn: &mut wasm_encoder::InstructionSink<'_>,
         ctx: &mut FuncCtx,
     ) -> Result<(), CodegenError> {
+        if ctx.generator.is_some_and(|generator| !generator.guarded) {
+            return self.compile_gen_stmt(stmt, insn, ctx);
+        }
         match stmt {
-            Statement::VarDecl(VarDecl::TypeDecl { name, .. }) => {
+            Statement::VarDecl(VarDecl::TypeDecl { name, type_name, span }) => {
+                // A struct has no zero value: its fields must come from `new`
+                if self.struct_map.contains_key(type_name) {
+                    return Err(codegen_err(
+                        format!("struct variable '{}' needs an initializer: var {} = new {} {{ ... }}", name, name, type_name),
+                        span,
+                    ));
+                }
                 // Type-only declaration: initialize to 0
-                let idx = ctx.declare_local(name);
+                ctx.begin_set(name, insn);
                 insn.i64_const(0);
-                insn.local_set(idx);
+                ctx.end_set(name, insn);
             }
             Statement::VarDecl(VarDecl::Assignment(assign)) => {
                 self.compile_assignment(assign, insn, ctx)?;
@@ -38,6 +62,9 @@ impl WasmCodegen {
             Statement::ForStmt(for_stmt) => {
                 self.compile_for(for_stmt, insn, ctx)?;
             }
+            Statement::ForIn(for_in) => {
+                self.compile_for_in(for_in, insn, ctx)?;
+            }
             Statement::Block(block) => {
                 self.compile_block(block, insn, ctx)?;
             }
@@ -47,18 +74,38 @@ impl WasmCodegen {
             Statement::Return(ret) => {
                 self.compile_return(ret, insn, ctx)?;
             }
+            Statement::Try(t) => {
+                self.compile_try(t, insn, ctx)?;
+            }
+            Statement::Throw(throw) => {
+                self.compile_throw(throw, insn, ctx)?;
+            }
+            Statement::Defer(d) => {
+                // Only reached outside `compile_stmts`, by a generator body
+                self.compile_defer(d, &[], insn, ctx)?;
+            }
+            Statement::Yield(y) => {
+                // Generator bodies compile their yields in `compile_gen_stmt`
+                return Err(codegen_err("yield can only be used in a top-level function", &y.span));
+            }
             Statement::Break(span) => {
-                if let Some(loop_info) = ctx.loop_depth_stack.last() {
+                if let Some(loop_info) = ctx.loop_depth_stack.last().copied() {
+                    self.emit_pending_defers(loop_info.defer_depth, insn, ctx)?;
                     let relative = ctx.block_depth - loop_info.break_depth;
                     insn.br(relative);
+                } else if ctx.in_defer {
+                    return Err(codegen_err("break cannot leave a defer block", span));
                 } else {
                     return Err(codegen_err("break outside of loop", span));
                 }
             }
             Statement::Continue(span) => {
-                if let Some(loop_info) = ctx.loop_depth_stack.last() {
+                if let Some(loop_info) = ctx.loop_depth_stack.last().copied() {
+                    self.emit_pending_defers(loop_info.defer_depth, insn, ctx)?;
                     let relative = ctx.block_depth - loop_info.continue_depth;
                     insn.br(relative);
+                } else if ctx.in_defer {
+                    return Err(codegen_err("continue cannot leave a defer block", span));
                 } else {
                     return Err(codegen_err("continue outside of loop", span));
                 }
@@ -66,9 +113,9 @@ impl WasmCodegen {
             Statement::TimerStmt(timer) => {
                 self.compile_timer(timer, insn, ctx)?;
             }
-            Statement::FuncDecl(_) => {
-                // Nested function declarations are not supported at statement level in codegen.
-                // They should only appear at top level.
+            Statement::FuncDecl(_) | Statement::ImplDecl(_) => {
+                // Compiled as functions of their own; nested `func`s were turned
+                // into closures by `resolve_calls`.
             }
             Statement::FieldAssign(fa) => {
                 self.compile_field_assign(fa, insn, ctx)?;
@@ -80,6 +127,21 @@ impl WasmCodegen {
                 self.compile_method_call_expr(mc, insn, ctx)?;
                 insn.drop(); // discard return value in statement context
             }
+            Statement::Expr(Expr::Match(m)) => {
+                self.compile_match(m, false, insn, ctx)?;
+            }
+            Statement::Expr(expr) => {
+                self.compile_expr(expr, insn, ctx)?;
+                insn.drop(); // x++ leaves the old value
+            }
+            Statement::StructDecl(_) | Statement::EnumDecl(_) => {} // layouts are fixed in collect_type_decls
+            Statement::Import(imp) => {
+                // The loader merges the imported files into one program without imports
+                return Err(codegen_err(
+                    format!("import of '{}' was not resolved; compile the file with the anehta CLI", imp.path),
+                    &imp.span,
+                ));
+            }
         }
         Ok(())
     }
@@ -106,23 +168,22 @@ impl WasmCodegen {
                 let (vec_get_idx, _) = self.func_map["__env_vec_get"];
                 for (i, target) in assign.targets.iter().enumerate() {
                     // Free old table if needed
-                    if ctx.owned_tables.contains(target) && !ctx.captured_tables.contains(target) {
-                        if matches!(ctx.var_types.get(target), Some(AhType::Table(_))) {
-                            let var_idx = ctx.locals[target];
-                            insn.local_get(var_idx);
-                            insn.call(table_free_idx);
-                        }
+                    if ctx.owned_tables.contains(target) && !ctx.captured_tables.contains(target)
+                        && matches!(ctx.var_types.get(target), Some(AhType::Table(_) | AhType::List(_))) {
+                        let var_idx = ctx.locals[target];
+                        insn.local_get(var_idx);
+                        insn.call(table_free_idx);
                     }
 
                     // Extract element: vec_get(vec, i)
+                    ctx.begin_set(target, insn);
                     insn.local_get(vec_temp);
                     insn.i64_const(i as i64);
                     insn.call(vec_get_idx);
 
                     // Element is f64 bits, track as Float
                     ctx.var_types.insert(target.clone(), AhType::Float);
-                    let idx = ctx.declare_local(target);
-                    insn.local_set(idx);
+                    ctx.end_set(target, insn);
                 }
                 return Ok(());
             }
@@ -132,14 +193,14 @@ impl WasmCodegen {
         for (i, target) in assign.targets.iter().enumerate() {
             // Free old table if this is an owned table variable being reassigned
             // Skip if captured by a closure (old value may still be referenced)
-            if ctx.owned_tables.contains(target) && !ctx.captured_tables.contains(target) {
-                if matches!(ctx.var_types.get(target), Some(AhType::Table(_))) {
-                    let var_idx = ctx.locals[target];
-                    insn.local_get(var_idx);
-                    insn.call(table_free_idx);
-                }
+            if ctx.owned_tables.contains(target) && !ctx.captured_tables.contains(target)
+                && matches!(ctx.var_types.get(target), Some(AhType::Table(_) | AhType::List(_))) {
+                let var_idx = ctx.locals[target];
+                insn.local_get(var_idx);
+                insn.call(table_free_idx);
             }
 
+            ctx.begin_set(target, insn);
             if i < assign.values.len() {
                 // Track type of the assigned value
                 let ty = self.infer_expr_type(&assign.values[i], ctx);
@@ -149,8 +210,7 @@ impl WasmCodegen {
                 // If fewer values than targets, use 0
                 insn.i64_const(0);
             }
-            let idx = ctx.declare_local(target);
-            insn.local_set(idx);
+            ctx.end_set(target, insn);
         }
         Ok(())
     }
@@ -201,6 +261,149 @@ impl WasmCodegen {
         Ok(())
     }
 
+    /// Compile `if (cond) { a } else { b }` used as a value. An int branch is
+    /// widened when the other branch is a float.
+    pub(super) fn compile_if_expr(
+        &self,
+        e: &IfExpr,
+        tail: bool,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let then_ty = self.infer_expr_type(&e.then_value, ctx);
+        let else_ty = self.infer_expr_type(&e.else_value, ctx);
+        let Some(result_ty) = unify_branch_types(then_ty, else_ty) else {
+            return Err(codegen_err(
+                format!(
+                    "if-expression branches have different types: {} and {}",
+                    self.type_display(then_ty),
+                    self.type_display(else_ty)
+                ),
+                &e.span,
+            ));
+        };
+
+        self.compile_boolean_expr(&e.condition, insn, ctx)?;
+        insn.if_(BlockType::Result(ValType::I64));
+        ctx.block_depth += 1;
+        self.compile_if_branch(result_ty, &e.then_value, tail, &e.span, insn, ctx)?;
+        insn.else_();
+        self.compile_if_branch(result_ty, &e.else_value, tail, &e.span, insn, ctx)?;
+        insn.end();
+        ctx.block_depth -= 1;
+        Ok(())
+    }
+
+    /// A branch of an if-expression; with `tail`, the if-expression is the value
+    /// the function returns, and so is a branch needing no conversion.
+    fn compile_if_branch(
+        &self,
+        result_ty: AhType,
+        branch: &Expr,
+        tail: bool,
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        if tail && self.infer_expr_type(branch, ctx) == result_ty {
+            return self.compile_tail_value(branch, insn, ctx);
+        }
+        self.compile_slot_value(result_ty, branch, "if-expression branch", span, insn, ctx)
+    }
+
+    /// Compile `match`: the subject's variant tag dispatches through a `br_table`
+    /// to its arm, after the arm's bindings are loaded from the payload slots.
+    /// As a value (`as_value`), every arm is an expression of the same type and the
+    /// result is left on the stack; otherwise arm results are dropped.
+    pub(super) fn compile_match(
+        &self,
+        m: &MatchExpr,
+        as_value: bool,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let subject_ty = self.infer_expr_type(&m.subject, ctx);
+        let AhType::Enum(id) = subject_ty else {
+            return Err(codegen_err(
+                format!("match needs an enum value, found {}", self.type_display(subject_ty)),
+                &m.span,
+            ));
+        };
+        let targets = self.match_targets(id, m)?;
+        let result_ty = self.infer_match_type(m, ctx);
+
+        let (subject, result) = ctx.claim_math_temps();
+        self.compile_expr(&m.subject, insn, ctx)?;
+        insn.local_set(subject);
+
+        // One block per arm inside a `done` block; arm 0's block is innermost
+        let arm_count = m.arms.len() as u32;
+        insn.block(BlockType::Empty);
+        for _ in 0..arm_count {
+            insn.block(BlockType::Empty);
+        }
+        insn.local_get(subject);
+        insn.i32_wrap_i64();
+        insn.i64_load(Self::slot_arg(0));
+        insn.i32_wrap_i64();
+        insn.br_table(targets, arm_count - 1);
+
+        for (i, arm) in m.arms.iter().enumerate() {
+            insn.end();
+            // Open blocks left around this arm: `done` plus the later arms
+            let depth_to_done = arm_count - 1 - i as u32;
+            ctx.block_depth += depth_to_done + 1;
+
+            for (name, slot, ty) in self.arm_bindings(id, &arm.pattern) {
+                ctx.begin_set(&name, insn);
+                insn.local_get(subject);
+                insn.i32_wrap_i64();
+                insn.i64_load(Self::slot_arg(slot));
+                ctx.end_set(&name, insn);
+                ctx.var_types.insert(name, ty);
+            }
+            match &arm.body {
+                MatchBody::Expr(body) if as_value => {
+                    let ty = self.infer_expr_type(body, ctx);
+                    if ty != result_ty {
+                        return Err(codegen_err(
+                            format!(
+                                "match arms have different types: {} and {}",
+                                self.type_display(result_ty),
+                                self.type_display(ty)
+                            ),
+                            &arm.span,
+                        ));
+                    }
+                    self.compile_expr(body, insn, ctx)?;
+                    insn.local_set(result);
+                }
+                MatchBody::Expr(body) => {
+                    self.compile_expr(body, insn, ctx)?;
+                    insn.drop();
+                }
+                MatchBody::Block(_) if as_value => {
+                    return Err(codegen_err(
+                        "a match used as a value needs an expression in every arm",
+                        &arm.span,
+                    ));
+                }
+                MatchBody::Block(block) => self.compile_block(block, insn, ctx)?,
+            }
+
+            ctx.block_depth -= depth_to_done + 1;
+            if depth_to_done > 0 {
+                insn.br(depth_to_done);
+            }
+        }
+        insn.end();
+
+        if as_value {
+            insn.local_get(result);
+        }
+        Ok(())
+    }
+
     fn compile_for(
         &self,
         for_stmt: &ForStmt,
@@ -225,6 +428,7 @@ impl WasmCodegen {
         ctx.loop_depth_stack.push(LoopInfo {
             break_depth,
             continue_depth,
+            defer_depth: ctx.defers.len(),
         });
 
         // Condition check
@@ -237,6 +441,8 @@ impl WasmCodegen {
         }
 
         // Body
+        let cells = Self::iteration_cells(&for_stmt.body, None, ctx);
+        self.emit_fresh_cells(&cells, insn, ctx);
         self.compile_block(&for_stmt.body, insn, ctx)?;
 
         // Step
@@ -293,6 +499,18 @@ impl WasmCodegen {
                 insn.call(func_idx);
                 return Ok(());
             }
+            if let AhType::List(_) = arg_type {
+                self.compile_expr(&call.args[0], insn, ctx)?;
+                let (func_idx, _) = self.func_map["__env_print_list"];
+                insn.call(func_idx);
+                return Ok(());
+            }
+            if let Some(fmt) = self.formatter(arg_type) {
+                self.compile_expr(&call.args[0], insn, ctx)?;
+                insn.call(fmt);
+                insn.call(self.func_map["__env_print_str"].0);
+                return Ok(());
+            }
         }
 
         // Built-in conversion: int(expr)
@@ -319,6 +537,50 @@ impl WasmCodegen {
             return Ok(());
         }
 
+        if let Some(builtin) = self.str_builtin(&call.name, ctx) {
+            self.compile_str_builtin(builtin, call, insn, ctx)?;
+            insn.drop(); // statement context: discard result
+            return Ok(());
+        }
+
+        if let Some(builtin) = self.table_builtin(call, ctx) {
+            self.compile_table_builtin(builtin, call, insn, ctx)?;
+            insn.drop(); // statement context: discard result
+            return Ok(());
+        }
+
+        if let Some(builtin) = self.list_builtin(&call.name, ctx) {
+            self.compile_list_builtin(builtin, call, insn, ctx)?;
+            insn.drop(); // statement context: discard result
+            return Ok(());
+        }
+
+        if let Some(builtin) = self.hof_builtin(&call.name, ctx) {
+            self.compile_hof_builtin(builtin, call, insn, ctx)?;
+            insn.drop(); // statement context: discard result
+            return Ok(());
+        }
+
+        if let Some(builtin) = self.fmt_builtin(call, ctx) {
+            self.compile_fmt_builtin(builtin, call, insn, ctx)?;
+            insn.drop(); // statement context: discard result
+            return Ok(());
+        }
+
+        if let Some(builtin) = self.math_builtin(&call.name, ctx) {
+            self.compile_math_builtin(builtin, call, insn, ctx)?;
+            insn.drop(); // statement context: discard result
+            return Ok(());
+        }
+
+        if let Some(builtin) = self.coroutine_builtin(call, ctx) {
+            self.compile_coroutine_builtin(builtin, call, insn, ctx)?;
+            insn.drop(); // statement context: discard result
+            return Ok(());
+        }
+
+        self.check_call_args(call, ctx)?;
+
         // Compile arguments
         for arg in &call.args {
             self.compile_expr(arg, insn, ctx)?;
@@ -338,14 +600,17 @@ impl WasmCodegen {
                 ));
             }
             insn.call(func_idx);
+            self.emit_error_check(insn, ctx);
             // If the function returns values, drop them (this is a statement call)
             for _ in results {
                 insn.drop();
             }
-        } else if let Some(&local_idx) = ctx.locals.get(&call.name) {
+        } else if ctx.locals.contains_key(&call.name) {
             // Check if it is a closure variable
-            if let Some(AhType::Closure(closure_id)) = ctx.var_types.get(&call.name) {
-                self.emit_closure_call_indirect(*closure_id, local_idx, call, insn, ctx)?;
+            if let Some(&ty) = ctx.var_types.get(&call.name)
+                && self.callable_signature(ty).is_some()
+            {
+                self.emit_closure_call_indirect(ty, call, false, insn, ctx)?;
                 // call_indirect returns i64; drop it for statement context
                 insn.drop();
             } else {
@@ -370,8 +635,38 @@ impl WasmCodegen {
         insn: &mut wasm_encoder::InstructionSink<'_>,
         ctx: &mut FuncCtx,
     ) -> Result<(), CodegenError> {
-        // If no owned tables, compile normally (no cleanup needed)
-        if ctx.owned_tables.is_empty() {
+        if ctx.generator.is_some() {
+            return self.compile_gen_return(ret, insn, ctx);
+        }
+        if ctx.in_defer {
+            return Err(codegen_err("return cannot be used inside a defer block", &ret.span));
+        }
+        // Every return with values has its temps claimed, whichever way it compiles
+        let temps = if ret.values.is_empty() {
+            Vec::new()
+        } else {
+            ctx.return_save_temps_cursor += 1;
+            ctx.return_save_temps[ctx.return_save_temps_cursor - 1].clone()
+        };
+
+        // If no owned tables or deferred bodies, compile normally (no cleanup needed)
+        if ctx.owned_tables.is_empty() && ctx.defers.is_empty() {
+            // `return f(...)`: nothing is left to do after the call, so it can
+            // replace this function's frame, unless a `catch` here must see its errors
+            if let [Expr::CallFunc(call)] = ret.values.as_slice()
+                && ctx.catch_depths.is_empty()
+            {
+                if let Some((name, loop_depth)) = &ctx.self_loop
+                    && *name == call.name
+                    && !ctx.locals.contains_key(name)
+                {
+                    let loop_depth = *loop_depth;
+                    return self.compile_self_tail_call(call, loop_depth, insn, ctx);
+                }
+                self.compile_call_func_expr(call, self.tail_calls, insn, ctx)?;
+                insn.return_();
+                return Ok(());
+            }
             for val in &ret.values {
                 self.compile_expr(val, insn, ctx)?;
             }
@@ -401,12 +696,11 @@ impl WasmCodegen {
             }
 
             // Save return values to pre-allocated temp locals
-            let temps = ctx.return_save_temps[ctx.return_save_temps_cursor].clone();
-            ctx.return_save_temps_cursor += 1;
             for i in (0..ret.values.len()).rev() {
                 insn.local_set(temps[i]);
             }
 
+            self.emit_pending_defers(0, insn, ctx)?;
             // Free owned tables (skip the one being returned)
             self.emit_table_cleanup(insn, ctx, skip_var.as_deref());
 
@@ -416,6 +710,7 @@ impl WasmCodegen {
             }
         } else {
             // No return value, just cleanup
+            self.emit_pending_defers(0, insn, ctx)?;
             self.emit_table_cleanup(insn, ctx, None);
         }
 
@@ -423,6 +718,38 @@ impl WasmCodegen {
         Ok(())
     }
 
+    /// `return f(...)` inside `f` when tail calls are off: set the parameters to the
+    /// arguments and branch back to the loop around the body.
+    fn compile_self_tail_call(
+        &self,
+        call: &CallFunc,
+        loop_depth: u32,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let (_, type_idx) = self.func_map[&call.name];
+        let arity = self.types[type_idx as usize].0.len();
+        if call.args.len() != arity {
+            return Err(codegen_err(
+                format!(
+                    "function '{}' expects {} argument(s), but {} were given",
+                    call.name, arity, call.args.len()
+                ),
+                &call.span,
+            ));
+        }
+        self.check_call_args(call, ctx)?;
+        // Every argument is evaluated before any parameter changes
+        for arg in &call.args {
+            self.compile_expr(arg, insn, ctx)?;
+        }
+        for param in (0..arity as u32).rev() {
+            insn.local_set(param);
+        }
+        insn.br(ctx.block_depth - loop_depth);
+        Ok(())
+    }
+
     /// Emit table_free calls for all owned table variables (except skip_var and captured tables).
     pub(super) fn emit_table_cleanup(
         &self,
@@ -484,21 +811,23 @@ impl WasmCodegen {
         insn: &mut wasm_encoder::InstructionSink<'_>,
         ctx: &mut FuncCtx,
     ) -> Result<(), CodegenError> {
-        // Reject assigning a table to a field (prevents cycles, ensures tree structure)
+        if let Some(&AhType::Struct(id)) = ctx.var_types.get(&fa.object) {
+            return self.compile_struct_field_assign(id, fa, insn, ctx);
+        }
+        // Reject assigning a table or list to a field (prevents cycles, ensures tree structure)
         let val_ty = self.infer_expr_type(&fa.value, ctx);
-        if matches!(val_ty, AhType::Table(_)) {
+        if matches!(val_ty, AhType::Table(_) | AhType::List(_)) {
             return Err(codegen_err(
-                "cannot assign a table to a field; nest tables in the literal instead",
+                format!("cannot assign a {} to a field; nest it in the literal instead", val_ty.name()),
                 &fa.span,
             ));
         }
 
+        self.field_checks.borrow_mut().assigned.insert(fa.field.clone());
         let (table_set_idx, _) = self.func_map["__env_table_set"];
 
         // Push object (table_id)
-        if let Some(idx) = ctx.get_local(&fa.object) {
-            insn.local_get(idx);
-        } else {
+        if !ctx.emit_get(&fa.object, insn) {
             return Err(codegen_err(
                 format!("undefined variable: {}", fa.object),
                 &fa.span,
@@ -533,9 +862,7 @@ impl WasmCodegen {
         if obj_ty == AhType::Vec {
             // v[i] = expr → vec_set(v, i, f64_bits)
             let (vec_set_idx, _) = self.func_map["__env_vec_set"];
-            if let Some(idx) = ctx.get_local(&ia.object) {
-                insn.local_get(idx);
-            } else {
+            if !ctx.emit_get(&ia.object, insn) {
                 return Err(codegen_err(
                     format!("undefined variable: {}", ia.object),
                     &ia.span,
@@ -550,10 +877,7 @@ impl WasmCodegen {
         }
         if obj_ty == AhType::Mat {
             // m[idx] = expr → mat_set(m, idx, f64_bits)
-            let (mat_set_idx, _) = self.func_map["__env_mat_set"];
-            if let Some(idx) = ctx.get_local(&ia.object) {
-                insn.local_get(idx);
-            } else {
+            if !ctx.emit_get(&ia.object, insn) {
                 return Err(codegen_err(
                     format!("undefined variable: {}", ia.object),
                     &ia.span,
@@ -563,25 +887,31 @@ impl WasmCodegen {
             // Compile value and ensure it's f64 bits
             self.emit_float_operand(&ia.value, insn, ctx)?;
             insn.i64_reinterpret_f64();
-            insn.call(mat_set_idx);
+            self.emit_raising_call("__env_mat_set", insn, ctx);
             return Ok(());
         }
 
-        // Reject assigning a table to an index (prevents cycles, ensures tree structure)
-        let val_ty = self.infer_expr_type(&ia.value, ctx);
-        if matches!(val_ty, AhType::Table(_)) {
+        if obj_ty == AhType::Str {
             return Err(codegen_err(
-                "cannot assign a table to a field; nest tables in the literal instead",
+                "strings are immutable; build a new string with slicing and '+'",
                 &ia.span,
             ));
         }
+        if let AhType::List(_) = obj_ty {
+            return self.compile_list_index_assign(ia, obj_ty, insn, ctx);
+        }
 
-        let (table_set_idx, _) = self.func_map["__env_table_set"];
+        // Reject assigning a table or list to an index (prevents cycles, ensures tree structure)
+        let val_ty = self.infer_expr_type(&ia.value, ctx);
+        if matches!(val_ty, AhType::Table(_) | AhType::List(_)) {
+            return Err(codegen_err(
+                format!("cannot assign a {} to a field; nest it in the literal instead", val_ty.name()),
+                &ia.span,
+            ));
+        }
 
         // Push object (table_id)
-        if let Some(idx) = ctx.get_local(&ia.object) {
-            insn.local_get(idx);
-        } else {
+        if !ctx.emit_get(&ia.object, insn) {
             return Err(codegen_err(
                 format!("undefined variable: {}", ia.object),
                 &ia.span,
@@ -589,13 +919,13 @@ impl WasmCodegen {
         }
 
         // Push key expression
-        self.compile_expr(&ia.index, insn, ctx)?;
+        self.note_table_write(obj_ty, &ia.index, ctx);
+        let (_, set) = self.compile_table_key(&ia.index, insn, ctx)?;
 
         // Push value
         self.compile_expr(&ia.value, insn, ctx)?;
 
-        // Call table_set
-        insn.call(table_set_idx);
+        insn.call(self.func_map[set].0);
         Ok(())
     }
 }
@@ -0,0 +1,74 @@
+use super::*;
+
+/// A string library builtin: source name, host import, arity and result type.
+pub(super) struct StrBuiltin {
+    pub(super) name: &'static str,
+    pub(super) import: &'static str,
+    pub(super) arity: usize,
+    pub(super) result: AhType,
+}
+
+const STR_BUILTINS: &[StrBuiltin] = &[
+    StrBuiltin { name: "find", import: "__env_str_find", arity: 2, result: AhType::Int },
+    StrBuiltin { name: "contains", import: "__env_str_contains", arity: 2, result: AhType::Int },
+    StrBuiltin { name: "starts_with", import: "__env_str_starts_with", arity: 2, result: AhType::Int },
+    StrBuiltin { name: "ends_with", import: "__env_str_ends_with", arity: 2, result: AhType::Int },
+    StrBuiltin { name: "replace", import: "__env_str_replace", arity: 3, result: AhType::Str },
+    StrBuiltin { name: "split", import: "__env_str_split", arity: 2, result: AhType::Table(STR_LIST_TABLE_TYPE) },
+    StrBuiltin { name: "join", import: "__env_str_join", arity: 2, result: AhType::Str },
+    StrBuiltin { name: "upper", import: "__env_str_upper", arity: 1, result: AhType::Str },
+    StrBuiltin { name: "lower", import: "__env_str_lower", arity: 1, result: AhType::Str },
+    StrBuiltin { name: "trim", import: "__env_str_trim", arity: 1, result: AhType::Str },
+    StrBuiltin { name: "parse_int", import: "__env_parse_int", arity: 1, result: AhType::Int },
+    StrBuiltin { name: "parse_float", import: "__env_parse_float", arity: 1, result: AhType::Float },
+];
+
+impl WasmCodegen {
+    /// Look up a string library builtin by name.
+    pub(super) fn str_builtin(&self, name: &str, ctx: &FuncCtx) -> Option<&'static StrBuiltin> {
+        if self.builtin_shadowed(name, ctx) {
+            return None;
+        }
+        STR_BUILTINS.iter().find(|b| b.name == name)
+    }
+
+    /// Compile a call to a string library builtin, leaving its result on the stack.
+    pub(super) fn compile_str_builtin(
+        &self,
+        builtin: &StrBuiltin,
+        call: &CallFunc,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        if call.args.len() != builtin.arity {
+            return Err(codegen_err(
+                format!(
+                    "function '{}' expects {} argument(s), but {} were given",
+                    builtin.name, builtin.arity, call.args.len()
+                ),
+                &call.span,
+            ));
+        }
+
+        for (i, arg) in call.args.iter().enumerate() {
+            // join's first argument is the table of parts; everything else is a string.
+            let arg_ty = self.infer_expr_type(arg, ctx);
+            let (ok, expected) = if builtin.name == "join" && i == 0 {
+                (matches!(arg_ty, AhType::Table(_)) || self.is_unknown(arg, arg_ty, ctx), "table")
+            } else {
+                (arg_ty == AhType::Str || self.is_unknown(arg, arg_ty, ctx), "string")
+            };
+            if !ok {
+                return Err(codegen_err(
+                    format!("argument {} of '{}' must be a {}", i + 1, builtin.name, expected),
+                    &call.span,
+                ));
+            }
+            self.compile_expr(arg, insn, ctx)?;
+        }
+
+        let (func_idx, _) = self.func_map[builtin.import];
+        insn.call(func_idx);
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+use super::*;
+
+/// Built-in type names a struct field may declare besides other structs.
+const FIELD_TYPE_NAMES: &[&str] = &["int", "bool", "float", "f64", "str", "string", "vec", "mat", "matrix"];
+
+impl WasmCodegen {
+    /// Phase 0a: register every struct and enum declaration and resolve their
+    /// layouts, so their names are usable as types anywhere in the program.
+    pub(super) fn collect_type_decls(&mut self, program: &Program) -> Result<(), CodegenError> {
+        // Pass 1: names, so fields and payloads can refer to types declared later
+        for stmt in &program.statements {
+            let (kind, name, span) = match stmt {
+                Statement::StructDecl(decl) => ("struct", &decl.name, &decl.span),
+                Statement::EnumDecl(decl) => ("enum", &decl.name, &decl.span),
+                _ => continue,
+            };
+            if self.struct_map.contains_key(name) || self.enum_map.contains_key(name) {
+                return Err(codegen_err(format!("{} '{}' is already declared", kind, name), span));
+            }
+            if FIELD_TYPE_NAMES.contains(&name.as_str()) || name == compile_try::ERROR_STRUCT {
+                return Err(codegen_err(
+                    format!("'{}' is a built-in type and cannot name a {}", name, kind),
+                    span,
+                ));
+            }
+            if kind == "struct" {
+                self.struct_map.insert(name.clone(), self.structs.len() as u32);
+                self.structs.push(StructInfo {
+                    name: name.clone(),
+                    fields: Vec::new(),
+                });
+            } else {
+                self.enum_map.insert(name.clone(), self.enums.len() as u32);
+                self.enums.push(EnumInfo {
+                    name: name.clone(),
+                    variants: Vec::new(),
+                });
+            }
+        }
+        self.register_error_type();
+
+        self.collect_struct_fields(program)?;
+        self.collect_enum_variants(program)?;
+
+        // Text pieces used by the generated formatters
+        for id in 0..self.structs.len() {
+            for piece in self.struct_fmt_pieces(id as u32) {
+                self.intern_string(&piece);
+            }
+        }
+        for id in 0..self.enums.len() {
+            for piece in self.enum_fmt_pieces(id as u32) {
+                self.intern_string(&piece);
+            }
+        }
+        Ok(())
+    }
+
+    /// Type named in a struct field or enum payload: a declared struct or enum, or a built-in.
+    pub(super) fn decl_type(&self, type_name: &str) -> Option<AhType> {
+        if let Some(&id) = self.struct_map.get(type_name) {
+            Some(AhType::Struct(id))
+        } else if let Some(&id) = self.enum_map.get(type_name) {
+            Some(AhType::Enum(id))
+        } else if FIELD_TYPE_NAMES.contains(&type_name) {
+            Some(type_name_to_ah(type_name))
+        } else if type_name.starts_with("fn(") {
+            Some(self.resolve_func_type(type_name))
+        } else {
+            None
+        }
+    }
+
+    /// Resolve struct field types; each field takes one 8-byte slot in declaration order.
+    fn collect_struct_fields(&mut self, program: &Program) -> Result<(), CodegenError> {
+        let decls: Vec<&StructDecl> = program
+            .statements
+            .iter()
+            .filter_map(|s| match s {
+                Statement::StructDecl(decl) => Some(decl),
+                _ => None,
+            })
+            .collect();
+
+        for (id, decl) in decls.iter().enumerate() {
+            let mut fields: Vec<(String, AhType)> = Vec::new();
+            for field in &decl.fields {
+                if fields.iter().any(|(name, _)| name == &field.name) {
+                    return Err(codegen_err(
+                        format!("duplicate field '{}' in struct '{}'", field.name, decl.name),
+                        &field.span,
+                    ));
+                }
+                let Some(ty) = self.decl_type(&field.type_name) else {
+                    return Err(codegen_err(
+                        format!("unknown type '{}' for field '{}'", field.type_name, field.name),
+                        &field.span,
+                    ));
+                };
+                fields.push((field.name.clone(), ty));
+            }
+            self.structs[id].fields = fields;
+        }
+
+        // A struct holding itself (directly or through other structs) could never be built
+        for (id, decl) in decls.iter().enumerate() {
+            if self.struct_reaches(id as u32, id as u32, &mut HashSet::new()) {
+                return Err(codegen_err(
+                    format!("struct '{}' contains itself", decl.name),
+                    &decl.span,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a value of struct `from` contains a `target` through its fields.
+    fn struct_reaches(&self, from: u32, target: u32, seen: &mut HashSet<u32>) -> bool {
+        if !seen.insert(from) {
+            return false;
+        }
+        self.structs[from as usize].fields.iter().any(|(_, ty)| match ty {
+            AhType::Struct(sid) => *sid == target || self.struct_reaches(*sid, target, seen),
+            _ => false,
+        })
+    }
+
+    /// Resolve a source-level type name, including declared struct and enum names.
+    pub(super) fn resolve_type(&self, name: &str) -> AhType {
+        if let Some(&id) = self.struct_map.get(name) {
+            AhType::Struct(id)
+        } else if let Some(&id) = self.enum_map.get(name) {
+            AhType::Enum(id)
+        } else if name.starts_with("fn(") {
+            self.resolve_func_type(name)
+        } else if let Some(value) = name.strip_prefix("coroutine(").and_then(|rest| rest.strip_suffix(')')) {
+            self.coroutine_type(self.resolve_type(value))
+        } else if name == "coroutine" {
+            self.coroutine_type(AhType::Int)
+        } else if let Some(element) = name.strip_prefix("...") {
+            self.variadic_type(element)
+        } else {
+            type_name_to_ah(name)
+        }
+    }
+
+    /// Type name for diagnostics; structs and enums are shown by their declared name.
+    pub(super) fn type_display(&self, ty: AhType) -> Cow<'_, str> {
+        match ty {
+            AhType::Struct(id) => Cow::Borrowed(&self.structs[id as usize].name),
+            AhType::Enum(id) => Cow::Borrowed(&self.enums[id as usize].name),
+            AhType::Func(id) => Cow::Owned(self.func_type_display(id)),
+            AhType::Coroutine(id) => Cow::Owned(format!("coroutine({})", self.type_display(self.func_types.borrow()[id as usize].ret))),
+            AhType::NIL => Cow::Borrowed("nil"),
+            AhType::Optional(_) => Cow::Owned(format!("{}?", self.type_display(ty.unwrap_optional()))),
+            _ => Cow::Borrowed(ty.name()),
+        }
+    }
+
+    /// Slot index and type of a struct field, or an error naming the struct.
+    pub(super) fn struct_field(&self, id: u32, field: &str, span: &Span) -> Result<(usize, AhType), CodegenError> {
+        let info = &self.structs[id as usize];
+        info.field(field).ok_or_else(|| {
+            codegen_err(format!("struct '{}' has no field '{}'", info.name, field), span)
+        })
+    }
+
+    pub(super) fn slot_arg(slot: usize) -> MemArg {
+        MemArg {
+            offset: (slot * 8) as u64,
+            align: 3,
+            memory_index: 0,
+        }
+    }
+
+    /// Compile a value stored into a struct field, checked against the field type.
+    fn compile_field_value(
+        &self,
+        id: u32,
+        field: &str,
+        value: &Expr,
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let (_, expected) = self.struct_field(id, field, span)?;
+        let what = format!("field '{}' of '{}'", field, self.structs[id as usize].name);
+        self.compile_slot_value(expected, value, &what, span, insn, ctx)
+    }
+
+    /// Compile a value stored into a typed 8-byte slot; ints widen to float slots.
+    /// `what` names the slot in the mismatch error.
+    pub(super) fn compile_slot_value(
+        &self,
+        expected: AhType,
+        value: &Expr,
+        what: &str,
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let actual = self.infer_expr_type(value, ctx);
+        match (expected, actual) {
+            (AhType::Float, AhType::Int) => {
+                self.emit_float_operand(value, insn, ctx)?;
+                insn.i64_reinterpret_f64();
+                Ok(())
+            }
+            _ if slot_accepts(expected, actual) => self.compile_expr(value, insn, ctx),
+            _ => Err(codegen_err(
+                format!(
+                    "{} expects {}, found {}",
+                    what,
+                    self.type_display(expected),
+                    self.type_display(actual)
+                ),
+                span,
+            )),
+        }
+    }
+
+    /// Compile `new Name { field: value, ... }`: every field must be given exactly
+    /// once. The fields are laid out in declaration order, 8 bytes each, on the heap.
+    pub(super) fn compile_new(
+        &self,
+        new: &NewExpr,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let Some(&id) = self.struct_map.get(&new.type_name) else {
+            return Err(codegen_err(format!("unknown struct '{}'", new.type_name), &new.span));
+        };
+        let info = &self.structs[id as usize];
+        let mut given = vec![false; info.fields.len()];
+        for entry in &new.fields {
+            let (slot, _) = self.struct_field(id, &entry.key, &new.span)?;
+            if given[slot] {
+                return Err(codegen_err(
+                    format!("field '{}' is given twice", entry.key),
+                    &new.span,
+                ));
+            }
+            given[slot] = true;
+        }
+        if let Some(slot) = given.iter().position(|g| !g) {
+            return Err(codegen_err(
+                format!("missing field '{}' in 'new {}'", info.fields[slot].0, info.name),
+                &new.span,
+            ));
+        }
+
+        // Reserve the slots before evaluating initializers, which may allocate too
+        let (ptr, _) = ctx.claim_math_temps();
+        insn.global_get(0);
+        insn.i64_extend_i32_u();
+        insn.local_set(ptr);
+        insn.global_get(0);
+        insn.i32_const((info.fields.len() * 8) as i32);
+        insn.i32_add();
+        insn.global_set(0);
+
+        // Initializers run in source order
+        for entry in &new.fields {
+            let (slot, _) = self.struct_field(id, &entry.key, &new.span)?;
+            insn.local_get(ptr);
+            insn.i32_wrap_i64();
+            self.compile_field_value(id, &entry.key, &entry.value, &new.span, insn, ctx)?;
+            insn.i64_store(Self::slot_arg(slot));
+        }
+        insn.local_get(ptr);
+        Ok(())
+    }
+
+    /// Load `object.field` from a struct value.
+    pub(super) fn compile_struct_field_access(
+        &self,
+        id: u32,
+        fa: &FieldAccess,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let (slot, _) = self.struct_field(id, &fa.field, &fa.span)?;
+        self.compile_expr(&fa.object, insn, ctx)?;
+        insn.i32_wrap_i64();
+        insn.i64_load(Self::slot_arg(slot));
+        Ok(())
+    }
+
+    /// Store `name.field = value` into a struct held in a local.
+    pub(super) fn compile_struct_field_assign(
+        &self,
+        id: u32,
+        fa: &FieldAssign,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let (slot, _) = self.struct_field(id, &fa.field, &fa.span)?;
+        if !ctx.emit_get(&fa.object, insn) {
+            return Err(codegen_err(format!("undefined variable: {}", fa.object), &fa.span));
+        }
+        insn.i32_wrap_i64();
+        self.compile_field_value(id, &fa.field, &fa.value, &fa.span, insn, ctx)?;
+        insn.i64_store(Self::slot_arg(slot));
+        Ok(())
+    }
+
+    /// `object.field++` / `--` on a struct: leaves the old value on the stack.
+    pub(super) fn compile_struct_field_step(
+        &self,
+        op: UnaryOp,
+        id: u32,
+        fa: &FieldAccess,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let (slot, ty) = self.struct_field(id, &fa.field, &fa.span)?;
+        let (object, _) = ctx.claim_math_temps();
+        let (old, _) = ctx.claim_math_temps();
+        self.compile_expr(&fa.object, insn, ctx)?;
+        insn.local_set(object);
+
+        insn.local_get(object);
+        insn.i32_wrap_i64();
+        insn.i64_load(Self::slot_arg(slot));
+        insn.local_set(old);
+
+        insn.local_get(object);
+        insn.i32_wrap_i64();
+        insn.local_get(old);
+        Self::emit_step(op, ty, insn);
+        insn.i64_store(Self::slot_arg(slot));
+
+        insn.local_get(old);
+        Ok(())
+    }
+
+    /// Literal text between the fields of a formatted struct:
+    /// `Name { a: `, `, b: `, ` }` (or just `Name {}` without fields).
+    fn struct_fmt_pieces(&self, id: u32) -> Vec<String> {
+        let info = &self.structs[id as usize];
+        if info.fields.is_empty() {
+            return vec![format!("{} {{}}", info.name)];
+        }
+        let mut pieces: Vec<String> = info
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| {
+                if i == 0 {
+                    format!("{} {{ {}: ", info.name, name)
+                } else {
+                    format!(", {}: ", name)
+                }
+            })
+            .collect();
+        pieces.push(" }".to_string());
+        pieces
+    }
+
+    /// Register one formatter function per struct and enum: `(ptr) -> str`.
+    pub(super) fn register_formatters(&mut self) {
+        let type_idx = self.add_type(vec![ValType::I64], vec![ValType::I64]);
+        let names: Vec<String> = self
+            .structs
+            .iter()
+            .map(|info| info.name.clone())
+            .chain(self.enums.iter().map(|info| info.name.clone()))
+            .collect();
+        for name in names {
+            self.func_map.insert(format!("__fmt_{}", name), (self.next_func_idx, type_idx));
+            self.next_func_idx += 1;
+        }
+    }
+
+    /// Function index of the formatter that renders a struct or enum value as a str.
+    pub(super) fn formatter(&self, ty: AhType) -> Option<u32> {
+        match ty {
+            AhType::Struct(_) | AhType::Enum(_) => {
+                Some(self.func_map[&format!("__fmt_{}", self.type_display(ty))].0)
+            }
+            _ => None,
+        }
+    }
+
+    /// Compile the struct formatters, in registration order. Each one renders its
+    /// fields through a host interpolation frame; nested structs call their own formatter.
+    pub(super) fn compile_struct_formatters(
+        &self,
+        function_section: &mut FunctionSection,
+        code_section: &mut CodeSection,
+    ) {
+        for (id, info) in self.structs.iter().enumerate() {
+            let (_, type_idx) = self.func_map[&format!("__fmt_{}", info.name)];
+            function_section.function(type_idx);
+
+            let mut func = Function::new(vec![]);
+            let mut insn = func.instructions();
+            insn.call(self.func_map["__env_interp_begin"].0);
+            let pieces = self.struct_fmt_pieces(id as u32);
+            for (i, piece) in pieces.iter().enumerate() {
+                self.emit_fmt_text(piece, &mut insn);
+                if let Some((_, ty)) = info.fields.get(i) {
+                    self.emit_fmt_slot(i, *ty, &mut insn);
+                }
+            }
+            insn.call(self.func_map["__env_interp_end"].0);
+            insn.end();
+            code_section.function(&func);
+        }
+    }
+
+    /// Formatter code: push an interned text piece onto the host frame.
+    pub(super) fn emit_fmt_text(&self, piece: &str, insn: &mut wasm_encoder::InstructionSink<'_>) {
+        let (offset, len) = self.string_pool[piece];
+        insn.i64_const(((offset as i64) << 32) | (len as i64));
+        insn.i64_const(compile_fmt::TAG_STR);
+        insn.call(self.func_map["__env_interp_push"].0);
+    }
+
+    /// Formatter code: push slot `slot` of the value in local 0, formatted as `ty`.
+    pub(super) fn emit_fmt_slot(&self, slot: usize, ty: AhType, insn: &mut wasm_encoder::InstructionSink<'_>) {
+        insn.local_get(0);
+        insn.i32_wrap_i64();
+        insn.i64_load(Self::slot_arg(slot));
+        if let Some(fmt) = self.formatter(ty) {
+            insn.call(fmt);
+        }
+        insn.i64_const(compile_fmt::type_tag(ty).unwrap_or_default());
+        insn.call(self.func_map["__env_interp_push"].0);
+    }
+}
@@ -0,0 +1,207 @@
+use super::*;
+
+/// Table library builtins; each takes the table as its first argument.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum TableBuiltin {
+    /// `keys(t)` — list of the keys as str: array indices first, then the rest sorted
+    Keys,
+    /// `values(t)` — list of the values, in the order of `keys`
+    Values,
+    /// `has(t, k)` — 1 if the key is present, else 0
+    Has,
+    /// `remove(t, k)` — remove and return an entry; later array elements shift down
+    Remove,
+    /// `insert(t, x)` / `insert(t, i, x)` — append to, or insert into, the array part
+    Insert,
+}
+
+impl WasmCodegen {
+    /// Look up a table library builtin. `remove` and `insert` are shared with lists,
+    /// so they only resolve here when the first argument is a table.
+    pub(super) fn table_builtin(&self, call: &CallFunc, ctx: &FuncCtx) -> Option<TableBuiltin> {
+        if self.builtin_shadowed(&call.name, ctx) {
+            return None;
+        }
+        let on_table = call
+            .args
+            .first()
+            .is_some_and(|arg| matches!(self.infer_expr_type(arg, ctx), AhType::Table(_)));
+        match call.name.as_str() {
+            "keys" => Some(TableBuiltin::Keys),
+            "values" => Some(TableBuiltin::Values),
+            "has" => Some(TableBuiltin::Has),
+            "remove" if on_table => Some(TableBuiltin::Remove),
+            "insert" if on_table => Some(TableBuiltin::Insert),
+            _ => None,
+        }
+    }
+
+    /// Result type of a table builtin.
+    pub(super) fn table_builtin_result(&self, builtin: TableBuiltin, call: &CallFunc, ctx: &FuncCtx) -> AhType {
+        let table_ty = call
+            .args
+            .first()
+            .map_or(AhType::Int, |arg| self.infer_expr_type(arg, ctx));
+        match builtin {
+            // Reserved type 0 has str elements, so it doubles as a list of str
+            TableBuiltin::Keys => AhType::List(STR_LIST_TABLE_TYPE),
+            TableBuiltin::Values => {
+                let key = call.span;
+                AhType::List(self.table_type_span_map.get(&key).copied().unwrap_or(ANY_LIST_TYPE))
+            }
+            TableBuiltin::Has => AhType::Int,
+            TableBuiltin::Remove => match call.args.get(1) {
+                Some(key) => self.table_entry_type(table_ty, key),
+                None => AhType::Int,
+            },
+            TableBuiltin::Insert => table_ty,
+        }
+    }
+
+    /// Type of the entry `t[key]`: a literal field's type, or the type of the
+    /// integer-keyed entries. Unknown entries fall back to int.
+    pub(super) fn table_entry_type(&self, table_ty: AhType, key: &Expr) -> AhType {
+        let AhType::Table(id) = table_ty else {
+            return AhType::Int;
+        };
+        let Some(info) = self.table_types.get(id as usize) else {
+            return AhType::Int;
+        };
+        match key {
+            Expr::StringLit(name, _) => info.fields.get(name).copied().unwrap_or(AhType::Int),
+            _ => info.elements.unwrap_or(AhType::Int),
+        }
+    }
+
+    /// Static type shared by every known entry of a table, if there is one.
+    pub(super) fn table_value_type(&self, table_ty: AhType) -> Option<AhType> {
+        let AhType::Table(id) = table_ty else {
+            return None;
+        };
+        let info = self.table_types.get(id as usize)?;
+        let mut types = info.fields.values().copied().chain(info.elements);
+        let first = types.next()?;
+        types.all(|ty| ty == first).then_some(first)
+    }
+
+    /// Compile a table key and return the host functions that read and write the
+    /// entry: int keys go straight to the array part, anything else is a string key.
+    pub(super) fn compile_table_key(
+        &self,
+        key: &Expr,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(&'static str, &'static str), CodegenError> {
+        self.compile_expr(key, insn, ctx)?;
+        if self.infer_expr_type(key, ctx) == AhType::Int {
+            return Ok(("__env_table_geti", "__env_table_seti"));
+        }
+        Ok(("__env_table_get", "__env_table_set"))
+    }
+
+    /// Compile a key followed by its tag, for host functions taking either kind.
+    fn compile_tagged_key(
+        &self,
+        key: &Expr,
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let ty = self.infer_expr_type(key, ctx);
+        if !matches!(ty, AhType::Int | AhType::Str) || matches!(key, Expr::BooleanExpr(_)) {
+            return Err(codegen_err(
+                format!("table key must be an int or str, found {}", self.type_display(ty)),
+                span,
+            ));
+        }
+        self.compile_expr(key, insn, ctx)?;
+        insn.i64_const(compile_fmt::type_tag(ty).unwrap_or_default());
+        Ok(())
+    }
+
+    /// Compile a call to a table library builtin, leaving its result on the stack.
+    pub(super) fn compile_table_builtin(
+        &self,
+        builtin: TableBuiltin,
+        call: &CallFunc,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let arity = match builtin {
+            TableBuiltin::Keys | TableBuiltin::Values => 1..=1,
+            TableBuiltin::Has | TableBuiltin::Remove => 2..=2,
+            TableBuiltin::Insert => 2..=3,
+        };
+        if !arity.contains(&call.args.len()) {
+            return Err(codegen_err(
+                format!(
+                    "function '{}' expects {} argument(s), but {} were given",
+                    call.name,
+                    if arity.start() == arity.end() {
+                        arity.start().to_string()
+                    } else {
+                        format!("{} or {}", arity.start(), arity.end())
+                    },
+                    call.args.len()
+                ),
+                &call.span,
+            ));
+        }
+        let table_ty = self.infer_expr_type(&call.args[0], ctx);
+        if !matches!(table_ty, AhType::Table(_)) && !self.is_unknown(&call.args[0], table_ty, ctx) {
+            return Err(codegen_err(
+                format!(
+                    "argument 1 of '{}' must be a table, found {}",
+                    call.name,
+                    self.type_display(table_ty)
+                ),
+                &call.span,
+            ));
+        }
+
+        self.compile_expr(&call.args[0], insn, ctx)?;
+        let import = match builtin {
+            TableBuiltin::Keys => "__env_table_keys",
+            TableBuiltin::Values => {
+                // Tables store no tags, so the values are tagged with their static type
+                let ty = self.table_value_type(table_ty).unwrap_or(AhType::Int);
+                insn.i64_const(self.element_tag(ty));
+                "__env_table_values"
+            }
+            TableBuiltin::Has => {
+                self.compile_tagged_key(&call.args[1], &call.span, insn, ctx)?;
+                "__env_table_has"
+            }
+            TableBuiltin::Remove => {
+                self.compile_tagged_key(&call.args[1], &call.span, insn, ctx)?;
+                "__env_table_remove"
+            }
+            TableBuiltin::Insert => {
+                let value = call.args.last().unwrap();
+                let value_ty = self.infer_expr_type(value, ctx);
+                if matches!(value_ty, AhType::Table(_) | AhType::List(_)) {
+                    return Err(codegen_err(
+                        format!("cannot assign a {} to a field; nest it in the literal instead", value_ty.name()),
+                        &call.span,
+                    ));
+                }
+                if call.args.len() == 3 {
+                    let index_ty = self.infer_expr_type(&call.args[1], ctx);
+                    if index_ty != AhType::Int || matches!(call.args[1], Expr::BooleanExpr(_)) {
+                        return Err(codegen_err(
+                            format!("table index must be an int, found {}", self.type_display(index_ty)),
+                            &call.span,
+                        ));
+                    }
+                    self.compile_expr(&call.args[1], insn, ctx)?;
+                } else {
+                    insn.i64_const(-1);
+                }
+                self.compile_expr(value, insn, ctx)?;
+                "__env_table_insert"
+            }
+        };
+        self.emit_raising_call(import, insn, ctx);
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+use super::*;
+
+/// Name of the built-in struct that carries a raised error.
+pub(super) const ERROR_STRUCT: &str = "Error";
+
+/// Global holding the address of the pending `Error`, or 0 when nothing is raised.
+pub(super) const ERROR_GLOBAL: u32 = 1;
+
+/// `Error.code` of an error thrown by the script with a str message
+const ERROR_THROWN: i64 = 0;
+/// `Error.code` of operands whose sizes do not match
+const ERROR_SIZE: i64 = 2;
+/// `Error.code` of an integer division or remainder by zero
+const ERROR_DIVISION: i64 = 4;
+
+const SIZE_MESSAGE: &str = "dimension mismatch";
+const DIVISION_MESSAGE: &str = "division by zero";
+
+impl WasmCodegen {
+    /// Register the `Error { message: str, code: int }` struct after the declared ones,
+    /// and the messages of the errors raised by generated code.
+    pub(super) fn register_error_type(&mut self) {
+        self.struct_map.insert(ERROR_STRUCT.to_string(), self.structs.len() as u32);
+        self.structs.push(StructInfo {
+            name: ERROR_STRUCT.to_string(),
+            fields: vec![("message".to_string(), AhType::Str), ("code".to_string(), AhType::Int)],
+        });
+        self.intern_string(SIZE_MESSAGE);
+        self.intern_string(DIVISION_MESSAGE);
+    }
+
+    /// Type of the value bound by `catch (e)`.
+    pub(super) fn error_type(&self) -> AhType {
+        AhType::Struct(self.struct_map[ERROR_STRUCT])
+    }
+
+    /// `__throw(message, code)` comes after the formatters in the function index space.
+    pub(super) fn register_throw(&mut self) {
+        let type_idx = self.add_type(vec![ValType::I64, ValType::I64], vec![]);
+        self.func_map.insert("__throw".to_string(), (self.next_func_idx, type_idx));
+        self.next_func_idx += 1;
+    }
+
+    /// Compile `__throw`: build an `Error` on the heap and make it the pending one.
+    pub(super) fn compile_throw_func(&self, function_section: &mut FunctionSection, code_section: &mut CodeSection) {
+        let (_, type_idx) = self.func_map["__throw"];
+        function_section.function(type_idx);
+
+        let mut func = Function::new(vec![]);
+        let mut insn = func.instructions();
+        for (slot, param) in [0u32, 1].into_iter().enumerate() {
+            insn.global_get(0);
+            insn.local_get(param);
+            insn.i64_store(Self::slot_arg(slot));
+        }
+        insn.global_get(0);
+        insn.global_set(ERROR_GLOBAL);
+        insn.global_get(0);
+        insn.i32_const(16);
+        insn.i32_add();
+        insn.global_set(0);
+        insn.end();
+        code_section.function(&func);
+    }
+
+    /// Leave for the innermost handler, or return from the function when there is
+    /// none. `open` counts the blocks entered since `ctx.block_depth` was last updated.
+    fn emit_unwind(&self, insn: &mut wasm_encoder::InstructionSink<'_>, ctx: &FuncCtx, open: u32) {
+        if let Some(&handler) = ctx.catch_depths.last() {
+            insn.br(ctx.block_depth + open - handler);
+            return;
+        }
+        if let Some(generator) = ctx.generator {
+            Self::emit_gen_finish(&generator, insn);
+        }
+        self.emit_table_cleanup(insn, ctx, None);
+        for _ in 0..ctx.result_count {
+            insn.i64_const(0);
+        }
+        insn.return_();
+    }
+
+    /// After a call that may raise: unwind when an error is pending.
+    pub(super) fn emit_error_check(&self, insn: &mut wasm_encoder::InstructionSink<'_>, ctx: &FuncCtx) {
+        insn.global_get(ERROR_GLOBAL);
+        insn.if_(BlockType::Empty);
+        self.emit_unwind(insn, ctx, 1);
+        insn.end();
+    }
+
+    /// Call a host function that raises its errors, then check for one.
+    pub(super) fn emit_raising_call(&self, name: &str, insn: &mut wasm_encoder::InstructionSink<'_>, ctx: &FuncCtx) {
+        insn.call(self.func_map[name].0);
+        self.emit_error_check(insn, ctx);
+    }
+
+    /// Raise one of the errors of generated code, from inside `open` blocks.
+    fn emit_raise(&self, code: i64, message: &str, insn: &mut wasm_encoder::InstructionSink<'_>, ctx: &FuncCtx, open: u32) {
+        let (offset, len) = self.string_pool[message];
+        insn.i64_const(((offset as i64) << 32) | len as i64);
+        insn.i64_const(code);
+        insn.call(self.func_map["__throw"].0);
+        self.emit_unwind(insn, ctx, open);
+    }
+
+    /// With an integer divisor on the stack, raise when it is zero.
+    pub(super) fn emit_divisor_check(&self, insn: &mut wasm_encoder::InstructionSink<'_>, ctx: &FuncCtx) {
+        let divisor = ctx.simd_helpers[0];
+        insn.local_tee(divisor);
+        insn.i64_eqz();
+        insn.if_(BlockType::Empty);
+        self.emit_raise(ERROR_DIVISION, DIVISION_MESSAGE, insn, ctx, 1);
+        insn.end();
+        insn.local_get(divisor);
+    }
+
+    /// With two i32 sizes on the stack, raise when they differ.
+    pub(super) fn emit_size_check(&self, insn: &mut wasm_encoder::InstructionSink<'_>, ctx: &FuncCtx) {
+        insn.i32_ne();
+        insn.if_(BlockType::Empty);
+        self.emit_raise(ERROR_SIZE, SIZE_MESSAGE, insn, ctx, 1);
+        insn.end();
+    }
+
+    /// Compile `try { body } catch (e) { handler }`. An error raised in the body
+    /// leaves it for the handler, with `e` bound to the `Error`.
+    pub(super) fn compile_try(
+        &self,
+        t: &TryStmt,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        // block $done (block $caught body br $done) handler
+        insn.block(BlockType::Empty);
+        ctx.block_depth += 1;
+        insn.block(BlockType::Empty);
+        ctx.block_depth += 1;
+        ctx.catch_depths.push(ctx.block_depth);
+        let body = self.compile_block(&t.body, insn, ctx);
+        ctx.catch_depths.pop();
+        body?;
+        insn.br(1);
+        insn.end();
+        ctx.block_depth -= 1;
+
+        if let Some(name) = &t.error_name {
+            ctx.begin_set(name, insn);
+            insn.global_get(ERROR_GLOBAL);
+            insn.i64_extend_i32_u();
+            ctx.end_set(name, insn);
+        }
+        insn.i32_const(0);
+        insn.global_set(ERROR_GLOBAL);
+        self.compile_block(&t.handler, insn, ctx)?;
+        insn.end();
+        ctx.block_depth -= 1;
+        Ok(())
+    }
+
+    /// Compile `throw expr`: a str becomes the message of a new `Error`, and an
+    /// `Error` is raised as it is.
+    pub(super) fn compile_throw(
+        &self,
+        throw: &ThrowStmt,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let ty = self.infer_expr_type(&throw.value, ctx);
+        if ty == AhType::Str {
+            self.compile_expr(&throw.value, insn, ctx)?;
+            insn.i64_const(ERROR_THROWN);
+            insn.call(self.func_map["__throw"].0);
+        } else if ty == self.error_type() {
+            self.compile_expr(&throw.value, insn, ctx)?;
+            insn.i32_wrap_i64();
+            insn.global_set(ERROR_GLOBAL);
+        } else {
+            return Err(codegen_err(
+                format!("throw needs a str message or an Error, found {}", self.type_display(ty)),
+                &throw.span,
+            ));
+        }
+        self.emit_unwind(insn, ctx, 0);
+        Ok(())
+    }
+}
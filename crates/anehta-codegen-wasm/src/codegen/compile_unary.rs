@@ -0,0 +1,197 @@
+use super::*;
+
+impl WasmCodegen {
+    /// Compile `-x`, `~x`, and `x++` / `x--` on a variable, table field or vec/mat/table element.
+    pub(super) fn compile_unary(
+        &self,
+        op: UnaryOp,
+        operand: &Expr,
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        match op {
+            UnaryOp::Neg => self.compile_neg(operand, span, insn, ctx),
+            UnaryOp::Complement => {
+                self.check_int_operand(operand, "~", span, ctx)?;
+                self.compile_expr(operand, insn, ctx)?;
+                insn.i64_const(-1);
+                insn.i64_xor();
+                Ok(())
+            }
+            UnaryOp::Increment | UnaryOp::Decrement => self.compile_step(op, operand, span, insn, ctx),
+        }
+    }
+
+    fn compile_neg(
+        &self,
+        operand: &Expr,
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        match self.infer_expr_type(operand, ctx) {
+            AhType::Int => {
+                insn.i64_const(0);
+                self.compile_expr(operand, insn, ctx)?;
+                insn.i64_sub();
+            }
+            AhType::Float => {
+                self.compile_expr(operand, insn, ctx)?;
+                insn.f64_reinterpret_i64();
+                insn.f64_neg();
+                insn.i64_reinterpret_f64();
+            }
+            AhType::Vec => {
+                self.compile_expr(operand, insn, ctx)?;
+                insn.i64_const((-1.0f64).to_bits() as i64);
+                self.emit_vec_scale_simd(insn, ctx)?;
+            }
+            AhType::Mat => {
+                self.compile_expr(operand, insn, ctx)?;
+                insn.i64_const((-1.0f64).to_bits() as i64);
+                self.emit_mat_scale_simd(insn, ctx)?;
+            }
+            ty => {
+                return Err(codegen_err(format!("cannot negate a {}", ty.name()), span));
+            }
+        }
+        Ok(())
+    }
+
+    /// Postfix `++` / `--`: store the stepped value and leave the old one on the stack.
+    fn compile_step(
+        &self,
+        op: UnaryOp,
+        operand: &Expr,
+        span: &Span,
+        insn: &mut wasm_encoder::InstructionSink<'_>,
+        ctx: &mut FuncCtx,
+    ) -> Result<(), CodegenError> {
+        let symbol = if matches!(op, UnaryOp::Increment) { "++" } else { "--" };
+        let value_ty = self.infer_expr_type(operand, ctx);
+        if !matches!(value_ty, AhType::Int | AhType::Float) {
+            return Err(codegen_err(
+                format!("cannot apply '{}' to a {}", symbol, value_ty.name()),
+                span,
+            ));
+        }
+
+        if let Expr::Variable(name, var_span) = operand {
+            let Some(idx) = ctx.get_local(name) else {
+                return Err(codegen_err(format!("undefined variable: {}", name), var_span));
+            };
+            if ctx.boxed.contains(name) {
+                let (old, _) = ctx.claim_math_temps();
+                ctx.begin_set(name, insn);
+                ctx.emit_get(name, insn);
+                insn.local_tee(old);
+                Self::emit_step(op, value_ty, insn);
+                ctx.end_set(name, insn);
+                insn.local_get(old);
+                return Ok(());
+            }
+            insn.local_get(idx);
+            insn.local_get(idx);
+            Self::emit_step(op, value_ty, insn);
+            insn.local_set(idx);
+            return Ok(());
+        }
+
+        if let Expr::FieldAccess(fa) = operand
+            && let AhType::Struct(id) = self.infer_expr_type(&fa.object, ctx)
+        {
+            return self.compile_struct_field_step(op, id, fa, insn, ctx);
+        }
+
+        // Object, key and old value live in temps so each is evaluated once
+        let (object, key) = ctx.claim_math_temps();
+        let (old, _) = ctx.claim_math_temps();
+        let (get, set) = match operand {
+            Expr::FieldAccess(fa) if matches!(self.infer_expr_type(&fa.object, ctx), AhType::Table(_)) => {
+                self.compile_expr(&fa.object, insn, ctx)?;
+                insn.local_set(object);
+                let (offset, len) = self
+                    .string_pool
+                    .get(fa.field.as_str())
+                    .copied()
+                    .unwrap_or((0, 0));
+                insn.i64_const(((offset as i64) << 32) | (len as i64));
+                insn.local_set(key);
+                ("__env_table_get", "__env_table_set")
+            }
+            Expr::IndexAccess(ia)
+                if !matches!(ia.index.as_ref(), Expr::Range { .. } | Expr::BooleanExpr(_))
+                    && self.infer_expr_type(&ia.index, ctx) != AhType::Vec =>
+            {
+                let names = match self.infer_expr_type(&ia.object, ctx) {
+                    AhType::Vec => ("__env_vec_get", "__env_vec_set"),
+                    AhType::Mat => ("__env_mat_get", "__env_mat_set"),
+                    AhType::Table(_) => ("__env_table_get", "__env_table_set"),
+                    AhType::List(_) => ("__env_list_get", "__env_list_set"),
+                    _ => {
+                        return Err(codegen_err(
+                            format!("cannot apply '{}' to an element of a {}", symbol, self.infer_expr_type(&ia.object, ctx).name()),
+                            span,
+                        ));
+                    }
+                };
+                self.compile_expr(&ia.object, insn, ctx)?;
+                insn.local_set(object);
+                let names = if names.0 == "__env_table_get" {
+                    self.compile_table_key(&ia.index, insn, ctx)?
+                } else {
+                    self.compile_expr(&ia.index, insn, ctx)?;
+                    names
+                };
+                insn.local_set(key);
+                names
+            }
+            _ => {
+                return Err(codegen_err(
+                    format!("'{}' needs a variable, table field or element", symbol),
+                    span,
+                ));
+            }
+        };
+
+        insn.local_get(object);
+        insn.local_get(key);
+        self.emit_raising_call(get, insn, ctx);
+        insn.local_set(old);
+
+        insn.local_get(object);
+        insn.local_get(key);
+        insn.local_get(old);
+        Self::emit_step(op, value_ty, insn);
+        if set == "__env_list_set" {
+            // List elements are stored with their tag
+            insn.i64_const(compile_fmt::type_tag(value_ty).unwrap_or_default());
+        }
+        insn.call(self.func_map[set].0);
+
+        insn.local_get(old);
+        Ok(())
+    }
+
+    /// Add or subtract one from the value on the stack.
+    pub(super) fn emit_step(op: UnaryOp, ty: AhType, insn: &mut wasm_encoder::InstructionSink<'_>) {
+        if ty == AhType::Float {
+            insn.f64_reinterpret_i64();
+            insn.f64_const(1.0);
+            if matches!(op, UnaryOp::Increment) {
+                insn.f64_add();
+            } else {
+                insn.f64_sub();
+            }
+            insn.i64_reinterpret_f64();
+        } else {
+            insn.i64_const(1);
+            if matches!(op, UnaryOp::Increment) {
+                insn.i64_add();
+            } else {
+                insn.i64_sub();
+            }
+        }
+    }
+}
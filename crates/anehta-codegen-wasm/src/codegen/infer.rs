@@ -1,17 +1,42 @@
 use super::*;
 
 impl WasmCodegen {
+    /// Whether `expr`, inferred as `ty`, is a value whose type is not known at
+    /// compile time. Int is the fallback for those, so type checks on builtin
+    /// arguments let an int through unless it evidently is one.
+    pub(super) fn is_unknown(&self, expr: &Expr, ty: AhType, ctx: &FuncCtx) -> bool {
+        ty == AhType::Int && !Self::evident_int(expr, ctx)
+    }
+
+    /// Int and bool literals, comparisons, variables known to hold ints, casts
+    /// to int and arithmetic on evident ints.
+    pub(super) fn evident_int(expr: &Expr, ctx: &FuncCtx) -> bool {
+        match expr {
+            Expr::Number(..) | Expr::Bool(..) | Expr::BooleanExpr(_) => true,
+            Expr::Variable(name, _) => ctx.int_vars.contains(name),
+            Expr::Grouped(inner) | Expr::UnaryOp { operand: inner, .. } => Self::evident_int(inner, ctx),
+            Expr::BinaryOp { left, right, .. } => Self::evident_int(left, ctx) && Self::evident_int(right, ctx),
+            Expr::Cast(cast) => cast.target == "int",
+            _ => false,
+        }
+    }
+
     /// Best-effort inference of the type an expression will produce at runtime.
     pub(super) fn infer_expr_type(&self, expr: &Expr, ctx: &FuncCtx) -> AhType {
         match expr {
-            Expr::StringLit(..) => AhType::Str,
+            Expr::StringLit(..) | Expr::Interpolated { .. } => AhType::Str,
             Expr::Number(s, _) => {
                 if s.contains('.') { AhType::Float } else { AhType::Int }
             }
             Expr::Bool(..) => AhType::Int,
-            Expr::Variable(name, _) => {
-                ctx.var_types.get(name).copied().unwrap_or(AhType::Int)
-            }
+            Expr::Nil(_) => AhType::NIL,
+            Expr::Variable(name, _) => match ctx.var_types.get(name) {
+                Some(&ty) => ty,
+                None if ctx.get_local(name).is_none() => {
+                    self.func_refs.get(name).map_or(AhType::Int, |func_ref| func_ref.ty)
+                }
+                None => AhType::Int,
+            },
             Expr::BinaryOp { left, op, right, .. } => {
                 let lt = self.infer_expr_type(left, ctx);
                 let rt = self.infer_expr_type(right, ctx);
@@ -46,9 +71,7 @@ impl WasmCodegen {
                             AhType::Mat
                         } else if lt == AhType::Mat && rt == AhType::Vec {
                             AhType::Vec
-                        } else if lt == AhType::Mat {
-                            AhType::Mat
-                        } else if rt == AhType::Mat {
+                        } else if lt == AhType::Mat || rt == AhType::Mat {
                             AhType::Mat
                         } else if lt == AhType::Vec || rt == AhType::Vec {
                             AhType::Vec
@@ -80,6 +103,9 @@ impl WasmCodegen {
                         }
                     }
                     BinaryOp::Rand => AhType::Int,
+                    BinaryOp::Coalesce => self.coalesce_type(lt, rt).unwrap_or(lt.unwrap_optional()),
+                    BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor
+                    | BinaryOp::Shl | BinaryOp::Shr | BinaryOp::UShr => AhType::Int,
                     BinaryOp::At => AhType::Float,
                     BinaryOp::Hash => AhType::Vec,
                     BinaryOp::Backslash => {
@@ -102,41 +128,86 @@ impl WasmCodegen {
                 if call.name == "float" && call.args.len() == 1 {
                     return AhType::Float;
                 }
+                if call.name == "len" && call.args.len() == 1 {
+                    return AhType::Int;
+                }
+                if let Some(builtin) = self.str_builtin(&call.name, ctx) {
+                    return builtin.result;
+                }
+                if let Some(builtin) = self.table_builtin(call, ctx) {
+                    return self.table_builtin_result(builtin, call, ctx);
+                }
+                if let Some(builtin) = self.list_builtin(&call.name, ctx) {
+                    return self.list_builtin_result(builtin, call, ctx);
+                }
+                if let Some(builtin) = self.hof_builtin(&call.name, ctx) {
+                    return self.hof_result_type(builtin, call, ctx);
+                }
+                if self.fmt_builtin(call, ctx) == Some(FmtBuiltin::Format) {
+                    return AhType::Str;
+                }
+                if let Some(builtin) = self.math_builtin(&call.name, ctx) {
+                    return self.math_result_type(builtin, &call.args, ctx);
+                }
+                if let Some(builtin) = self.coroutine_builtin(call, ctx) {
+                    return self.coroutine_result_type(builtin, call, ctx);
+                }
                 // Check named function return types first
                 if let Some(&ty) = self.func_return_types.get(&call.name) {
                     return ty;
                 }
-                // Check if it's a closure variable call
-                if let Some(AhType::Closure(id)) = ctx.var_types.get(&call.name) {
-                    if let Some(info) = self.closures.get(*id as usize) {
-                        return info.return_type;
-                    }
+                // Check if it's a call of a closure or function value
+                if let Some(&ty) = ctx.var_types.get(&call.name)
+                    && let Some((_, ret)) = self.callable_signature(ty) {
+                    return ret;
                 }
                 AhType::Int
             }
             Expr::Grouped(inner) => self.infer_expr_type(inner, ctx),
-            Expr::UnaryOp { operand, .. } => {
-                ctx.var_types.get(operand).copied().unwrap_or(AhType::Int)
-            }
+            Expr::UnaryOp { op: UnaryOp::Complement, .. } => AhType::Int,
+            Expr::UnaryOp { operand, .. } => self.infer_expr_type(operand, ctx),
             Expr::Closure(closure) => {
                 // Look up the closure ID by span
-                let key = (closure.span.line, closure.span.column);
+                let key = closure.span;
                 if let Some(&id) = self.closure_span_map.get(&key) {
                     AhType::Closure(id)
                 } else {
                     AhType::Int
                 }
             }
+            Expr::Match(m) => self.infer_match_type(m, ctx),
+            Expr::If(e) => {
+                let then_ty = self.infer_expr_type(&e.then_value, ctx);
+                let else_ty = self.infer_expr_type(&e.else_value, ctx);
+                unify_branch_types(then_ty, else_ty).unwrap_or(then_ty)
+            }
+            Expr::New(new) => match self.struct_map.get(&new.type_name) {
+                Some(&id) => AhType::Struct(id),
+                None => AhType::Int,
+            },
             Expr::TableLiteral(table) => {
-                let key = (table.span.line, table.span.column);
+                let key = table.span;
                 if let Some(&id) = self.table_type_span_map.get(&key) {
                     AhType::Table(id)
                 } else {
                     AhType::Int
                 }
             }
+            Expr::ListLiteral(list) => {
+                let key = list.span;
+                AhType::List(self.table_type_span_map.get(&key).copied().unwrap_or(ANY_LIST_TYPE))
+            }
+            Expr::FieldAccess(fa) if fa.safe => self.safe_field_type(fa, ctx),
             Expr::FieldAccess(fa) => {
+                if let Some(id) = self.enum_ref(&fa.object) {
+                    return AhType::Enum(id);
+                }
                 let obj_ty = self.infer_expr_type(&fa.object, ctx);
+                if let AhType::Struct(id) = obj_ty {
+                    return self.structs[id as usize]
+                        .field(&fa.field)
+                        .map_or(AhType::Int, |(_, ty)| ty);
+                }
                 // Mat field access: .T → Mat, .det/.rows/.cols → Float/Int, .inv → Mat
                 if obj_ty == AhType::Mat {
                     match fa.field.as_str() {
@@ -160,16 +231,26 @@ impl WasmCodegen {
                         return AhType::Vec;
                     }
                 }
-                if let AhType::Table(id) = obj_ty {
-                    if let Some(info) = self.table_types.get(id as usize) {
-                        return info.fields.get(&fa.field).copied().unwrap_or(AhType::Int);
-                    }
+                if let AhType::Table(id) = obj_ty
+                    && let Some(info) = self.table_types.get(id as usize) {
+                    return info.fields.get(&fa.field).copied().unwrap_or(AhType::Int);
                 }
                 AhType::Int
             }
             Expr::IndexAccess(ia) => {
                 let obj_ty = self.infer_expr_type(&ia.object, ctx);
                 let idx_ty = self.infer_expr_type(&ia.index, ctx);
+                // Strings: both s[i] and s[a..b] produce strings
+                if obj_ty == AhType::Str {
+                    return AhType::Str;
+                }
+                // Lists: a slice is a list of the same type, an element has the element type
+                if let AhType::List(_) = obj_ty {
+                    if let Expr::Range { .. } = &*ia.index {
+                        return obj_ty;
+                    }
+                    return self.element_type(obj_ty);
+                }
                 // Check if index is a Range (slicing), Vec (fancy indexing), or BooleanExpr (masking)
                 if matches!(&*ia.index, Expr::Range { .. } | Expr::BooleanExpr(_)) || idx_ty == AhType::Vec {
                     // Slicing, fancy indexing, and masking return Vec
@@ -182,25 +263,28 @@ impl WasmCodegen {
                     return obj_ty;
                 }
                 // Regular indexing
-                if obj_ty == AhType::Mat {
-                    AhType::Float  // m[i,j] → Float
-                } else if obj_ty == AhType::Vec {
-                    AhType::Float
+                if obj_ty == AhType::Mat || obj_ty == AhType::Vec {
+                    AhType::Float  // m[i,j] / v[i] → Float
                 } else {
-                    AhType::Int
+                    self.table_entry_type(obj_ty, &ia.index)
                 }
             }
             Expr::MethodCall(mc) => {
+                if let Some((id, _)) = self.variant_call(mc) {
+                    return AhType::Enum(id);
+                }
+                if let Some((name, _)) = self.method_target(mc, ctx) {
+                    return self.func_return_types.get(&name).copied().unwrap_or(AhType::Int);
+                }
                 let callee_ty = self.infer_expr_type(&mc.callee, ctx);
-                if let AhType::Closure(id) = callee_ty {
-                    if let Some(info) = self.closures.get(id as usize) {
-                        return info.return_type;
-                    }
+                if let Some((_, ret)) = self.callable_signature(callee_ty) {
+                    return ret;
                 }
                 AhType::Int
             }
             Expr::VecLiteral(_) => AhType::Vec,
             Expr::MatLiteral(_) => AhType::Mat,
+            Expr::Cast(cast) => type_name_to_ah(&cast.target),
             Expr::Transpose(t) => self.infer_expr_type(&t.operand, ctx), // Transpose preserves type (Mat→Mat, Vec→Vec)
             Expr::Range { .. } => AhType::Int, // Range is not a standalone value, only used in indexing
             Expr::BooleanExpr(_) => AhType::Int, // BooleanExpr only meaningful in masking context, not standalone
@@ -229,6 +313,12 @@ impl WasmCodegen {
                         return ty;
                     }
                 }
+                Statement::ForIn(for_in) => {
+                    let ty = Self::infer_block_return_type(&for_in.body, codegen, ctx);
+                    if ty != AhType::Int {
+                        return ty;
+                    }
+                }
                 _ => {}
             }
         }
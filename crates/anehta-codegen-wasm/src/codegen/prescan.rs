@@ -6,7 +6,11 @@ impl WasmCodegen {
         match stmt {
             Statement::VarDecl(VarDecl::TypeDecl { name, type_name, .. }) => {
                 ctx.declare_local(name);
-                ctx.var_types.insert(name.clone(), type_name_to_ah(type_name));
+                let ty = self.resolve_type(type_name);
+                if ty == AhType::Int {
+                    ctx.int_vars.insert(name.clone());
+                }
+                ctx.var_types.insert(name.clone(), ty);
             }
             Statement::VarDecl(VarDecl::Assignment(assign)) => {
                 // Check for vector destructuring: allocate temp for vec storage
@@ -24,8 +28,11 @@ impl WasmCodegen {
                     if i < assign.values.len() {
                         let ty = self.infer_expr_type(&assign.values[i], ctx);
                         ctx.var_types.insert(target.clone(), ty);
+                        if ty == AhType::Int && Self::evident_int(&assign.values[i], ctx) {
+                            ctx.int_vars.insert(target.clone());
+                        }
                         // Track table ownership
-                        if matches!(ty, AhType::Table(_))
+                        if matches!(ty, AhType::Table(_) | AhType::List(_))
                             && !ctx.param_names.contains(target)
                             && !ctx.owned_tables.contains(target)
                         {
@@ -52,8 +59,11 @@ impl WasmCodegen {
                     if i < assign.values.len() {
                         let ty = self.infer_expr_type(&assign.values[i], ctx);
                         ctx.var_types.insert(target.clone(), ty);
+                        if !Self::evident_int(&assign.values[i], ctx) {
+                            ctx.int_vars.remove(target);
+                        }
                         // Track table ownership
-                        if matches!(ty, AhType::Table(_))
+                        if matches!(ty, AhType::Table(_) | AhType::List(_))
                             && !ctx.param_names.contains(target)
                             && !ctx.owned_tables.contains(target)
                         {
@@ -88,6 +98,15 @@ impl WasmCodegen {
                 }
                 self.prescan_block(&for_stmt.body, ctx);
             }
+            Statement::ForIn(for_in) => {
+                self.prescan_expr(&for_in.iterable, ctx);
+                ctx.alloc_for_in_temps();
+                // The loop variable borrows each element; it is never freed
+                let elem_ty = self.iteration_type(&for_in.iterable, ctx);
+                ctx.declare_local(&for_in.var);
+                ctx.var_types.insert(for_in.var.clone(), elem_ty);
+                self.prescan_block(&for_in.body, ctx);
+            }
             Statement::Block(block) => {
                 self.prescan_block(block, ctx);
             }
@@ -104,9 +123,42 @@ impl WasmCodegen {
                     ctx.return_save_temps.push(temps);
                 }
             }
+            Statement::Yield(y) => {
+                if let Some(val) = &y.value {
+                    self.prescan_expr(val, ctx);
+                }
+            }
+            Statement::Try(t) => {
+                self.prescan_block(&t.body, ctx);
+                if let Some(name) = &t.error_name {
+                    ctx.declare_local(name);
+                    ctx.var_types.insert(name.clone(), self.error_type());
+                }
+                self.prescan_block(&t.handler, ctx);
+            }
+            Statement::Defer(d) => {
+                let error = ctx.alloc_anonymous_local();
+                // Nested defers come after this one, as they are compiled
+                let slot = ctx.defer_temps.len();
+                ctx.defer_temps.push(DeferTemps { error, end: ctx.temp_counts() });
+                self.prescan_block(&d.body, ctx);
+                ctx.defer_temps[slot].end = ctx.temp_counts();
+            }
+            Statement::Throw(throw) => {
+                self.prescan_expr(&throw.value, ctx);
+            }
             Statement::CallFunc(call) => {
-                for arg in &call.args {
-                    self.prescan_expr(arg, ctx);
+                self.prescan_call_args(call, ctx);
+                if compile_math::needs_math_temps(&call.name) {
+                    ctx.alloc_math_temps();
+                }
+                if compile_hof::needs_hof_temps(&call.name) {
+                    ctx.alloc_hof_temps();
+                }
+                if !self.func_map.contains_key(&call.name)
+                    && let Some(&ty) = ctx.var_types.get(&call.name)
+                    && self.callable_signature(ty).is_some() {
+                    ctx.alloc_closure_call_temps(call.args.len());
                 }
             }
             Statement::TimerStmt(timer) => {
@@ -121,12 +173,10 @@ impl WasmCodegen {
                 self.prescan_expr(&ia.value, ctx);
             }
             Statement::MethodCall(mc) => {
-                self.prescan_expr(&mc.callee, ctx);
-                for arg in &mc.args {
-                    self.prescan_expr(arg, ctx);
-                }
-                // Always allocate closure call temps: num_args + 1 (extra for closure value)
-                ctx.alloc_closure_call_temps(mc.args.len() + 1);
+                self.prescan_method_call(mc, ctx);
+            }
+            Statement::Expr(expr) => {
+                self.prescan_expr(expr, ctx);
             }
             _ => {}
         }
@@ -138,11 +188,75 @@ impl WasmCodegen {
         }
     }
 
+    fn prescan_method_call(&self, mc: &MethodCall, ctx: &mut FuncCtx) {
+        if self.variant_call(mc).is_some() {
+            // Enum.Variant(values...): a temp pointer to the new value
+            ctx.alloc_math_temps();
+        } else if let Some((_, fa)) = self.method_target(mc, ctx) {
+            // Direct call of a struct or enum method: only the receiver is evaluated
+            self.prescan_expr(&fa.object, ctx);
+        } else {
+            self.prescan_expr(&mc.callee, ctx);
+            // Always allocate closure call temps: num_args + 1 (extra for closure value),
+            // plus one for the receiver of obj:method(args)
+            ctx.alloc_closure_call_temps(mc.args.len() + 1 + usize::from(mc.pass_self));
+        }
+        for arg in &mc.args {
+            self.prescan_expr(arg, ctx);
+        }
+    }
+
+    /// Prescan the arguments of a call. A closure literal with an expression body
+    /// passed to a higher-order builtin is compiled inline, in this function.
+    fn prescan_call_args(&self, call: &CallFunc, ctx: &mut FuncCtx) {
+        let inlines = compile_hof::needs_hof_temps(&call.name) && !self.func_map.contains_key(&call.name);
+        for arg in &call.args {
+            match arg {
+                Expr::Closure(closure) if inlines && matches!(closure.body, ClosureBody::Expr(_)) => {
+                    self.prescan_closure(closure, true, ctx);
+                }
+                _ => self.prescan_expr(arg, ctx),
+            }
+        }
+    }
+
+    fn prescan_closure(&self, closure: &ClosureExpr, inline: bool, ctx: &mut FuncCtx) {
+        // Recurse into the closure body for prescan (e.g. nested power ops). Unless
+        // it is inlined, the body is compiled in its own function, which claims its
+        // temps from its own prescan: drop the ones allocated here, or the calls
+        // compiled in this function would claim them in the wrong order.
+        let counts = ctx.temp_counts();
+        match &closure.body {
+            ClosureBody::Expr(e) => self.prescan_expr(e, ctx),
+            ClosureBody::Block(b) => self.prescan_block(b, ctx),
+        }
+        if !inline {
+            ctx.truncate_temps(counts);
+        }
+        // Pre-allocate a temp local for env_ptr (used when closure has captures)
+        let key = closure.span;
+        if let Some(&closure_id) = self.closure_span_map.get(&key) {
+            let info = &self.closures[closure_id as usize];
+            if !info.captures.is_empty() {
+                let temp = ctx.alloc_anonymous_local();
+                ctx.closure_env_temps.push(temp);
+            }
+            // Record captured variables so their tables are NOT freed
+            for cap in &info.captures {
+                ctx.captured_tables.insert(cap.clone());
+            }
+        }
+    }
+
     pub(super) fn prescan_expr(&self, expr: &Expr, ctx: &mut FuncCtx) {
         match expr {
             Expr::BinaryOp {
                 left, op, right, ..
             } => {
+                // Claimed by compile_coalesce before either operand is compiled
+                if matches!(op, BinaryOp::Coalesce) {
+                    ctx.alloc_coalesce_temp();
+                }
                 self.prescan_expr(left, ctx);
                 self.prescan_expr(right, ctx);
                 if matches!(op, BinaryOp::Power | BinaryOp::DotPow) {
@@ -155,40 +269,62 @@ impl WasmCodegen {
                 }
             }
             Expr::CallFunc(call) => {
-                for arg in &call.args {
-                    self.prescan_expr(arg, ctx);
+                self.prescan_call_args(call, ctx);
+                // Integer abs/min/max/clamp and round need scratch locals; allocate by
+                // name since shadowing is only known once every local is declared
+                if compile_math::needs_math_temps(&call.name) {
+                    ctx.alloc_math_temps();
+                }
+                if compile_hof::needs_hof_temps(&call.name) {
+                    ctx.alloc_hof_temps();
                 }
                 // If the call target is not a known function, it may be a closure call.
                 // Pre-allocate temp locals for argument reordering.
-                if !self.func_map.contains_key(&call.name) {
-                    if let Some(AhType::Closure(_)) = ctx.var_types.get(&call.name) {
-                        ctx.alloc_closure_call_temps(call.args.len());
-                    }
+                if !self.func_map.contains_key(&call.name)
+                    && let Some(&ty) = ctx.var_types.get(&call.name)
+                    && self.callable_signature(ty).is_some() {
+                    ctx.alloc_closure_call_temps(call.args.len());
                 }
             }
             Expr::Grouped(inner) => {
                 self.prescan_expr(inner, ctx);
             }
-            Expr::Closure(closure) => {
-                // Recurse into the closure body for prescan (e.g. nested power ops)
-                match &closure.body {
-                    ClosureBody::Expr(e) => self.prescan_expr(e, ctx),
-                    ClosureBody::Block(b) => self.prescan_block(b, ctx),
-                }
-                // Pre-allocate a temp local for env_ptr (used when closure has captures)
-                let key = (closure.span.line, closure.span.column);
-                if let Some(&closure_id) = self.closure_span_map.get(&key) {
-                    let info = &self.closures[closure_id as usize];
-                    if !info.captures.is_empty() {
-                        let temp = ctx.alloc_anonymous_local();
-                        ctx.closure_env_temps.push(temp);
+            Expr::Interpolated { parts, .. } => {
+                for part in parts {
+                    if let InterpPart::Expr(e) = part {
+                        self.prescan_expr(e, ctx);
+                    }
+                }
+            }
+            Expr::Closure(closure) => self.prescan_closure(closure, false, ctx),
+            Expr::Match(m) => {
+                self.prescan_expr(&m.subject, ctx);
+                // Subject and result
+                ctx.alloc_math_temps();
+                let enum_id = match self.infer_expr_type(&m.subject, ctx) {
+                    AhType::Enum(id) => Some(id),
+                    _ => None,
+                };
+                for arm in &m.arms {
+                    if let Some(id) = enum_id {
+                        for (name, _, ty) in self.arm_bindings(id, &arm.pattern) {
+                            ctx.declare_local(&name);
+                            ctx.var_types.insert(name, ty);
+                        }
                     }
-                    // Record captured variables so their tables are NOT freed
-                    for cap in &info.captures {
-                        ctx.captured_tables.insert(cap.clone());
+                    match &arm.body {
+                        MatchBody::Expr(body) => self.prescan_expr(body, ctx),
+                        MatchBody::Block(block) => self.prescan_block(block, ctx),
                     }
                 }
             }
+            Expr::New(new) => {
+                // Fields are evaluated after the slots are reserved, into a temp pointer
+                ctx.alloc_math_temps();
+                for entry in &new.fields {
+                    self.prescan_expr(&entry.value, ctx);
+                }
+            }
             Expr::TableLiteral(table) => {
                 for entry in &table.entries {
                     self.prescan_expr(&entry.value, ctx);
@@ -197,7 +333,16 @@ impl WasmCodegen {
                 let temp = ctx.alloc_anonymous_local();
                 ctx.table_temps.push(temp);
             }
+            Expr::ListLiteral(list) => {
+                for element in &list.elements {
+                    self.prescan_expr(element, ctx);
+                }
+            }
             Expr::FieldAccess(fa) => {
+                if self.enum_ref(&fa.object).is_some() {
+                    // Unit variant: allocated like a payload variant
+                    ctx.alloc_math_temps();
+                }
                 self.prescan_expr(&fa.object, ctx);
             }
             Expr::IndexAccess(ia) => {
@@ -205,12 +350,7 @@ impl WasmCodegen {
                 self.prescan_expr(&ia.index, ctx);
             }
             Expr::MethodCall(mc) => {
-                self.prescan_expr(&mc.callee, ctx);
-                for arg in &mc.args {
-                    self.prescan_expr(arg, ctx);
-                }
-                // Always allocate closure call temps: num_args + 1 (extra for closure value)
-                ctx.alloc_closure_call_temps(mc.args.len() + 1);
+                self.prescan_method_call(mc, ctx);
             }
             Expr::VecLiteral(vec_lit) => {
                 for elem in &vec_lit.elements {
@@ -231,6 +371,24 @@ impl WasmCodegen {
             Expr::Transpose(t) => {
                 self.prescan_expr(&t.operand, ctx);
             }
+            Expr::UnaryOp { operand, .. } => {
+                self.prescan_expr(operand, ctx);
+                // ++/-- on a field or element keeps the key and the old value in temps,
+                // and on a boxed variable the old value
+                match operand.as_ref() {
+                    Expr::Variable(name, _) if ctx.boxed.contains(name) => ctx.alloc_math_temps(),
+                    Expr::Variable(..) => {}
+                    _ => {
+                        ctx.alloc_math_temps();
+                        ctx.alloc_math_temps();
+                    }
+                }
+            }
+            Expr::Cast(cast) => {
+                self.prescan_expr(&cast.expr, ctx);
+                // vec <-> mat reshapes and float -> int.round reuse the operand
+                ctx.alloc_math_temps();
+            }
             Expr::Range { start, end, .. } => {
                 if let Some(s) = start {
                     self.prescan_expr(s, ctx);
@@ -239,6 +397,11 @@ impl WasmCodegen {
                     self.prescan_expr(e, ctx);
                 }
             }
+            Expr::If(e) => {
+                self.prescan_boolean_expr(&e.condition, ctx);
+                self.prescan_expr(&e.then_value, ctx);
+                self.prescan_expr(&e.else_value, ctx);
+            }
             Expr::BooleanExpr(bool_expr) => {
                 self.prescan_boolean_expr(bool_expr, ctx);
             }
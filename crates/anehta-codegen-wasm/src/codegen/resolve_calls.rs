@@ -0,0 +1,479 @@
+use super::*;
+
+use anehta_parser::{ClosureParam, ElseIfBranch, FuncParam, NamedArg, VecLiteral};
+
+/// Rewrites calls to functions with default, named or variadic arguments into
+/// plain positional calls, so the later phases only ever see exact arity, and
+/// functions declared inside blocks into closures.
+struct CallResolver<'a> {
+    /// Parameters of every top-level function, by name
+    signatures: HashMap<&'a str, &'a [FuncParam]>,
+    /// Functions whose default values are being expanded, to catch a default that calls its own function
+    expanding: Vec<String>,
+}
+
+impl WasmCodegen {
+    /// Phase 0: a copy of `program` where every call to a user function passes
+    /// exactly one argument per parameter, in declaration order, and functions
+    /// declared inside blocks are closures.
+    pub(super) fn resolve_calls(&self, program: &Program) -> Result<Program, CodegenError> {
+        for stmt in &program.statements {
+            if let Statement::ImplDecl(imp) = stmt {
+                for method in &imp.methods {
+                    if let Some(param) = method.params.iter().find(|p| p.default.is_some() || p.variadic) {
+                        return Err(codegen_err(
+                            format!(
+                                "method '{}' cannot have default or variadic parameters; only functions can",
+                                method.name
+                            ),
+                            &param.span,
+                        ));
+                    }
+                }
+            }
+        }
+        // Defaults are expanded at each call site, where a variable of the same
+        // name would stand in for whatever the default meant
+        for func in user_funcs(program) {
+            for param in &func.params {
+                let Some(default) = &param.default else {
+                    continue;
+                };
+                let mut refs = HashSet::new();
+                Self::find_variables_expr(default, &mut refs);
+                let Some(name) = refs.into_iter().min() else {
+                    continue;
+                };
+                let what = if func.params.iter().any(|p| p.name == name) { "parameter" } else { "variable" };
+                return Err(codegen_err(
+                    format!(
+                        "default value of '{}' in '{}' cannot refer to {} '{}'",
+                        param.name, func.name, what, name
+                    ),
+                    &param.span,
+                ));
+            }
+        }
+
+        let mut resolver = CallResolver {
+            signatures: program
+                .statements
+                .iter()
+                .filter_map(|stmt| match stmt {
+                    Statement::FuncDecl(func) => Some((func.name.as_str(), func.params.as_slice())),
+                    _ => None,
+                })
+                .collect(),
+            expanding: Vec::new(),
+        };
+        let mut resolved = program.clone();
+        for stmt in &mut resolved.statements {
+            resolver.stmt(stmt)?;
+        }
+        Ok(resolved)
+    }
+
+    /// Check that every default value fits the type of its parameter.
+    pub(super) fn check_param_defaults(&self, program: &Program) -> Result<(), CodegenError> {
+        let ctx = FuncCtx::new();
+        for func in user_funcs(program) {
+            for param in &func.params {
+                let Some(default) = &param.default else {
+                    continue;
+                };
+                let expected = self.resolve_type(&param.type_name);
+                let actual = self.infer_expr_type(default, &ctx);
+                if !slot_accepts(expected, actual) {
+                    return Err(codegen_err(
+                        format!(
+                            "default value of '{}' in '{}' must be {}, found {}",
+                            param.name,
+                            func.name,
+                            self.type_display(expected),
+                            self.type_display(actual)
+                        ),
+                        &param.span,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Register a list type for each element type named by a variadic parameter
+    /// (`...tags: str`), so `resolve_type` can give the parameter its element type.
+    pub(super) fn collect_variadic_types(&mut self, program: &Program) {
+        for func in user_funcs(program) {
+            for param in func.params.iter().filter(|p| p.variadic) {
+                let element = &param.type_name[3..];
+                if element.is_empty() || matches!(element, "float" | "f64") {
+                    continue;
+                }
+                if !self.variadic_list_types.contains_key(element) {
+                    let elements = Some(self.resolve_type(element));
+                    self.variadic_list_types
+                        .insert(element.to_string(), self.table_types.len() as u32);
+                    self.table_types.push(TableTypeInfo {
+                        fields: HashMap::new(),
+                        elements,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Type of a variadic parameter declared `...element`: a vec for floats, else a list.
+    pub(super) fn variadic_type(&self, element: &str) -> AhType {
+        match element {
+            "float" | "f64" => AhType::Vec,
+            _ => AhType::List(self.variadic_list_types.get(element).copied().unwrap_or(ANY_LIST_TYPE)),
+        }
+    }
+}
+
+/// "1 argument", "2 to 3 arguments", "at least 1 argument"
+fn arity_text(params: &[FuncParam]) -> String {
+    let variadic = params.last().is_some_and(|p| p.variadic);
+    let fixed = params.len() - variadic as usize;
+    let required = params.iter().filter(|p| p.default.is_none() && !p.variadic).count();
+    if variadic {
+        format!("at least {} argument(s)", required)
+    } else if required == fixed {
+        format!("{} argument(s)", fixed)
+    } else {
+        format!("{} to {} argument(s)", required, fixed)
+    }
+}
+
+impl CallResolver<'_> {
+    fn block(&mut self, block: &mut Block) -> Result<(), CodegenError> {
+        for stmt in &mut block.statements {
+            if let Statement::FuncDecl(func) = stmt {
+                *stmt = self.nested_func(func)?;
+            }
+            self.stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    /// A function declared inside a block becomes a closure bound to its name:
+    /// it sees the enclosing locals through its captures, and the variable
+    /// lives in a heap cell when called from a closure, so it can call itself.
+    fn nested_func(&self, func: &FuncDecl) -> Result<Statement, CodegenError> {
+        if self.signatures.contains_key(func.name.as_str()) {
+            return Err(codegen_err(
+                format!("nested function '{}' has the name of a top-level function", func.name),
+                &func.span,
+            ));
+        }
+        if func.return_types.len() > 1 {
+            return Err(codegen_err(
+                format!("nested function '{}' cannot return several values", func.name),
+                &func.span,
+            ));
+        }
+        let mut params = Vec::with_capacity(func.params.len());
+        for param in &func.params {
+            if param.default.is_some() || param.variadic {
+                return Err(codegen_err(
+                    format!(
+                        "nested function '{}' cannot have default or variadic parameters",
+                        func.name
+                    ),
+                    &param.span,
+                ));
+            }
+            params.push(ClosureParam {
+                name: param.name.clone(),
+                type_name: Some(param.type_name.clone()),
+            });
+        }
+        let closure = Expr::Closure(ClosureExpr {
+            params,
+            body: ClosureBody::Block(func.body.clone()),
+            span: func.span,
+        });
+        Ok(Statement::VarDecl(VarDecl::Assignment(Assignment {
+            targets: vec![func.name.clone()],
+            values: vec![closure],
+            span: func.span,
+        })))
+    }
+
+    fn stmt(&mut self, stmt: &mut Statement) -> Result<(), CodegenError> {
+        match stmt {
+            Statement::FuncDecl(func) => self.block(&mut func.body),
+            Statement::VarDecl(VarDecl::Assignment(assign)) | Statement::Assignment(assign) => {
+                self.exprs(&mut assign.values)
+            }
+            Statement::VarDecl(VarDecl::TypeDecl { .. }) => Ok(()),
+            Statement::IfStmt(stmt) => {
+                self.boolean(&mut stmt.condition)?;
+                self.block(&mut stmt.body)?;
+                for ElseIfBranch { condition, body, .. } in &mut stmt.else_if {
+                    self.boolean(condition)?;
+                    self.block(body)?;
+                }
+                if let Some(body) = &mut stmt.else_body {
+                    self.block(body)?;
+                }
+                Ok(())
+            }
+            Statement::ForStmt(stmt) => {
+                if let Some(init) = &mut stmt.init {
+                    self.stmt(init)?;
+                }
+                if let Some(condition) = &mut stmt.condition {
+                    self.boolean(condition)?;
+                }
+                if let Some(step) = &mut stmt.step {
+                    self.stmt(step)?;
+                }
+                self.block(&mut stmt.body)
+            }
+            Statement::ForIn(stmt) => {
+                self.expr(&mut stmt.iterable)?;
+                self.block(&mut stmt.body)
+            }
+            Statement::Block(block) => self.block(block),
+            Statement::CallFunc(call) => self.call(call),
+            Statement::Return(ret) => self.exprs(&mut ret.values),
+            Statement::Yield(y) => match &mut y.value {
+                Some(val) => self.expr(val),
+                None => Ok(()),
+            },
+            Statement::Try(t) => {
+                self.block(&mut t.body)?;
+                self.block(&mut t.handler)
+            }
+            Statement::Throw(throw) => self.expr(&mut throw.value),
+            Statement::Defer(d) => self.block(&mut d.body),
+            Statement::TimerStmt(timer) => self.block(&mut timer.body),
+            Statement::FieldAssign(assign) => self.expr(&mut assign.value),
+            Statement::IndexAssign(assign) => {
+                self.expr(&mut assign.index)?;
+                self.expr(&mut assign.value)
+            }
+            Statement::MethodCall(call) => self.method_call(call),
+            Statement::Expr(expr) => self.expr(expr),
+            Statement::ImplDecl(imp) => {
+                for method in &mut imp.methods {
+                    self.block(&mut method.body)?;
+                }
+                Ok(())
+            }
+            Statement::Break(_)
+            | Statement::Continue(_)
+            | Statement::StructDecl(_)
+            | Statement::EnumDecl(_)
+            | Statement::Import(_) => Ok(()),
+        }
+    }
+
+    fn exprs(&mut self, exprs: &mut [Expr]) -> Result<(), CodegenError> {
+        exprs.iter_mut().try_for_each(|expr| self.expr(expr))
+    }
+
+    fn boolean(&mut self, expr: &mut BooleanExpr) -> Result<(), CodegenError> {
+        match expr {
+            BooleanExpr::Comparison { left, right, .. } => {
+                self.expr(left)?;
+                self.expr(right)
+            }
+            BooleanExpr::Logical { left, right, .. } => {
+                self.boolean(left)?;
+                self.boolean(right)
+            }
+            BooleanExpr::Grouped(inner) => self.boolean(inner),
+        }
+    }
+
+    fn method_call(&mut self, call: &mut MethodCall) -> Result<(), CodegenError> {
+        self.expr(&mut call.callee)?;
+        self.exprs(&mut call.args)
+    }
+
+    fn expr(&mut self, expr: &mut Expr) -> Result<(), CodegenError> {
+        match expr {
+            Expr::Number(..) | Expr::StringLit(..) | Expr::Bool(..) | Expr::Nil(_) | Expr::Variable(..) => Ok(()),
+            Expr::Interpolated { parts, .. } => {
+                for part in parts {
+                    if let InterpPart::Expr(expr) = part {
+                        self.expr(expr)?;
+                    }
+                }
+                Ok(())
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                self.expr(left)?;
+                self.expr(right)
+            }
+            Expr::UnaryOp { operand, .. } => self.expr(operand),
+            Expr::CallFunc(call) => self.call(call),
+            Expr::Closure(closure) => match &mut closure.body {
+                ClosureBody::Expr(body) => self.expr(body),
+                ClosureBody::Block(body) => self.block(body),
+            },
+            Expr::TableLiteral(table) => {
+                for entry in &mut table.entries {
+                    self.expr(&mut entry.value)?;
+                }
+                Ok(())
+            }
+            Expr::New(new) => {
+                for entry in &mut new.fields {
+                    self.expr(&mut entry.value)?;
+                }
+                Ok(())
+            }
+            Expr::Match(m) => {
+                self.expr(&mut m.subject)?;
+                for arm in &mut m.arms {
+                    match &mut arm.body {
+                        MatchBody::Expr(body) => self.expr(body)?,
+                        MatchBody::Block(body) => self.block(body)?,
+                    }
+                }
+                Ok(())
+            }
+            Expr::If(e) => {
+                self.boolean(&mut e.condition)?;
+                self.expr(&mut e.then_value)?;
+                self.expr(&mut e.else_value)
+            }
+            Expr::VecLiteral(v) => self.exprs(&mut v.elements),
+            Expr::MatLiteral(m) => {
+                for row in &mut m.rows {
+                    self.exprs(row)?;
+                }
+                Ok(())
+            }
+            Expr::ListLiteral(l) => self.exprs(&mut l.elements),
+            Expr::FieldAccess(access) => self.expr(&mut access.object),
+            Expr::IndexAccess(access) => {
+                self.expr(&mut access.object)?;
+                self.expr(&mut access.index)
+            }
+            Expr::Transpose(t) => self.expr(&mut t.operand),
+            Expr::Cast(cast) => self.expr(&mut cast.expr),
+            Expr::MethodCall(call) => self.method_call(call),
+            Expr::Grouped(inner) => self.expr(inner),
+            Expr::Range { start, end, .. } => {
+                if let Some(start) = start {
+                    self.expr(start)?;
+                }
+                if let Some(end) = end {
+                    self.expr(end)?;
+                }
+                Ok(())
+            }
+            Expr::BooleanExpr(b) => self.boolean(b),
+        }
+    }
+
+    /// Resolve the arguments inside a call, then the call itself.
+    fn call(&mut self, call: &mut CallFunc) -> Result<(), CodegenError> {
+        self.exprs(&mut call.args)?;
+        for arg in &mut call.named {
+            self.expr(&mut arg.value)?;
+        }
+
+        let Some(&params) = self.signatures.get(call.name.as_str()) else {
+            if let Some(arg) = call.named.first() {
+                return Err(codegen_err(
+                    format!("'{}' does not take named arguments", call.name),
+                    &arg.span,
+                ));
+            }
+            return Ok(());
+        };
+        let plain = params.iter().all(|p| p.default.is_none() && !p.variadic);
+        if plain && call.named.is_empty() {
+            // Arity is checked where the call is compiled
+            return Ok(());
+        }
+
+        let variadic = params.last().filter(|p| p.variadic);
+        let fixed = params.len() - variadic.is_some() as usize;
+        let mut slots: Vec<Option<Expr>> = vec![None; fixed];
+        let mut positional = std::mem::take(&mut call.args).into_iter();
+        for slot in slots.iter_mut() {
+            *slot = positional.next();
+        }
+        let rest: Vec<Expr> = positional.collect();
+        if !rest.is_empty() && variadic.is_none() {
+            return Err(codegen_err(
+                format!(
+                    "function '{}' expects {}, but {} were given",
+                    call.name,
+                    arity_text(params),
+                    fixed + rest.len() + call.named.len()
+                ),
+                &call.span,
+            ));
+        }
+
+        for NamedArg { name, value, span } in std::mem::take(&mut call.named) {
+            let Some(index) = params[..fixed].iter().position(|p| p.name == name) else {
+                let message = if variadic.is_some_and(|p| p.name == name) {
+                    format!("variadic parameter '{}' of '{}' cannot be given by name", name, call.name)
+                } else {
+                    format!("function '{}' has no parameter named '{}'", call.name, name)
+                };
+                return Err(codegen_err(message, &span));
+            };
+            if slots[index].is_some() {
+                return Err(codegen_err(
+                    format!("argument '{}' of '{}' is given twice", name, call.name),
+                    &span,
+                ));
+            }
+            slots[index] = Some(value);
+        }
+
+        let mut args = Vec::with_capacity(params.len());
+        for (slot, param) in slots.into_iter().zip(params) {
+            if let Some(arg) = slot {
+                args.push(arg);
+                continue;
+            }
+            let Some(default) = &param.default else {
+                return Err(codegen_err(
+                    format!("function '{}' is missing argument '{}'", call.name, param.name),
+                    &call.span,
+                ));
+            };
+            if self.expanding.contains(&call.name) {
+                return Err(codegen_err(
+                    format!("default value of '{}' in '{}' calls '{}' itself", param.name, call.name, call.name),
+                    &param.span,
+                ));
+            }
+            // Defaults are evaluated at the call site, so calls inside them are resolved here too
+            let mut value = default.clone();
+            self.expanding.push(call.name.clone());
+            let resolved = self.expr(&mut value);
+            self.expanding.pop();
+            resolved?;
+            // `check_param_defaults` lets an int default through for a float parameter
+            if matches!(param.type_name.as_str(), "float" | "f64") {
+                value = Expr::Cast(Cast {
+                    expr: Box::new(value),
+                    target: "float".to_string(),
+                    mode: None,
+                    span: param.span,
+                });
+            }
+            args.push(value);
+        }
+        if let Some(param) = variadic {
+            let span = call.span;
+            args.push(match &param.type_name[3..] {
+                "float" | "f64" => Expr::VecLiteral(VecLiteral { elements: rest, span }),
+                _ => Expr::ListLiteral(ListLiteral { elements: rest, span }),
+            });
+        }
+        call.args = args;
+        Ok(())
+    }
+}
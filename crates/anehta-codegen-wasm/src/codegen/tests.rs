@@ -301,6 +301,210 @@ fn closure_call() {
     validate_wasm(&wasm);
 }
 
+#[test]
+fn closures_capture_by_reference() {
+    let src = "var n = 0
+var inc = || => {
+n = n + 1
+return n
+}
+inc()
+n++
+print(inc(), n)
+func counter(start: int) -> int {
+var count = start
+var step = || => {
+count++
+return count
+}
+step()
+return count
+}
+print(counter(5))";
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn closures_capture_variables_they_only_assign() {
+    let src = "var y = 1
+var sety = || => {
+y = 9
+return 0
+}
+sety()
+print(y)
+func f() -> int {
+var z = 1
+var setz = || => {
+z = 7
+return 0
+}
+setz()
+return z
+}
+print(f())";
+    let tokens = Lexer::new(src).tokenize().expect("lexer failed");
+    let program = Parser::new(tokens).parse().expect("parser failed");
+    let mut codegen = WasmCodegen::new();
+    let wasm = codegen.compile(&program).expect("codegen failed");
+    validate_wasm(&wasm);
+    assert!(codegen.boxed_vars["_start"].contains("y"));
+    assert!(codegen.boxed_vars["f"].contains("z"));
+    assert!(codegen.closures.iter().all(|info| info.captures.len() == 1));
+}
+
+#[test]
+fn closures_created_in_loops() {
+    let src = "var hs = list[|| => 0]
+for (var c = 0; c < 2; c = c + 1) {
+var q = c + 100
+push(hs, || => q)
+}
+for (k in list[5, 6, 7]) {
+push(hs, || => k)
+}
+for (var i = 0; i < 3; i = i + 1) {
+var m = i
+push(hs, || => {
+m = m + 10
+return m
+})
+}
+print(hs[1](), hs[3](), hs[6]())";
+    let tokens = Lexer::new(src).tokenize().expect("lexer failed");
+    let program = Parser::new(tokens).parse().expect("parser failed");
+    let mut codegen = WasmCodegen::new();
+    let wasm = codegen.compile(&program).expect("codegen failed");
+    validate_wasm(&wasm);
+    // Only the mutated capture lives in a cell, made anew on each iteration
+    let boxed = &codegen.boxed_vars["_start"];
+    assert!(boxed.contains("m"));
+    assert!(!boxed.contains("q") && !boxed.contains("k"));
+}
+
+#[test]
+fn recursive_closures_of_different_arity() {
+    let src = "var fib = |n| => if (n < 2) { n } else { fib(n - 1) + fib(n - 2) }
+print(fib(5))
+var loopy = |n, acc| => {
+if (n == 0) { return acc }
+return loopy(n - 1, acc + 1)
+}
+print(loopy(10, 0))";
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn recursive_closures_and_nested_funcs() {
+    let src = "var fib = |n| => if (n < 2) { n } else { fib(n - 1) + fib(n - 2) }
+print(fib(10))
+func outer(base: int) -> int {
+var total = 0
+func is_even(k: int) -> int {
+if (k == 0) { return 1 }
+return is_odd(k - 1)
+}
+func is_odd(k: int) -> int {
+if (k == 0) { return 0 }
+return is_even(k - 1)
+}
+func bump(k: int) -> int {
+total = total + k * base
+return total
+}
+bump(2)
+return total + is_even(4)
+}
+print(outer(10), map(list[1, 2], |x| => fib(x)))";
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn nested_func_errors() {
+    let err = compile_err("func f() -> int {\nfunc g(a: int = 1) -> int { return a }\nreturn g()\n}");
+    assert!(err.contains("nested function 'g' cannot have default or variadic parameters"));
+    let err = compile_err("func f() -> int {\nfunc f() -> int { return 1 }\nreturn 0\n}");
+    assert!(err.contains("nested function 'f' has the name of a top-level function"));
+    let err = compile_err("func f() -> int {\nfunc g() -> int, int { return 1, 2 }\nreturn 0\n}");
+    assert!(err.contains("nested function 'g' cannot return several values"));
+}
+
+#[test]
+fn functions_as_values() {
+    let src = "func add(a: int, b: int) -> int {
+return a + b
+}
+func apply(f: fn(int, int) -> int, x: int) -> int {
+return f(x, x)
+}
+func pick() -> fn(int, int) -> int {
+return add
+}
+struct Button {
+onClick: fn(int, int) -> int
+}
+var op = pick()
+var b = new Button { onClick: add }
+var t = { plus: add }
+print(apply(add, 2), apply(|a, b| => a - b, 2), op(1, 2), b.onClick(3, 4), t.plus(5, 6))";
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn function_type_errors() {
+    let funcs = "func neg(a: int) -> int {\nreturn 0 - a\n}\nfunc apply(f: fn(int, int) -> int) -> int {\nreturn f(1, 2)\n}\n";
+    let err = compile_err(&format!("{funcs}print(apply(neg))"));
+    assert!(err.contains("argument 1 of 'apply' must be fn(int, int) -> int, found fn(int) -> int"));
+    let err = compile_err(&format!("{funcs}print(apply(|x| => x))"));
+    assert!(err.contains("found closure"));
+    let err = compile_err(&format!("{funcs}var g = neg\nprint(g(1, 2))"));
+    assert!(err.contains("function 'g' expects 1 argument(s), but 2 were given"));
+    let err = compile_err("func two() -> int, int {\nreturn 1, 2\n}\nvar t = two");
+    assert!(err.contains("function 'two' returns several values and cannot be used as a value"));
+}
+
+#[test]
+fn higher_order_builtins() {
+    let src = "var v = [1.0, 2.0, 3.0]
+var xs = list[3, 1, 2]
+var t = {a: 1, b: 2}
+var scale = 2
+var f = |x| => x * scale
+func inc(x: int) -> int {
+return x + 1
+}
+print(map(v, |x| => x * 2), filter(v, |x| => x > 1), reduce(v, 0, |a, x| => a + x))
+print(map(xs, f), map(xs, inc), filter(xs, |x| => x % 2 == 1), sort_by(xs, |x| => 0 - x))
+var m = map(t, |x| => x * 10)
+print(any(xs, |x| => x > 2), all(xs, |x| => x > 0), m.a, reduce(t, 0, |a, x| => a + x))
+for (p in zip(xs, v)) {
+print(p.first, p.second)
+}
+for (p in enumerate(xs)) {
+print(p.index, p.value)
+}";
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn higher_order_builtin_errors() {
+    let err = compile_err("var f = |x| => x * 2\nprint(map([1.0], f))");
+    assert!(err.contains("the function passed to 'map' takes int as parameter 1, but is given float (annotate it, e.g. |x: float|)"));
+    let err = compile_err("print(map(list[1], |x, y| => x))");
+    assert!(err.contains("the function passed to 'map' must take 1 parameter(s), but takes 2"));
+    let err = compile_err("print(filter(list[1], |x| => \"a\"))");
+    assert!(err.contains("the function passed to 'filter' must return a bool, found str"));
+    let err = compile_err("print(sort_by({a: 1}, |x| => x))");
+    assert!(err.contains("argument 1 of 'sort_by' must be a vec or list, found table"));
+    let err = compile_err("print(map(list[1], 5))");
+    assert!(err.contains("argument 2 of 'map' must be a function, found int"));
+}
+
 // ── Table tests ───────────────────────────────────────
 
 #[test]
@@ -519,3 +723,992 @@ print(test())"#;
     assert!(!wasm.is_empty());
     validate_wasm(&wasm);
 }
+
+fn compile_err(src: &str) -> String {
+    let tokens = Lexer::new(src).tokenize().expect("lexer failed");
+    let mut parser = Parser::new(tokens);
+    let program = parser.parse().expect("parser failed");
+    let mut codegen = WasmCodegen::new();
+    let result = codegen.compile(&program);
+    assert!(result.is_err(), "expected codegen error for: {src}");
+    format!("{}", result.unwrap_err())
+}
+
+#[test]
+fn func_params_occupy_first_locals() {
+    // Params must be locals 0..n-1; closures also take an i32 env_ptr first
+    let src = r#"func add(a: int, b: float) -> float {
+    var v = [1.0, 2.0] + [3.0, 4.0]
+    return a + b
+}
+print(add(1, 2.0))"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn string_len_index_and_slice() {
+    let src = r#"var s = "héllo"
+print(len(s))
+print(s[1])
+print(s[1..3])
+print(s[..2])
+print(s[2..])"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn string_search_and_transform() {
+    let src = r#"var s = trim("  Hello World  ")
+print(find(s, "World"))
+if (contains(s, "lo") > 0) {
+    print(upper(s))
+}
+print(starts_with(s, "He") + ends_with(s, "ld"))
+print(replace(lower(s), "o", "0"))"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn string_split_and_join() {
+    let src = r#"var parts = split("a,b,c", ",")
+for (var i = 0; i < len(parts); i = i + 1) {
+    print(parts[i] + "!")
+}
+print(join(parts, " | "))"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn string_parse_numbers() {
+    let src = r#"var n = parse_int("42") + 1
+var f = parse_float("2.5") * 2.0
+print(n)
+print(f)"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn string_builtin_shadowed_by_user_function() {
+    let src = r#"func upper(x: int) -> int {
+    return x + 1
+}
+print(upper(1))"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn string_builtin_arity_error() {
+    let err = compile_err("var i = find(\"abc\")");
+    assert!(err.contains("function 'find' expects 2 argument(s), but 1 were given"));
+}
+
+#[test]
+fn string_builtin_type_error() {
+    let err = compile_err("var s = upper([1.0, 2.0])");
+    assert!(err.contains("argument 1 of 'upper' must be a string"));
+}
+
+#[test]
+fn interpolated_string_all_types() {
+    let src = r#"var hp = 42
+var ratio = 0.5
+var name = "Anehta"
+var v = [1.0, 2.0]
+var m = [1.0, 0.0; 0.0, 1.0]
+print(f"{name}: HP {hp} ({ratio}) v={v} m={m}")
+var s = f"{f"[{upper(name)}]"} {len(name) * 2}"
+print(s)"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn interpolated_table_rejected() {
+    let err = compile_err("var t = {a: 1}\nprint(f\"{t}\")");
+    assert!(err.contains("cannot format a table or closure value"));
+}
+
+#[test]
+fn print_multiple_args_and_write() {
+    let src = r#"var hp = 42
+print("hp:", hp, 1.5, [1.0, 2.0])
+print()
+write("no newline ", hp)
+write("\n")"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn format_and_printf() {
+    let src = r#"var r = 3.14159
+var s = format("{:.3} {:>5} {:x} {{}}", r, 42, 255)
+print(len(s))
+printf("{:<8}|{:08.2}\n", "name", r)
+print(format("{:6.1}", [1.0, 2.0; 3.0, 4.0]))"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn format_placeholder_count_error() {
+    let err = compile_err(r#"var s = format("{} {}", 1)"#);
+    assert!(err.contains("format string has 2 placeholder(s), but 1 argument(s) were given"));
+}
+
+#[test]
+fn format_invalid_placeholder_error() {
+    let err = compile_err(r#"printf("{name}", 1)"#);
+    assert!(err.contains("invalid placeholder '{name}'"));
+}
+
+#[test]
+fn format_shadowed_by_user_function() {
+    let src = r#"func format(x: int) -> int {
+    return x + 1
+}
+print(format(1))"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn math_scalar_builtins() {
+    let src = r#"var a = abs(0 - 5) + min(3, 9) + max(3, 9) + clamp(15, 0, 10)
+var b = sqrt(2.0) + floor(2.7) + ceil(2.1) + round(2.5)
+var c = sin(pi()) + cos(0) + exp(1) + log(e()) + atan2(1, 2.0)
+print(min(min(1, 2), max(5, min(3, 4))))
+print(a)
+print(b + c)"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn math_elementwise_vec_and_mat() {
+    let src = r#"var v = [1.0, 4.0, 9.0]
+var m = [1.5, 2.5; 3.5, 4.5]
+print(sqrt(v))
+print(min(v, 5.0))
+print(max(2, v))
+print(clamp(v, 2.0, 5.0))
+print(max(v, [3.0, 3.0, 3.0]))
+print(round(m))
+print(atan2(m, 1.0))"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn math_builtin_arity_error() {
+    let err = compile_err("var x = clamp(1, 2)");
+    assert!(err.contains("function 'clamp' expects 3 argument(s), but 2 were given"));
+}
+
+#[test]
+fn math_builtin_type_error() {
+    let err = compile_err("var x = sqrt(\"four\")");
+    assert!(err.contains("argument 1 of 'sqrt' must be a number, vec or mat"));
+}
+
+#[test]
+fn math_elementwise_mixing_vec_and_mat_rejected() {
+    let err = compile_err("var x = min([1.0, 2.0], [1.0, 2.0; 3.0, 4.0])");
+    assert!(err.contains("cannot mix vec and mat"));
+}
+
+#[test]
+fn where_selects_elementwise() {
+    let src = "var v = [3, -1, 4]\nvar m = [1, 5; 3, 2]\nprint(where(v > 0, v, 0), where(m >= 2, m, v.x), where(1 < v, 1, -1))";
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+    let err = compile_err("var x = where(1 > 0, 1, 2)");
+    assert!(err.contains("'where' needs a vec or mat operand"));
+    let err = compile_err("var v = [1.0]\nvar x = where(v, 1, 2)");
+    assert!(err.contains("'where' expects a comparison and two values"));
+    let err = compile_err("var v = [1.0]\nvar x = where(v > 0, [1.0, 2.0; 3.0, 4.0], 0)");
+    assert!(err.contains("'where' cannot mix vec and mat"));
+}
+
+#[test]
+fn math_builtin_shadowed_by_user_function() {
+    let src = r#"func max(a: int, b: int, c: int) -> int {
+    return a
+}
+print(max(1, 2, 3))"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn cast_conversions() {
+    let src = r#"var f = 7 -> float
+var i = 3.7 -> int
+var r = 2.5 -> int.round
+var c = (2.1 -> int.ceil) + (2.9 -> int.floor)
+var s = (i -> str) + (f -> str)
+var n = ("12" -> int) + ("2.5" -> float -> int)
+var v = [1.0, 2.0, 3.0]
+var row = v -> mat.row
+var col = v -> mat
+print(row -> vec, col, [1, 2; 3, 4] -> str, s)"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn cast_impossible_conversion_error() {
+    let err = compile_err("var x = 3 -> vec");
+    assert!(err.contains("cannot convert int to vec"));
+    let err = compile_err("var t = {}\nvar x = t -> str");
+    assert!(err.contains("cannot convert table to str"));
+}
+
+#[test]
+fn cast_unknown_target_or_mode_error() {
+    let err = compile_err("var x = 3 -> bool");
+    assert!(err.contains("unknown conversion target 'bool'"));
+    let err = compile_err("var x = \"1\" -> int.round");
+    assert!(err.contains("conversion mode '.round' does not apply to str -> int"));
+}
+
+#[test]
+fn bitwise_operators() {
+    let src = r#"var flags = 1 | (1 << 3)
+var masked = flags & 8
+var toggled = flags xor 1
+var shifted = (0 - 16) >> 2 + (0 - 16) >>> 60
+var f = |x: int| => x | ~x
+print(~flags, masked, toggled, shifted, f(5))"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn bitwise_on_non_int_rejected() {
+    let err = compile_err("var x = 1.5 & 3");
+    assert!(err.contains("operator '&' requires int operands, found float"));
+    let err = compile_err("var x = ~\"a\"");
+    assert!(err.contains("operator '~' requires int operands, found str"));
+}
+
+#[test]
+fn unary_negation() {
+    let src = r#"var x = 5
+var f = 2.5
+var v = [1.0, 2.0, 3.0]
+var m = [1, 2; 3, 4]
+print(-x, -f, -(x + 1) * 2, 3 - -x, -v, -m)"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn increment_fields_and_elements() {
+    let src = r#"var t = { count: 1, hp: 2.5 }
+t.count++
+t.hp--
+var old = t.count++
+var v = [1.0, 2.0]
+v[0]++
+var m = [1, 2; 3, 4]
+m[3]--
+var s = { a: 1 }
+s["a"]++
+for (var i = 0; i < 3; i++) {
+    v[i % 2]++
+}
+print(old, t.count, v, m, s.a)"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn unary_type_errors() {
+    let err = compile_err("var x = -\"a\"");
+    assert!(err.contains("cannot negate a str"));
+    let err = compile_err("var s = \"a\"\ns++");
+    assert!(err.contains("cannot apply '++' to a str"));
+    let err = compile_err("var v = [1.0, 2.0]\nvar y = v.x--");
+    assert!(err.contains("'--' needs a variable, table field or element"));
+}
+
+#[test]
+fn struct_construct_access_and_assign() {
+    let src = r#"struct Vec2 { x: float, y: float }
+struct Player {
+    name: str
+    hp: int
+    pos: Vec2
+}
+func heal(p: Player, n: int) -> int {
+    p.hp = p.hp + n
+    return p.hp
+}
+func spawn(name: str) -> Player {
+    return new Player { name: name, hp: 100, pos: new Vec2 { x: 0, y: 0.5 } }
+}
+var p = spawn("ann")
+p.hp++
+var pos = p.pos
+pos.x = pos.x + 1.0
+print(heal(p, 10), p.pos.y)
+print(p)
+print(f"{p.name}: {p}")"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn struct_field_errors() {
+    let decl = "struct P { a: int, b: str }\n";
+    let err = compile_err(&format!("{decl}var p = new P {{ a: 1, b: \"x\" }}\nprint(p.c)"));
+    assert!(err.contains("struct 'P' has no field 'c'"));
+    let err = compile_err(&format!("{decl}var p = new P {{ a: 1, b: \"x\" }}\np.c = 2"));
+    assert!(err.contains("struct 'P' has no field 'c'"));
+    let err = compile_err(&format!("{decl}var p = new P {{ a: 1 }}"));
+    assert!(err.contains("missing field 'b' in 'new P'"));
+    let err = compile_err(&format!("{decl}var p = new P {{ a: \"x\", b: \"y\" }}"));
+    assert!(err.contains("field 'a' of 'P' expects int, found str"));
+    let err = compile_err(&format!("{decl}var p = new P {{ a: 1, a: 2, b: \"y\" }}"));
+    assert!(err.contains("field 'a' is given twice"));
+    let err = compile_err(&format!("{decl}var p: P"));
+    assert!(err.contains("needs an initializer"));
+}
+
+#[test]
+fn struct_declaration_errors() {
+    let err = compile_err("var p = new Q { a: 1 }");
+    assert!(err.contains("unknown struct 'Q'"));
+    let err = compile_err("struct P { a: thing }");
+    assert!(err.contains("unknown type 'thing' for field 'a'"));
+    let err = compile_err("struct A { b: B }\nstruct B { a: A }");
+    assert!(err.contains("struct 'A' contains itself"));
+    let err = compile_err("struct P { a: int }\nstruct P { b: int }");
+    assert!(err.contains("struct 'P' is already declared"));
+}
+
+#[test]
+fn enum_match_value_and_statement() {
+    let src = r#"enum State {
+    Idle
+    Moving(vec)
+    Attacking(int, str)
+}
+enum List { Nil, Cons(int, List) }
+func speed(s: State) -> float {
+    return match s {
+        Idle => 0.0
+        Moving(dir) => dir.len * 1.5
+        Attacking(_, _) => 0.5
+    }
+}
+func sum(l: List) -> int {
+    return match l { Nil => 0, Cons(head, tail) => head + sum(tail) }
+}
+var s = State.Moving([1, 0])
+print(speed(s), sum(List.Cons(1, List.Cons(2, List.Nil))))
+for (var i = 0; i < 3; i++) {
+    match s {
+        State.Attacking(dmg, target) => {
+            print(target, dmg)
+            break
+        }
+        _ => {
+            s = State.Attacking(12, "slime")
+        }
+    }
+}
+print(s)
+print(f"{State.Idle}")"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn match_exhaustiveness_and_pattern_errors() {
+    let decl = "enum S { A, B(int) }\nvar s = S.A\n";
+    let err = compile_err(&format!("{decl}var x = match s {{ A => 1 }}"));
+    assert!(err.contains("match on 'S' is not exhaustive: missing B"));
+    let err = compile_err(&format!("{decl}var x = match s {{ _ => 1, A => 2 }}"));
+    assert!(err.contains("unreachable match arm"));
+    let err = compile_err(&format!("{decl}var x = match s {{ A => 1, A => 2, B => 3 }}"));
+    assert!(err.contains("'A' is already matched"));
+    let err = compile_err(&format!("{decl}var x = match s {{ A => 1, B(a, b) => 2 }}"));
+    assert!(err.contains("variant 'S.B' has 1 value(s), but the pattern binds 2"));
+    let err = compile_err(&format!("{decl}var x = match s {{ A => 1, B(v) => \"x\" }}"));
+    assert!(err.contains("match arms have different types: int and str"));
+    let err = compile_err(&format!("{decl}var x = match s {{ A => 1, C => 2 }}"));
+    assert!(err.contains("enum 'S' has no variant 'C'"));
+    let err = compile_err("var x = match 3 { _ => 1 }");
+    assert!(err.contains("match needs an enum value, found int"));
+}
+
+#[test]
+fn enum_variant_errors() {
+    let decl = "enum S { A, B(int) }\n";
+    let err = compile_err(&format!("{decl}var s = S.C"));
+    assert!(err.contains("enum 'S' has no variant 'C'"));
+    let err = compile_err(&format!("{decl}var s = S.B"));
+    assert!(err.contains("variant 'S.B' expects 1 value(s), found 0"));
+    let err = compile_err(&format!("{decl}var s = S.B(\"x\")"));
+    assert!(err.contains("value 1 of 'S.B' expects int, found str"));
+    let err = compile_err("enum S { A, A }");
+    assert!(err.contains("duplicate variant 'A' in enum 'S'"));
+    let err = compile_err("struct S { a: int }\nenum S { A }");
+    assert!(err.contains("enum 'S' is already declared"));
+}
+
+#[test]
+fn struct_and_enum_methods() {
+    let src = r#"struct Player { name: str, hp: int }
+enum Shape { Circle(float), Rect(float, float) }
+impl Player {
+    func heal(n: int) -> int {
+        self.hp = self.hp + n
+        return self.hp
+    }
+    func label() -> str {
+        return f"{self.name}: {self.heal(0)}"
+    }
+}
+impl Shape {
+    func area() -> float {
+        return match self { Circle(r) => 3.0 * r * r, Rect(w, h) => w * h }
+    }
+}
+var p = new Player { name: "ann", hp: 10 }
+p.heal(5)
+print(p:label(), Shape.Rect(2.0, 3.0).area() + 1.0)"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn table_method_call_passes_receiver() {
+    let wasm = compile_source("var t = { n: 1, add: |self, k| => self.n + k }\nprint(t:add(2))\nt:add(3)");
+    validate_wasm(&wasm);
+    let err = compile_err("var v = [1, 2]\nv:len()");
+    assert!(err.contains("'vec' values have no methods"));
+}
+
+#[test]
+fn method_errors() {
+    let decl = "struct P { x: int }\n";
+    let err = compile_err(&format!("{decl}impl P {{ func x() -> int {{ return 1 }} }}"));
+    assert!(err.contains("'x' is both a field and a method of 'P'"));
+    let err = compile_err(&format!("{decl}impl P {{ func a() -> int {{ return 1 }} }}\nimpl P {{ func a() -> int {{ return 2 }} }}"));
+    assert!(err.contains("method 'a' is already defined for 'P'"));
+    let err = compile_err("impl Q { func a() -> int { return 1 } }");
+    assert!(err.contains("cannot impl unknown type 'Q'"));
+    let err = compile_err("impl int { func a() -> int { return 1 } }");
+    assert!(err.contains("methods can only be added to structs and enums, not 'int'"));
+    let err = compile_err(&format!("{decl}var p = new P {{ x: 1 }}\nprint(p.nope())"));
+    assert!(err.contains("'P' has no method 'nope'"));
+    let err = compile_err(&format!("{decl}impl P {{ func a(n: int) -> int {{ return n }} }}\nvar p = new P {{ x: 1 }}\nprint(p.a())"));
+    assert!(err.contains("method 'P.a' expects 1 argument(s), but 0 were given"));
+}
+
+#[test]
+fn if_expression_values() {
+    let src = r#"func sign(x: int) -> int {
+    return if (x < 0) { -1 } elseif (x == 0) { 0 } else { 1 }
+}
+var base = 10
+var dmg = if (sign(base) > 0 && base > 5) { base * 2 } else { 1.5 }
+var label = if (dmg > 15) {
+    f"crit {dmg}"
+} else {
+    "hit"
+}
+print(dmg, label)"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+    let err = compile_err("var x = if (1 > 0) { 1 } else { \"a\" }");
+    assert!(err.contains("if-expression branches have different types: int and str"));
+}
+
+#[test]
+fn string_index_assign_rejected() {
+    let err = compile_err("var s = \"abc\"\ns[0] = \"x\"");
+    assert!(err.contains("strings are immutable"));
+}
+
+#[test]
+fn list_literal_builtins_and_for_in() {
+    let src = r#"struct P { x: int }
+var xs = list[new P { x: 1 }, new P { x: 2 }, new P { x: 3 }]
+var names = list[]
+push(names, "a")
+insert(names, 0, "b")
+print(pop(names) + names[0], len(xs), xs[1..3])
+xs[0] = new P { x: 4 }
+var rows = list[list[1], list[]]
+for (n in names) {
+    if (n == "b") { continue }
+    print(n)
+}
+for (c in "abc") { write(c) }
+for (f in [1, 2]) { print(f * 2.0) }"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn list_errors() {
+    let err = compile_err("var xs = list[1]\nprint(xs[\"a\"])");
+    assert!(err.contains("list index must be an int, found str"));
+    let err = compile_err("var t = { a: 1 }\nt.b = list[1]");
+    assert!(err.contains("cannot assign a list to a field"));
+    let err = compile_err("var s = \"a\"\npush(s, 1)");
+    assert!(err.contains("argument 1 of 'push' must be a list, found str"));
+    let err = compile_err("var xs = list[1]\npop(xs, 1)");
+    assert!(err.contains("function 'pop' expects 1 argument(s), but 2 were given"));
+    let err = compile_err("var m = list[1, \"two\", 3.5]");
+    assert!(err.contains("list elements must share one type, found int and str"));
+    let err = compile_err("var xs = list[1]\npush(xs, \"s\")");
+    assert!(err.contains("cannot store str in a list of int"));
+    let err = compile_err("var xs = list[]\npush(xs, \"a\")\ninsert(xs, 0, 5)");
+    assert!(err.contains("cannot store int in a list of str"));
+    let err = compile_err("var xs = list[1.5]\nxs[0] = nil");
+    assert!(err.contains("cannot store"));
+    let err = compile_err("var t = { a: 1 }\nfor (x in t) { print(x) }");
+    assert!(err.contains("cannot iterate over a table value"));
+}
+
+#[test]
+fn table_array_part_and_builtins() {
+    let src = r#"var t = {}
+t[0] = 10
+t["name"] = 7
+insert(t, 20)
+insert(t, 0, 5)
+print(len(t), keys(t), values(t), has(t, "name"), has(t, 1))
+var first = remove(t, 0)
+remove(t, "name")
+t[1]++
+var rec = { x: 1.5, y: 2.5 }
+var total = 0.0
+for (v in values(rec)) { total = total + v }
+for (k in keys(rec)) { print(k + "=" + rec[k]) }"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn table_builtin_errors() {
+    let err = compile_err("var t = { a: 1 }\nhas(t, 1.5)");
+    assert!(err.contains("table key must be an int or str, found float"));
+    let err = compile_err("var s = \"a\"\nkeys(s)");
+    assert!(err.contains("argument 1 of 'keys' must be a table, found str"));
+    let err = compile_err("var t = { a: 1 }\ninsert(t, 1, 2, 3)");
+    assert!(err.contains("function 'insert' expects 2 or 3 argument(s), but 4 were given"));
+    let err = compile_err("var t = {}\ninsert(t, list[1])");
+    assert!(err.contains("cannot assign a list to a field"));
+}
+
+#[test]
+fn builtin_arguments_tell_ints_from_unknown_values() {
+    let err = compile_err("keys(3)");
+    assert!(err.contains("argument 1 of 'keys' must be a table, found int"));
+    let err = compile_err("var n = 5\nprint(len(n + 1))");
+    assert!(err.contains("argument 1 of 'len' must be a str, vec, list or table, found int"));
+    let err = compile_err("func f(n: int) -> int {\nreturn len(n)\n}");
+    assert!(err.contains("argument 1 of 'len' must be a str, vec, list or table, found int"));
+    let err = compile_err("var x: int\nx = 2\nprint(upper(x))");
+    assert!(err.contains("must be a string"));
+    // An element of a mixed table has no known type and is checked at run time
+    let src = r#"var t = { a: "xy", b: 1 }
+var v = t["a"]
+print(len(v), upper(v))
+var g = |x| => upper(x)"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn nil_safe_navigation_and_coalescing() {
+    let src = r#"var cfg = { name: "app", port: 8080 }
+var host = cfg?.host ?? "localhost"
+var port = cfg?.port ?? 80
+var ratio = cfg?.ratio ?? 1.5
+var debug = cfg?.debug
+if (debug == nil) { print(host, port, ratio) }
+print(f"debug={debug}", list[1, nil])"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn optional_errors() {
+    let err = compile_err("var t = { a: 1 }\nvar p = t?.a\nprint(p + 1)");
+    assert!(err.contains("value of type int? may be nil; give it a default with '??'"));
+    let err = compile_err("var t = { a: 1 }\nvar p = t?.a\nif (p < 3) { print(p) }");
+    assert!(err.contains("value of type int? may be nil"));
+    let err = compile_err("var t = { a: 1 }\nprint(t?.a ?? \"x\")");
+    assert!(err.contains("'??' operands have different types: int? and str"));
+    let err = compile_err("var v = [1.0, 2.0]\nprint(v?.x)");
+    assert!(err.contains("'?.' needs a table, found vec"));
+    let err = compile_err("var t = {a: 9219994337134247936}\nprint(t?.a ?? 5)");
+    assert!(err.contains("9219994337134247936 is reserved for nil"));
+}
+
+#[test]
+fn missing_table_field_warning() {
+    let compile = |src: &str| {
+        let tokens = Lexer::new(src).tokenize().expect("lexer failed");
+        let program = Parser::new(tokens).parse().expect("parser failed");
+        let mut codegen = WasmCodegen::new();
+        codegen.compile(&program).expect("codegen failed");
+        codegen.warnings().to_vec()
+    };
+    let warnings = compile("var t = { a: 1 }\nprint(t.b)\nprint(t?.c ?? 0)");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].line, 2);
+    assert!(warnings[0].message.contains("table has no field 'b'"));
+    // A field assigned anywhere, or a write with a runtime key, may add it
+    assert!(compile("var t = { a: 1 }\nt.b = 2\nprint(t.b)").is_empty());
+    assert!(compile("var t = { a: 1 }\nvar k = \"b\"\nt[k] = 2\nprint(t.b)").is_empty());
+}
+
+#[test]
+fn default_named_and_variadic_args() {
+    let src = r#"func spawn(kind: str, hp: int = 100, ...tags: str) -> str {
+    var s = f"{kind}:{hp}"
+    for (t in tags) { s = s + "+" + t }
+    return s
+}
+func total(...xs: float) -> float {
+    var sum = 0.0
+    for (x in xs) { sum = sum + x }
+    return sum
+}
+print(spawn("orc"), spawn("orc", hp: 50), spawn(hp: 7, kind: "imp"))
+print(spawn("elf", 80, "archer", "fast"), total(), total(1.5, 2))"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn call_arity_errors() {
+    let decl = "func spawn(kind: str, hp: int = 100) -> int { return hp }\n";
+    let err = compile_err(&format!("{}spawn()", decl));
+    assert!(err.contains("function 'spawn' is missing argument 'kind'"));
+    let err = compile_err(&format!("{}spawn(\"a\", 1, 2)", decl));
+    assert!(err.contains("function 'spawn' expects 1 to 2 argument(s), but 3 were given"));
+    let err = compile_err(&format!("{}spawn(\"a\", mp: 1)", decl));
+    assert!(err.contains("function 'spawn' has no parameter named 'mp'"));
+    let err = compile_err(&format!("{}spawn(\"a\", kind: \"b\")", decl));
+    assert!(err.contains("argument 'kind' of 'spawn' is given twice"));
+    let err = compile_err("print(x: 1)");
+    assert!(err.contains("'print' does not take named arguments"));
+    let err = compile_err("func f(a: int = f()) -> int { return a }\nf()");
+    assert!(err.contains("default value of 'a' in 'f' calls 'f' itself"));
+}
+
+#[test]
+fn defaults_match_parameter_types() {
+    let err = compile_err("func f(a: int = \"x\") -> int { return a }\nprint(f())");
+    assert!(err.contains("default value of 'a' in 'f' must be int, found str"));
+    let err = compile_err("func f(s: str = 1.5) -> int { return len(s) }\nprint(f())");
+    assert!(err.contains("default value of 's' in 'f' must be str, found float"));
+    // An int default of a float parameter is converted where it is filled in
+    let wasm = compile_source("func f(a: float = 1, b: float = 2.5) -> float { return a + b }\nprint(f(), f(0.5))");
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn defaults_do_not_bind_call_site_names() {
+    let err = compile_err("var a = 100\nfunc f(a: int, b: int = a) -> int { return b }\nprint(f(1))");
+    assert!(err.contains("default value of 'b' in 'f' cannot refer to parameter 'a'"));
+    let err = compile_err("func f(x: int, cap: int = limit) -> int { return cap }\nfunc g() -> int {\n    var limit = 5\n    return f(1)\n}");
+    assert!(err.contains("default value of 'cap' in 'f' cannot refer to variable 'limit'"));
+    let wasm = compile_source("func f(x: int, cap: int = abs(-3) * 2) -> int { return cap }\nprint(f(1))");
+    validate_wasm(&wasm);
+}
+
+/// Number of `return_call` and `return_call_indirect` instructions in a module.
+fn count_tail_calls(bytes: &[u8]) -> usize {
+    let mut count = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+        if let wasmparser::Payload::CodeSectionEntry(body) = payload.expect("invalid wasm") {
+            let mut ops = body.get_operators_reader().expect("invalid body");
+            while !ops.eof() {
+                let op = ops.read().expect("invalid operator");
+                if matches!(op, wasmparser::Operator::ReturnCall { .. } | wasmparser::Operator::ReturnCallIndirect { .. }) {
+                    count += 1;
+                }
+            }
+        }
+    }
+    count
+}
+
+#[test]
+fn tail_calls() {
+    let src = "func sum_to(n: int, acc: int) -> int {
+if (n == 0) { return acc }
+return sum_to(n - 1, acc + n)
+}
+func count(n: int) -> int {
+var step = |k, acc| => {
+if (k == 0) { return acc }
+return step(k - 1, acc + 1)
+}
+return step(n, 0)
+}
+print(sum_to(10, 0), count(10))";
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+    assert_eq!(count_tail_calls(&wasm), 3);
+
+    // Through a function value (and its trampoline), and from the branches of
+    // an expression-bodied closure
+    let wasm = compile_source("func a(n: int) -> int {
+if (n == 0) { return 0 }
+return b(n - 1, a)
+}
+func b(n: int, f: fn(int) -> int) -> int {
+return f(n)
+}
+var lp = |n, acc| => if (n == 0) { acc } else { lp(n - 1, acc + 1) }
+print(a(10), lp(10, 0))");
+    validate_wasm(&wasm);
+    assert_eq!(count_tail_calls(&wasm), 4);
+
+    // Without tail calls, the self call in `sum_to` becomes a loop
+    let tokens = Lexer::new(src).tokenize().expect("lexer failed");
+    let program = Parser::new(tokens).parse().expect("parser failed");
+    let mut codegen = WasmCodegen::new();
+    codegen.set_tail_calls(false);
+    let wasm = codegen.compile(&program).expect("codegen failed");
+    validate_wasm(&wasm);
+    assert_eq!(count_tail_calls(&wasm), 0);
+}
+
+#[test]
+fn no_tail_calls_inside_try() {
+    let src = r#"func thrower(n: int) -> int {
+if (n > 0) { throw "boom" }
+return n
+}
+func g(n: int) -> int {
+try {
+    return thrower(n)
+} catch (e) {
+    return -1
+}
+}
+func h(n: int) -> int {
+try {
+    return h(n - 1)
+} catch (e) {
+    return 0
+}
+}
+print(g(1), h(0))"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+    assert_eq!(count_tail_calls(&wasm), 0);
+}
+
+fn export_names(bytes: &[u8]) -> Vec<String> {
+    let mut names = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+        if let wasmparser::Payload::ExportSection(reader) = payload.expect("invalid wasm") {
+            for export in reader {
+                names.push(export.expect("invalid export").name.to_string());
+            }
+        }
+    }
+    names
+}
+
+#[test]
+fn generators() {
+    let src = r#"func patrol(points: list, rounds: int) -> str {
+var done = 0
+for (var r = 0; r < rounds; r++) {
+    for (p in points) {
+        if (p == "B") {
+            yield "wait"
+            continue
+        } elseif (p == "C") {
+            yield "attack"
+        } else {
+            yield "walk"
+        }
+    }
+    done++
+}
+return f"rounds {done}"
+}
+func halves(x: float) -> float {
+var total = 0
+var add = |n: float| => { total = total + 1
+return n }
+for (var k = 0; k < 3; k++) {
+    x = x / 2
+    yield add(x)
+}
+}
+func drain(co: coroutine(str)) -> int {
+var n = 0
+for (; is_done(co) == 0;) {
+    print(resume(co))
+    n++
+}
+return n
+}
+print(drain(patrol(list["A", "B", "C"], 2)))
+var h = halves(8.0)
+var first = resume(h)
+print(first, resume(h), is_done(h))"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+    let exports = export_names(&wasm);
+    assert!(exports.iter().any(|name| name == "__resume"));
+    assert!(exports.iter().any(|name| name == "__is_done"));
+
+    // Without generators there is nothing to resume
+    let exports = export_names(&compile_source("print(1)"));
+    assert!(!exports.iter().any(|name| name == "__resume"));
+}
+
+#[test]
+fn generator_errors() {
+    let err = compile_err("func f() -> int {\nvar g = || => {\nyield 1\nreturn 0\n}\nreturn 0\n}");
+    assert!(err.contains("yield can only be used in a top-level function"));
+    let err = compile_err("func f() -> int {\ntimer {\nyield 1\n}\n}");
+    assert!(err.contains("yield cannot be used inside a timer block"));
+    let err = compile_err("struct P { x: int }\nimpl P {\nfunc m() -> int {\nyield 1\n}\n}");
+    assert!(err.contains("method 'P.m' cannot yield"));
+    let err = compile_err("func f() -> int {\nyield 1\nreturn 1, 2\n}");
+    assert!(err.contains("a generator cannot return several values"));
+    let err = compile_err("func f() -> int {\nyield 1\n}\nprint(resume(2.5))");
+    assert!(err.contains("'resume' needs a coroutine, found float"));
+}
+
+#[test]
+fn try_catch() {
+    let src = r#"func check(n: int) -> int {
+if (n < 0) {
+    throw "negative"
+}
+return 100 / n
+}
+func first(xs: list) -> int {
+return xs[0]
+}
+var total = 0
+var i = 0
+for (i = -1; i < 2; i++) {
+    try {
+        total = total + check(i)
+    } catch (e) {
+        print(e.message, e.code)
+    }
+}
+try {
+    try {
+        print(first(list[]))
+    } catch (e) {
+        throw e
+    }
+} catch {
+    print("rethrown")
+}
+try {
+    throw new Error { message: "custom", code: 7 }
+} catch (err) {
+    print(err)
+}
+var g = |x: int| => x % 0
+try {
+    print(g(1))
+} catch (e) {
+    print(e.code)
+}
+var fs = "{} and {}"
+try {
+    print(format(fs, 1))
+} catch (e) {
+    print(e.message)
+}"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+    assert!(export_names(&wasm).iter().any(|name| name == "__error"));
+}
+
+#[test]
+fn try_catch_errors() {
+    let err = compile_err("throw 1");
+    assert!(err.contains("throw needs a str message or an Error, found int"));
+    let err = compile_err("struct Error { code: int }");
+    assert!(err.contains("'Error' is a built-in type and cannot name a struct"));
+    let err = compile_err("func f() -> int {\ntry {\nyield 1\n} catch {\n}\nreturn 0\n}");
+    assert!(err.contains("yield cannot be used inside a try block"));
+}
+
+#[test]
+fn defer_runs_on_every_exit() {
+    let src = r#"func early(n: int) -> int {
+var t = { hp: n }
+defer { print("first") }
+defer {
+    for (x in list[1, 2]) {
+        print(x + t.hp)
+    }
+}
+if (n > 0) {
+    return n * 2
+}
+return 0
+}
+func loop() -> int {
+var i = 0
+for (; i < 4;) {
+    defer { print(i) }
+    i = i + 1
+    if (i == 2) {
+        continue
+    }
+    if (i == 3) {
+        break
+    }
+}
+return i
+}
+try {
+    defer { print("unwound") }
+    throw "boom"
+} catch (e) {
+    print(e.message)
+}
+defer { print(early(1) + loop()) }
+print("end")"#;
+    let wasm = compile_source(src);
+    validate_wasm(&wasm);
+}
+
+#[test]
+fn defer_errors() {
+    let err = compile_err("func f() -> int {\ndefer {\nreturn 1\n}\nreturn 0\n}");
+    assert!(err.contains("return cannot be used inside a defer block"));
+    let err = compile_err("for (;;) {\ndefer {\nbreak\n}\n}");
+    assert!(err.contains("break cannot leave a defer block"));
+    let err = compile_err("func f() -> int {\ndefer {\nprint(1)\n}\nyield 1\n}");
+    assert!(err.contains("defer cannot be used in a generator"));
+}
+
+#[test]
+fn unresolved_import_is_an_error() {
+    let err = compile_err("import util\nprint(1)");
+    assert!(err.contains("import of 'util.ah' was not resolved"));
+}
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 
+use anehta_lexer::Span;
+use anehta_parser::Block;
 use wasm_encoder::ValType;
 
 /// Simple type tag used to distinguish integer vs string vs closure values at compile time.
@@ -13,8 +15,130 @@ pub(crate) enum AhType {
     Mat,
     /// Closure value. The u32 is the closure ID (index into closures vec).
     Closure(u32),
+    /// Value of a function type `fn(...) -> T`: a closure or a named function,
+    /// called through the function table. The u32 indexes `func_types`.
+    Func(u32),
     /// Table value. The u32 is the table type ID (index into table_types vec).
     Table(u32),
+    /// List value: a host handle. The u32 is a table type ID whose `elements`
+    /// gives the element type.
+    List(u32),
+    /// Struct value: pointer to its fields in linear memory. The u32 is the struct ID.
+    Struct(u32),
+    /// Enum value: pointer to its tag and payload in linear memory. The u32 is the enum ID.
+    Enum(u32),
+    /// A value that may be `nil` (the `NIL` bit pattern). The wrapped type is
+    /// stored flat so that `AhType` stays `Copy`; see `AhType::optional`.
+    Optional(Wrapped),
+    /// Coroutine returned by a generator function: its step function's table slot
+    /// and its frame, like a closure. The u32 indexes `func_types`, whose result
+    /// is the type of the yielded values.
+    Coroutine(u32),
+}
+
+/// The type inside an `AhType::Optional`: its variant and, for the variants
+/// that carry one, the id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Wrapped {
+    kind: WrappedKind,
+    id: u32,
+}
+
+/// Variant of the type inside an optional.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WrappedKind {
+    /// The bare `nil` literal, whose wrapped type is not known
+    Nil,
+    Int,
+    Float,
+    Str,
+    Vec,
+    Mat,
+    Closure,
+    Func,
+    Table,
+    List,
+    Struct,
+    Enum,
+    Coroutine,
+}
+
+/// Memory access of a boxed variable's heap cell (8 bytes).
+pub(crate) const CELL_ARG: wasm_encoder::MemArg = wasm_encoder::MemArg {
+    offset: 0,
+    align: 3,
+    memory_index: 0,
+};
+
+/// Runtime representation of `nil`: a signalling NaN, which float arithmetic
+/// never produces, far outside the range of handles and string pointers. As an
+/// int it is 9219994337134247936, so that value is reserved: the literal is
+/// rejected, and an int computed to it reads as `nil` in an optional.
+pub(crate) const NIL: i64 = 0x7FF4_0000_0000_0000;
+
+impl AhType {
+    /// Source-level name of the type, for diagnostics.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            AhType::Int => "int",
+            AhType::Float => "float",
+            AhType::Str => "str",
+            AhType::Vec => "vec",
+            AhType::Mat => "mat",
+            AhType::Closure(_) => "closure",
+            AhType::Func(_) => "fn",
+            AhType::Table(_) => "table",
+            AhType::List(_) => "list",
+            AhType::Struct(_) => "struct",
+            AhType::Enum(_) => "enum",
+            AhType::Optional(_) => "optional",
+            AhType::Coroutine(_) => "coroutine",
+        }
+    }
+
+    /// Type of the `nil` literal: optional, wrapping an unknown type.
+    pub(crate) const NIL: AhType = AhType::Optional(Wrapped { kind: WrappedKind::Nil, id: 0 });
+
+    /// This type made optional; optional types are returned unchanged.
+    pub(crate) fn optional(self) -> AhType {
+        let (kind, id) = match self {
+            AhType::Optional(_) => return self,
+            AhType::Int => (WrappedKind::Int, 0),
+            AhType::Float => (WrappedKind::Float, 0),
+            AhType::Str => (WrappedKind::Str, 0),
+            AhType::Vec => (WrappedKind::Vec, 0),
+            AhType::Mat => (WrappedKind::Mat, 0),
+            AhType::Closure(id) => (WrappedKind::Closure, id),
+            AhType::Func(id) => (WrappedKind::Func, id),
+            AhType::Table(id) => (WrappedKind::Table, id),
+            AhType::List(id) => (WrappedKind::List, id),
+            AhType::Struct(id) => (WrappedKind::Struct, id),
+            AhType::Enum(id) => (WrappedKind::Enum, id),
+            AhType::Coroutine(id) => (WrappedKind::Coroutine, id),
+        };
+        AhType::Optional(Wrapped { kind, id })
+    }
+
+    /// The type inside an optional (int for a bare `nil`); other types are returned unchanged.
+    pub(crate) fn unwrap_optional(self) -> AhType {
+        let AhType::Optional(Wrapped { kind, id }) = self else {
+            return self;
+        };
+        match kind {
+            WrappedKind::Nil | WrappedKind::Int => AhType::Int,
+            WrappedKind::Float => AhType::Float,
+            WrappedKind::Str => AhType::Str,
+            WrappedKind::Vec => AhType::Vec,
+            WrappedKind::Mat => AhType::Mat,
+            WrappedKind::Closure => AhType::Closure(id),
+            WrappedKind::Func => AhType::Func(id),
+            WrappedKind::Table => AhType::Table(id),
+            WrappedKind::List => AhType::List(id),
+            WrappedKind::Struct => AhType::Struct(id),
+            WrappedKind::Enum => AhType::Enum(id),
+            WrappedKind::Coroutine => AhType::Coroutine(id),
+        }
+    }
 }
 
 /// Map a source-level type name (e.g. "int", "str") to our internal tag.
@@ -24,10 +148,67 @@ pub(crate) fn type_name_to_ah(name: &str) -> AhType {
         "float" | "f64" => AhType::Float,
         "vec" => AhType::Vec,
         "mat" | "matrix" => AhType::Mat,
+        "list" => AhType::List(ANY_LIST_TYPE),
         _ => AhType::Int,
     }
 }
 
+/// Split a function type in its canonical spelling `fn(int,int)->int` into the
+/// parameter type names and the result type name.
+pub(crate) fn split_func_type(name: &str) -> Option<(Vec<&str>, &str)> {
+    let rest = name.strip_prefix("fn(")?;
+    let mut depth = 0;
+    let mut params = Vec::new();
+    let mut start = 0;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ')' => {
+                if i > start {
+                    params.push(&rest[start..i]);
+                }
+                return Some((params, rest[i + 1..].strip_prefix("->")?));
+            }
+            ',' if depth == 0 => {
+                params.push(&rest[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Common type of two conditional branches: equal types, or float when one side is an int.
+/// When either side is optional the result is optional too; a bare `nil` fits any type.
+pub(crate) fn unify_branch_types(a: AhType, b: AhType) -> Option<AhType> {
+    match (a, b) {
+        _ if a == b => Some(a),
+        (AhType::NIL, other) | (other, AhType::NIL) => Some(other.optional()),
+        // No int to float widening here: the nil bit pattern must stay intact
+        (AhType::Optional(_), _) | (_, AhType::Optional(_)) => {
+            (a.unwrap_optional() == b.unwrap_optional()).then(|| a.optional())
+        }
+        (AhType::Int, AhType::Float) | (AhType::Float, AhType::Int) => Some(AhType::Float),
+        _ => None,
+    }
+}
+
+/// Whether a value of type `actual` can be stored in a slot of type `expected`;
+/// an int is converted for a float slot.
+pub(crate) fn slot_accepts(expected: AhType, actual: AhType) -> bool {
+    match (expected, actual) {
+        _ if expected == actual => true,
+        // Element types are tracked per literal; any list fits a list slot
+        (AhType::List(_), AhType::List(_)) => true,
+        // An optional slot takes nil or a plain value of its type
+        (AhType::Optional(_), _) => actual == AhType::NIL || actual == expected.unwrap_optional(),
+        (AhType::Float, AhType::Int) => true,
+        _ => false,
+    }
+}
+
 /// Information about a single closure collected during the analysis pass.
 #[allow(dead_code)]
 pub(crate) struct ClosureInfo {
@@ -45,13 +226,90 @@ pub(crate) struct ClosureInfo {
     pub(crate) table_idx: u32,
     /// Inferred return type of the closure body
     pub(crate) return_type: AhType,
+    /// Names that live in shared heap cells in the enclosing function, and so in
+    /// this closure too (see `box_captured_vars`)
+    pub(crate) boxed: HashSet<String>,
+    /// Declared parameter types; unannotated parameters are ints
+    pub(crate) param_types: Vec<AhType>,
+    /// Captures the closure calls, such as itself or a sibling declared after it
+    pub(crate) called: HashSet<String>,
+}
+
+/// Signature of a function type; equal signatures share one `AhType::Func` id.
+#[derive(PartialEq)]
+pub(crate) struct FuncTypeInfo {
+    pub(crate) params: Vec<AhType>,
+    pub(crate) ret: AhType,
+}
+
+/// A named function used as a value. Its trampoline takes the closure calling
+/// convention (an ignored env pointer, then the arguments) and sits in the
+/// function table after the closures.
+pub(crate) struct FuncRef {
+    /// Function index of the trampoline
+    pub(crate) func_idx: u32,
+    /// Type index of the trampoline: (i32, i64 * param_count) -> i64
+    pub(crate) type_idx: u32,
+    /// Index into the WASM function table
+    pub(crate) table_idx: u32,
+    /// The function's type, an `AhType::Func`
+    pub(crate) ty: AhType,
+}
+
+/// A generator function: its step function, `(i32 frame) -> i64`, runs the body
+/// up to the next `yield` and sits in the function table after the function values.
+pub(crate) struct GenInfo {
+    pub(crate) step_func_idx: u32,
+    pub(crate) table_idx: u32,
 }
 
-/// Compile-time type info for a table literal's fields.
+/// Compile-time type info for a table literal's fields, or a list literal's elements.
 pub(crate) struct TableTypeInfo {
     pub(crate) fields: HashMap<String, AhType>,
+    /// Type of the integer-keyed entries or list elements, when known (e.g. results
+    /// of `split`). Lists whose elements have different types leave it unknown.
+    pub(crate) elements: Option<AhType>,
 }
 
+/// A declared struct: fields are 8-byte slots at `index * 8` from the struct pointer.
+pub(crate) struct StructInfo {
+    pub(crate) name: String,
+    pub(crate) fields: Vec<(String, AhType)>,
+}
+
+impl StructInfo {
+    /// Slot index and type of a field.
+    pub(crate) fn field(&self, name: &str) -> Option<(usize, AhType)> {
+        self.fields
+            .iter()
+            .position(|(field, _)| field == name)
+            .map(|i| (i, self.fields[i].1))
+    }
+}
+
+/// A declared enum: a value is a pointer to its variant tag (8 bytes), followed
+/// by the variant's payload in 8-byte slots.
+pub(crate) struct EnumInfo {
+    pub(crate) name: String,
+    pub(crate) variants: Vec<(String, Vec<AhType>)>,
+}
+
+impl EnumInfo {
+    /// Tag and payload types of a variant.
+    pub(crate) fn variant(&self, name: &str) -> Option<(usize, &[AhType])> {
+        self.variants
+            .iter()
+            .position(|(variant, _)| variant == name)
+            .map(|tag| (tag, self.variants[tag].1.as_slice()))
+    }
+}
+
+/// Reserved table type for string lists produced by `split`.
+pub(crate) const STR_LIST_TABLE_TYPE: u32 = 0;
+
+/// Reserved type for lists of unknown element type (`list` parameters and returns).
+pub(crate) const ANY_LIST_TYPE: u32 = 1;
+
 /// Context for compiling a single function body
 pub(crate) struct FuncCtx {
     /// Map from variable name to local index
@@ -91,8 +349,32 @@ pub(crate) struct FuncCtx {
     pub(crate) owned_tables: Vec<String>,
     /// Parameter names (borrowed references, not freed by this function)
     pub(crate) param_names: HashSet<String>,
+    /// Variables declared `int` or initialized with an evident int; any other
+    /// variable of type `Int` may hold a value of unknown type
+    pub(crate) int_vars: HashSet<String>,
     /// Table variables captured by closures in this function (must NOT be freed)
     pub(crate) captured_tables: HashSet<String>,
+    /// Variables that live in a heap cell shared with closures; their local holds
+    /// the cell address
+    pub(crate) boxed: HashSet<String>,
+    /// Number of values the function returns; `return f(...)` of a call returning
+    /// as many becomes a tail call
+    pub(crate) result_count: usize,
+    /// The function's name and the depth of the loop around its body, when
+    /// tail calls are off and `return` of a call to itself jumps back instead
+    pub(crate) self_loop: Option<(String, u32)>,
+    /// Set while compiling the step function of a generator
+    pub(crate) generator: Option<GenFrame>,
+    /// Block depths of the handlers of the enclosing `try` statements, innermost last
+    pub(crate) catch_depths: Vec<u32>,
+    /// `defer` bodies still to run when the enclosing blocks are left, innermost last
+    pub(crate) defers: Vec<Deferred>,
+    /// Set while compiling a `defer` body, which `return`, `break` and `continue` cannot leave
+    pub(crate) in_defer: bool,
+    /// Pre-allocated temps of the `defer` statements (one per statement)
+    pub(crate) defer_temps: Vec<DeferTemps>,
+    /// Index into defer_temps for the next `defer` to consume
+    pub(crate) defer_temps_cursor: usize,
     /// Pre-allocated temp locals for saving return values during table cleanup
     pub(crate) return_save_temps: Vec<Vec<u32>>,
     /// Index into return_save_temps for the next return statement to consume
@@ -109,25 +391,103 @@ pub(crate) struct FuncCtx {
     pub(crate) destructure_temps: Vec<u32>,
     /// Index into destructure_temps for the next destructuring to consume
     pub(crate) destructure_temps_cursor: usize,
+    /// Pre-allocated temp pairs for integer abs/min/max/clamp (one per call named like them)
+    pub(crate) math_temps: Vec<(u32, u32)>,
+    /// Index into math_temps for the next integer math builtin to consume
+    pub(crate) math_temps_cursor: usize,
+    /// Pre-allocated temp locals for for-in loops (iterable, index, length)
+    pub(crate) for_in_temps: Vec<(u32, u32, u32)>,
+    /// Index into for_in_temps for the next for-in loop to consume
+    pub(crate) for_in_temps_cursor: usize,
+    /// Pre-allocated temp locals for `a ?? b` (one per coalescing operator)
+    pub(crate) coalesce_temps: Vec<u32>,
+    /// Index into coalesce_temps for the next `??` to consume
+    pub(crate) coalesce_temps_cursor: usize,
+    /// Pre-allocated temp locals for higher-order builtins (one group per call named like them)
+    pub(crate) hof_temps: Vec<HofTemps>,
+    /// Index into hof_temps for the next higher-order builtin to consume
+    pub(crate) hof_temps_cursor: usize,
     /// Pre-allocated fixed helper locals for inline SIMD operations (always 12 locals)
     /// Used by emit_vec_add_simd and similar inline SIMD codegen helpers
     pub(crate) simd_helpers: [u32; 12],
 }
 
+/// Temp locals of a higher-order builtin such as `map` or `zip`.
+#[derive(Clone, Copy)]
+pub(crate) struct HofTemps {
+    /// The collection walked
+    pub(crate) source: u32,
+    /// The second collection of `zip`, or the key list of a table
+    pub(crate) other: u32,
+    pub(crate) index: u32,
+    pub(crate) len: u32,
+    /// The collection being built, or the answer of `any`/`all`
+    pub(crate) result: u32,
+    /// The current element
+    pub(crate) item: u32,
+    /// The accumulator of `reduce`, the count of a vec `filter`, the sort keys
+    /// of `sort_by` or the pair being built by `zip`/`enumerate`
+    pub(crate) acc: u32,
+    /// The function value, when it is not compiled in place
+    pub(crate) callee: u32,
+}
+
+/// State of a generator's step function. Its frame holds the resume state
+/// (0 before the first run, `k` when suspended at the k-th `yield`, -1 when
+/// finished), the last value, and then every local in an 8-byte slot.
+#[derive(Clone, Copy)]
+pub(crate) struct GenFrame {
+    /// The frame pointer parameter
+    pub(crate) frame: u32,
+    /// Local that is 1 while jumping back to the `yield` the generator stopped at
+    pub(crate) resuming: u32,
+    /// Declared type of the yielded and returned values
+    pub(crate) value_type: AhType,
+    /// Number of the next `yield` to compile, counting from 1 in source order
+    pub(crate) next_yield: i64,
+    /// Inside a statement without `yield`, which is compiled as usual
+    pub(crate) guarded: bool,
+}
+
 #[derive(Clone, Copy)]
 pub(crate) struct LoopInfo {
     /// Label depth for `break` (the outer block)
     pub(crate) break_depth: u32,
     /// Label depth for `continue` (the loop itself)
     pub(crate) continue_depth: u32,
+    /// Number of `ctx.defers` registered outside the loop
+    pub(crate) defer_depth: usize,
+}
+
+/// Positions in the pre-allocated temp lists, in the order of `FuncCtx::temp_cursors`.
+pub(crate) type TempCursors = [usize; 14];
+
+/// A `defer` body waiting for its block to be left. It is compiled again at every
+/// exit, each time with the catch handlers and temps of the `defer` statement.
+#[derive(Clone)]
+pub(crate) struct Deferred {
+    pub(crate) body: Block,
+    /// Number of `ctx.catch_depths` outside the `defer` statement
+    pub(crate) catch_len: usize,
+    /// Temp cursors at the start of the body
+    pub(crate) cursors: TempCursors,
+}
+
+/// Temps of a `defer` statement, pre-allocated in prescan.
+#[derive(Clone, Copy)]
+pub(crate) struct DeferTemps {
+    /// Saves the pending error while the body runs
+    pub(crate) error: u32,
+    /// Temp cursors past the body
+    pub(crate) end: TempCursors,
 }
 
 impl FuncCtx {
     pub(crate) fn new() -> Self {
         Self {
             locals: HashMap::new(),
-            next_local: 12, // 从 12 开始，因为 0-11 被 SIMD helpers 占用
-            extra_locals: vec![ValType::I64; 12], // 预先添加 12 个 i64 locals
+            next_local: 0,
+            extra_locals: Vec::new(),
             loop_depth_stack: Vec::new(),
             block_depth: 0,
             power_temps: Vec::new(),
@@ -141,9 +501,19 @@ impl FuncCtx {
             closure_env_temps_cursor: 0,
             table_temps: Vec::new(),
             table_temps_cursor: 0,
+            boxed: HashSet::new(),
             owned_tables: Vec::new(),
             param_names: HashSet::new(),
+            int_vars: HashSet::new(),
             captured_tables: HashSet::new(),
+            result_count: 0,
+            self_loop: None,
+            generator: None,
+            catch_depths: Vec::new(),
+            defers: Vec::new(),
+            in_defer: false,
+            defer_temps: Vec::new(),
+            defer_temps_cursor: 0,
             return_save_temps: Vec::new(),
             return_save_temps_cursor: 0,
             vec_literal_temps: Vec::new(),
@@ -152,7 +522,15 @@ impl FuncCtx {
             mat_literal_temps_cursor: 0,
             destructure_temps: Vec::new(),
             destructure_temps_cursor: 0,
-            simd_helpers: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11], // 预分配 SIMD helper locals
+            math_temps: Vec::new(),
+            math_temps_cursor: 0,
+            for_in_temps: Vec::new(),
+            for_in_temps_cursor: 0,
+            coalesce_temps: Vec::new(),
+            coalesce_temps_cursor: 0,
+            hof_temps: Vec::new(),
+            hof_temps_cursor: 0,
+            simd_helpers: [0; 12], // 由 reserve_simd_helpers 在参数之后分配
         }
     }
 
@@ -177,6 +555,14 @@ impl FuncCtx {
         idx
     }
 
+    /// Reserve the 12 SIMD helper locals. Must run after all parameters are added,
+    /// because wasm places parameters at local indices 0..n-1.
+    pub(crate) fn reserve_simd_helpers(&mut self) {
+        for slot in 0..self.simd_helpers.len() {
+            self.simd_helpers[slot] = self.alloc_anonymous_local();
+        }
+    }
+
     pub(crate) fn declare_local(&mut self, name: &str) -> u32 {
         if let Some(&idx) = self.locals.get(name) {
             return idx;
@@ -199,6 +585,38 @@ impl FuncCtx {
         self.locals.get(name).copied()
     }
 
+    /// Push the value of a variable, loading it from its cell when boxed.
+    /// Returns false for an undeclared name.
+    pub(crate) fn emit_get(&self, name: &str, insn: &mut wasm_encoder::InstructionSink<'_>) -> bool {
+        let Some(idx) = self.get_local(name) else {
+            return false;
+        };
+        insn.local_get(idx);
+        if self.boxed.contains(name) {
+            insn.i32_wrap_i64();
+            insn.i64_load(CELL_ARG);
+        }
+        true
+    }
+
+    /// Start a store to a variable: a boxed one needs its cell address below the value.
+    pub(crate) fn begin_set(&self, name: &str, insn: &mut wasm_encoder::InstructionSink<'_>) {
+        if self.boxed.contains(name) {
+            insn.local_get(self.locals[name]);
+            insn.i32_wrap_i64();
+        }
+    }
+
+    /// Finish a store started by `begin_set`, declaring the variable if needed.
+    pub(crate) fn end_set(&mut self, name: &str, insn: &mut wasm_encoder::InstructionSink<'_>) {
+        if self.boxed.contains(name) {
+            insn.i64_store(CELL_ARG);
+        } else {
+            let idx = self.declare_local(name);
+            insn.local_set(idx);
+        }
+    }
+
     /// Pre-allocate a group of 3 temp locals for a power operation
     pub(crate) fn alloc_power_temps(&mut self) {
         let base = self.alloc_anonymous_local();
@@ -228,6 +646,136 @@ impl FuncCtx {
         temps
     }
 
+    /// Cursors of every pre-allocated temp list
+    pub(crate) fn temp_cursors(&self) -> TempCursors {
+        [
+            self.power_temps_cursor,
+            self.timer_temps_cursor,
+            self.closure_call_temps_cursor,
+            self.closure_env_temps_cursor,
+            self.table_temps_cursor,
+            self.return_save_temps_cursor,
+            self.vec_literal_temps_cursor,
+            self.mat_literal_temps_cursor,
+            self.destructure_temps_cursor,
+            self.math_temps_cursor,
+            self.for_in_temps_cursor,
+            self.coalesce_temps_cursor,
+            self.hof_temps_cursor,
+            self.defer_temps_cursor,
+        ]
+    }
+
+    /// Lengths of the pre-allocated temp lists: the cursors once every temp allocated
+    /// so far has been claimed
+    pub(crate) fn temp_counts(&self) -> TempCursors {
+        [
+            self.power_temps.len(),
+            self.timer_temps.len(),
+            self.closure_call_temps.len(),
+            self.closure_env_temps.len(),
+            self.table_temps.len(),
+            self.return_save_temps.len(),
+            self.vec_literal_temps.len(),
+            self.mat_literal_temps.len(),
+            self.destructure_temps.len(),
+            self.math_temps.len(),
+            self.for_in_temps.len(),
+            self.coalesce_temps.len(),
+            self.hof_temps.len(),
+            self.defer_temps.len(),
+        ]
+    }
+
+    pub(crate) fn set_temp_cursors(&mut self, cursors: TempCursors) {
+        [
+            self.power_temps_cursor,
+            self.timer_temps_cursor,
+            self.closure_call_temps_cursor,
+            self.closure_env_temps_cursor,
+            self.table_temps_cursor,
+            self.return_save_temps_cursor,
+            self.vec_literal_temps_cursor,
+            self.mat_literal_temps_cursor,
+            self.destructure_temps_cursor,
+            self.math_temps_cursor,
+            self.for_in_temps_cursor,
+            self.coalesce_temps_cursor,
+            self.hof_temps_cursor,
+            self.defer_temps_cursor,
+        ] = cursors;
+    }
+
+    /// Forget the temps allocated since `temp_counts` returned `counts`; their
+    /// locals stay declared but are never claimed
+    pub(crate) fn truncate_temps(&mut self, counts: TempCursors) {
+        let [power, timer, closure_call, closure_env, table, return_save, vec_literal, mat_literal, destructure, math, for_in, coalesce, hof, defer] = counts;
+        self.power_temps.truncate(power);
+        self.timer_temps.truncate(timer);
+        self.closure_call_temps.truncate(closure_call);
+        self.closure_env_temps.truncate(closure_env);
+        self.table_temps.truncate(table);
+        self.return_save_temps.truncate(return_save);
+        self.vec_literal_temps.truncate(vec_literal);
+        self.mat_literal_temps.truncate(mat_literal);
+        self.destructure_temps.truncate(destructure);
+        self.math_temps.truncate(math);
+        self.for_in_temps.truncate(for_in);
+        self.coalesce_temps.truncate(coalesce);
+        self.hof_temps.truncate(hof);
+        self.defer_temps.truncate(defer);
+    }
+
+    /// Pre-allocate the temp locals of a for-in loop (iterable, index, length)
+    pub(crate) fn alloc_for_in_temps(&mut self) {
+        let items = self.alloc_anonymous_local();
+        let index = self.alloc_anonymous_local();
+        let len = self.alloc_anonymous_local();
+        self.for_in_temps.push((items, index, len));
+    }
+
+    /// Claim the next pre-allocated for-in temp group
+    pub(crate) fn claim_for_in_temps(&mut self) -> (u32, u32, u32) {
+        let temps = self.for_in_temps[self.for_in_temps_cursor];
+        self.for_in_temps_cursor += 1;
+        temps
+    }
+
+    /// Pre-allocate the temp local of a `??` operator (holds the left operand)
+    pub(crate) fn alloc_coalesce_temp(&mut self) {
+        let temp = self.alloc_anonymous_local();
+        self.coalesce_temps.push(temp);
+    }
+
+    /// Claim the next pre-allocated `??` temp
+    pub(crate) fn claim_coalesce_temp(&mut self) -> u32 {
+        let temp = self.coalesce_temps[self.coalesce_temps_cursor];
+        self.coalesce_temps_cursor += 1;
+        temp
+    }
+
+    /// Pre-allocate the temp locals of a higher-order builtin
+    pub(crate) fn alloc_hof_temps(&mut self) {
+        let temps = HofTemps {
+            source: self.alloc_anonymous_local(),
+            other: self.alloc_anonymous_local(),
+            index: self.alloc_anonymous_local(),
+            len: self.alloc_anonymous_local(),
+            result: self.alloc_anonymous_local(),
+            item: self.alloc_anonymous_local(),
+            acc: self.alloc_anonymous_local(),
+            callee: self.alloc_anonymous_local(),
+        };
+        self.hof_temps.push(temps);
+    }
+
+    /// Claim the next pre-allocated higher-order builtin temp group
+    pub(crate) fn claim_hof_temps(&mut self) -> HofTemps {
+        let temps = self.hof_temps[self.hof_temps_cursor];
+        self.hof_temps_cursor += 1;
+        temps
+    }
+
     /// Pre-allocate temp locals for a closure call_indirect (one per argument)
     pub(crate) fn alloc_closure_call_temps(&mut self, num_args: usize) {
         let mut temps = Vec::with_capacity(num_args);
@@ -243,6 +791,50 @@ impl FuncCtx {
         self.closure_call_temps_cursor += 1;
         temps
     }
+
+    /// Pre-allocate a pair of temp locals for an integer math builtin
+    pub(crate) fn alloc_math_temps(&mut self) {
+        let a = self.alloc_anonymous_local();
+        let b = self.alloc_anonymous_local();
+        self.math_temps.push((a, b));
+    }
+
+    /// Claim the next pre-allocated math temp pair
+    pub(crate) fn claim_math_temps(&mut self) -> (u32, u32) {
+        let temps = self.math_temps[self.math_temps_cursor];
+        self.math_temps_cursor += 1;
+        temps
+    }
+}
+
+/// A problem that does not stop compilation, such as a read of a table field
+/// that is never present.
+#[derive(Debug, Clone)]
+pub struct CodegenWarning {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    /// Index of the source file, as in `Span::file`
+    pub file: usize,
+}
+
+impl std::fmt::Display for CodegenWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "warning at line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Table field reads and writes collected while compiling.
+#[derive(Default)]
+pub(super) struct FieldChecks {
+    /// Reads of fields missing from a literal's type: (table type, field, span)
+    pub reads: Vec<(u32, String, Span)>,
+    /// Field names assigned anywhere in the program
+    pub assigned: HashSet<String>,
+    /// Table types written with keys known only at runtime
+    pub open: HashSet<u32>,
+    /// A table of unknown type was written with a runtime key
+    pub all_open: bool,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -252,5 +844,7 @@ pub enum CodegenError {
         message: String,
         line: usize,
         column: usize,
+        /// Index of the source file, as in `Span::file`
+        file: usize,
     },
 }
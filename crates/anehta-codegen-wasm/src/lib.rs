@@ -1,3 +1,3 @@
 mod codegen;
 
-pub use codegen::{WasmCodegen, CodegenError};
+pub use codegen::{WasmCodegen, CodegenError, CodegenWarning};
@@ -8,10 +8,17 @@ pub struct Lexer {
     column: usize,
     tokens: Vec<Token>,
     errors: Vec<LexError>,
+    /// Index of the source file, recorded in every span
+    file: usize,
 }
 
 impl Lexer {
     pub fn new(source: &str) -> Self {
+        Self::for_file(source, 0)
+    }
+
+    /// Lexer for the file at index `file` of a program made of several files
+    pub fn for_file(source: &str, file: usize) -> Self {
         Self {
             source: source.chars().collect(),
             pos: 0,
@@ -19,186 +26,203 @@ impl Lexer {
             column: 1,
             tokens: Vec::new(),
             errors: Vec::new(),
+            file,
         }
     }
 
     /// Tokenize the entire source and return the token list
     pub fn tokenize(&mut self) -> Result<Vec<Token>, LexError> {
         while self.pos < self.source.len() {
-            let ch = self.source[self.pos];
-            match ch {
-                // Whitespace (skip)
-                ' ' | '\t' => {
-                    self.advance();
-                }
+            self.lex_token();
+        }
 
-                // Newline handling
-                '\n' => {
-                    let span = self.span();
-                    self.push_token(TokenType::Newline, "\\n".to_string(), span);
-                    self.pos += 1;
-                    self.line += 1;
-                    self.column = 1;
-                }
-                '\r' => {
-                    let span = self.span();
+        // Append EOF
+        let span = self.span();
+        self.push_token(TokenType::Eof, "End".to_string(), span);
+
+        if let Some(err) = self.errors.pop() {
+            return Err(err);
+        }
+
+        Ok(std::mem::take(&mut self.tokens))
+    }
+
+    /// Lex a single token (or skip whitespace) starting at the current position
+    fn lex_token(&mut self) {
+        let ch = self.source[self.pos];
+        match ch {
+            // Whitespace (skip)
+            ' ' | '\t' => {
+                self.advance();
+            }
+
+            // Newline handling
+            '\n' => {
+                let span = self.span();
+                self.push_token(TokenType::Newline, "\\n".to_string(), span);
+                self.pos += 1;
+                self.line += 1;
+                self.column = 1;
+            }
+            '\r' => {
+                let span = self.span();
+                self.pos += 1;
+                if self.pos < self.source.len() && self.source[self.pos] == '\n' {
+                    self.push_token(TokenType::Newline, "\\r\\n".to_string(), span);
                     self.pos += 1;
-                    if self.pos < self.source.len() && self.source[self.pos] == '\n' {
-                        self.push_token(TokenType::Newline, "\\r\\n".to_string(), span);
-                        self.pos += 1;
-                    } else {
-                        self.push_token(TokenType::Newline, "\\r".to_string(), span);
-                    }
-                    self.line += 1;
-                    self.column = 1;
+                } else {
+                    self.push_token(TokenType::Newline, "\\r".to_string(), span);
                 }
+                self.line += 1;
+                self.column = 1;
+            }
 
-                // String literal
-                '"' => {
-                    self.read_string();
-                }
+            // String literal
+            '"' => {
+                self.read_string();
+            }
 
-                // Digits -> number
-                '0'..='9' => {
-                    self.read_number();
-                }
+            // Digits -> number
+            '0'..='9' => {
+                self.read_number();
+            }
 
-                // Identifiers and keywords
-                'a'..='z' | 'A'..='Z' | '_' => {
-                    self.read_identifier_or_keyword();
-                }
+            // f"..." interpolated string
+            'f' if self.source.get(self.pos + 1) == Some(&'"') => {
+                self.read_fstring();
+            }
 
-                // Operators and delimiters
-                '+' => self.read_plus(),
-                '-' => self.read_minus(),
-                '*' => self.read_star(),
-                '/' => self.read_slash(),
-                '^' => {
-                    let span = self.span();
-                    self.push_token(TokenType::Power, "^".to_string(), span);
-                    self.advance();
-                }
-                '%' => {
-                    let span = self.span();
-                    self.push_token(TokenType::Mod, "%".to_string(), span);
-                    self.advance();
-                }
-                '~' => {
-                    let span = self.span();
-                    self.push_token(TokenType::Rand, "~".to_string(), span);
-                    self.advance();
-                }
-                '!' => self.read_bang(),
-                '>' => self.read_gt(),
-                '<' => self.read_lt(),
-                '=' => self.read_eq(),
-                '&' => self.read_amp(),
-                '|' => self.read_pipe(),
-                '.' => {
-                    let span = self.span();
-                    self.advance();
-                    // Check for .. or .^
-                    match self.current() {
-                        Some('.') => {
-                            self.push_token(TokenType::Range, "..".to_string(), span);
-                            self.advance();
-                        }
-                        Some('^') => {
-                            self.push_token(TokenType::DotPow, ".^".to_string(), span);
+            // Identifiers and keywords
+            'a'..='z' | 'A'..='Z' | '_' => {
+                self.read_identifier_or_keyword();
+            }
+
+            // Operators and delimiters
+            '+' => self.read_plus(),
+            '-' => self.read_minus(),
+            '*' => self.read_star(),
+            '/' => self.read_slash(),
+            '^' => {
+                let span = self.span();
+                self.push_token(TokenType::Power, "^".to_string(), span);
+                self.advance();
+            }
+            '%' => {
+                let span = self.span();
+                self.push_token(TokenType::Mod, "%".to_string(), span);
+                self.advance();
+            }
+            '~' => {
+                let span = self.span();
+                self.push_token(TokenType::Rand, "~".to_string(), span);
+                self.advance();
+            }
+            '!' => self.read_bang(),
+            '?' => self.read_question(),
+            '>' => self.read_gt(),
+            '<' => self.read_lt(),
+            '=' => self.read_eq(),
+            '&' => self.read_amp(),
+            '|' => self.read_pipe(),
+            '.' => {
+                let span = self.span();
+                self.advance();
+                // Check for ..., .. or .^
+                match self.current() {
+                    Some('.') => {
+                        self.advance();
+                        if self.current() == Some('.') {
+                            self.push_token(TokenType::Ellipsis, "...".to_string(), span);
                             self.advance();
-                        }
-                        _ => {
-                            self.push_token(TokenType::Dot, ".".to_string(), span);
+                        } else {
+                            self.push_token(TokenType::Range, "..".to_string(), span);
                         }
                     }
-                }
-                ',' => {
-                    let span = self.span();
-                    self.push_token(TokenType::Comma, ",".to_string(), span);
-                    self.advance();
-                }
-                ':' => {
-                    let span = self.span();
-                    self.push_token(TokenType::Colon, ":".to_string(), span);
-                    self.advance();
-                }
-                ';' => {
-                    let span = self.span();
-                    self.push_token(TokenType::Semicolon, ";".to_string(), span);
-                    self.advance();
-                }
-                '(' => {
-                    let span = self.span();
-                    self.push_token(TokenType::LParen, "(".to_string(), span);
-                    self.advance();
-                }
-                ')' => {
-                    let span = self.span();
-                    self.push_token(TokenType::RParen, ")".to_string(), span);
-                    self.advance();
-                }
-                '{' => {
-                    let span = self.span();
-                    self.push_token(TokenType::LBrace, "{".to_string(), span);
-                    self.advance();
-                }
-                '}' => {
-                    let span = self.span();
-                    self.push_token(TokenType::RBrace, "}".to_string(), span);
-                    self.advance();
-                }
-                '[' => {
-                    let span = self.span();
-                    self.push_token(TokenType::LBracket, "[".to_string(), span);
-                    self.advance();
-                }
-                ']' => {
-                    let span = self.span();
-                    self.push_token(TokenType::RBracket, "]".to_string(), span);
-                    self.advance();
-                }
-                '@' => {
-                    let span = self.span();
-                    self.push_token(TokenType::At, "@".to_string(), span);
-                    self.advance();
-                }
-                '#' => {
-                    let span = self.span();
-                    self.push_token(TokenType::Hash, "#".to_string(), span);
-                    self.advance();
-                }
-                '\'' => {
-                    let span = self.span();
-                    self.push_token(TokenType::Transpose, "'".to_string(), span);
-                    self.advance();
-                }
-                '\\' => {
-                    let span = self.span();
-                    self.push_token(TokenType::Backslash, "\\".to_string(), span);
-                    self.advance();
-                }
-
-                // Illegal character
-                _ => {
-                    self.errors.push(LexError::Error {
-                        line: self.line,
-                        column: self.column,
-                        message: format!("illegal token '{}'", ch),
-                    });
-                    self.advance();
+                    Some('^') => {
+                        self.push_token(TokenType::DotPow, ".^".to_string(), span);
+                        self.advance();
+                    }
+                    _ => {
+                        self.push_token(TokenType::Dot, ".".to_string(), span);
+                    }
                 }
             }
-        }
-
-        // Append EOF
-        let span = self.span();
-        self.push_token(TokenType::Eof, "End".to_string(), span);
+            ',' => {
+                let span = self.span();
+                self.push_token(TokenType::Comma, ",".to_string(), span);
+                self.advance();
+            }
+            ':' => {
+                let span = self.span();
+                self.push_token(TokenType::Colon, ":".to_string(), span);
+                self.advance();
+            }
+            ';' => {
+                let span = self.span();
+                self.push_token(TokenType::Semicolon, ";".to_string(), span);
+                self.advance();
+            }
+            '(' => {
+                let span = self.span();
+                self.push_token(TokenType::LParen, "(".to_string(), span);
+                self.advance();
+            }
+            ')' => {
+                let span = self.span();
+                self.push_token(TokenType::RParen, ")".to_string(), span);
+                self.advance();
+            }
+            '{' => {
+                let span = self.span();
+                self.push_token(TokenType::LBrace, "{".to_string(), span);
+                self.advance();
+            }
+            '}' => {
+                let span = self.span();
+                self.push_token(TokenType::RBrace, "}".to_string(), span);
+                self.advance();
+            }
+            '[' => {
+                let span = self.span();
+                self.push_token(TokenType::LBracket, "[".to_string(), span);
+                self.advance();
+            }
+            ']' => {
+                let span = self.span();
+                self.push_token(TokenType::RBracket, "]".to_string(), span);
+                self.advance();
+            }
+            '@' => {
+                let span = self.span();
+                self.push_token(TokenType::At, "@".to_string(), span);
+                self.advance();
+            }
+            '#' => {
+                let span = self.span();
+                self.push_token(TokenType::Hash, "#".to_string(), span);
+                self.advance();
+            }
+            '\'' => {
+                let span = self.span();
+                self.push_token(TokenType::Transpose, "'".to_string(), span);
+                self.advance();
+            }
+            '\\' => {
+                let span = self.span();
+                self.push_token(TokenType::Backslash, "\\".to_string(), span);
+                self.advance();
+            }
 
-        if let Some(err) = self.errors.pop() {
-            return Err(err);
+            // Illegal character
+            _ => {
+                self.errors.push(LexError::Error {
+                    line: self.line,
+                    column: self.column,
+                    message: format!("illegal token '{}'", ch),
+                });
+                self.advance();
+            }
         }
-
-        Ok(std::mem::take(&mut self.tokens))
     }
 
     // ── Helpers ──────────────────────────────────────────────
@@ -215,7 +239,7 @@ impl Lexer {
     }
 
     fn span(&self) -> Span {
-        Span { line: self.line, column: self.column }
+        Span { line: self.line, column: self.column, file: self.file }
     }
 
     fn push_token(&mut self, token_type: TokenType, value: String, span: Span) {
@@ -234,6 +258,10 @@ impl Lexer {
                 value.push(ch);
                 self.advance();
             } else if ch == '.' {
+                // `1..5` is a range, not a malformed float
+                if self.source.get(self.pos + 1) == Some(&'.') {
+                    break;
+                }
                 dot_count += 1;
                 if dot_count > 1 {
                     self.errors.push(LexError::Error {
@@ -269,12 +297,7 @@ impl Lexer {
                 break;
             }
             if ch == '\\' {
-                // Escape: skip backslash and take next char literally
-                self.advance();
-                if let Some(escaped) = self.current() {
-                    value.push(escaped);
-                    self.advance();
-                }
+                self.read_escape(&mut value);
                 continue;
             }
             value.push(ch);
@@ -292,6 +315,115 @@ impl Lexer {
         self.push_token(TokenType::StringLit, value, span);
     }
 
+    /// Escape sequence after a backslash: `\n`, `\t`, `\r` and `\0` are control
+    /// characters; any other character is taken literally (`\"`, `\\`).
+    fn read_escape(&mut self, out: &mut String) {
+        self.advance(); // skip backslash
+        if let Some(escaped) = self.current() {
+            out.push(match escaped {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                other => other,
+            });
+            self.advance();
+        }
+    }
+
+    /// `f"HP: {hp} / {max}"` — emits FStringStart, then StringLit pieces and
+    /// InterpStart ... InterpEnd runs around the embedded expression tokens, then
+    /// FStringEnd. `{{` and `}}` produce literal braces.
+    fn read_fstring(&mut self) {
+        let span = self.span();
+        self.advance(); // f
+        self.advance(); // "
+        self.push_token(TokenType::FStringStart, "f\"".to_string(), span);
+
+        let mut text = String::new();
+        let mut text_span = self.span();
+
+        loop {
+            let Some(ch) = self.current() else {
+                self.errors.push(LexError::Error {
+                    line: span.line,
+                    column: span.column,
+                    message: "lose a '\"'".to_string(),
+                });
+                break;
+            };
+            let next = self.source.get(self.pos + 1).copied();
+            match ch {
+                '"' => {
+                    self.flush_fstring_text(&mut text, text_span);
+                    let end_span = self.span();
+                    self.push_token(TokenType::FStringEnd, "\"".to_string(), end_span);
+                    self.advance();
+                    break;
+                }
+                '\\' => self.read_escape(&mut text),
+                '{' | '}' if next == Some(ch) => {
+                    text.push(ch);
+                    self.advance();
+                    self.advance();
+                }
+                '{' => {
+                    self.flush_fstring_text(&mut text, text_span);
+                    let open_span = self.span();
+                    self.push_token(TokenType::InterpStart, "{".to_string(), open_span);
+                    self.advance();
+                    self.read_interpolation(open_span);
+                    text_span = self.span();
+                }
+                '}' => {
+                    self.errors.push(LexError::Error {
+                        line: self.line,
+                        column: self.column,
+                        message: "single '}' in f-string; use '}}' for a literal brace".to_string(),
+                    });
+                    self.advance();
+                }
+                _ => {
+                    text.push(ch);
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    /// Lex the tokens of an embedded expression up to its matching `}`
+    fn read_interpolation(&mut self, open_span: Span) {
+        let mut depth = 0usize;
+        loop {
+            match self.current() {
+                None | Some('\n') | Some('\r') => {
+                    self.errors.push(LexError::Error {
+                        line: open_span.line,
+                        column: open_span.column,
+                        message: "unterminated '{' in f-string".to_string(),
+                    });
+                    return;
+                }
+                Some('}') if depth == 0 => {
+                    let span = self.span();
+                    self.push_token(TokenType::InterpEnd, "}".to_string(), span);
+                    self.advance();
+                    return;
+                }
+                Some('{') => depth += 1,
+                Some('}') => depth -= 1,
+                _ => {}
+            }
+            self.lex_token();
+        }
+    }
+
+    fn flush_fstring_text(&mut self, text: &mut String, span: Span) {
+        if !text.is_empty() {
+            self.push_token(TokenType::StringLit, std::mem::take(text), span);
+        }
+    }
+
     // ── Identifier / Keyword ─────────────────────────────────
 
     fn read_identifier_or_keyword(&mut self) {
@@ -317,12 +449,25 @@ impl Lexer {
             "break" => TokenType::Break,
             "continue" => TokenType::Continue,
             "return" => TokenType::Return,
+            "yield" => TokenType::Yield,
+            "try" => TokenType::Try,
+            "catch" => TokenType::Catch,
+            "throw" => TokenType::Throw,
+            "defer" => TokenType::Defer,
+            "import" => TokenType::Import,
+            "pub" => TokenType::Pub,
             "true" => TokenType::True,
             "false" => TokenType::False,
+            "nil" => TokenType::Nil,
             "switch" => TokenType::Switch,
             "case" => TokenType::Case,
             "new" => TokenType::New,
+            "struct" => TokenType::Struct,
+            "enum" => TokenType::Enum,
+            "match" => TokenType::Match,
+            "impl" => TokenType::Impl,
             "timer" => TokenType::Timer,
+            "xor" => TokenType::Xor,
             _ => TokenType::Word,
         };
 
@@ -427,7 +572,30 @@ impl Lexer {
         }
     }
 
-    /// `>`, `>=`
+    /// `?.`, `??` (a lone `?` is illegal)
+    fn read_question(&mut self) {
+        let span = self.span();
+        self.advance(); // consume '?'
+        match self.current() {
+            Some('.') => {
+                self.advance();
+                self.push_token(TokenType::SafeDot, "?.".to_string(), span);
+            }
+            Some('?') => {
+                self.advance();
+                self.push_token(TokenType::Coalesce, "??".to_string(), span);
+            }
+            _ => {
+                self.errors.push(LexError::Error {
+                    line: span.line,
+                    column: span.column,
+                    message: "illegal token '?'".to_string(),
+                });
+            }
+        }
+    }
+
+    /// `>`, `>=`, `>>`, `>>>`
     fn read_gt(&mut self) {
         let span = self.span();
         self.advance(); // consume '>'
@@ -436,13 +604,22 @@ impl Lexer {
                 self.advance();
                 self.push_token(TokenType::GtEq, ">=".to_string(), span);
             }
+            Some('>') => {
+                self.advance();
+                if self.current() == Some('>') {
+                    self.advance();
+                    self.push_token(TokenType::UShr, ">>>".to_string(), span);
+                } else {
+                    self.push_token(TokenType::Shr, ">>".to_string(), span);
+                }
+            }
             _ => {
                 self.push_token(TokenType::Gt, ">".to_string(), span);
             }
         }
     }
 
-    /// `<`, `<=`
+    /// `<`, `<=`, `<<`
     fn read_lt(&mut self) {
         let span = self.span();
         self.advance(); // consume '<'
@@ -451,6 +628,10 @@ impl Lexer {
                 self.advance();
                 self.push_token(TokenType::LtEq, "<=".to_string(), span);
             }
+            Some('<') => {
+                self.advance();
+                self.push_token(TokenType::Shl, "<<".to_string(), span);
+            }
             _ => {
                 self.push_token(TokenType::Lt, "<".to_string(), span);
             }
@@ -30,6 +30,24 @@ fn float_literal() {
     assert_eq!(tokens[0].value, "3.14");
 }
 
+#[test]
+fn integer_followed_by_range() {
+    let tokens = lex("1..5");
+    assert_eq!(types(&tokens), vec![
+        TokenType::Number, TokenType::Range, TokenType::Number, TokenType::Eof,
+    ]);
+    assert_eq!(tokens[0].value, "1");
+}
+
+#[test]
+fn ellipsis_and_range() {
+    let tokens = lex("...xs 1..2");
+    assert_eq!(types(&tokens), vec![
+        TokenType::Ellipsis, TokenType::Word, TokenType::Number, TokenType::Range, TokenType::Number,
+        TokenType::Eof,
+    ]);
+}
+
 #[test]
 fn illegal_number_multiple_dots() {
     let result = Lexer::new("1.2.3").tokenize();
@@ -49,15 +67,60 @@ fn string_with_escape() {
     assert_eq!(tokens[0].value, "a\"b");
 }
 
+#[test]
+fn string_control_escapes() {
+    let tokens = lex(r#""a\tb\n" f"{x}\n""#);
+    assert_eq!(tokens[0].value, "a\tb\n");
+    assert_eq!(tokens[5].value, "\n");
+}
+
 #[test]
 fn unclosed_string_error() {
     let result = Lexer::new("\"hello").tokenize();
     assert!(result.is_err());
 }
 
+#[test]
+fn fstring_tokens() {
+    let tokens = lex(r#"f"HP: {hp} / {max}""#);
+    assert_eq!(types(&tokens), vec![
+        TokenType::FStringStart,
+        TokenType::StringLit,
+        TokenType::InterpStart, TokenType::Word, TokenType::InterpEnd,
+        TokenType::StringLit,
+        TokenType::InterpStart, TokenType::Word, TokenType::InterpEnd,
+        TokenType::FStringEnd,
+        TokenType::Eof,
+    ]);
+    assert_eq!(tokens[1].value, "HP: ");
+    assert_eq!(tokens[3].value, "hp");
+    assert_eq!(tokens[5].value, " / ");
+}
+
+#[test]
+fn fstring_nested_braces_and_strings() {
+    let tokens = lex(r#"f"{{a}} {t["k"]} {f"{x}"}""#);
+    assert_eq!(tokens[1].value, "{a} ");
+    assert!(tokens.iter().any(|t| t.token_type == TokenType::LBracket));
+    assert_eq!(tokens.iter().filter(|t| t.token_type == TokenType::FStringStart).count(), 2);
+}
+
+#[test]
+fn plain_string_braces_are_literal() {
+    let tokens = lex(r#""{hp}""#);
+    assert_eq!(types(&tokens), vec![TokenType::StringLit, TokenType::Eof]);
+    assert_eq!(tokens[0].value, "{hp}");
+}
+
+#[test]
+fn fstring_unterminated_interpolation_error() {
+    assert!(Lexer::new(r#"f"{hp""#).tokenize().is_err());
+    assert!(Lexer::new(r#"f"a } b""#).tokenize().is_err());
+}
+
 #[test]
 fn keywords() {
-    let tokens = lex("func var if else elseif for break continue return true false switch case new");
+    let tokens = lex("func var if else elseif for break continue return yield try catch throw defer import pub true false switch case new");
     let expected = vec![
         TokenType::Func,
         TokenType::Var,
@@ -68,6 +131,13 @@ fn keywords() {
         TokenType::Break,
         TokenType::Continue,
         TokenType::Return,
+        TokenType::Yield,
+        TokenType::Try,
+        TokenType::Catch,
+        TokenType::Throw,
+        TokenType::Defer,
+        TokenType::Import,
+        TokenType::Pub,
         TokenType::True,
         TokenType::False,
         TokenType::Switch,
@@ -213,10 +283,20 @@ fn column_tracking() {
 
 #[test]
 fn illegal_character_error() {
-    let result = Lexer::new("@").tokenize();
+    let result = Lexer::new("$").tokenize();
     assert!(result.is_err());
 }
 
+#[test]
+fn nil_safe_navigation_and_coalescing() {
+    let tokens = lex("t?.hp ?? nil");
+    assert_eq!(types(&tokens), vec![
+        TokenType::Word, TokenType::SafeDot, TokenType::Word,
+        TokenType::Coalesce, TokenType::Nil, TokenType::Eof,
+    ]);
+    assert!(Lexer::new("a ? b").tokenize().is_err());
+}
+
 #[test]
 fn bitwise_operators() {
     let tokens = lex("& |");
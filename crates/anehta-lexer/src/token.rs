@@ -1,8 +1,10 @@
 /// Source location span
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Span {
     pub line: usize,
     pub column: usize,
+    /// Index of the source file among the files of the program (0 for the first)
+    pub file: usize,
 }
 
 /// All token types in AnehtaLanguage
@@ -11,8 +13,13 @@ pub enum TokenType {
     // Literals
     Number,       // 42, 3.14
     StringLit,    // "hello"
+    FStringStart, // f"  (text pieces inside are StringLit)
+    FStringEnd,   // closing " of an f-string
+    InterpStart,  // { opening an embedded expression
+    InterpEnd,    // } closing an embedded expression
     True,         // true
     False,        // false
+    Nil,          // nil
     Word,         // identifiers
 
     // Arithmetic operators
@@ -46,9 +53,13 @@ pub enum TokenType {
     Also,         // &&
     Perhaps,      // ||
 
-    // Bitwise (reserved)
+    // Bitwise (~ in prefix position is complement)
     And,          // &
     Or,           // |
+    Xor,          // xor
+    Shl,          // <<
+    Shr,          // >> (arithmetic)
+    UShr,         // >>> (logical)
 
     // Vec/Mat operators
     At,           // @ (dot product)
@@ -62,6 +73,9 @@ pub enum TokenType {
     Casting,      // ->
     Dot,          // .
     Range,        // .. (range for slicing)
+    Ellipsis,     // ... (variadic parameter)
+    SafeDot,      // ?. (safe navigation)
+    Coalesce,     // ?? (nil coalescing)
 
     // Delimiters
     LParen,       // (
@@ -84,9 +98,20 @@ pub enum TokenType {
     Break,
     Continue,
     Return,
+    Yield,
+    Try,
+    Catch,
+    Throw,
+    Defer,
+    Import,
+    Pub,
     Switch,
     Case,
     New,
+    Struct,
+    Enum,
+    Match,
+    Impl,
     Timer,
 
     // Special
@@ -791,13 +791,8 @@ fn float_with_trailing_dot() {
 // ═══════════════════════════════════════════════════════════════════
 
 #[test]
-fn illegal_character_at() {
-    lex_err("@");
-}
-
-#[test]
-fn illegal_character_hash() {
-    lex_err("#");
+fn at_and_hash_are_vec_operators() {
+    assert_eq!(types(&lex("@ #")), vec![TokenType::At, TokenType::Hash, TokenType::Eof]);
 }
 
 #[test]
@@ -811,8 +806,8 @@ fn illegal_character_backtick() {
 }
 
 #[test]
-fn illegal_character_backslash() {
-    lex_err("\\");
+fn backslash_is_solve_operator() {
+    assert_eq!(types(&lex("\\")), vec![TokenType::Backslash, TokenType::Eof]);
 }
 
 #[test]
@@ -843,7 +838,7 @@ fn unclosed_string_with_escape_at_end() {
 #[test]
 fn illegal_char_in_valid_context() {
     // Valid tokens surrounding an illegal character
-    lex_err("var x = @10");
+    lex_err("var x = $10");
 }
 
 // ═══════════════════════════════════════════════════════════════════
@@ -910,6 +905,33 @@ fn all_double_operators() {
     );
 }
 
+#[test]
+fn bitwise_shift_operators() {
+    let tokens = lex("a<<2 >> b >>> 1 & c | d xor ~e >= f");
+    let mt = meaningful_types(&tokens);
+    assert_eq!(
+        mt,
+        vec![
+            TokenType::Word,
+            TokenType::Shl,
+            TokenType::Number,
+            TokenType::Shr,
+            TokenType::Word,
+            TokenType::UShr,
+            TokenType::Number,
+            TokenType::And,
+            TokenType::Word,
+            TokenType::Or,
+            TokenType::Word,
+            TokenType::Xor,
+            TokenType::Rand,
+            TokenType::Word,
+            TokenType::GtEq,
+            TokenType::Word,
+        ]
+    );
+}
+
 #[test]
 fn operators_without_spaces() {
     // All operators jammed together should still tokenize greedily
@@ -965,6 +987,11 @@ fn all_keywords_individually() {
         ("switch", TokenType::Switch),
         ("case", TokenType::Case),
         ("new", TokenType::New),
+        ("xor", TokenType::Xor),
+        ("struct", TokenType::Struct),
+        ("enum", TokenType::Enum),
+        ("match", TokenType::Match),
+        ("impl", TokenType::Impl),
     ];
 
     for (keyword, expected_type) in cases {
@@ -1015,7 +1042,7 @@ fn keyword_like_identifiers() {
 
 #[test]
 fn string_with_escaped_backslash() {
-    // "a\\b" -> content should be a\b (the lexer takes next char literally)
+    // "a\\b" -> content should be a\b (other escapes take the next char literally)
     let tokens = lex(r#""a\\b""#);
     assert_eq!(tokens[0].token_type, TokenType::StringLit);
     assert_eq!(tokens[0].value, "a\\b");
@@ -1023,10 +1050,10 @@ fn string_with_escaped_backslash() {
 
 #[test]
 fn string_with_escaped_n() {
-    // "hello\nworld" — the lexer escape takes 'n' literally (not as newline)
+    // "hello\nworld" — \n is a newline character
     let tokens = lex(r#""hello\nworld""#);
     assert_eq!(tokens[0].token_type, TokenType::StringLit);
-    assert_eq!(tokens[0].value, "hellonworld");
+    assert_eq!(tokens[0].value, "hello\nworld");
 }
 
 #[test]
@@ -1,57 +1,126 @@
 use anehta_lexer::Span;
 
 /// Top-level program: a list of statements
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Program {
     pub statements: Vec<Statement>,
 }
 
 /// Statement types
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Statement {
     FuncDecl(FuncDecl),
     VarDecl(VarDecl),
     Assignment(Assignment),
     IfStmt(IfStmt),
     ForStmt(ForStmt),
+    ForIn(ForInStmt),
     Block(Block),
     CallFunc(CallFunc),
     Return(ReturnStmt),
+    /// Suspend a generator function: yield expr
+    Yield(YieldStmt),
+    /// Run a block and handle the errors raised in it: try { } catch (e) { }
+    Try(TryStmt),
+    /// Raise an error: throw expr
+    Throw(ThrowStmt),
+    /// Run a block when the enclosing block is left: defer { }
+    Defer(DeferStmt),
+    /// Load another file as a module: import "lib/util.ah"  or  import util
+    Import(ImportStmt),
     Break(Span),
     Continue(Span),
     TimerStmt(TimerStmt),
     FieldAssign(FieldAssign),
     IndexAssign(IndexAssign),
     MethodCall(MethodCall),
+    /// Expression evaluated for its side effect: x++, t.count--, v[0]++
+    Expr(Expr),
+    StructDecl(StructDecl),
+    EnumDecl(EnumDecl),
+    ImplDecl(ImplDecl),
+}
+
+/// Struct declaration: struct Player { hp: int, pos: vec }
+#[derive(Debug, Clone)]
+pub struct StructDecl {
+    pub name: String,
+    pub fields: Vec<StructField>,
+    /// Declared `pub`: visible to the files that import this one
+    pub is_pub: bool,
+    pub span: Span,
+}
+
+/// A declared struct field: name: type
+#[derive(Debug, Clone)]
+pub struct StructField {
+    pub name: String,
+    pub type_name: String,
+    pub span: Span,
+}
+
+/// Method block: impl Player { func heal(n: int) -> int { ... } }
+/// Each method is an ordinary function named `Player.heal` whose first
+/// parameter is the receiver `self: Player`.
+#[derive(Debug, Clone)]
+pub struct ImplDecl {
+    pub type_name: String,
+    pub methods: Vec<FuncDecl>,
+    pub span: Span,
+}
+
+/// Enum declaration: enum State { Idle, Moving(vec), Attacking(int) }
+#[derive(Debug, Clone)]
+pub struct EnumDecl {
+    pub name: String,
+    pub variants: Vec<EnumVariant>,
+    /// Declared `pub`: visible to the files that import this one
+    pub is_pub: bool,
+    pub span: Span,
+}
+
+/// A declared enum variant with its payload types: Moving(vec)
+#[derive(Debug, Clone)]
+pub struct EnumVariant {
+    pub name: String,
+    pub payload: Vec<String>,
+    pub span: Span,
 }
 
 /// Timer block: timer { body } — auto-measures and prints elapsed time
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TimerStmt {
     pub body: Block,
     pub span: Span,
 }
 
 /// Function declaration: func name(params) -> return_types { body }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FuncDecl {
     pub name: String,
     pub params: Vec<FuncParam>,
     pub return_types: Vec<String>,
     pub body: Block,
+    /// Declared `pub`: visible to the files that import this one
+    pub is_pub: bool,
     pub span: Span,
 }
 
-/// Function parameter: name: type
-#[derive(Debug)]
+/// Function parameter: `name: type`, `name: type = default`, or the variadic
+/// rest parameter `...name` / `...name: type`, typed `...` / `...type`
+#[derive(Debug, Clone)]
 pub struct FuncParam {
     pub name: String,
     pub type_name: String,
+    /// Evaluated at each call site that leaves the parameter out
+    pub default: Option<Expr>,
+    /// Collects the remaining arguments: into a vec for `...name: float`, a list otherwise
+    pub variadic: bool,
     pub span: Span,
 }
 
 /// Variable declaration: var name: type  OR  var name = expr
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum VarDecl {
     TypeDecl {
         name: String,
@@ -62,7 +131,7 @@ pub enum VarDecl {
 }
 
 /// Assignment: name1, name2 = expr1, expr2
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Assignment {
     pub targets: Vec<String>,
     pub values: Vec<Expr>,
@@ -70,7 +139,7 @@ pub struct Assignment {
 }
 
 /// If statement: if (cond) { block } elseif ... else ...
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IfStmt {
     pub condition: BooleanExpr,
     pub body: Block,
@@ -79,7 +148,7 @@ pub struct IfStmt {
     pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ElseIfBranch {
     pub condition: BooleanExpr,
     pub body: Block,
@@ -87,7 +156,7 @@ pub struct ElseIfBranch {
 }
 
 /// For statement: for (init; cond; step) { body }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ForStmt {
     pub init: Option<Box<Statement>>,
     pub condition: Option<BooleanExpr>,
@@ -96,30 +165,88 @@ pub struct ForStmt {
     pub span: Span,
 }
 
+/// For-in statement: for (item in items) { body } — over a list, vec or str
+#[derive(Debug, Clone)]
+pub struct ForInStmt {
+    pub var: String,
+    pub iterable: Expr,
+    pub body: Block,
+    pub span: Span,
+}
+
 /// Block: { statements }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Block {
     pub statements: Vec<Statement>,
     pub span: Span,
 }
 
-/// Function call: name(args)
-#[derive(Debug)]
+/// Function call: name(args), with named arguments after the positional ones:
+/// spawn("orc", hp: 50)
+#[derive(Debug, Clone)]
 pub struct CallFunc {
     pub name: String,
     pub args: Vec<Expr>,
+    pub named: Vec<NamedArg>,
+    pub span: Span,
+}
+
+/// Named call argument: name: value
+#[derive(Debug, Clone)]
+pub struct NamedArg {
+    pub name: String,
+    pub value: Expr,
     pub span: Span,
 }
 
 /// Return statement: return expr1, expr2
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ReturnStmt {
     pub values: Vec<Expr>,
     pub span: Span,
 }
 
+/// Yield statement: yield expr (a bare `yield` has no value)
+#[derive(Debug, Clone)]
+pub struct YieldStmt {
+    pub value: Option<Expr>,
+    pub span: Span,
+}
+
+/// Try statement: try { body } catch (name) { handler }; the name is optional
+#[derive(Debug, Clone)]
+pub struct TryStmt {
+    pub body: Block,
+    pub error_name: Option<String>,
+    pub handler: Block,
+    pub span: Span,
+}
+
+/// Throw statement: throw expr (a str message or an Error value)
+#[derive(Debug, Clone)]
+pub struct ThrowStmt {
+    pub value: Expr,
+    pub span: Span,
+}
+
+/// Defer statement: defer { body }, run at the exit of the enclosing block
+#[derive(Debug, Clone)]
+pub struct DeferStmt {
+    pub body: Block,
+    pub span: Span,
+}
+
+/// Import statement: the file at `path`, relative to the importing file, is
+/// reached through `name`, its file stem: util.clamp(x)
+#[derive(Debug, Clone)]
+pub struct ImportStmt {
+    pub path: String,
+    pub name: String,
+    pub span: Span,
+}
+
 /// Boolean expression (comparison with logical connectors)
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum BooleanExpr {
     Comparison {
         left: Expr,
@@ -143,58 +270,129 @@ pub enum ComparisonOp { Gt, Lt, GtEq, LtEq, Eq, NotEq }
 pub enum LogicalOp { And, Or }
 
 /// Table literal: { key: value, ... }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TableLiteral {
     pub entries: Vec<TableEntry>,
     pub span: Span,
 }
 
 /// Vec literal: [elem1, elem2, ...]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VecLiteral {
     pub elements: Vec<Expr>,
     pub span: Span,
 }
 
+/// List literal: list[elem1, elem2, ...] — elements may be of any type
+#[derive(Debug, Clone)]
+pub struct ListLiteral {
+    pub elements: Vec<Expr>,
+    pub span: Span,
+}
+
 /// Mat literal: [row1_elem1, row1_elem2; row2_elem1, row2_elem2]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MatLiteral {
     pub rows: Vec<Vec<Expr>>,  // row-major storage
     pub span: Span,
 }
 
 /// A single key-value entry in a table literal
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TableEntry {
     pub key: String,
     pub value: Expr,
 }
 
-/// Field access: expr.field
-#[derive(Debug)]
+/// Field access: expr.field, or expr?.field (safe navigation: nil when the
+/// object is nil or has no such field)
+#[derive(Debug, Clone)]
 pub struct FieldAccess {
     pub object: Box<Expr>,
     pub field: String,
+    pub safe: bool,
     pub span: Span,
 }
 
 /// Index access: expr["key"]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IndexAccess {
     pub object: Box<Expr>,
     pub index: Box<Expr>,
     pub span: Span,
 }
 
+/// Struct construction: new Name { field: value, ... }
+#[derive(Debug, Clone)]
+pub struct NewExpr {
+    pub type_name: String,
+    pub fields: Vec<TableEntry>,
+    pub span: Span,
+}
+
+/// If-expression: if (cond) { a } else { b }
+/// An `elseif` chain nests another IfExpr in `else_value`.
+#[derive(Debug, Clone)]
+pub struct IfExpr {
+    pub condition: Box<BooleanExpr>,
+    pub then_value: Box<Expr>,
+    pub else_value: Box<Expr>,
+    pub span: Span,
+}
+
+/// Match expression: match subject { Pattern => body, ... }
+#[derive(Debug, Clone)]
+pub struct MatchExpr {
+    pub subject: Box<Expr>,
+    pub arms: Vec<MatchArm>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: MatchPattern,
+    pub body: MatchBody,
+    pub span: Span,
+}
+
+/// Match arm pattern
+#[derive(Debug, Clone)]
+pub enum MatchPattern {
+    /// Moving(v) or State.Moving(v); `_` bindings are ignored
+    Variant {
+        enum_name: Option<String>,
+        name: String,
+        bindings: Vec<String>,
+    },
+    /// _
+    Wildcard,
+}
+
+/// Match arm body: a single expression, or a block (statement matches only)
+#[derive(Debug, Clone)]
+pub enum MatchBody {
+    Expr(Expr),
+    Block(Block),
+}
+
 /// Matrix transpose: m'
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Transpose {
     pub operand: Box<Expr>,
     pub span: Span,
 }
 
+/// Type conversion: expr -> type  or  expr -> type.mode (e.g. x -> int.round)
+#[derive(Debug, Clone)]
+pub struct Cast {
+    pub expr: Box<Expr>,
+    pub target: String,
+    pub mode: Option<String>,
+    pub span: Span,
+}
+
 /// Field assignment: object.field = value
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FieldAssign {
     pub object: String,
     pub field: String,
@@ -203,7 +401,7 @@ pub struct FieldAssign {
 }
 
 /// Index assignment: object["key"] = value
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IndexAssign {
     pub object: String,
     pub index: Expr,
@@ -212,41 +410,56 @@ pub struct IndexAssign {
 }
 
 /// Method/indirect call: expr(args) — e.g. table.field(args)
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MethodCall {
     pub callee: Box<Expr>,
     pub args: Vec<Expr>,
+    /// `obj:method(args)` — the callee's object is passed as the first argument
+    pub pass_self: bool,
     pub span: Span,
 }
 
 /// Closure parameter
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ClosureParam {
     pub name: String,
     pub type_name: Option<String>,
 }
 
 /// Closure body: single expression or block
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ClosureBody {
     Expr(Box<Expr>),
     Block(Block),
 }
 
 /// Closure expression: |params| => body
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ClosureExpr {
     pub params: Vec<ClosureParam>,
     pub body: ClosureBody,
     pub span: Span,
 }
 
+/// A piece of an interpolated string: literal text or an embedded expression
+#[derive(Debug, Clone)]
+pub enum InterpPart {
+    Text(String),
+    Expr(Expr),
+}
+
 /// Arithmetic expression
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Expr {
     Number(String, Span),
     StringLit(String, Span),
+    /// Interpolated string: f"HP: {hp} / {max}"
+    Interpolated {
+        parts: Vec<InterpPart>,
+        span: Span,
+    },
     Bool(bool, Span),
+    Nil(Span),
     Variable(String, Span),
     BinaryOp {
         left: Box<Expr>,
@@ -254,19 +467,26 @@ pub enum Expr {
         right: Box<Expr>,
         span: Span,
     },
+    /// Prefix -x / ~x, or postfix x++ / x-- on a variable, field or element
     UnaryOp {
         op: UnaryOp,
-        operand: String,
+        operand: Box<Expr>,
         span: Span,
     },
     CallFunc(CallFunc),
     Closure(ClosureExpr),
     TableLiteral(TableLiteral),
+    /// Struct construction: new Player { hp: 100, pos: [0, 0] }
+    New(NewExpr),
+    Match(MatchExpr),
+    If(IfExpr),
     VecLiteral(VecLiteral),
     MatLiteral(MatLiteral),
+    ListLiteral(ListLiteral),
     FieldAccess(FieldAccess),
     IndexAccess(IndexAccess),
     Transpose(Transpose),
+    Cast(Cast),
     MethodCall(MethodCall),
     Grouped(Box<Expr>),
     Range {
@@ -279,7 +499,13 @@ pub enum Expr {
 }
 
 #[derive(Debug, Clone, Copy)]
-pub enum BinaryOp { Add, Sub, Mul, Div, Power, DotPow, Mod, Rand, At, Hash, Backslash }
+pub enum BinaryOp {
+    Add, Sub, Mul, Div, Power, DotPow, Mod, Rand, At, Hash, Backslash,
+    // Bitwise, int only
+    BitAnd, BitOr, BitXor, Shl, Shr, UShr,
+    /// `a ?? b`: a unless it is nil, else b (evaluated only then)
+    Coalesce,
+}
 
 #[derive(Debug, Clone, Copy)]
-pub enum UnaryOp { Increment, Decrement }
+pub enum UnaryOp { Increment, Decrement, Neg, Complement }
@@ -1,5 +1,7 @@
 mod ast;
+mod link;
 mod parser;
 
 pub use ast::*;
+pub use link::{link, LinkError, SourceModule};
 pub use parser::{Parser, ParseError};
@@ -0,0 +1,523 @@
+use std::collections::{HashMap, HashSet};
+
+use anehta_lexer::Span;
+
+use crate::ast::*;
+
+/// A parsed file of a program made of several files.
+pub struct SourceModule {
+    /// Prefix of the file's declarations in the linked program, or `None` for
+    /// the entry file, whose names stay as they are
+    pub prefix: Option<String>,
+    /// Index of the file, as in `Span::file`
+    pub file: usize,
+    pub program: Program,
+    /// Files this one imports, by the name it reaches them through, as indices
+    /// into the module list
+    pub imports: HashMap<String, usize>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LinkError {
+    #[error("Import error at line {line}, column {column}: {message}")]
+    Error {
+        line: usize,
+        column: usize,
+        /// Index of the source file, as in `Span::file`
+        file: usize,
+        message: String,
+    },
+}
+
+fn link_err(message: String, span: Span) -> LinkError {
+    LinkError::Error {
+        line: span.line,
+        column: span.column,
+        file: span.file,
+        message,
+    }
+}
+
+/// A top-level declaration of a file.
+struct Item {
+    /// Name in the linked program: `prefix.name`
+    qualified: String,
+    /// A struct or enum, which can name a type
+    is_type: bool,
+    is_pub: bool,
+}
+
+/// Merge the files of a program into one. The declarations of an imported file
+/// are renamed `prefix.name`, and `module.name` in the files importing it becomes
+/// that name. Imported files may only hold declarations; the statements of the
+/// entry file run as the program.
+pub fn link(modules: Vec<SourceModule>) -> Result<Program, LinkError> {
+    let items: Vec<HashMap<String, Item>> = modules.iter().map(module_items).collect();
+
+    let mut statements = Vec::new();
+    for (index, module) in modules.iter().enumerate() {
+        let mut linker = Linker {
+            own: &items[index],
+            imports: module
+                .imports
+                .iter()
+                .map(|(name, &target)| (name.as_str(), &items[target]))
+                .collect(),
+            locals: HashSet::new(),
+        };
+        for stmt in &module.program.statements {
+            let mut stmt = stmt.clone();
+            match &mut stmt {
+                Statement::Import(_) => continue,
+                Statement::FuncDecl(func) => linker.func(func, true)?,
+                Statement::StructDecl(decl) => {
+                    decl.name = linker.own[&decl.name].qualified.clone();
+                    for field in &mut decl.fields {
+                        field.type_name = linker.type_name(&field.type_name, field.span)?;
+                    }
+                }
+                Statement::EnumDecl(decl) => {
+                    decl.name = linker.own[&decl.name].qualified.clone();
+                    for variant in &mut decl.variants {
+                        for ty in &mut variant.payload {
+                            *ty = linker.type_name(ty, variant.span)?;
+                        }
+                    }
+                }
+                Statement::ImplDecl(imp) => {
+                    let type_name = linker.type_name(&imp.type_name, imp.span)?;
+                    for method in &mut imp.methods {
+                        // Methods are named `Type.method` by the parser
+                        let name = &method.name[imp.type_name.len() + 1..];
+                        method.name = format!("{}.{}", type_name, name);
+                        linker.func(method, false)?;
+                    }
+                    imp.type_name = type_name;
+                }
+                other if module.prefix.is_some() => {
+                    return Err(link_err(
+                        "an imported file can only declare func, struct, enum and impl at the top level".to_string(),
+                        stmt_span(other, module.file),
+                    ));
+                }
+                other => linker.stmt(other)?,
+            }
+            statements.push(stmt);
+        }
+    }
+    Ok(Program { statements })
+}
+
+/// The functions, structs and enums a file declares, by their name in it.
+fn module_items(module: &SourceModule) -> HashMap<String, Item> {
+    let mut items = HashMap::new();
+    for stmt in &module.program.statements {
+        let (name, is_type, is_pub) = match stmt {
+            Statement::FuncDecl(func) => (&func.name, false, func.is_pub),
+            Statement::StructDecl(decl) => (&decl.name, true, decl.is_pub),
+            Statement::EnumDecl(decl) => (&decl.name, true, decl.is_pub),
+            _ => continue,
+        };
+        let qualified = match &module.prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name.clone(),
+        };
+        items.insert(name.clone(), Item { qualified, is_type, is_pub });
+    }
+    items
+}
+
+/// Where a top-level statement of `file` starts, for the error about it.
+fn stmt_span(stmt: &Statement, file: usize) -> Span {
+    match stmt {
+        Statement::VarDecl(VarDecl::TypeDecl { span, .. }) => *span,
+        Statement::VarDecl(VarDecl::Assignment(assign)) | Statement::Assignment(assign) => assign.span,
+        Statement::IfStmt(s) => s.span,
+        Statement::ForStmt(s) => s.span,
+        Statement::ForIn(s) => s.span,
+        Statement::Block(s) => s.span,
+        Statement::CallFunc(s) => s.span,
+        Statement::Return(s) => s.span,
+        Statement::Yield(s) => s.span,
+        Statement::Try(s) => s.span,
+        Statement::Throw(s) => s.span,
+        Statement::Defer(s) => s.span,
+        Statement::TimerStmt(s) => s.span,
+        Statement::FieldAssign(s) => s.span,
+        Statement::IndexAssign(s) => s.span,
+        Statement::MethodCall(s) => s.span,
+        Statement::Break(span) | Statement::Continue(span) => *span,
+        Statement::Expr(Expr::UnaryOp { span, .. }) => *span,
+        Statement::Expr(Expr::Match(m)) => m.span,
+        // The file's first line otherwise
+        _ => Span { line: 1, column: 1, file },
+    }
+}
+
+/// Renames the references of one file.
+struct Linker<'a> {
+    /// Declarations of the file
+    own: &'a HashMap<String, Item>,
+    /// Declarations of the files it imports, by import name
+    imports: HashMap<&'a str, &'a HashMap<String, Item>>,
+    /// Variables and parameters seen so far in the function being linked; they
+    /// hide declarations and imports of the same name
+    locals: HashSet<String>,
+}
+
+impl Linker<'_> {
+    /// A top-level function or method: `rename` gives a function its qualified name.
+    fn func(&mut self, func: &mut FuncDecl, rename: bool) -> Result<(), LinkError> {
+        if rename {
+            func.name = self.own[&func.name].qualified.clone();
+        }
+        let outer = std::mem::take(&mut self.locals);
+        let result = self.func_body(func);
+        self.locals = outer;
+        result
+    }
+
+    fn func_body(&mut self, func: &mut FuncDecl) -> Result<(), LinkError> {
+        for param in &mut func.params {
+            self.locals.insert(param.name.clone());
+            param.type_name = self.type_name(&param.type_name, param.span)?;
+            if let Some(default) = &mut param.default {
+                self.expr(default)?;
+            }
+        }
+        for ty in &mut func.return_types {
+            *ty = self.type_name(ty, func.span)?;
+        }
+        self.block(&mut func.body)
+    }
+
+    /// Rename the struct and enum names in a type: `Point` declared in this file,
+    /// `fn(geo.Point)->int` through an import.
+    fn type_name(&self, ty: &str, span: Span) -> Result<String, LinkError> {
+        let mut out = String::new();
+        let mut chars = ty.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if !(c.is_alphabetic() || c == '_') {
+                out.push(c);
+                continue;
+            }
+            let mut end = start + c.len_utf8();
+            while let Some(&(i, next)) = chars.peek() {
+                if !(next.is_alphanumeric() || next == '_' || next == '.') {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+            let word = &ty[start..end];
+            match word.split_once('.') {
+                Some((module, name)) if self.imports.contains_key(module) => {
+                    out.push_str(&self.imported(module, name, true, span)?);
+                }
+                None => match self.own.get(word) {
+                    Some(item) if item.is_type => out.push_str(&item.qualified),
+                    _ => out.push_str(word),
+                },
+                Some(_) => out.push_str(word),
+            }
+        }
+        Ok(out)
+    }
+
+    /// The linked name of `module.name`, which must be a `pub` declaration, and
+    /// a struct or enum when `want_type` is set.
+    fn imported(&self, module: &str, name: &str, want_type: bool, span: Span) -> Result<String, LinkError> {
+        let Some(item) = self.imports[module].get(name) else {
+            return Err(link_err(format!("module '{}' has no declaration '{}'", module, name), span));
+        };
+        if !item.is_pub {
+            return Err(link_err(
+                format!("'{}' is not pub in module '{}'", name, module),
+                span,
+            ));
+        }
+        if want_type && !item.is_type {
+            return Err(link_err(format!("'{}.{}' is not a struct or enum", module, name), span));
+        }
+        Ok(item.qualified.clone())
+    }
+
+    /// `module.name` when `module` is an import not hidden by a variable.
+    fn module_item(&self, fa: &FieldAccess) -> Result<Option<String>, LinkError> {
+        match &*fa.object {
+            Expr::Variable(module, _) if self.imports.contains_key(module.as_str()) && !self.locals.contains(module) => {
+                self.imported(module, &fa.field, false, fa.span).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// `module.func(args)` as a plain call of the linked function.
+    fn module_call(&self, mc: &mut MethodCall) -> Result<Option<CallFunc>, LinkError> {
+        let Expr::FieldAccess(fa) = &*mc.callee else {
+            return Ok(None);
+        };
+        if mc.pass_self {
+            return Ok(None);
+        }
+        Ok(self.module_item(fa)?.map(|name| CallFunc {
+            name,
+            args: std::mem::take(&mut mc.args),
+            named: Vec::new(),
+            span: mc.span,
+        }))
+    }
+
+    /// A name called or used as a value: a declaration of this file unless a
+    /// variable hides it.
+    fn local_or_own(&self, name: &str) -> Option<String> {
+        if self.locals.contains(name) {
+            return None;
+        }
+        self.own.get(name).map(|item| item.qualified.clone())
+    }
+
+    fn block(&mut self, block: &mut Block) -> Result<(), LinkError> {
+        for stmt in &mut block.statements {
+            self.stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &mut Statement) -> Result<(), LinkError> {
+        match stmt {
+            Statement::FuncDecl(func) => {
+                // A nested function is a variable holding a closure
+                self.locals.insert(func.name.clone());
+                self.func_body(func)?;
+            }
+            Statement::VarDecl(VarDecl::TypeDecl { name, type_name, span }) => {
+                self.locals.insert(name.clone());
+                *type_name = self.type_name(type_name, *span)?;
+            }
+            Statement::VarDecl(VarDecl::Assignment(assign)) | Statement::Assignment(assign) => {
+                for value in &mut assign.values {
+                    self.expr(value)?;
+                }
+                self.locals.extend(assign.targets.iter().cloned());
+            }
+            Statement::IfStmt(if_stmt) => {
+                self.bool_expr(&mut if_stmt.condition)?;
+                self.block(&mut if_stmt.body)?;
+                for branch in &mut if_stmt.else_if {
+                    self.bool_expr(&mut branch.condition)?;
+                    self.block(&mut branch.body)?;
+                }
+                if let Some(else_body) = &mut if_stmt.else_body {
+                    self.block(else_body)?;
+                }
+            }
+            Statement::ForStmt(for_stmt) => {
+                if let Some(init) = &mut for_stmt.init {
+                    self.stmt(init)?;
+                }
+                if let Some(cond) = &mut for_stmt.condition {
+                    self.bool_expr(cond)?;
+                }
+                if let Some(step) = &mut for_stmt.step {
+                    self.stmt(step)?;
+                }
+                self.block(&mut for_stmt.body)?;
+            }
+            Statement::ForIn(for_in) => {
+                self.expr(&mut for_in.iterable)?;
+                self.locals.insert(for_in.var.clone());
+                self.block(&mut for_in.body)?;
+            }
+            Statement::Block(block) => self.block(block)?,
+            Statement::CallFunc(call) => self.call(call)?,
+            Statement::Return(ret) => {
+                for value in &mut ret.values {
+                    self.expr(value)?;
+                }
+            }
+            Statement::Yield(y) => {
+                if let Some(value) = &mut y.value {
+                    self.expr(value)?;
+                }
+            }
+            Statement::Try(t) => {
+                self.block(&mut t.body)?;
+                self.locals.extend(t.error_name.iter().cloned());
+                self.block(&mut t.handler)?;
+            }
+            Statement::Throw(throw) => self.expr(&mut throw.value)?,
+            Statement::Defer(d) => self.block(&mut d.body)?,
+            Statement::TimerStmt(timer) => self.block(&mut timer.body)?,
+            Statement::FieldAssign(assign) => self.expr(&mut assign.value)?,
+            Statement::IndexAssign(assign) => {
+                self.expr(&mut assign.index)?;
+                self.expr(&mut assign.value)?;
+            }
+            Statement::MethodCall(mc) => match self.module_call(mc)? {
+                Some(mut call) => {
+                    self.call(&mut call)?;
+                    *stmt = Statement::CallFunc(call);
+                }
+                None => self.method_call(mc)?,
+            },
+            Statement::Expr(expr) => self.expr(expr)?,
+            Statement::Break(_)
+            | Statement::Continue(_)
+            | Statement::Import(_)
+            | Statement::StructDecl(_)
+            | Statement::EnumDecl(_)
+            | Statement::ImplDecl(_) => {}
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, call: &mut CallFunc) -> Result<(), LinkError> {
+        if let Some(name) = self.local_or_own(&call.name) {
+            call.name = name;
+        }
+        for arg in &mut call.args {
+            self.expr(arg)?;
+        }
+        for arg in &mut call.named {
+            self.expr(&mut arg.value)?;
+        }
+        Ok(())
+    }
+
+    fn method_call(&mut self, mc: &mut MethodCall) -> Result<(), LinkError> {
+        self.expr(&mut mc.callee)?;
+        for arg in &mut mc.args {
+            self.expr(arg)?;
+        }
+        Ok(())
+    }
+
+    fn bool_expr(&mut self, expr: &mut BooleanExpr) -> Result<(), LinkError> {
+        match expr {
+            BooleanExpr::Comparison { left, right, .. } => {
+                self.expr(left)?;
+                self.expr(right)
+            }
+            BooleanExpr::Logical { left, right, .. } => {
+                self.bool_expr(left)?;
+                self.bool_expr(right)
+            }
+            BooleanExpr::Grouped(inner) => self.bool_expr(inner),
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr) -> Result<(), LinkError> {
+        match expr {
+            Expr::Number(..) | Expr::StringLit(..) | Expr::Bool(..) | Expr::Nil(_) => {}
+            Expr::Interpolated { parts, .. } => {
+                for part in parts {
+                    if let InterpPart::Expr(e) = part {
+                        self.expr(e)?;
+                    }
+                }
+            }
+            Expr::Variable(name, span) => {
+                if self.imports.contains_key(name.as_str()) && !self.locals.contains(name) {
+                    return Err(link_err(
+                        format!("module '{}' is not a value; use its declarations as {}.name", name, name),
+                        *span,
+                    ));
+                }
+                if let Some(qualified) = self.local_or_own(name) {
+                    *name = qualified;
+                }
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                self.expr(left)?;
+                self.expr(right)?;
+            }
+            Expr::UnaryOp { operand, .. } => self.expr(operand)?,
+            Expr::CallFunc(call) => self.call(call)?,
+            Expr::Closure(closure) => {
+                for param in &mut closure.params {
+                    self.locals.insert(param.name.clone());
+                    if let Some(ty) = &mut param.type_name {
+                        *ty = self.type_name(ty, closure.span)?;
+                    }
+                }
+                match &mut closure.body {
+                    ClosureBody::Expr(body) => self.expr(body)?,
+                    ClosureBody::Block(body) => self.block(body)?,
+                }
+            }
+            Expr::TableLiteral(table) => {
+                for entry in &mut table.entries {
+                    self.expr(&mut entry.value)?;
+                }
+            }
+            Expr::New(new) => {
+                new.type_name = self.type_name(&new.type_name, new.span)?;
+                for entry in &mut new.fields {
+                    self.expr(&mut entry.value)?;
+                }
+            }
+            Expr::Match(m) => {
+                self.expr(&mut m.subject)?;
+                for arm in &mut m.arms {
+                    if let MatchPattern::Variant { enum_name, bindings, .. } = &mut arm.pattern {
+                        if let Some(enum_name) = enum_name {
+                            *enum_name = self.type_name(enum_name, arm.span)?;
+                        }
+                        self.locals.extend(bindings.iter().cloned());
+                    }
+                    match &mut arm.body {
+                        MatchBody::Expr(body) => self.expr(body)?,
+                        MatchBody::Block(body) => self.block(body)?,
+                    }
+                }
+            }
+            Expr::If(if_expr) => {
+                self.bool_expr(&mut if_expr.condition)?;
+                self.expr(&mut if_expr.then_value)?;
+                self.expr(&mut if_expr.else_value)?;
+            }
+            Expr::VecLiteral(VecLiteral { elements, .. }) | Expr::ListLiteral(ListLiteral { elements, .. }) => {
+                for element in elements {
+                    self.expr(element)?;
+                }
+            }
+            Expr::MatLiteral(mat) => {
+                for element in mat.rows.iter_mut().flatten() {
+                    self.expr(element)?;
+                }
+            }
+            Expr::FieldAccess(fa) => match self.module_item(fa)? {
+                Some(name) => *expr = Expr::Variable(name, fa.span),
+                None => self.expr(&mut fa.object)?,
+            },
+            Expr::IndexAccess(ia) => {
+                self.expr(&mut ia.object)?;
+                self.expr(&mut ia.index)?;
+            }
+            Expr::Transpose(t) => self.expr(&mut t.operand)?,
+            Expr::Cast(cast) => self.expr(&mut cast.expr)?,
+            Expr::MethodCall(mc) => match self.module_call(mc)? {
+                Some(mut call) => {
+                    self.call(&mut call)?;
+                    *expr = Expr::CallFunc(call);
+                }
+                None => self.method_call(mc)?,
+            },
+            Expr::Grouped(inner) => self.expr(inner)?,
+            Expr::Range { start, end, .. } => {
+                if let Some(start) = start {
+                    self.expr(start)?;
+                }
+                if let Some(end) = end {
+                    self.expr(end)?;
+                }
+            }
+            Expr::BooleanExpr(b) => self.bool_expr(b)?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
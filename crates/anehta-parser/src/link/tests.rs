@@ -0,0 +1,91 @@
+use super::*;
+use crate::Parser;
+use anehta_lexer::Lexer;
+
+/// A module parsed from `src`; `imports` pairs import names with module indices.
+fn module(file: usize, prefix: Option<&str>, src: &str, imports: &[(&str, usize)]) -> SourceModule {
+    let tokens = Lexer::for_file(src, file).tokenize().expect("lexer should succeed");
+    let program = Parser::new(tokens).parse().expect("parse should succeed");
+    SourceModule {
+        prefix: prefix.map(str::to_string),
+        file,
+        program,
+        imports: imports.iter().map(|&(name, index)| (name.to_string(), index)).collect(),
+    }
+}
+
+const GEO: &str = "pub struct Point { x: int, y: int }\n\
+impl Point {\nfunc sum() -> int {\nreturn self.x + self.y\n}\n}\n\
+func sq(n: int) -> int {\nreturn n * n\n}\n\
+pub func norm(p: Point) -> int {\nreturn sq(p.x) + sq(p.y)\n}";
+
+fn link_main(src: &str) -> Result<Program, LinkError> {
+    link(vec![module(0, Some("geo"), GEO, &[]), module(1, None, src, &[("geo", 0)])])
+}
+
+fn link_message(src: &str) -> String {
+    match link_main(src) {
+        Err(LinkError::Error { message, .. }) => message,
+        Ok(_) => panic!("link should fail"),
+    }
+}
+
+#[test]
+fn imported_declarations_are_prefixed() {
+    let prog = link_main("import geo\nvar p = new geo.Point { x: 3, y: 4 }\nprint(geo.norm(p))").unwrap();
+    let Statement::StructDecl(decl) = &prog.statements[0] else {
+        panic!("expected StructDecl");
+    };
+    assert_eq!(decl.name, "geo.Point");
+    let Statement::ImplDecl(imp) = &prog.statements[1] else {
+        panic!("expected ImplDecl");
+    };
+    assert_eq!(imp.type_name, "geo.Point");
+    assert_eq!(imp.methods[0].name, "geo.Point.sum");
+    assert_eq!(imp.methods[0].params[0].type_name, "geo.Point");
+    let Statement::FuncDecl(norm) = &prog.statements[3] else {
+        panic!("expected FuncDecl");
+    };
+    assert_eq!(norm.name, "geo.norm");
+    assert_eq!(norm.params[0].type_name, "geo.Point");
+    // The call to the private `sq` inside the module is renamed too
+    let Statement::Return(ret) = &norm.body.statements[0] else {
+        panic!("expected Return");
+    };
+    let Expr::BinaryOp { left, .. } = &ret.values[0] else {
+        panic!("expected BinaryOp");
+    };
+    assert!(matches!(left.as_ref(), Expr::CallFunc(call) if call.name == "geo.sq"));
+
+    // The import statement is dropped and `geo.norm(p)` becomes a call
+    assert_eq!(prog.statements.len(), 6);
+    let Statement::CallFunc(print) = &prog.statements[5] else {
+        panic!("expected CallFunc");
+    };
+    assert!(matches!(&print.args[0], Expr::CallFunc(call) if call.name == "geo.norm"));
+}
+
+#[test]
+fn locals_shadow_module_declarations() {
+    let src = "pub func f() -> int {\nreturn 1\n}\npub func g(f: int) -> int {\nreturn f\n}";
+    let prog = link(vec![module(0, Some("m"), src, &[])]).unwrap();
+    let Statement::FuncDecl(g) = &prog.statements[1] else {
+        panic!("expected FuncDecl");
+    };
+    let Statement::Return(ret) = &g.body.statements[0] else {
+        panic!("expected Return");
+    };
+    assert!(matches!(&ret.values[0], Expr::Variable(name, _) if name == "f"));
+}
+
+#[test]
+fn link_errors() {
+    assert_eq!(link_message("import geo\nprint(geo.sq(2))"), "'sq' is not pub in module 'geo'");
+    assert_eq!(link_message("import geo\nprint(geo.cube(2))"), "module 'geo' has no declaration 'cube'");
+    assert_eq!(link_message("import geo\nvar g = geo"), "module 'geo' is not a value; use its declarations as geo.name");
+    assert_eq!(link_message("import geo\nvar p = new geo.norm { x: 1 }"), "'geo.norm' is not a struct or enum");
+
+    let err = link(vec![module(0, Some("m"), "var x = 1", &[])]).unwrap_err();
+    let LinkError::Error { file, line, .. } = err;
+    assert_eq!((file, line), (0, 1));
+}
@@ -63,10 +63,60 @@ impl Parser {
         Ok(tok)
     }
 
+    /// `name`, or `module.name` for a type declared in an imported file
+    fn qualified_name(&mut self) -> Result<String, ParseError> {
+        let name = self.expect(TokenType::Word)?.value;
+        if self.peek_type() == TokenType::Dot
+            && self.peek_ahead(1).is_some_and(|tok| tok.token_type == TokenType::Word)
+        {
+            self.advance(); // consume .
+            let item = self.advance().value.clone();
+            return Ok(format!("{}.{}", name, item));
+        }
+        Ok(name)
+    }
+
+    /// Parse a type annotation: a type name, a coroutine type such as
+    /// `coroutine(str)`, or a function type such as `fn(int, int) -> int`, kept
+    /// in the canonical spelling `fn(int,int)->int`.
+    fn type_name(&mut self) -> Result<String, ParseError> {
+        if self.peek_ahead(1).is_some_and(|tok| tok.token_type == TokenType::Dot) {
+            return self.qualified_name();
+        }
+        let tok = self.expect(TokenType::Word)?;
+        if tok.value == "coroutine" && self.peek_type() == TokenType::LParen {
+            self.advance(); // consume (
+            let value = self.type_name()?;
+            self.expect(TokenType::RParen)?;
+            return Ok(format!("coroutine({})", value));
+        }
+        if tok.value != "fn" || self.peek_type() != TokenType::LParen {
+            return Ok(tok.value);
+        }
+        self.advance(); // consume (
+        let mut params = Vec::new();
+        while self.peek_type() != TokenType::RParen {
+            params.push(self.type_name()?);
+            if self.peek_type() != TokenType::Comma {
+                break;
+            }
+            self.advance(); // consume ,
+        }
+        self.expect(TokenType::RParen)?;
+        self.expect(TokenType::Casting)?;
+        let ret = self.type_name()?;
+        Ok(format!("fn({})->{}", params.join(","), ret))
+    }
+
     fn peek_type(&self) -> TokenType {
         self.current_type()
     }
 
+    /// Token `offset` places after the current one, if any.
+    fn peek_ahead(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
     fn is_at_end(&self) -> bool {
         self.pos >= self.tokens.len() || self.current_type() == TokenType::Eof
     }
@@ -109,18 +159,27 @@ impl Parser {
     fn statement(&mut self) -> Result<Statement, ParseError> {
         match self.peek_type() {
             TokenType::Func => self.func_statement(),
+            TokenType::Struct => self.struct_statement(),
+            TokenType::Enum => self.enum_statement(),
+            TokenType::Impl => self.impl_statement(),
             TokenType::Var => self.var_statement(),
             TokenType::LBrace => self.block_statement_as_stmt(),
             TokenType::If => self.if_statement(),
             TokenType::For => self.for_statement(),
             TokenType::Timer => self.timer_statement(),
+            TokenType::Match => self.match_statement(),
+            TokenType::Try => self.try_statement(),
+            TokenType::Throw => self.throw_statement(),
+            TokenType::Defer => self.defer_statement(),
+            TokenType::Import => self.import_statement(),
+            TokenType::Pub => self.pub_statement(),
             TokenType::Word => self.word_dispatch_statement(),
             _ => {
                 let tok = self.current().clone();
                 Err(self.error_at(
                     tok.span,
                     format!(
-                        "unexpected '{}', expected func/var/if/for/word/{{ ->Statement",
+                        "unexpected '{}', expected func/struct/enum/impl/var/if/for/match/try/defer/import/pub/word/{{ ->Statement",
                         tok.value
                     ),
                 ))
@@ -141,7 +200,14 @@ impl Parser {
             self.back(); // put back WORD
             let call = self.call_func_statement()?;
             Ok(Statement::CallFunc(call))
-        } else if next.token_type == TokenType::Assignment
+        } else if matches!(next.token_type, TokenType::AddSelf | TokenType::SubSelf) {
+            // Step statement: word++ / word--
+            Ok(Statement::Expr(Expr::UnaryOp {
+                op: Self::step_op(next.token_type),
+                operand: Box::new(Expr::Variable(word_tok.value, word_tok.span)),
+                span: word_tok.span,
+            }))
+      } else if next.token_type == TokenType::Assignment
             || next.token_type == TokenType::Comma
         {
             // Assignment: word, ... = ...
@@ -149,6 +215,14 @@ impl Parser {
             self.back(); // put back WORD
             let assign = self.assignment_statement()?;
             Ok(Statement::Assignment(assign))
+        } else if next.token_type == TokenType::Colon {
+            // Method call with receiver: word:method(args)
+            self.back(); // put back ':'
+            self.back(); // put back WORD
+            match self.arithmetic_expression()? {
+                Expr::MethodCall(mc) => Ok(Statement::MethodCall(mc)),
+                _ => Err(self.error_at(word_tok.span, "expected method call statement".to_string())),
+            }
         } else if next.token_type == TokenType::Dot {
             // Could be field assignment (word.field = expr) or method call (word.field(args))
             let span = word_tok.span;
@@ -171,11 +245,13 @@ impl Parser {
                 let callee = Expr::FieldAccess(FieldAccess {
                     object: Box::new(Expr::Variable(word_tok.value, span)),
                     field: field_tok.value.clone(),
+                    safe: false,
                     span,
                 });
                 let mut result = Expr::MethodCall(MethodCall {
                     callee: Box::new(callee),
                     args,
+                    pass_self: false,
                     span,
                 });
                 // Handle further chaining: .field, ["key"], (args)
@@ -188,6 +264,7 @@ impl Parser {
                             result = Expr::FieldAccess(FieldAccess {
                                 object: Box::new(result),
                                 field: ft.value,
+                                safe: false,
                                 span: s,
                             });
                         }
@@ -221,6 +298,7 @@ impl Parser {
                                 result = Expr::MethodCall(MethodCall {
                                     callee: Box::new(result),
                                     args: a,
+                                    pass_self: false,
                                     span: s,
                                 });
                             } else {
@@ -235,6 +313,20 @@ impl Parser {
                     Expr::MethodCall(mc) => Ok(Statement::MethodCall(mc)),
                     _ => Err(self.error_at(span, "expected method call statement".to_string())),
                 }
+            } else if matches!(self.peek_type(), TokenType::AddSelf | TokenType::SubSelf) {
+                // Step statement: word.field++ / word.field--
+                let op = Self::step_op(self.advance().token_type);
+                let field = Expr::FieldAccess(FieldAccess {
+                    object: Box::new(Expr::Variable(word_tok.value, span)),
+                    field: field_tok.value,
+                    safe: false,
+                    span,
+                });
+                Ok(Statement::Expr(Expr::UnaryOp {
+                    op,
+                    operand: Box::new(field),
+                    span,
+                }))
             } else {
                 // Field assignment: word.field = expr (original logic)
                 self.expect(TokenType::Assignment)?;
@@ -251,6 +343,20 @@ impl Parser {
             let span = word_tok.span;
             let index = self.arithmetic_expression()?;
             self.expect(TokenType::RBracket)?;
+            if matches!(self.peek_type(), TokenType::AddSelf | TokenType::SubSelf) {
+                // Step statement: word[index]++ / word[index]--
+                let op = Self::step_op(self.advance().token_type);
+                let element = Expr::IndexAccess(IndexAccess {
+                    object: Box::new(Expr::Variable(word_tok.value, span)),
+                    index: Box::new(index),
+                    span,
+                });
+                return Ok(Statement::Expr(Expr::UnaryOp {
+                    op,
+                    operand: Box::new(element),
+                    span,
+                }));
+            }
             self.expect(TokenType::Assignment)?;
             let value = self.arithmetic_expression()?;
             Ok(Statement::IndexAssign(IndexAssign {
@@ -263,7 +369,7 @@ impl Parser {
             Err(self.error_at(
                 word_tok.span,
                 format!(
-                    "unexpected '{}' after '{}', expected '(' or '=' or ',' or '.' or '[' ->Statement",
+                    "unexpected '{}' after '{}', expected '(' or '=' or ',' or '.' or '[' or '++' or '--' ->Statement",
                     next.value, word_tok.value
                 ),
             ))
@@ -74,7 +74,7 @@ impl Parser {
         })
     }
 
-    fn comparison_op(&mut self) -> Result<ComparisonOp, ParseError> {
+    pub(super) fn comparison_op(&mut self) -> Result<ComparisonOp, ParseError> {
         let tok = self.advance().clone();
         match tok.token_type {
             TokenType::Gt => Ok(ComparisonOp::Gt),
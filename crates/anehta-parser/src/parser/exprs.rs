@@ -2,9 +2,108 @@ use super::*;
 
 impl Parser {
     // ── Arithmetic Expression ────────────────────────────────
-    // Expression -> Term ((+|-) Term)*
+    // Expression -> Cast (?? Expression)?     -- right-associative, lowest precedence
+    // Cast       -> BitOr (-> TYPE(.MODE)?)*
 
     pub(super) fn arithmetic_expression(&mut self) -> Result<Expr, ParseError> {
+        let left = self.cast_expression()?;
+        if self.peek_type() != TokenType::Coalesce {
+            return Ok(left);
+        }
+        let span = self.current_span();
+        self.advance(); // consume ??
+        let right = self.arithmetic_expression()?;
+        Ok(Expr::BinaryOp {
+            left: Box::new(left),
+            op: BinaryOp::Coalesce,
+            right: Box::new(right),
+            span,
+        })
+    }
+
+    fn cast_expression(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.bit_or_expression()?;
+
+        while self.peek_type() == TokenType::Casting {
+            let span = self.current_span();
+            self.advance(); // consume ->
+            let target = self.expect(TokenType::Word)?.value;
+            let mode = if self.peek_type() == TokenType::Dot {
+                self.advance(); // consume .
+                Some(self.expect(TokenType::Word)?.value)
+            } else {
+                None
+            };
+            expr = Expr::Cast(Cast {
+                expr: Box::new(expr),
+                target,
+                mode,
+                span,
+            });
+        }
+
+        Ok(expr)
+    }
+
+    // BitOr  -> BitXor (| BitXor)*
+    // BitXor -> BitAnd (xor BitAnd)*
+    // BitAnd -> Shift (& Shift)*
+    // Shift  -> Additive ((<<|>>|>>>) Additive)*
+
+    fn bit_or_expression(&mut self) -> Result<Expr, ParseError> {
+        self.left_assoc(Self::bit_xor_expression, |t| match t {
+            TokenType::Or => Some(BinaryOp::BitOr),
+            _ => None,
+        })
+    }
+
+    fn bit_xor_expression(&mut self) -> Result<Expr, ParseError> {
+        self.left_assoc(Self::bit_and_expression, |t| match t {
+            TokenType::Xor => Some(BinaryOp::BitXor),
+            _ => None,
+        })
+    }
+
+    fn bit_and_expression(&mut self) -> Result<Expr, ParseError> {
+        self.left_assoc(Self::shift_expression, |t| match t {
+            TokenType::And => Some(BinaryOp::BitAnd),
+            _ => None,
+        })
+    }
+
+    fn shift_expression(&mut self) -> Result<Expr, ParseError> {
+        self.left_assoc(Self::additive_expression, |t| match t {
+            TokenType::Shl => Some(BinaryOp::Shl),
+            TokenType::Shr => Some(BinaryOp::Shr),
+            TokenType::UShr => Some(BinaryOp::UShr),
+            _ => None,
+        })
+    }
+
+    /// Parse a left-associative chain of `operand (op operand)*`.
+    fn left_assoc(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expr, ParseError>,
+        op_for: fn(TokenType) -> Option<BinaryOp>,
+    ) -> Result<Expr, ParseError> {
+        let mut left = operand(self)?;
+        while let Some(op) = op_for(self.peek_type()) {
+            let span = self.current_span();
+            self.advance(); // consume operator
+            let right = operand(self)?;
+            left = Expr::BinaryOp {
+                left: Box::new(left),
+                op,
+                right: Box::new(right),
+                span,
+            };
+        }
+        Ok(left)
+    }
+
+    // Additive -> Term ((+|-) Term)*
+
+    fn additive_expression(&mut self) -> Result<Expr, ParseError> {
         let mut left = self.arithmetic_term()?;
 
         loop {
@@ -59,13 +158,16 @@ impl Parser {
         Ok(left)
     }
 
-    // Factor -> NUM | WORD | TRUE | FALSE | STRING
+    // Factor -> NUM | WORD | TRUE | FALSE | NIL | STRING | F-STRING
     //         | WORD++ | WORD-- | WORD(args) -- call
     //         | (Expression)
+    //         | -Factor | ~Factor  -- negation, bitwise complement
     //         | |params| => body   -- closure
     //         | || => body         -- zero-param closure
     //         | { key: value, ... } -- table literal
-    // Postfix: .field | ["key"]
+    //         | new Name { field: value, ... } -- struct construction
+    //         | match subject { Pattern => body, ... }
+    // Postfix: .field | ?.field | ["key"] | ++ | --
 
     fn arithmetic_factor(&mut self) -> Result<Expr, ParseError> {
         let tok = self.advance().clone();
@@ -75,16 +177,50 @@ impl Parser {
 
             TokenType::StringLit => Ok(Expr::StringLit(tok.value, tok.span)),
 
+            // f"text {expr} text"
+            TokenType::FStringStart => self.parse_fstring(tok.span),
+
             TokenType::True => Ok(Expr::Bool(true, tok.span)),
 
             TokenType::False => Ok(Expr::Bool(false, tok.span)),
 
+            TokenType::Nil => Ok(Expr::Nil(tok.span)),
+
             TokenType::LParen => {
                 let inner = self.arithmetic_expression()?;
                 self.expect(TokenType::RParen)?;
                 Ok(Expr::Grouped(Box::new(inner)))
             }
 
+            // -x, ~x  -- `~` in prefix position is complement, not random
+            TokenType::Sub | TokenType::Rand => {
+                let operand = self.arithmetic_factor()?;
+                let op = if tok.token_type == TokenType::Sub {
+                    UnaryOp::Neg
+                } else {
+                    UnaryOp::Complement
+                };
+                Ok(Expr::UnaryOp {
+                    op,
+                    operand: Box::new(operand),
+                    span: tok.span,
+                })
+            }
+
+            // new Name { field: value, ... } -- struct construction
+            TokenType::New => {
+                let type_name = self.qualified_name()?;
+                let fields = self.parse_table_entries()?;
+                Ok(Expr::New(NewExpr {
+                    type_name,
+                    fields,
+                    span: tok.span,
+                }))
+            }
+
+            TokenType::Match => self.parse_match(tok.span),
+            TokenType::If => self.parse_if_expr(tok.span),
+
             // { key: value, ... } -- table literal
             TokenType::LBrace => {
                 self.back(); // put back {
@@ -109,22 +245,19 @@ impl Parser {
                 self.parse_closure()
             }
 
+            // list[elem, elem, ...] -- list literal
+            TokenType::Word if tok.value == "list" && self.peek_type() == TokenType::LBracket => {
+                self.parse_list_literal(tok.span)
+            }
+
             TokenType::Word => {
                 // Look ahead for ++ / -- / (
                 match self.peek_type() {
-                    TokenType::AddSelf => {
-                        self.advance(); // consume ++
+                    TokenType::AddSelf | TokenType::SubSelf => {
+                        let op = Self::step_op(self.advance().token_type);
                         Ok(Expr::UnaryOp {
-                            op: UnaryOp::Increment,
-                            operand: tok.value,
-                            span: tok.span,
-                        })
-                    }
-                    TokenType::SubSelf => {
-                        self.advance(); // consume --
-                        Ok(Expr::UnaryOp {
-                            op: UnaryOp::Decrement,
-                            operand: tok.value,
+                            op,
+                            operand: Box::new(Expr::Variable(tok.value, tok.span)),
                             span: tok.span,
                         })
                     }
@@ -147,16 +280,17 @@ impl Parser {
             )),
         }?;
 
-        // Postfix operators: .field, ["key"], and (args) for method calls (supports chaining)
+        // Postfix operators: .field, ["key"], (args) and :method(args) for method calls (supports chaining)
         loop {
             match self.peek_type() {
-                TokenType::Dot => {
+                TokenType::Dot | TokenType::SafeDot => {
                     let span = self.current_span();
-                    self.advance(); // consume .
+                    let safe = self.advance().token_type == TokenType::SafeDot; // consume . or ?.
                     let field_tok = self.expect(TokenType::Word)?;
                     result = Expr::FieldAccess(FieldAccess {
                         object: Box::new(result),
                         field: field_tok.value,
+                        safe,
                         span,
                     });
                 }
@@ -221,6 +355,19 @@ impl Parser {
                         span,
                     });
                 }
+                // t.count++ / v[0]-- (ends the postfix chain)
+                TokenType::AddSelf | TokenType::SubSelf
+                    if matches!(result, Expr::FieldAccess(_) | Expr::IndexAccess(_)) =>
+                {
+                    let span = self.current_span();
+                    let op = Self::step_op(self.advance().token_type);
+                    result = Expr::UnaryOp {
+                        op,
+                        operand: Box::new(result),
+                        span,
+                    };
+                    break;
+                }
                 TokenType::Transpose => {
                     let span = self.current_span();
                     self.advance(); // consume '
@@ -234,28 +381,36 @@ impl Parser {
                     // Plain word(args) is handled above in the Word+LParen branch
                     if matches!(result, Expr::FieldAccess(_) | Expr::IndexAccess(_) | Expr::MethodCall(_)) {
                         let span = self.current_span();
-                        self.advance(); // consume (
-                        let mut args = Vec::new();
-                        if self.peek_type() != TokenType::RParen {
-                            loop {
-                                args.push(self.arithmetic_expression()?);
-                                if self.peek_type() == TokenType::Comma {
-                                    self.advance();
-                                } else {
-                                    break;
-                                }
-                            }
-                        }
-                        self.expect(TokenType::RParen)?;
+                        let args = self.call_args()?;
                         result = Expr::MethodCall(MethodCall {
                             callee: Box::new(result),
                             args,
+                            pass_self: false,
                             span,
                         });
                     } else {
                         break;
                     }
                 }
+                TokenType::Colon => {
+                    // obj:method(args) — calls obj.method with obj as the first argument
+                    let span = self.current_span();
+                    self.advance(); // consume :
+                    let method_tok = self.expect(TokenType::Word)?;
+                    let args = self.call_args()?;
+                    let callee = Expr::FieldAccess(FieldAccess {
+                        object: Box::new(result),
+                        field: method_tok.value,
+                        safe: false,
+                        span,
+                    });
+                    result = Expr::MethodCall(MethodCall {
+                        callee: Box::new(callee),
+                        args,
+                        pass_self: true,
+                        span,
+                    });
+                }
                 _ => break,
             }
         }
@@ -263,11 +418,73 @@ impl Parser {
         Ok(result)
     }
 
+    /// `(arg, ...)` after a callee, including both parentheses.
+    fn call_args(&mut self) -> Result<Vec<Expr>, ParseError> {
+        self.expect(TokenType::LParen)?;
+        let mut args = Vec::new();
+        if self.peek_type() != TokenType::RParen {
+            loop {
+                args.push(self.arithmetic_expression()?);
+                if self.peek_type() == TokenType::Comma {
+                    self.advance();
+                } else {
+                    break;
+                }
+            }
+        }
+        self.expect(TokenType::RParen)?;
+        Ok(args)
+    }
+
+    /// `++` → Increment, `--` → Decrement.
+    pub(super) fn step_op(token_type: TokenType) -> UnaryOp {
+        if token_type == TokenType::AddSelf {
+            UnaryOp::Increment
+        } else {
+            UnaryOp::Decrement
+        }
+    }
+
+    // ── Interpolated string parsing ────────────────────────
+    // f"text {expr} text" -- FStringStart already consumed
+
+    fn parse_fstring(&mut self, span: Span) -> Result<Expr, ParseError> {
+        let mut parts = Vec::new();
+        loop {
+            let tok = self.advance().clone();
+            match tok.token_type {
+                TokenType::StringLit => parts.push(InterpPart::Text(tok.value)),
+                TokenType::InterpStart => {
+                    if self.peek_type() == TokenType::InterpEnd {
+                        return Err(self.error_at(tok.span, "empty '{}' in f-string".to_string()));
+                    }
+                    let expr = self.arithmetic_expression()?;
+                    self.expect(TokenType::InterpEnd)?;
+                    parts.push(InterpPart::Expr(expr));
+                }
+                TokenType::FStringEnd => break,
+                _ => {
+                    return Err(self.error_at(
+                        tok.span,
+                        format!("unexpected {:?} '{}' in f-string", tok.token_type, tok.value),
+                    ));
+                }
+            }
+        }
+        Ok(Expr::Interpolated { parts, span })
+    }
+
     // ── Table literal parsing ──────────────────────────────
     // { key: value, key: value, ... }
 
     fn parse_table_literal(&mut self) -> Result<Expr, ParseError> {
         let span = self.current_span();
+        let entries = self.parse_table_entries()?;
+        Ok(Expr::TableLiteral(TableLiteral { entries, span }))
+    }
+
+    /// `{ key: value, ... }` shared by table literals and `new` expressions.
+    fn parse_table_entries(&mut self) -> Result<Vec<TableEntry>, ParseError> {
         self.expect(TokenType::LBrace)?;
         self.skip_newlines();
 
@@ -300,7 +517,128 @@ impl Parser {
         self.skip_newlines();
         self.expect(TokenType::RBrace)?;
 
-        Ok(Expr::TableLiteral(TableLiteral { entries, span }))
+        Ok(entries)
+    }
+
+    // ── If-expression parsing ──────────────────────────────
+    // if (cond) { expr } elseif (cond) { expr } else { expr }  (else is required)
+
+    fn parse_if_expr(&mut self, span: Span) -> Result<Expr, ParseError> {
+        self.expect(TokenType::LParen)?;
+        let condition = self.boolean_expression()?;
+        self.expect(TokenType::RParen)?;
+        let then_value = self.if_branch_value()?;
+
+        self.skip_newlines();
+        let else_value = match self.peek_type() {
+            TokenType::ElseIf => {
+                let elseif_span = self.current_span();
+                self.advance(); // consume elseif
+                self.parse_if_expr(elseif_span)?
+            }
+            TokenType::Else => {
+                self.advance(); // consume else
+                self.if_branch_value()?
+            }
+            _ => {
+                return Err(self.error_at(span, "if-expression needs an else branch".to_string()));
+            }
+        };
+
+        Ok(Expr::If(IfExpr {
+            condition: Box::new(condition),
+            then_value: Box::new(then_value),
+            else_value: Box::new(else_value),
+            span,
+        }))
+    }
+
+    /// `{ expr }` branch of an if-expression.
+    fn if_branch_value(&mut self) -> Result<Expr, ParseError> {
+        self.skip_newlines();
+        self.expect(TokenType::LBrace)?;
+        self.skip_newlines();
+        let value = self.arithmetic_expression()?;
+        self.skip_newlines();
+        self.expect(TokenType::RBrace)?;
+        Ok(value)
+    }
+
+    // ── Match parsing ──────────────────────────────────────
+    // match subject { Pattern => expr | { block }, ... }  (arms separated by commas or newlines)
+
+    fn parse_match(&mut self, span: Span) -> Result<Expr, ParseError> {
+        let subject = self.arithmetic_expression()?;
+        self.skip_newlines();
+        self.expect(TokenType::LBrace)?;
+        self.skip_newlines();
+
+        let mut arms = Vec::new();
+        while self.peek_type() != TokenType::RBrace {
+            let arm_span = self.current_span();
+            let pattern = self.parse_match_pattern()?;
+            self.expect(TokenType::FatArrow)?;
+            let body = if self.peek_type() == TokenType::LBrace {
+                MatchBody::Block(self.block_statement()?)
+            } else {
+                MatchBody::Expr(self.arithmetic_expression()?)
+            };
+            arms.push(MatchArm {
+                pattern,
+                body,
+                span: arm_span,
+            });
+            if self.peek_type() == TokenType::Comma {
+                self.advance(); // consume ,
+            } else if self.peek_type() != TokenType::Newline {
+                break;
+            }
+            self.skip_newlines();
+        }
+        self.expect(TokenType::RBrace)?;
+
+        if arms.is_empty() {
+            return Err(self.error_at(span, "match needs at least one arm".to_string()));
+        }
+        Ok(Expr::Match(MatchExpr {
+            subject: Box::new(subject),
+            arms,
+            span,
+        }))
+    }
+
+    /// `_`, `Variant`, `Variant(a, _)`, `Enum.Variant(...)` or `module.Enum.Variant(...)`
+    fn parse_match_pattern(&mut self) -> Result<MatchPattern, ParseError> {
+        let first = self.expect(TokenType::Word)?;
+        if first.value == "_" {
+            return Ok(MatchPattern::Wildcard);
+        }
+        let mut path = vec![first.value];
+        while path.len() < 3 && self.peek_type() == TokenType::Dot {
+            self.advance(); // consume .
+            path.push(self.expect(TokenType::Word)?.value);
+        }
+        let name = path.pop().expect("path starts with a word");
+        let enum_name = (!path.is_empty()).then(|| path.join("."));
+
+        let mut bindings = Vec::new();
+        if self.peek_type() == TokenType::LParen {
+            self.advance(); // consume (
+            loop {
+                bindings.push(self.expect(TokenType::Word)?.value);
+                if self.peek_type() == TokenType::Comma {
+                    self.advance();
+                } else {
+                    break;
+                }
+            }
+            self.expect(TokenType::RParen)?;
+        }
+        Ok(MatchPattern::Variant {
+            enum_name,
+            name,
+            bindings,
+        })
     }
 
     // ── Closure parsing ────────────────────────────────────
@@ -323,8 +661,7 @@ impl Parser {
                     let name_tok = self.expect(TokenType::Word)?;
                     let type_name = if self.peek_type() == TokenType::Colon {
                         self.advance(); // consume :
-                        let ty = self.expect(TokenType::Word)?;
-                        Some(ty.value)
+                        Some(self.type_name()?)
                     } else {
                         None
                     };
@@ -351,7 +688,8 @@ impl Parser {
             let block = self.block_statement()?;
             ClosureBody::Block(block)
         } else {
-            let expr = self.arithmetic_expression()?;
+            // A comparison body makes a predicate, e.g. `|x| => x > 0`
+            let expr = self.call_func_arg()?;
             ClosureBody::Expr(Box::new(expr))
         };
 
@@ -362,6 +700,26 @@ impl Parser {
         }))
     }
 
+    // ── List literal parsing ───────────────────────────────────
+    // list[elem1, elem2, ...]  (elements may span lines)
+
+    fn parse_list_literal(&mut self, span: Span) -> Result<Expr, ParseError> {
+        self.expect(TokenType::LBracket)?;
+        self.skip_newlines();
+        let mut elements = Vec::new();
+        while self.peek_type() != TokenType::RBracket {
+            elements.push(self.arithmetic_expression()?);
+            self.skip_newlines();
+            if self.peek_type() != TokenType::Comma {
+                break;
+            }
+            self.advance(); // consume ,
+            self.skip_newlines();
+        }
+        self.expect(TokenType::RBracket)?;
+        Ok(Expr::ListLiteral(ListLiteral { elements, span }))
+    }
+
     // ── Vec/Mat literal parsing ────────────────────────────────
     // Vec: [elem1, elem2, ...]
     // Mat: [row1_elem1, row1_elem2; row2_elem1, row2_elem2]
@@ -25,52 +25,116 @@ impl Parser {
             params,
             return_types,
             body,
+            is_pub: false,
             span,
         }))
     }
 
     /// Parse function parameter list (may be empty).
-    /// Each param: `name: type`
+    /// Each param: `name: type` or `name: type = default`; the last may be the
+    /// variadic `...name` or `...name: type`
     fn func_params(&mut self) -> Result<Vec<FuncParam>, ParseError> {
-        let mut params = Vec::new();
+        let mut params: Vec<FuncParam> = Vec::new();
 
         // empty param list: next token is ')'
         if self.peek_type() == TokenType::RParen {
             return Ok(params);
         }
 
-        // first param (must start with Word)
-        if self.peek_type() != TokenType::Word {
+        // first param (must start with Word or ...)
+        if !matches!(self.peek_type(), TokenType::Word | TokenType::Ellipsis) {
             return Ok(params);
         }
 
-        params.push(self.func_param_factor()?);
-
-        while self.peek_type() == TokenType::Comma {
+        loop {
+            let param = self.func_param_factor()?;
+            if let Some(last) = params.last() {
+                if last.variadic {
+                    return Err(self.error_at(
+                        last.span,
+                        format!("variadic parameter '{}' must be the last parameter", last.name),
+                    ));
+                }
+                if last.default.is_some() && param.default.is_none() && !param.variadic {
+                    return Err(self.error_at(
+                        param.span,
+                        format!("parameter '{}' needs a default value, since it follows one with a default", param.name),
+                    ));
+                }
+            }
+            params.push(param);
+            if self.peek_type() != TokenType::Comma {
+                break;
+            }
             self.advance(); // consume ','
-            params.push(self.func_param_factor()?);
         }
 
         Ok(params)
     }
 
+    /// Whether the next call argument is named: `hp: 50`. A colon directly
+    /// followed by a call, as in `p:label()`, is a method call instead.
+    fn at_named_arg(&self) -> bool {
+        if self.peek_type() != TokenType::Word {
+            return false;
+        }
+        let Some(colon) = self.peek_ahead(1).filter(|tok| tok.token_type == TokenType::Colon) else {
+            return false;
+        };
+        let method_call = match (self.peek_ahead(2), self.peek_ahead(3)) {
+            (Some(method), Some(paren)) => {
+                method.token_type == TokenType::Word
+                    && paren.token_type == TokenType::LParen
+                    && method.span.line == colon.span.line
+                    && method.span.column == colon.span.column + 1
+            }
+            _ => false,
+        };
+        !method_call
+    }
+
     fn func_param_factor(&mut self) -> Result<FuncParam, ParseError> {
         let span = self.current_span();
+        if self.peek_type() == TokenType::Ellipsis {
+            self.advance(); // consume ...
+            let name_tok = self.expect(TokenType::Word)?;
+            let element = if self.peek_type() == TokenType::Colon {
+                self.advance(); // consume :
+                self.type_name()?
+            } else {
+                String::new()
+            };
+            return Ok(FuncParam {
+                name: name_tok.value,
+                type_name: format!("...{}", element),
+                default: None,
+                variadic: true,
+                span,
+            });
+        }
+
         let name_tok = self.expect(TokenType::Word)?;
         self.expect(TokenType::Colon)?;
-        let type_tok = self.expect(TokenType::Word)?;
+        let type_name = self.type_name()?;
+        let default = if self.peek_type() == TokenType::Assignment {
+            self.advance(); // consume =
+            Some(self.call_func_arg()?)
+        } else {
+            None
+        };
 
         Ok(FuncParam {
             name: name_tok.value,
-            type_name: type_tok.value,
+            type_name,
+            default,
+            variadic: false,
             span,
         })
     }
 
     fn func_return_types(&mut self) -> Result<Vec<String>, ParseError> {
         let mut types = Vec::new();
-        let first = self.expect(TokenType::Word)?;
-        types.push(first.value);
+        types.push(self.type_name()?);
 
         while self.peek_type() == TokenType::Comma {
             self.advance(); // consume ','
@@ -78,13 +142,155 @@ impl Parser {
             // If we see LBrace here, the comma was part of the return type list end,
             // but actually the grammar says return types are separated by commas,
             // so we just keep parsing words.
-            let t = self.expect(TokenType::Word)?;
-            types.push(t.value);
+            types.push(self.type_name()?);
         }
 
         Ok(types)
     }
 
+    // ── StructStatement ──────────────────────────────────────
+    // struct Name { field: type, ... }  (fields separated by commas or newlines)
+
+    pub(super) fn struct_statement(&mut self) -> Result<Statement, ParseError> {
+        let span = self.current_span();
+        self.expect(TokenType::Struct)?;
+        let name_tok = self.expect(TokenType::Word)?;
+        self.skip_newlines();
+        self.expect(TokenType::LBrace)?;
+        self.skip_newlines();
+
+        let mut fields = Vec::new();
+        while self.peek_type() != TokenType::RBrace {
+            let field_span = self.current_span();
+            let field_tok = self.expect(TokenType::Word)?;
+            self.expect(TokenType::Colon)?;
+            let type_name = self.type_name()?;
+            fields.push(StructField {
+                name: field_tok.value,
+                type_name,
+                span: field_span,
+            });
+            if self.peek_type() == TokenType::Comma {
+                self.advance(); // consume ,
+            } else if self.peek_type() != TokenType::Newline {
+                break;
+            }
+            self.skip_newlines();
+        }
+        self.expect(TokenType::RBrace)?;
+
+        Ok(Statement::StructDecl(StructDecl {
+            name: name_tok.value,
+            fields,
+            is_pub: false,
+            span,
+        }))
+    }
+
+    // ── EnumStatement ────────────────────────────────────────
+    // enum Name { Variant, Variant(type, ...), ... }  (variants separated by commas or newlines)
+
+    pub(super) fn enum_statement(&mut self) -> Result<Statement, ParseError> {
+        let span = self.current_span();
+        self.expect(TokenType::Enum)?;
+        let name_tok = self.expect(TokenType::Word)?;
+        self.skip_newlines();
+        self.expect(TokenType::LBrace)?;
+        self.skip_newlines();
+
+        let mut variants = Vec::new();
+        while self.peek_type() != TokenType::RBrace {
+            let variant_span = self.current_span();
+            let variant_tok = self.expect(TokenType::Word)?;
+            let mut payload = Vec::new();
+            if self.peek_type() == TokenType::LParen {
+                self.advance(); // consume (
+                loop {
+                    payload.push(self.type_name()?);
+                    if self.peek_type() == TokenType::Comma {
+                        self.advance();
+                    } else {
+                        break;
+                    }
+                }
+                self.expect(TokenType::RParen)?;
+            }
+            variants.push(EnumVariant {
+                name: variant_tok.value,
+                payload,
+                span: variant_span,
+            });
+            if self.peek_type() == TokenType::Comma {
+                self.advance(); // consume ,
+            } else if self.peek_type() != TokenType::Newline {
+                break;
+            }
+            self.skip_newlines();
+        }
+        self.expect(TokenType::RBrace)?;
+
+        Ok(Statement::EnumDecl(EnumDecl {
+            name: name_tok.value,
+            variants,
+            is_pub: false,
+            span,
+        }))
+    }
+
+    // ── ImplStatement ────────────────────────────────────────
+    // impl Name { func method(params) -> types { body } ... }
+    // Each method gets the receiver `self: Name` as its first parameter.
+
+    pub(super) fn impl_statement(&mut self) -> Result<Statement, ParseError> {
+        let span = self.current_span();
+        self.expect(TokenType::Impl)?;
+        let name_tok = self.expect(TokenType::Word)?;
+        self.skip_newlines();
+        self.expect(TokenType::LBrace)?;
+        self.skip_newlines();
+
+        let mut methods = Vec::new();
+        while self.peek_type() != TokenType::RBrace {
+            let Statement::FuncDecl(mut method) = self.func_statement()? else {
+                unreachable!("func_statement always yields a FuncDecl");
+            };
+            if let Some(param) = method.params.iter().find(|p| p.name == "self") {
+                return Err(self.error_at(
+                    param.span,
+                    format!("'self' is implicit in method '{}'", method.name),
+                ));
+            }
+            method.name = format!("{}.{}", name_tok.value, method.name);
+            method.params.insert(
+                0,
+                FuncParam {
+                    name: "self".to_string(),
+                    type_name: name_tok.value.clone(),
+                    default: None,
+                    variadic: false,
+                    span: method.span,
+                },
+            );
+            methods.push(method);
+            self.skip_newlines();
+        }
+        self.expect(TokenType::RBrace)?;
+
+        Ok(Statement::ImplDecl(ImplDecl {
+            type_name: name_tok.value,
+            methods,
+            span,
+        }))
+    }
+
+    // ── MatchStatement ───────────────────────────────────────
+    // match used for its arms' effects; arms may be blocks
+
+    pub(super) fn match_statement(&mut self) -> Result<Statement, ParseError> {
+        let expr = self.arithmetic_expression()?;
+        Ok(Statement::Expr(expr))
+    }
+
     // ── VarStatement ─────────────────────────────────────────
     // var x: type   OR   var x = expr  OR  var a, b = e1, e2
 
@@ -98,10 +304,10 @@ impl Parser {
 
         if next.token_type == TokenType::Colon {
             // var x: type
-            let type_tok = self.expect(TokenType::Word)?;
+            let type_name = self.type_name()?;
             Ok(Statement::VarDecl(VarDecl::TypeDecl {
                 name: name_tok.value,
-                type_name: type_tok.value,
+                type_name,
                 span,
             }))
         } else {
@@ -208,13 +414,31 @@ impl Parser {
     }
 
     // ── ForStatement ─────────────────────────────────────────
-    // for (init; cond; step) { body }
+    // for (init; cond; step) { body }   OR   for (item in items) { body }
 
     pub(super) fn for_statement(&mut self) -> Result<Statement, ParseError> {
         let span = self.current_span();
         self.expect(TokenType::For)?;
         self.expect(TokenType::LParen)?;
 
+        if self.peek_type() == TokenType::Word {
+            let var_tok = self.advance().clone();
+            if self.current_type() == TokenType::Word && self.current().value == "in" {
+                self.advance(); // consume `in`
+                let iterable = self.arithmetic_expression()?;
+                self.expect(TokenType::RParen)?;
+                self.skip_newlines();
+                let body = self.block_statement()?;
+                return Ok(Statement::ForIn(ForInStmt {
+                    var: var_tok.value,
+                    iterable,
+                    body,
+                    span,
+                }));
+            }
+            self.back(); // put back WORD
+        }
+
         // init (optional)
         let init = if self.peek_type() == TokenType::Semicolon {
             None
@@ -253,10 +477,13 @@ impl Parser {
         }))
     }
 
-    /// Parse for-loop init or step: either VarStatement or AssignmentStatement.
+    /// Parse for-loop init or step: VarStatement, or a word statement
+    /// (assignment, `word++` / `word--`, ...).
     fn for_init_or_step(&mut self) -> Result<Statement, ParseError> {
         if self.peek_type() == TokenType::Var {
             self.var_statement()
+        } else if self.peek_type() == TokenType::Word {
+            self.word_dispatch_statement()
         } else {
             let assign = self.assignment_statement()?;
             Ok(Statement::Assignment(assign))
@@ -296,17 +523,23 @@ impl Parser {
     }
 
     /// Parse a single statement inside a block. Allows more statement types
-    /// than top-level (return, break, continue).
+    /// than top-level (return, break, continue), and nested functions.
     fn block_statement_factor(&mut self) -> Result<Statement, ParseError> {
         match self.peek_type() {
+            TokenType::Func => self.func_statement(),
             TokenType::Var => self.var_statement(),
             TokenType::If => self.if_statement(),
             TokenType::For => self.for_statement(),
             TokenType::Return => self.return_statement(),
+            TokenType::Yield => self.yield_statement(),
+            TokenType::Try => self.try_statement(),
+            TokenType::Throw => self.throw_statement(),
+            TokenType::Defer => self.defer_statement(),
             TokenType::Break => self.break_statement(),
             TokenType::Continue => self.continue_statement(),
             TokenType::LBrace => self.block_statement_as_stmt(),
             TokenType::Timer => self.timer_statement(),
+            TokenType::Match => self.match_statement(),
             TokenType::Word => self.word_dispatch_statement(),
             _ => {
                 let tok = self.current().clone();
@@ -330,13 +563,28 @@ impl Parser {
         self.expect(TokenType::LParen)?;
 
         let mut args = Vec::new();
-
-        if self.peek_type() != TokenType::RParen {
-            args.push(self.arithmetic_expression()?);
-            while self.peek_type() == TokenType::Comma {
-                self.advance(); // consume ','
-                args.push(self.arithmetic_expression()?);
+        let mut named = Vec::new();
+
+        while self.peek_type() != TokenType::RParen {
+            let arg_span = self.current_span();
+            if self.at_named_arg() {
+                let name = self.advance().value.clone();
+                self.advance(); // consume ':'
+                named.push(NamedArg {
+                    name,
+                    value: self.call_func_arg()?,
+                    span: arg_span,
+                });
+            } else {
+                if !named.is_empty() {
+                    return Err(self.error_at(arg_span, "positional argument after named arguments".to_string()));
+                }
+                args.push(self.call_func_arg()?);
             }
+            if self.peek_type() != TokenType::Comma {
+                break;
+            }
+            self.advance(); // consume ','
         }
 
         self.expect(TokenType::RParen)?;
@@ -344,10 +592,31 @@ impl Parser {
         Ok(CallFunc {
             name: name_tok.value,
             args,
+            named,
             span,
         })
     }
 
+    /// A call argument; a comparison such as `v > 0` is kept as a mask for `where`.
+    pub(super) fn call_func_arg(&mut self) -> Result<Expr, ParseError> {
+        let span = self.current_span();
+        let left = self.arithmetic_expression()?;
+        if !matches!(
+            self.peek_type(),
+            TokenType::Gt | TokenType::Lt | TokenType::GtEq | TokenType::LtEq | TokenType::Eq | TokenType::NotEq
+        ) {
+            return Ok(left);
+        }
+        let op = self.comparison_op()?;
+        let right = self.arithmetic_expression()?;
+        Ok(Expr::BooleanExpr(Box::new(BooleanExpr::Comparison {
+            left,
+            op,
+            right,
+            span,
+        })))
+    }
+
     // ── ReturnStatement ──────────────────────────────────────
     // return expr1, expr2
 
@@ -374,6 +643,129 @@ impl Parser {
         Ok(Statement::Return(ReturnStmt { values, span }))
     }
 
+    // ── YieldStatement ───────────────────────────────────────
+    // yield expr
+
+    fn yield_statement(&mut self) -> Result<Statement, ParseError> {
+        let span = self.current_span();
+        self.expect(TokenType::Yield)?;
+
+        let value = if matches!(self.peek_type(), TokenType::Newline | TokenType::RBrace | TokenType::Eof) {
+            None
+        } else {
+            Some(self.arithmetic_expression()?)
+        };
+
+        Ok(Statement::Yield(YieldStmt { value, span }))
+    }
+
+    // ── TryStatement ─────────────────────────────────────────
+    // try { body } catch (name) { handler }
+
+    pub(super) fn try_statement(&mut self) -> Result<Statement, ParseError> {
+        let span = self.current_span();
+        self.expect(TokenType::Try)?;
+        self.skip_newlines();
+        let body = self.block_statement()?;
+
+        self.skip_newlines();
+        self.expect(TokenType::Catch)?;
+        let error_name = if self.peek_type() == TokenType::LParen {
+            self.advance(); // consume '('
+            let name = self.expect(TokenType::Word)?.value;
+            self.expect(TokenType::RParen)?;
+            Some(name)
+        } else {
+            None
+        };
+        self.skip_newlines();
+        let handler = self.block_statement()?;
+
+        Ok(Statement::Try(TryStmt {
+            body,
+            error_name,
+            handler,
+            span,
+        }))
+    }
+
+    // ── ThrowStatement ───────────────────────────────────────
+    // throw expr
+
+    pub(super) fn throw_statement(&mut self) -> Result<Statement, ParseError> {
+        let span = self.current_span();
+        self.expect(TokenType::Throw)?;
+        let value = self.arithmetic_expression()?;
+        Ok(Statement::Throw(ThrowStmt { value, span }))
+    }
+
+    // ── DeferStatement ───────────────────────────────────────
+    // defer { body }
+
+    pub(super) fn defer_statement(&mut self) -> Result<Statement, ParseError> {
+        let span = self.current_span();
+        self.expect(TokenType::Defer)?;
+        self.skip_newlines();
+        let body = self.block_statement()?;
+        Ok(Statement::Defer(DeferStmt { body, span }))
+    }
+
+    // ── PubStatement ─────────────────────────────────────────
+    // pub func ... | pub struct ... | pub enum ...
+
+    pub(super) fn pub_statement(&mut self) -> Result<Statement, ParseError> {
+        self.expect(TokenType::Pub)?;
+        let mut stmt = match self.peek_type() {
+            TokenType::Func => self.func_statement()?,
+            TokenType::Struct => self.struct_statement()?,
+            TokenType::Enum => self.enum_statement()?,
+            _ => {
+                let tok = self.current().clone();
+                return Err(self.error_at(
+                    tok.span,
+                    format!("unexpected '{}', expected func/struct/enum after pub", tok.value),
+                ));
+            }
+        };
+        match &mut stmt {
+            Statement::FuncDecl(func) => func.is_pub = true,
+            Statement::StructDecl(decl) => decl.is_pub = true,
+            Statement::EnumDecl(decl) => decl.is_pub = true,
+            _ => unreachable!(),
+        }
+        Ok(stmt)
+    }
+
+    // ── ImportStatement ──────────────────────────────────────
+    // import "dir/name.ah"  |  import name   (the file name.ah next to this one)
+
+    pub(super) fn import_statement(&mut self) -> Result<Statement, ParseError> {
+        let span = self.current_span();
+        self.expect(TokenType::Import)?;
+        let tok = self.advance().clone();
+        let path = match tok.token_type {
+            TokenType::StringLit => tok.value,
+            TokenType::Word => format!("{}.ah", tok.value),
+            _ => {
+                return Err(self.error_at(
+                    tok.span,
+                    format!("unexpected '{}', expected a file path or module name after import", tok.value),
+                ));
+            }
+        };
+        let file_name = path.rsplit(['/', '\\']).next().unwrap_or(&path);
+        let name = file_name.strip_suffix(".ah").unwrap_or(file_name).to_string();
+        let is_word = name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !is_word {
+            return Err(self.error_at(
+                tok.span,
+                format!("module name '{}' (from \"{}\") is not a valid name", name, path),
+            ));
+        }
+        Ok(Statement::Import(ImportStmt { path, name, span }))
+    }
+
     // ── Break / Continue ─────────────────────────────────────
 
     fn break_statement(&mut self) -> Result<Statement, ParseError> {
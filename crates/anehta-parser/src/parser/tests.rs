@@ -151,6 +151,19 @@ fn func_decl_simple() {
     }
 }
 
+#[test]
+fn func_decl_function_types() {
+    let prog = parse_ok("func compose(f: fn(int) -> int, g: fn(int, fn() -> int) -> float) -> fn(int) -> int {\nreturn f\n}");
+    match &prog.statements[0] {
+        Statement::FuncDecl(f) => {
+            assert_eq!(f.params[0].type_name, "fn(int)->int");
+            assert_eq!(f.params[1].type_name, "fn(int,fn()->int)->float");
+            assert_eq!(f.return_types, vec!["fn(int)->int"]);
+        }
+        _ => panic!("expected FuncDecl"),
+    }
+}
+
 #[test]
 fn func_decl_multi_return() {
     let prog = parse_ok("func swap(a: int, b: int) -> int, int {\nreturn b, a\n}");
@@ -182,6 +195,60 @@ fn func_decl_empty_body() {
     }
 }
 
+#[test]
+fn func_decl_default_and_variadic_params() {
+    let prog = parse_ok("func spawn(kind: str, hp: int = 100, ...tags: str) -> str {\nreturn kind\n}");
+    let Statement::FuncDecl(f) = &prog.statements[0] else {
+        panic!("expected FuncDecl");
+    };
+    assert!(f.params[0].default.is_none());
+    assert!(matches!(&f.params[1].default, Some(Expr::Number(n, _)) if n == "100"));
+    assert!(f.params[2].variadic);
+    assert_eq!(f.params[2].type_name, "...str");
+
+    assert!(parse_source("func f(a: int = 1, b: int) -> int {\n}").is_err());
+    assert!(parse_source("func f(...a, b: int) -> int {\n}").is_err());
+}
+
+#[test]
+fn call_named_args() {
+    let prog = parse_ok("spawn(\"orc\", hp: 50)");
+    let Statement::CallFunc(call) = &prog.statements[0] else {
+        panic!("expected CallFunc");
+    };
+    assert_eq!(call.args.len(), 1);
+    assert_eq!(call.named.len(), 1);
+    assert_eq!(call.named[0].name, "hp");
+    assert!(parse_source("spawn(hp: 50, \"orc\")").is_err());
+    // `p:label()` stays a method call
+    let prog = parse_ok("show(p:label(), hp: 1)");
+    let Statement::CallFunc(call) = &prog.statements[0] else {
+        panic!("expected CallFunc");
+    };
+    assert!(matches!(&call.args[0], Expr::MethodCall(_)));
+    assert_eq!(call.named.len(), 1);
+}
+
+#[test]
+fn func_decl_nested() {
+    let prog = parse_ok("func outer() -> int {\nfunc inner(x: int) -> int {\nreturn x\n}\nreturn inner(1)\n}");
+    let Statement::FuncDecl(f) = &prog.statements[0] else {
+        panic!("expected FuncDecl");
+    };
+    assert!(matches!(&f.body.statements[0], Statement::FuncDecl(inner) if inner.name == "inner"));
+}
+
+#[test]
+fn func_decl_generator() {
+    let prog = parse_ok("func walk(co: coroutine(str)) -> int {\nyield 1\nyield\n}");
+    let Statement::FuncDecl(f) = &prog.statements[0] else {
+        panic!("expected FuncDecl");
+    };
+    assert_eq!(f.params[0].type_name, "coroutine(str)");
+    assert!(matches!(&f.body.statements[0], Statement::Yield(y) if y.value.is_some()));
+    assert!(matches!(&f.body.statements[1], Statement::Yield(y) if y.value.is_none()));
+}
+
 // ── If / elseif / else ──────────────────────────────────
 
 #[test]
@@ -291,6 +358,55 @@ fn break_and_continue_in_loop() {
     }
 }
 
+// ── Try / catch / throw ─────────────────────────────────
+
+#[test]
+fn try_catch_and_throw() {
+    let src = "try {\nthrow \"bad\"\n}\ncatch (e) {\nprint(e.code)\n}\ntry { f() } catch { }";
+    let prog = parse_ok(src);
+    let Statement::Try(t) = &prog.statements[0] else {
+        panic!("expected Try");
+    };
+    assert!(matches!(&t.body.statements[0], Statement::Throw(_)));
+    assert_eq!(t.error_name.as_deref(), Some("e"));
+    assert_eq!(t.handler.statements.len(), 1);
+    assert!(matches!(&prog.statements[1], Statement::Try(t) if t.error_name.is_none()));
+}
+
+// ── Defer ───────────────────────────────────────────────
+
+#[test]
+fn defer_in_function_body() {
+    let src = "func f() -> int {\ndefer {\nprint(1)\n}\ndefer { print(2) }\n}\ndefer { print(3) }";
+    let prog = parse_ok(src);
+    let Statement::FuncDecl(f) = &prog.statements[0] else {
+        panic!("expected FuncDecl");
+    };
+    assert_eq!(f.body.statements.len(), 2);
+    assert!(matches!(&f.body.statements[0], Statement::Defer(d) if d.body.statements.len() == 1));
+    assert!(matches!(&prog.statements[1], Statement::Defer(_)));
+}
+
+#[test]
+fn import_and_pub_declarations() {
+    let src = "import util\nimport \"lib/geo.ah\"\npub func f(p: geo.Point) -> int {\nreturn 1\n}\npub struct S { a: int }\nvar p = new geo.Point { x: 1 }";
+    let prog = parse_ok(src);
+    assert!(matches!(&prog.statements[0], Statement::Import(i) if i.path == "util.ah" && i.name == "util"));
+    assert!(matches!(&prog.statements[1], Statement::Import(i) if i.path == "lib/geo.ah" && i.name == "geo"));
+    let Statement::FuncDecl(f) = &prog.statements[2] else {
+        panic!("expected FuncDecl");
+    };
+    assert!(f.is_pub);
+    assert_eq!(f.params[0].type_name, "geo.Point");
+    assert!(matches!(&prog.statements[3], Statement::StructDecl(s) if s.is_pub));
+    let Statement::VarDecl(VarDecl::Assignment(a)) = &prog.statements[4] else {
+        panic!("expected VarDecl");
+    };
+    assert!(matches!(&a.values[0], Expr::New(n) if n.type_name == "geo.Point"));
+    assert!(parse_source("pub var x = 1").is_err());
+    assert!(parse_source("import \"my-lib.ah\"").is_err());
+}
+
 // ── Go test complete code snippet ───────────────────────
 
 #[test]
@@ -377,7 +493,7 @@ fn increment_decrement() {
             match &a.values[0] {
                 Expr::UnaryOp { op, operand, .. } => {
                     assert!(matches!(op, UnaryOp::Increment));
-                    assert_eq!(operand, "i");
+                    assert!(matches!(operand.as_ref(), Expr::Variable(name, _) if name == "i"));
                 }
                 _ => panic!("expected UnaryOp"),
             }
@@ -386,6 +502,282 @@ fn increment_decrement() {
     }
 }
 
+#[test]
+fn interpolated_string() {
+    let prog = parse_ok(r#"var s = f"HP: {hp + 1}!""#);
+    match &prog.statements[0] {
+        Statement::VarDecl(VarDecl::Assignment(a)) => match &a.values[0] {
+            Expr::Interpolated { parts, .. } => {
+                assert_eq!(parts.len(), 3);
+                assert!(matches!(&parts[0], InterpPart::Text(t) if t == "HP: "));
+                assert!(matches!(&parts[1], InterpPart::Expr(Expr::BinaryOp { .. })));
+                assert!(matches!(&parts[2], InterpPart::Text(t) if t == "!"));
+            }
+            _ => panic!("expected Interpolated"),
+        },
+        _ => panic!("expected VarDecl"),
+    }
+}
+
+#[test]
+fn bitwise_precedence() {
+    // a | b xor c & d << 1 + 1  ==  a | (b xor (c & (d << (1 + 1))))
+    let prog = parse_ok("var x = a | b xor c & d << 1 + 1");
+    let Statement::VarDecl(VarDecl::Assignment(a)) = &prog.statements[0] else {
+        panic!("expected VarDecl");
+    };
+    let Expr::BinaryOp { op: BinaryOp::BitOr, right, .. } = &a.values[0] else {
+        panic!("expected | at the top");
+    };
+    let Expr::BinaryOp { op: BinaryOp::BitXor, right, .. } = right.as_ref() else {
+        panic!("expected xor under |");
+    };
+    let Expr::BinaryOp { op: BinaryOp::BitAnd, right, .. } = right.as_ref() else {
+        panic!("expected & under xor");
+    };
+    let Expr::BinaryOp { op: BinaryOp::Shl, right, .. } = right.as_ref() else {
+        panic!("expected << under &");
+    };
+    assert!(matches!(right.as_ref(), Expr::BinaryOp { op: BinaryOp::Add, .. }));
+}
+
+#[test]
+fn complement_and_random_disambiguation() {
+    let prog = parse_ok("var x = ~a ~ ~b");
+    match &prog.statements[0] {
+        Statement::VarDecl(VarDecl::Assignment(a)) => match &a.values[0] {
+            Expr::BinaryOp { left, op: BinaryOp::Rand, right, .. } => {
+                assert!(matches!(left.as_ref(), Expr::UnaryOp { op: UnaryOp::Complement, .. }));
+                assert!(matches!(right.as_ref(), Expr::UnaryOp { op: UnaryOp::Complement, .. }));
+            }
+            _ => panic!("expected Rand"),
+        },
+        _ => panic!("expected VarDecl"),
+    }
+}
+
+#[test]
+fn cast_expression() {
+    let prog = parse_ok("var n = x * 2 + 1 -> int.round -> str");
+    match &prog.statements[0] {
+        Statement::VarDecl(VarDecl::Assignment(a)) => match &a.values[0] {
+            Expr::Cast(outer) => {
+                assert_eq!(outer.target, "str");
+                assert_eq!(outer.mode, None);
+                match outer.expr.as_ref() {
+                    Expr::Cast(inner) => {
+                        assert_eq!(inner.target, "int");
+                        assert_eq!(inner.mode.as_deref(), Some("round"));
+                        assert!(matches!(inner.expr.as_ref(), Expr::BinaryOp { .. }));
+                    }
+                    _ => panic!("expected inner Cast"),
+                }
+            }
+            _ => panic!("expected Cast"),
+        },
+        _ => panic!("expected VarDecl"),
+    }
+}
+
+#[test]
+fn cast_missing_target_error() {
+    assert!(parse_source("var n = x -> 3").is_err());
+}
+
+#[test]
+fn struct_declaration() {
+    let prog = parse_ok("struct Player { hp: int, name: str\n    pos: vec\n}");
+    match &prog.statements[0] {
+        Statement::StructDecl(decl) => {
+            assert_eq!(decl.name, "Player");
+            let fields: Vec<(&str, &str)> = decl
+                .fields
+                .iter()
+                .map(|f| (f.name.as_str(), f.type_name.as_str()))
+                .collect();
+            assert_eq!(fields, vec![("hp", "int"), ("name", "str"), ("pos", "vec")]);
+        }
+        _ => panic!("expected StructDecl"),
+    }
+    assert!(parse_source("struct P { hp }").is_err());
+}
+
+#[test]
+fn enum_declaration() {
+    let prog = parse_ok("enum State {\n    Idle\n    Moving(vec)\n    Attacking(int, str)\n}");
+    match &prog.statements[0] {
+        Statement::EnumDecl(decl) => {
+            assert_eq!(decl.name, "State");
+            let variants: Vec<(&str, usize)> = decl
+                .variants
+                .iter()
+                .map(|v| (v.name.as_str(), v.payload.len()))
+                .collect();
+            assert_eq!(variants, vec![("Idle", 0), ("Moving", 1), ("Attacking", 2)]);
+            assert_eq!(decl.variants[2].payload, vec!["int", "str"]);
+        }
+        _ => panic!("expected EnumDecl"),
+    }
+}
+
+#[test]
+fn impl_declaration() {
+    let prog = parse_ok("impl Player {\n    func heal(n: int) -> int {\n        return n\n    }\n\n    func hp() -> int {\n        return self.hp\n    }\n}");
+    let Statement::ImplDecl(imp) = &prog.statements[0] else {
+        panic!("expected ImplDecl");
+    };
+    assert_eq!(imp.type_name, "Player");
+    let names: Vec<&str> = imp.methods.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, vec!["Player.heal", "Player.hp"]);
+    let params: Vec<(&str, &str)> = imp.methods[0]
+        .params
+        .iter()
+        .map(|p| (p.name.as_str(), p.type_name.as_str()))
+        .collect();
+    assert_eq!(params, vec![("self", "Player"), ("n", "int")]);
+    assert!(parse_source("impl P {\n    func f(self: P) -> int {\n        return 1\n    }\n}").is_err());
+}
+
+#[test]
+fn method_call_with_receiver() {
+    let prog = parse_ok("var n = t:add(1, 2).x\nt:reset()");
+    let Statement::VarDecl(VarDecl::Assignment(a)) = &prog.statements[0] else {
+        panic!("expected VarDecl");
+    };
+    let Expr::FieldAccess(outer) = &a.values[0] else {
+        panic!("expected FieldAccess");
+    };
+    let Expr::MethodCall(mc) = outer.object.as_ref() else {
+        panic!("expected MethodCall");
+    };
+    assert!(mc.pass_self);
+    assert_eq!(mc.args.len(), 2);
+    assert!(matches!(mc.callee.as_ref(), Expr::FieldAccess(fa) if fa.field == "add"));
+    let Statement::MethodCall(mc) = &prog.statements[1] else {
+        panic!("expected method call statement");
+    };
+    assert!(mc.pass_self && mc.args.is_empty());
+}
+
+#[test]
+fn if_expression_with_elseif_chain() {
+    let prog = parse_ok("var n = if (x > 0) { 1 } elseif (x < 0) {\n    -1\n}\nelse { 0 }");
+    let Statement::VarDecl(VarDecl::Assignment(a)) = &prog.statements[0] else {
+        panic!("expected VarDecl");
+    };
+    let Expr::If(outer) = &a.values[0] else {
+        panic!("expected If");
+    };
+    assert!(matches!(outer.condition.as_ref(), BooleanExpr::Comparison { op: ComparisonOp::Gt, .. }));
+    let Expr::If(inner) = outer.else_value.as_ref() else {
+        panic!("expected elseif to nest an If");
+    };
+    assert!(matches!(inner.then_value.as_ref(), Expr::UnaryOp { op: UnaryOp::Neg, .. }));
+    assert!(matches!(inner.else_value.as_ref(), Expr::Number(n, _) if n == "0"));
+    assert!(parse_source("var n = if (x > 0) { 1 }").is_err());
+}
+
+#[test]
+fn call_argument_comparison() {
+    let prog = parse_ok("var w = where(v >= 0, v, 0)");
+    let Statement::VarDecl(VarDecl::Assignment(a)) = &prog.statements[0] else {
+        panic!("expected VarDecl");
+    };
+    let Expr::CallFunc(call) = &a.values[0] else {
+        panic!("expected CallFunc");
+    };
+    assert_eq!(call.args.len(), 3);
+    assert!(matches!(&call.args[0], Expr::BooleanExpr(b) if matches!(b.as_ref(), BooleanExpr::Comparison { op: ComparisonOp::GtEq, .. })));
+}
+
+#[test]
+fn closure_predicate_body() {
+    let prog = parse_ok("var odd = filter(xs, |x| => x % 2 == 1)");
+    let Statement::VarDecl(VarDecl::Assignment(a)) = &prog.statements[0] else {
+        panic!("expected VarDecl");
+    };
+    let Expr::CallFunc(call) = &a.values[0] else {
+        panic!("expected CallFunc");
+    };
+    let Expr::Closure(closure) = &call.args[1] else {
+        panic!("expected Closure");
+    };
+    assert!(matches!(&closure.body, ClosureBody::Expr(e) if matches!(e.as_ref(), Expr::BooleanExpr(_))));
+}
+
+#[test]
+fn list_literal_and_for_in() {
+    let prog = parse_ok("var items = list[1, \"two\",\n    {hp: 3}, list[]]\nfor (item in items[1..]) {\n    print(item)\n}");
+    let Statement::VarDecl(VarDecl::Assignment(a)) = &prog.statements[0] else {
+        panic!("expected VarDecl");
+    };
+    let Expr::ListLiteral(lit) = &a.values[0] else {
+        panic!("expected ListLiteral");
+    };
+    assert_eq!(lit.elements.len(), 4);
+    assert!(matches!(&lit.elements[2], Expr::TableLiteral(_)));
+    assert!(matches!(&lit.elements[3], Expr::ListLiteral(inner) if inner.elements.is_empty()));
+    let Statement::ForIn(f) = &prog.statements[1] else {
+        panic!("expected ForIn");
+    };
+    assert_eq!(f.var, "item");
+    assert!(matches!(&f.iterable, Expr::IndexAccess(ia) if matches!(ia.index.as_ref(), Expr::Range { .. })));
+    assert_eq!(f.body.statements.len(), 1);
+    // A C-style for loop whose init starts with a word is unaffected
+    assert!(matches!(parse_ok("for (i = 0; i < 3; i++) {\n}").statements[0], Statement::ForStmt(_)));
+}
+
+#[test]
+fn match_expression_patterns() {
+    let prog = parse_ok("var n = match s {\n    Idle => 0\n    State.Moving(v) => v.x, Attacking(_, t) => 2\n    _ => 3\n}");
+    let Statement::VarDecl(VarDecl::Assignment(a)) = &prog.statements[0] else {
+        panic!("expected VarDecl");
+    };
+    let Expr::Match(m) = &a.values[0] else {
+        panic!("expected Match");
+    };
+    assert!(matches!(m.subject.as_ref(), Expr::Variable(name, _) if name == "s"));
+    assert_eq!(m.arms.len(), 4);
+    assert!(matches!(&m.arms[0].pattern, MatchPattern::Variant { enum_name: None, name, bindings } if name == "Idle" && bindings.is_empty()));
+    assert!(matches!(&m.arms[1].pattern, MatchPattern::Variant { enum_name: Some(e), bindings, .. } if e == "State" && bindings == &["v"]));
+    assert!(matches!(&m.arms[2].pattern, MatchPattern::Variant { bindings, .. } if bindings == &["_", "t"]));
+    assert!(matches!(m.arms[3].pattern, MatchPattern::Wildcard));
+    assert!(matches!(m.arms[1].body, MatchBody::Expr(Expr::FieldAccess(_))));
+}
+
+#[test]
+fn match_statement_with_block_arms() {
+    let prog = parse_ok("match s {\n    Idle => {\n        print(1)\n    }\n    _ => print(2)\n}");
+    let Statement::Expr(Expr::Match(m)) = &prog.statements[0] else {
+        panic!("expected match statement");
+    };
+    assert!(matches!(&m.arms[0].body, MatchBody::Block(b) if b.statements.len() == 1));
+    assert!(matches!(m.arms[1].body, MatchBody::Expr(Expr::CallFunc(_))));
+    assert!(parse_source("var n = match s { }").is_err());
+}
+
+#[test]
+fn new_expression() {
+    let prog = parse_ok("var p = new Player { hp: 100, pos: new Vec2 { x: 0, y: 1 } }");
+    match &prog.statements[0] {
+        Statement::VarDecl(VarDecl::Assignment(a)) => match &a.values[0] {
+            Expr::New(new) => {
+                assert_eq!(new.type_name, "Player");
+                assert_eq!(new.fields.len(), 2);
+                assert_eq!(new.fields[0].key, "hp");
+                assert!(matches!(&new.fields[1].value, Expr::New(inner) if inner.type_name == "Vec2"));
+            }
+            _ => panic!("expected New"),
+        },
+        _ => panic!("expected VarDecl"),
+    }
+}
+
+#[test]
+fn interpolated_string_empty_expr_error() {
+    assert!(parse_source(r#"var s = f"{}""#).is_err());
+}
+
 #[test]
 fn string_literal_in_var() {
     let prog = parse_ok(r#"var name = "Anehta""#);
@@ -1350,7 +1350,7 @@ fn increment_in_expr() {
     match &expr {
         Expr::UnaryOp { op, operand, .. } => {
             assert!(matches!(op, UnaryOp::Increment));
-            assert_eq!(operand, "i");
+            assert!(matches!(operand.as_ref(), Expr::Variable(name, _) if name == "i"));
         }
         _ => panic!("expected UnaryOp(Increment)"),
     }
@@ -1362,12 +1362,87 @@ fn decrement_in_expr() {
     match &expr {
         Expr::UnaryOp { op, operand, .. } => {
             assert!(matches!(op, UnaryOp::Decrement));
-            assert_eq!(operand, "j");
+            assert!(matches!(operand.as_ref(), Expr::Variable(name, _) if name == "j"));
         }
         _ => panic!("expected UnaryOp(Decrement)"),
     }
 }
 
+#[test]
+fn negation_binds_tighter_than_binary_ops() {
+    // -a * -(b + 1)  ==  Mul(Neg(a), Neg(Grouped(b + 1)))
+    let expr = parse_expr("-a * -(b + 1)");
+    match &expr {
+        Expr::BinaryOp { op: BinaryOp::Mul, left, right, .. } => {
+            assert!(matches!(
+                left.as_ref(),
+                Expr::UnaryOp { op: UnaryOp::Neg, operand, .. } if matches!(operand.as_ref(), Expr::Variable(..))
+            ));
+            assert!(matches!(
+                right.as_ref(),
+                Expr::UnaryOp { op: UnaryOp::Neg, operand, .. } if matches!(operand.as_ref(), Expr::Grouped(_))
+            ));
+        }
+        _ => panic!("expected BinaryOp(Mul)"),
+    }
+}
+
+#[test]
+fn subtraction_of_negative() {
+    let expr = parse_expr("a - -1");
+    match &expr {
+        Expr::BinaryOp { op: BinaryOp::Sub, right, .. } => {
+            assert!(matches!(right.as_ref(), Expr::UnaryOp { op: UnaryOp::Neg, .. }));
+        }
+        _ => panic!("expected BinaryOp(Sub)"),
+    }
+}
+
+#[test]
+fn increment_field_and_element_in_expr() {
+    let expr = parse_expr("t.count++");
+    assert!(matches!(
+        &expr,
+        Expr::UnaryOp { op: UnaryOp::Increment, operand, .. } if matches!(operand.as_ref(), Expr::FieldAccess(_))
+    ));
+    let expr = parse_expr("v[0]--");
+    assert!(matches!(
+        &expr,
+        Expr::UnaryOp { op: UnaryOp::Decrement, operand, .. } if matches!(operand.as_ref(), Expr::IndexAccess(_))
+    ));
+}
+
+#[test]
+fn step_statements() {
+    let prog = parse("i++\nt.count--\nv[i + 1]++");
+    assert_eq!(prog.statements.len(), 3);
+    let operands: Vec<&Expr> = prog
+        .statements
+        .iter()
+        .map(|stmt| match stmt {
+            Statement::Expr(Expr::UnaryOp { operand, .. }) => operand.as_ref(),
+            other => panic!("expected step statement, got {other:?}"),
+        })
+        .collect();
+    assert!(matches!(operands[0], Expr::Variable(name, _) if name == "i"));
+    assert!(matches!(operands[1], Expr::FieldAccess(fa) if fa.field == "count"));
+    assert!(matches!(operands[2], Expr::IndexAccess(_)));
+}
+
+#[test]
+fn for_with_increment_step() {
+    let prog = parse("for (var i = 0; i < 10; i++) {\n}");
+    match &prog.statements[0] {
+        Statement::ForStmt(f) => {
+            assert!(matches!(
+                f.step.as_deref(),
+                Some(Statement::Expr(Expr::UnaryOp { op: UnaryOp::Increment, .. }))
+            ));
+        }
+        _ => panic!("expected ForStmt"),
+    }
+}
+
 // ═══════════════════════════════════════════════════════════════════
 // 14. Expression factor types
 // ═══════════════════════════════════════════════════════════════════
@@ -2038,7 +2113,7 @@ fn expr_increment_decrement_mixed_with_arithmetic() {
             match left.as_ref() {
                 Expr::UnaryOp { op: uop, operand, .. } => {
                     assert!(matches!(uop, UnaryOp::Increment));
-                    assert_eq!(operand, "i");
+                    assert!(matches!(operand.as_ref(), Expr::Variable(name, _) if name == "i"));
                 }
                 _ => panic!("expected UnaryOp(Increment)"),
             }
@@ -2049,7 +2124,7 @@ fn expr_increment_decrement_mixed_with_arithmetic() {
                     match inner_left.as_ref() {
                         Expr::UnaryOp { op: uop, operand, .. } => {
                             assert!(matches!(uop, UnaryOp::Decrement));
-                            assert_eq!(operand, "j");
+                            assert!(matches!(operand.as_ref(), Expr::Variable(name, _) if name == "j"));
                         }
                         _ => panic!("expected UnaryOp(Decrement)"),
                     }